@@ -0,0 +1,32 @@
+// Declarations shared by the shaders, through `#include "common.wgsl"`.
+// Only types and functions go here: a binding would show up in the
+// reflected interface of every shader including it.
+
+// The camera and model transforms, bound by each shader as it needs:
+// `@group(0) @binding(0) var<uniform> uniforms: CameraUniforms;`
+struct CameraUniforms {
+    camera: mat4x4<f32>,
+    transform: mat4x4<f32>,
+}
+
+// Applies the camera uniforms to a position in model space.
+fn to_clip(uniforms: CameraUniforms, position: vec3<f32>) -> vec4<f32> {
+    return uniforms.camera * uniforms.transform * vec4<f32>(position, 1.0);
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+// Scales the color by its alpha, for premultiplied blending.
+fn premultiply(color: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
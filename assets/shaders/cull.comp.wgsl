@@ -0,0 +1,67 @@
+// Culls the objects of a `FrustumCulling`, one invocation each: an object
+// whose box is inside its frustum has its instance copied into the next
+// free slot of its draw's range, counted in the draw's instance count, so
+// each draw ends up with only the instances that survived, packed.
+
+// `CullObject`.
+struct Object {
+    min: vec3<f32>,
+    draw: u32,
+    max: vec3<f32>,
+    frustum: u32,
+}
+
+// `Frustum`: inward-facing planes, all zero for a side not tested.
+struct Frustum {
+    planes: array<vec4<f32>, 6>,
+}
+
+// `CullParams`.
+struct Params {
+    objects: u32,
+    instance_words: u32,
+    _pad0: u32,
+    _pad1: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> objects: array<Object>;
+@group(0) @binding(2) var<storage, read> frustums: array<Frustum>;
+// The instances of the objects, by object, as words to copy whatever
+// their type.
+@group(0) @binding(3) var<storage, read> instances: array<u32>;
+@group(0) @binding(4) var<storage, read_write> culled: array<u32>;
+// `DrawIndexedArgs`, five words each: index count, instance count, first
+// index, base vertex and first instance.
+@group(0) @binding(5) var<storage, read_write> draws: array<atomic<u32>>;
+
+fn inside(object: Object, frustum: Frustum) -> bool {
+    for (var i = 0u; i < 6u; i++) {
+        let plane = frustum.planes[i];
+        // The corner furthest along the plane's normal.
+        let corner = select(object.min, object.max, plane.xyz >= vec3<f32>(0.0));
+        if dot(plane.xyz, corner) + plane.w < 0.0 {
+            return false;
+        }
+    }
+    return true;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.objects {
+        return;
+    }
+    let object = objects[index];
+    if !inside(object, frustums[object.frustum]) {
+        return;
+    }
+
+    let args = object.draw * 5u;
+    let slot = atomicLoad(&draws[args + 4u]) + atomicAdd(&draws[args + 1u], 1u);
+    let words = params.instance_words;
+    for (var word = 0u; word < words; word++) {
+        culled[slot * words + word] = instances[index * words + word];
+    }
+}
@@ -0,0 +1,44 @@
+// The interface draws with TEXTURED and VERTEX_COLOR; SDF_TEXT is for
+// glyphs from a distance field atlas. PREMULTIPLIED expects the texture to
+// be premultiplied already and premultiplies everything else.
+
+#include "common.wgsl"
+
+#ifdef TEXTURED
+@group(1) @binding(0) var u_texture: texture_2d_array<f32>;
+@group(1) @binding(1) var u_sampler: sampler;
+#endif
+
+struct FragmentInput {
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) @interpolate(flat) index: u32,
+}
+
+@fragment
+fn main(fragment: FragmentInput) -> @location(0) vec4<f32> {
+    var color = vec4<f32>(1.0);
+#ifdef TEXTURED
+    let texel = textureSample(u_texture, u_sampler, fragment.uv, fragment.index);
+#ifdef SDF_TEXT
+    // The distance to the glyph edge is stored in alpha, 0.5 on the edge;
+    // smoothing over one pixel keeps it sharp at any scale.
+    let distance = texel.a;
+    let width = fwidth(distance);
+    color = vec4<f32>(texel.rgb, smoothstep(0.5 - width, 0.5 + width, distance));
+#ifdef PREMULTIPLIED
+    color = premultiply(color);
+#endif
+#else
+    color = texel;
+#endif
+#endif
+#ifdef VERTEX_COLOR
+#ifdef PREMULTIPLIED
+    color *= premultiply(fragment.color);
+#else
+    color *= fragment.color;
+#endif
+#endif
+    return color;
+}
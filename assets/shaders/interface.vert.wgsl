@@ -0,0 +1,56 @@
+// INSTANCED places a copy of the vertices per instance: moved by the
+// instance transform, tinted by its color and with the uvs mapped into its
+// uv rect on its atlas layer.
+
+#include "common.wgsl"
+
+@group(0) @binding(0) var<uniform> uniforms: CameraUniforms;
+
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) index: u32,
+}
+
+#ifdef INSTANCED
+// `InterfaceInstance`: the columns of a 2D affine transform, then the
+// tint, uv rect and layer.
+struct InstanceInput {
+    @location(4) x_axis: vec2<f32>,
+    @location(5) y_axis: vec2<f32>,
+    @location(6) offset: vec2<f32>,
+    @location(7) color: vec4<f32>,
+    @location(8) uv_rect: vec4<f32>,
+    @location(9) index: u32,
+}
+#endif
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) @interpolate(flat) index: u32,
+}
+
+@vertex
+#ifdef INSTANCED
+fn main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let pos = instance.x_axis * vertex.pos.x + instance.y_axis * vertex.pos.y + instance.offset;
+    out.position = to_clip(uniforms, vec3<f32>(pos, 0.0));
+    out.color = vertex.color * instance.color;
+    out.uv = mix(instance.uv_rect.xy, instance.uv_rect.zw, vertex.uv);
+    out.index = instance.index;
+    return out;
+}
+#else
+fn main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = to_clip(uniforms, vec3<f32>(vertex.pos, 0.0));
+    out.color = vertex.color;
+    out.uv = vertex.uv;
+    out.index = vertex.index;
+    return out;
+}
+#endif
@@ -0,0 +1,89 @@
+// Simulates the particles of a `ParticlePass`, one invocation each.
+// Particles in the spawn window, a ring over the buffer the pass moves
+// along every frame, are respawned at the emitter, replacing the oldest;
+// the others fall under gravity and drag until their lifetime runs out,
+// and are then left alone, dead, until the window comes round again.
+
+// `Particle`.
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    color: vec4<f32>,
+    size: f32,
+    age: f32,
+    lifetime: f32,
+    _pad: f32,
+}
+
+// `ParticleParams`.
+struct Params {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    gravity: vec2<f32>,
+    spread: f32,
+    drag: f32,
+    color: vec4<f32>,
+    end_color: vec4<f32>,
+    size: f32,
+    lifetime: f32,
+    dt: f32,
+    seed: u32,
+    spawn_start: u32,
+    spawn_count: u32,
+    capacity: u32,
+    reset: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> particles: array<Particle>;
+
+// PCG, as a hash of one word.
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// A number in [0, 1), advancing `state`.
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state >> 8u) / 16777216.0;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.capacity {
+        return;
+    }
+
+    var particle = particles[index];
+    // Pooled buffers come with whatever they last held.
+    if params.reset != 0u {
+        particle.age = 0.0;
+        particle.lifetime = 0.0;
+    }
+
+    let offset = (index + params.capacity - params.spawn_start) % params.capacity;
+    if offset < params.spawn_count {
+        var state = hash(index ^ hash(params.seed));
+        let angle = random(&state) * 6.2831853;
+        let speed = sqrt(random(&state)) * params.spread;
+        particle.position = params.position;
+        particle.velocity = params.velocity + vec2<f32>(cos(angle), sin(angle)) * speed;
+        particle.size = params.size;
+        particle.age = 0.0;
+        particle.lifetime = params.lifetime * mix(0.75, 1.25, random(&state));
+    } else if particle.age < particle.lifetime {
+        particle.velocity += params.gravity * params.dt;
+        particle.velocity *= max(1.0 - params.drag * params.dt, 0.0);
+        particle.position += particle.velocity * params.dt;
+        particle.age += params.dt;
+    } else if params.reset == 0u {
+        return;
+    }
+
+    let t = clamp(particle.age / max(particle.lifetime, 1e-6), 0.0, 1.0);
+    particle.color = mix(params.color, params.end_color, t);
+    particles[index] = particle;
+}
@@ -0,0 +1,14 @@
+// A round dot fading out towards its edge, premultiplied.
+
+#include "common.wgsl"
+
+struct FragmentInput {
+    @location(0) color: vec4<f32>,
+    @location(1) offset: vec2<f32>,
+}
+
+@fragment
+fn main(fragment: FragmentInput) -> @location(0) vec4<f32> {
+    let falloff = clamp(1.0 - length(fragment.offset), 0.0, 1.0);
+    return premultiply(vec4<f32>(fragment.color.rgb, fragment.color.a * falloff));
+}
@@ -0,0 +1,40 @@
+// Draws each particle of a `ParticlePass` as an instance of a quad made
+// from the vertex index, so no vertex buffer besides the particles is
+// bound. Dead particles collapse to a point and draw nothing.
+
+#include "common.wgsl"
+
+@group(0) @binding(0) var<uniform> uniforms: CameraUniforms;
+
+// `Particle`, read straight from the buffer the simulation writes.
+struct ParticleInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) size: f32,
+    // Age and lifetime.
+    @location(3) life: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // From -1 to 1 across the quad.
+    @location(1) offset: vec2<f32>,
+}
+
+@vertex
+fn main(@builtin(vertex_index) vertex: u32, particle: ParticleInput) -> VertexOutput {
+    var out: VertexOutput;
+    if particle.life.x >= particle.life.y {
+        out.position = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        return out;
+    }
+    // The corners of two triangles, (0, 0) (1, 0) (0, 1) (0, 1) (1, 0)
+    // (1, 1), as bits.
+    let corner = vec2<f32>(f32((0x32u >> vertex) & 1u), f32((0x2cu >> vertex) & 1u));
+    out.offset = corner * 2.0 - 1.0;
+    let pos = particle.position + out.offset * particle.size * 0.5;
+    out.position = to_clip(uniforms, vec3<f32>(pos, 0.0));
+    out.color = particle.color;
+    return out;
+}
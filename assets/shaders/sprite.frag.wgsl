@@ -0,0 +1,14 @@
+// The sprite's texture, tinted.
+
+@group(1) @binding(0) var u_texture: texture_2d<f32>;
+@group(1) @binding(1) var u_sampler: sampler;
+
+struct FragmentInput {
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@fragment
+fn main(fragment: FragmentInput) -> @location(0) vec4<f32> {
+    return textureSample(u_texture, u_sampler, fragment.uv) * fragment.color;
+}
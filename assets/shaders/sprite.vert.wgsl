@@ -0,0 +1,24 @@
+#include "common.wgsl"
+
+@group(0) @binding(0) var<uniform> uniforms: CameraUniforms;
+
+struct VertexInput {
+    @location(0) pos: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = to_clip(uniforms, vec3<f32>(vertex.pos, 0.0));
+    out.uv = vertex.uv;
+    out.color = vertex.color;
+    return out;
+}
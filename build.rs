@@ -0,0 +1,97 @@
+//! Compiles the shaders for builds with the `embed-assets` feature, which
+//! embed the SPIR-V rather than compiling it at startup. Other builds
+//! compile them when they are loaded; see `src/shader_compiler.rs`.
+//!
+//! Every variant of a shader in the features its source checks for is
+//! compiled, and listed in `$OUT_DIR/shaders/shaders.rs` for
+//! `src/vfs/embedded.rs` to embed.
+
+use std::{collections::BTreeSet, env, fmt::Write, fs, path::Path, process};
+
+#[path = "src/shader_compiler.rs"]
+#[allow(dead_code)]
+mod shader_compiler;
+
+use shader_compiler::ShaderFeatures;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/shader_compiler.rs");
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_none() {
+        return;
+    }
+
+    let out_dir = Path::new(&env::var_os("OUT_DIR").expect("cargo sets OUT_DIR")).join("shaders");
+    fs::create_dir_all(&out_dir).expect("failed to create the shader output directory");
+    let mut failed = false;
+    let mut embedded = String::from("&[\n");
+    println!("cargo:rerun-if-changed=assets/shaders");
+    let mut entries: Vec<_> = fs::read_dir("assets/shaders")
+        .expect("failed to list assets/shaders")
+        .map(|entry| entry.expect("failed to list assets/shaders").path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        let file = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file) => file.to_owned(),
+            None => continue,
+        };
+        // Included files are only compiled as part of the shaders.
+        println!("cargo:rerun-if-changed={}", path.display());
+        if !shader_compiler::is_shader(&file) {
+            continue;
+        }
+        // `interface.vert.wgsl` is loaded as `interface.vert`.
+        let name = file.trim_end_matches(".wgsl");
+        let source = fs::read_to_string(&path).expect("failed to read a shader");
+        let mut include = |path: &str| fs::read_to_string(path).map_err(|err| err.to_string());
+
+        // A variant can check for features the ones before it didn't, e.g.
+        // in a file it only includes with some feature, so this goes on
+        // until no variant turns up a new one.
+        let mut tested = ShaderFeatures::NONE;
+        let mut compiled = BTreeSet::new();
+        loop {
+            let pending: Vec<_> = tested
+                .subsets()
+                .filter(|features| !compiled.contains(features))
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+            for features in pending {
+                compiled.insert(features);
+                let output = match shader_compiler::compile(
+                    &format!("assets/shaders/{}", file),
+                    &source,
+                    features,
+                    &mut include,
+                ) {
+                    Ok(output) => output,
+                    Err(err) => {
+                        // Printed to the build output as an error cargo
+                        // shows.
+                        eprintln!("error: {}", err);
+                        failed = true;
+                        continue;
+                    }
+                };
+                tested |= output.tested;
+                let variant = shader_compiler::variant_name(name, features);
+                let path = out_dir.join(format!("{}.spv", variant));
+                fs::write(&path, output.spirv).expect("failed to write a compiled shader");
+                writeln!(
+                    embedded,
+                    "    (\"shaders/{}.spv\", include_bytes!({:?})),",
+                    variant, path
+                )
+                .unwrap();
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+    embedded.push(']');
+    fs::write(out_dir.join("shaders.rs"), embedded).expect("failed to write the shader list");
+}
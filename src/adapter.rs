@@ -0,0 +1,137 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The backends adapters are looked up on; see [`set_backends`].
+static BACKENDS: AtomicU32 = AtomicU32::new(wgpu::BackendBit::PRIMARY.bits());
+
+/// Restricts adapters to `backends`, e.g. to reproduce a driver issue on
+/// one API. Applies to contexts created afterwards.
+pub fn set_backends(backends: wgpu::BackendBit) {
+    BACKENDS.store(backends.bits(), Ordering::Relaxed);
+}
+
+pub fn backends() -> wgpu::BackendBit {
+    wgpu::BackendBit::from_bits_truncate(BACKENDS.load(Ordering::Relaxed))
+}
+
+/// Parses `--backend` / `WGPU_BACKEND` values: a comma-separated list of
+/// `vulkan`, `metal`, `dx12`, `dx11`, `gl`, `primary`, `secondary` or `all`.
+pub fn parse_backends(value: &str) -> Option<wgpu::BackendBit> {
+    value
+        .split(',')
+        .map(|name| match name.trim() {
+            "vulkan" => Some(wgpu::BackendBit::VULKAN),
+            "metal" => Some(wgpu::BackendBit::METAL),
+            "dx12" => Some(wgpu::BackendBit::DX12),
+            "dx11" => Some(wgpu::BackendBit::DX11),
+            "gl" => Some(wgpu::BackendBit::GL),
+            "primary" => Some(wgpu::BackendBit::PRIMARY),
+            "secondary" => Some(wgpu::BackendBit::SECONDARY),
+            "all" => Some(wgpu::BackendBit::all()),
+            _ => None,
+        })
+        .try_fold(wgpu::BackendBit::empty(), |all, backend| {
+            Some(all | backend?)
+        })
+}
+
+/// Which GPU the context runs on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdapterChoice {
+    /// Let wgpu pick one compatible with the surface.
+    Preference(wgpu::PowerPreference),
+    /// The adapter at this position in [`enumerate`].
+    Index(usize),
+    /// The first adapter whose name contains this, ignoring case.
+    Name(String),
+}
+
+impl Default for AdapterChoice {
+    fn default() -> Self {
+        AdapterChoice::Preference(wgpu::PowerPreference::HighPerformance)
+    }
+}
+
+impl AdapterChoice {
+    /// Parses `--adapter` / `GPU_ADAPTER` values: `high-performance`,
+    /// `low-power`, an index into [`enumerate`], or part of an adapter name.
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "high-performance" => AdapterChoice::Preference(wgpu::PowerPreference::HighPerformance),
+            "low-power" => AdapterChoice::Preference(wgpu::PowerPreference::LowPower),
+            _ => match value.parse() {
+                Ok(index) => AdapterChoice::Index(index),
+                Err(_) => AdapterChoice::Name(value.to_owned()),
+            },
+        }
+    }
+}
+
+/// No adapter matched an [`AdapterChoice`].
+#[derive(Debug)]
+pub struct NoAdapter(pub AdapterChoice);
+
+impl fmt::Display for NoAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no GPU adapter matches {:?}", self.0)
+    }
+}
+
+impl std::error::Error for NoAdapter {}
+
+/// Every adapter on the selected [`backends`], in the order
+/// [`AdapterChoice::Index`] refers to them.
+pub fn enumerate() -> Vec<(wgpu::Adapter, wgpu::AdapterInfo)> {
+    wgpu::Adapter::enumerate(backends())
+        .into_iter()
+        .map(|adapter| {
+            let info = adapter.get_info();
+            (adapter, info)
+        })
+        .collect()
+}
+
+/// One line per adapter, for `--list-adapters`.
+pub fn describe(index: usize, info: &wgpu::AdapterInfo) -> String {
+    format!(
+        "{}: {} ({:?}, {:?})",
+        index, info.name, info.backend, info.device_type
+    )
+}
+
+/// Finds the adapter `choice` names.
+///
+/// Only [`AdapterChoice::Preference`] checks compatibility with `surface`;
+/// wgpu 0.5 has no way to ask an explicitly picked adapter whether it can
+/// present to it. Headless contexts pass `None`.
+pub async fn select(
+    choice: &AdapterChoice,
+    surface: Option<&wgpu::Surface>,
+) -> Result<wgpu::Adapter, NoAdapter> {
+    let adapter = match choice {
+        AdapterChoice::Preference(power_preference) => {
+            wgpu::Adapter::request(
+                &wgpu::RequestAdapterOptions {
+                    power_preference: *power_preference,
+                    compatible_surface: surface,
+                },
+                backends(),
+            )
+            .await
+        }
+        AdapterChoice::Index(index) => enumerate()
+            .into_iter()
+            .nth(*index)
+            .map(|(adapter, _)| adapter),
+        AdapterChoice::Name(name) => {
+            let name = name.to_lowercase();
+            enumerate()
+                .into_iter()
+                .find(|(_, info)| info.name.to_lowercase().contains(&name))
+                .map(|(adapter, _)| adapter)
+        }
+    };
+    adapter.ok_or_else(|| NoAdapter(choice.clone()))
+}
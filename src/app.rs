@@ -0,0 +1,878 @@
+//! The demo application: the passes it draws and the event loop driving
+//! them.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use futures::executor::block_on;
+use winit::{
+    event::*,
+    event_loop::{ControlFlow, EventLoop},
+    window::Window,
+};
+
+use crate::{
+    adapter::AdapterChoice,
+    assets::{AssetLoader, Assets, FileWatcher},
+    color::Color,
+    cursor::{self, CursorImage, CursorStyle},
+    display::{DisplayMode, DisplaySettings},
+    ecs::World,
+    error::{EngineError, FrameError},
+    events::EventBus,
+    gpu_mem,
+    input::{self, Input},
+    logging,
+    overlay::DebugOverlay,
+    pacing,
+    passes::{ParticlePass, Pass},
+    pipeline_cache::ShaderVariant,
+    profiler, recorder,
+    render_graph::RenderGraph,
+    renderdoc,
+    rng::Rngs,
+    savegame::{self, SaveGame},
+    scene::Scene,
+    scene_graph,
+    settings::{KeyBindings, Settings},
+    state::{GameState, StateContext, StateStack, Transition},
+    tasks::Tasks,
+    time::Time,
+    timers::Timers,
+    vfs, Context, Frame, InterfacePass,
+};
+
+mod screens;
+
+/// Time scale the slow motion key switches to.
+const SLOW_MOTION: f32 = 0.25;
+
+/// Offscreen size of headless runs without a resolution.
+const HEADLESS_SIZE: [u32; 2] = [1280, 720];
+
+/// Particles alive at once in gameplay's trail.
+const PARTICLE_CAPACITY: u32 = 4096;
+
+/// Sent when the window's logical size changes.
+#[derive(Copy, Clone, Debug)]
+pub struct WindowResized {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// What a run starts with: the saved settings, with whatever the command
+/// line and environment override applied on top.
+pub struct Options {
+    pub adapter_choice: AdapterChoice,
+    pub present_mode: wgpu::PresentMode,
+    pub sample_count: u32,
+    pub frame_limit: Option<u32>,
+    pub display: DisplaySettings,
+    /// Window size, or the offscreen size of headless runs.
+    pub resolution: Option<[u32; 2]>,
+    /// A scene file to play instead of showing the menu.
+    pub scene: Option<PathBuf>,
+    /// Reload scenes and shaders when their files change. On by default
+    /// in debug builds.
+    pub hot_reload: bool,
+    /// Seed of the random streams; taken from the clock if `None`.
+    pub seed: Option<u64>,
+    /// Where gameplay is saved on quitting and resumed from on startup.
+    /// `None` doesn't save.
+    pub save_path: Option<PathBuf>,
+    /// Saved to `settings_path` when changed from within the application,
+    /// e.g. by toggling vsync. Overrides in the fields above aren't saved.
+    pub settings: Settings,
+    pub settings_path: PathBuf,
+}
+
+impl Options {
+    /// Options matching `settings`, which were loaded from `settings_path`.
+    pub fn new(settings: Settings, settings_path: PathBuf) -> Self {
+        let defaults = DisplaySettings::default();
+        let display = DisplaySettings {
+            mode: settings.fullscreen,
+            fullscreen_mode: match settings.fullscreen {
+                DisplayMode::Windowed => defaults.fullscreen_mode,
+                mode => mode,
+            },
+            ..defaults
+        };
+        Self {
+            adapter_choice: AdapterChoice::parse(&settings.adapter),
+            present_mode: if settings.vsync {
+                wgpu::PresentMode::Fifo
+            } else {
+                wgpu::PresentMode::Immediate
+            },
+            sample_count: settings.msaa,
+            frame_limit: None,
+            display,
+            resolution: settings.resolution,
+            scene: None,
+            hot_reload: cfg!(debug_assertions),
+            seed: None,
+            save_path: Some(savegame::DEFAULT_PATH.into()),
+            settings,
+            settings_path,
+        }
+    }
+}
+
+pub struct Application {
+    graph: RenderGraph,
+    /// Given to the graph before the interface.
+    passes: Vec<Box<dyn Pass>>,
+    interface_pass: InterfacePass,
+    cursor: CursorStyle,
+    /// The style last applied to the window, to skip redundant updates.
+    applied_cursor: Option<CursorStyle>,
+    input: Input,
+    world: World,
+    states: StateStack,
+    tasks: Tasks,
+    timers: Timers,
+    rngs: Rngs,
+    events: EventBus,
+    scenes: Assets<Scene>,
+    loader: AssetLoader,
+    /// Set when hot reloading.
+    watcher: Option<FileWatcher>,
+    key_bindings: KeyBindings,
+    time_scale: f32,
+    /// Stops the simulation without forgetting `time_scale`.
+    paused: bool,
+    /// Window size in logical pixels, as of the last resize.
+    logical_size: [f32; 2],
+    show_profiler: bool,
+    overlay: DebugOverlay,
+    /// Print the render stats on the next update.
+    log_stats: bool,
+}
+
+impl Application {
+    pub fn new(ctx: &mut Context, options: &Options) -> Result<Self, EngineError> {
+        let mut interface_pass = InterfacePass::new(ctx)?;
+        interface_pass.set_virtual_size(options.settings.interface_size);
+        let size = ctx.logical_size();
+        let logical_size = [size.width, size.height];
+        let mut world = World::default();
+        let mut events = EventBus::default();
+        let mut timers = Timers::default();
+        let mut rngs = match options.seed {
+            Some(seed) => Rngs::new(seed),
+            None => Rngs::from_time(),
+        };
+        // Logged so a run can be reproduced with --seed.
+        log::info!("Random seed {}", rngs.seed());
+        let mut scenes = Assets::default();
+        let loader = AssetLoader::default();
+        let first: Box<dyn GameState> = match &options.scene {
+            Some(path) => Box::new(screens::Loading::new(path.clone())),
+            None => {
+                let scene = scenes.load(ctx, "scenes/gameplay.ron")?;
+                Box::new(screens::Menu {
+                    scene,
+                    save_path: options.save_path.clone(),
+                    saved: options.save_path.as_deref().and_then(load_save),
+                })
+            }
+        };
+        let mut states = StateStack::default();
+        let mut cx = StateContext {
+            world: &mut world,
+            events: &mut events,
+            timers: &mut timers,
+            rngs: &mut rngs,
+            scenes: &mut scenes,
+            loader: &loader,
+            logical_size,
+        };
+        states.apply(&mut cx, Transition::Push(first));
+        let watcher = if options.hot_reload {
+            Some(FileWatcher::default())
+        } else {
+            None
+        };
+
+        // Screens paint their own background over the clear, except
+        // gameplay, which shows the particles drawn under the interface.
+        let mut graph = RenderGraph::default();
+        graph.set_clear_color(Some(Color::WHITE));
+        let particles = ParticlePass::new(ctx, PARTICLE_CAPACITY)?;
+
+        Ok(Self {
+            graph,
+            passes: vec![Box::new(particles)],
+            interface_pass,
+            cursor: CursorStyle::default(),
+            applied_cursor: None,
+            input: Input::default(),
+            world,
+            states,
+            tasks: Tasks::default(),
+            timers,
+            rngs,
+            events,
+            scenes,
+            loader,
+            watcher,
+            key_bindings: options.settings.key_bindings.clone(),
+            time_scale: 1.0,
+            paused: false,
+            logical_size,
+            show_profiler: false,
+            overlay: DebugOverlay::default(),
+            log_stats: false,
+        })
+    }
+
+    /// Advances the simulation by one [`FIXED_STEP`](crate::time::FIXED_STEP).
+    pub fn update(&mut self, time: &Time) {
+        let _scope = profiler::scope("update");
+        let mut cx = StateContext {
+            world: &mut self.world,
+            events: &mut self.events,
+            timers: &mut self.timers,
+            rngs: &mut self.rngs,
+            scenes: &mut self.scenes,
+            loader: &self.loader,
+            logical_size: self.logical_size,
+        };
+        self.states.update(&mut cx, time);
+        self.tasks.update(&mut cx, time);
+        Timers::update(&mut cx);
+        scene_graph::propagate(&mut self.world);
+        self.events.update();
+        self.scenes.collect();
+    }
+
+    /// Sends [`WindowResized`] after `ctx` was resized.
+    pub fn resized(&mut self, ctx: &Context) {
+        let size = ctx.logical_size();
+        self.logical_size = [size.width, size.height];
+        self.events.send(WindowResized {
+            width: size.width,
+            height: size.height,
+        });
+    }
+
+    /// How fast the simulation runs, e.g. 0.25 for slow motion. The
+    /// interface keeps animating in real time.
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Stops the simulation until unpaused, keeping the time scale.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The scale the main loop gives [`Time`]: 0 while paused.
+    pub fn effective_time_scale(&self) -> f32 {
+        if self.paused {
+            0.0
+        } else {
+            self.time_scale
+        }
+    }
+
+    /// Lets subsystems outside the states send and read events.
+    pub fn events_mut(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    /// Exits every state, e.g. so gameplay saves when the window closes.
+    pub fn quit(&mut self) {
+        let mut cx = StateContext {
+            world: &mut self.world,
+            events: &mut self.events,
+            timers: &mut self.timers,
+            rngs: &mut self.rngs,
+            scenes: &mut self.scenes,
+            loader: &self.loader,
+            logical_size: self.logical_size,
+        };
+        self.states.apply(&mut cx, Transition::Quit);
+    }
+
+    /// Whether the last state was popped, which ends the application.
+    pub fn should_quit(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Queues this frame's interface. Anything simulated should be drawn
+    /// `time.alpha()` of the way from its previous state to its current one.
+    fn build_interface(&mut self, ctx: &Context, time: &Time) {
+        let cursor = self.effective_cursor();
+        let pass = &mut self.interface_pass;
+
+        let build = profiler::scope("interface/build");
+        pass.clear();
+        let size = ctx.logical_size();
+        pass.set_logical_size(size.width, size.height);
+
+        self.states.draw(pass, &self.world, time);
+
+        if let (CursorStyle::Custom(image), Some([x, y])) = (cursor, self.input.cursor_position()) {
+            let scale = ctx.scale_factor() as f32;
+            let [x, y] = pass.to_interface([x / scale, y / scale]);
+            let min = [x - image.hotspot[0] as f32, y - image.hotspot[1] as f32];
+            let max = [min[0] + image.size[0] as f32, min[1] + image.size[1] as f32];
+            pass.draw_image(image.uv, min, max, Color::WHITE);
+        }
+        drop(build);
+
+        if self.show_profiler {
+            let width = (pass.logical_size()[0] - 16.0).clamp(0.0, 480.0);
+            profiler::draw(pass, [8.0, 8.0], width);
+        }
+
+        // The counts are from the previous frame; this one isn't drawn yet.
+        self.overlay.frame(time.delta());
+        self.overlay.draw(pass, &ctx.frame_stats());
+        if std::mem::take(&mut self.log_stats) {
+            log::info!("last frame: {}", ctx.frame_stats());
+        }
+
+        pass.update();
+    }
+
+    pub fn cursor(&self) -> CursorStyle {
+        self.cursor
+    }
+
+    /// Changes the pointer; the window picks it up on the next
+    /// `apply_cursor`.
+    pub fn set_cursor(&mut self, cursor: CursorStyle) {
+        self.cursor = cursor;
+    }
+
+    /// Adds a custom cursor image to the interface atlas.
+    pub fn load_cursor(
+        &mut self,
+        ctx: &mut Context,
+        image: &image::RgbaImage,
+        hotspot: [u32; 2],
+    ) -> Result<Option<CursorImage>, gpu_mem::BudgetExceeded> {
+        CursorImage::new(ctx, self.interface_pass.atlas_mut(), image, hotspot)
+    }
+
+    /// Hidden while the pointer is locked, `cursor` otherwise.
+    fn effective_cursor(&self) -> CursorStyle {
+        if self.input.pointer_lock().is_locked() {
+            CursorStyle::Hidden
+        } else {
+            self.cursor
+        }
+    }
+
+    /// Switches relative mouse mode on or off; see [`PointerLock`](cursor::PointerLock).
+    pub fn set_pointer_locked(&mut self, locked: bool) {
+        self.input.pointer_lock_mut().set_locked(locked);
+    }
+
+    pub fn is_pointer_locked(&self) -> bool {
+        self.input.pointer_lock().is_locked()
+    }
+
+    /// Raw mouse motion since the last call, while the pointer is locked.
+    pub fn take_mouse_delta(&mut self) -> [f64; 2] {
+        self.input.pointer_lock_mut().take_delta()
+    }
+
+    /// Shows the current cursor style and pointer lock on `window` if they
+    /// changed.
+    pub fn apply_cursor(&mut self, window: &Window) {
+        self.input.pointer_lock_mut().apply(window);
+        let cursor = self.effective_cursor();
+        if self.applied_cursor != Some(cursor) {
+            cursor.apply(window);
+            self.applied_cursor = Some(cursor);
+        }
+    }
+
+    /// Adds a pass to the render graph. Passes drawing into the frame are
+    /// drawn in the order they were added, after the gameplay particles and
+    /// before the interface. Each takes what it draws from the world in
+    /// [`Pass::extract`].
+    pub fn add_pass(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    /// Where passes added with [`Application::add_pass`] declare their
+    /// attachments.
+    pub fn graph_mut(&mut self) -> &mut RenderGraph {
+        &mut self.graph
+    }
+
+    /// Reloads the scenes and shaders whose files changed, if hot
+    /// reloading. Shaders are watched by their WGSL source and the files it
+    /// includes, and recompiled when any of them changes. Files served from
+    /// an archive or embedded in the binary never change.
+    fn reload_changed(&mut self, ctx: &mut Context) {
+        let watcher = match &self.watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+        // Files are watched where the vfs finds them, and reloaded by the
+        // path they were loaded with.
+        let mut scene_files = HashMap::new();
+        for path in self.scenes.paths() {
+            if let Some(file) = vfs::resolve(path) {
+                watcher.watch(&file);
+                scene_files.insert(file, path.to_owned());
+            }
+        }
+        let mut shader_files: HashMap<PathBuf, Vec<ShaderVariant>> = HashMap::new();
+        for variant in ctx.pipelines.shader_variants() {
+            for path in ctx.pipelines.shader_files(variant) {
+                if let Some(file) = vfs::resolve(path) {
+                    watcher.watch(&file);
+                    shader_files.entry(file).or_default().push(variant);
+                }
+            }
+        }
+        let changed = watcher.changed();
+        if changed.is_empty() {
+            return;
+        }
+
+        let scenes: Vec<PathBuf> = changed
+            .iter()
+            .filter_map(|file| scene_files.get(file).cloned())
+            .collect();
+        self.scenes.reload_changed(ctx, &scenes, &mut self.events);
+
+        // A changed include recompiles every shader variant using it, but
+        // each only once.
+        let mut variants: Vec<ShaderVariant> = vec![];
+        for variant in changed
+            .iter()
+            .filter_map(|file| shader_files.get(file))
+            .flatten()
+        {
+            if !variants.contains(variant) {
+                variants.push(*variant);
+            }
+        }
+        for variant in variants {
+            // A shader that fails to compile keeps its old module and
+            // pipelines, so the last good version stays on screen.
+            match ctx
+                .pipelines
+                .recompile_shader(&ctx.device, &ctx.bind_groups, variant)
+            {
+                Ok(rebuilt) => log::info!(
+                    "Reloaded shader {}, rebuilding {} pipelines",
+                    variant,
+                    rebuilt.unwrap_or(0)
+                ),
+                Err(err) => log::error!("Failed to reload shader {}: {}", variant, err),
+            }
+        }
+    }
+
+    /// Reloads changed files, finishes background asset loads, builds the
+    /// interface, lets the other passes extract from the world and records
+    /// every pass into `frame`.
+    pub fn render(
+        &mut self,
+        ctx: &mut Context,
+        frame: &mut Frame,
+        time: &Time,
+    ) -> Result<(), EngineError> {
+        self.reload_changed(ctx);
+        self.scenes.poll(ctx, &mut self.events);
+        self.build_interface(ctx, time);
+        for pass in &mut self.passes {
+            pass.extract(&self.world, time);
+        }
+        let mut passes: Vec<&mut dyn Pass> = self
+            .passes
+            .iter_mut()
+            .map(|pass| pass.as_mut() as &mut dyn Pass)
+            .collect();
+        passes.push(&mut self.interface_pass);
+        self.graph.render(ctx, frame, &mut passes)
+    }
+
+    /// Rebuilds all passes after the context recovered from a lost device.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), EngineError> {
+        self.graph.release();
+        for pass in &mut self.passes {
+            pass.recreate(ctx)?;
+        }
+        self.interface_pass.recreate(ctx)
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.input.modifiers()
+    }
+
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        self.input.window_event(event);
+        let keys = &self.key_bindings;
+        match input::pressed_key(event) {
+            Some(key) if key == keys.profiler => {
+                // The profiler key (F4 by default) shows the profiler;
+                // pressing it while shown also logs the last frame with
+                // scope names.
+                if self.show_profiler {
+                    profiler::log_last_frame();
+                }
+                self.show_profiler = !self.show_profiler;
+                profiler::set_enabled(self.show_profiler);
+            }
+            Some(key) if key == keys.overlay => {
+                // Like the profiler key, pressing the overlay key while the
+                // overlay is shown also logs the counts.
+                self.log_stats = self.overlay.is_visible();
+                self.overlay.toggle();
+            }
+            Some(key) if key == keys.wireframe => {
+                let wireframe = !self.interface_pass.wireframe();
+                self.interface_pass.set_wireframe(wireframe);
+            }
+            Some(key) if key == keys.pause_time => {
+                self.paused = !self.paused;
+                log::info!(
+                    "Simulation {}",
+                    if self.paused { "paused" } else { "resumed" }
+                );
+            }
+            Some(key) if key == keys.slow_motion => {
+                self.time_scale = if self.time_scale == 1.0 {
+                    SLOW_MOTION
+                } else {
+                    1.0
+                };
+                log::info!("Time scale {}", self.time_scale);
+            }
+            Some(key) if key == keys.save_scene => {
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs());
+                let path = format!("scene-{}.ron", timestamp);
+                match Scene::from_world(&self.world).save(&path) {
+                    Ok(()) => log::info!("Saved scene to {}", path),
+                    Err(err) => log::error!("Failed to save scene: {}", err),
+                }
+            }
+            _ => {
+                let mut cx = StateContext {
+                    world: &mut self.world,
+                    events: &mut self.events,
+                    timers: &mut self.timers,
+                    rngs: &mut self.rngs,
+                    scenes: &mut self.scenes,
+                    loader: &self.loader,
+                    logical_size: self.logical_size,
+                };
+                self.states.input(&mut cx, event);
+            }
+        }
+        true
+    }
+
+    pub fn device_input(&mut self, event: &DeviceEvent) {
+        self.input.device_event(event);
+    }
+}
+
+/// Opens the application in `window` and runs it until it exits.
+pub fn run(event_loop: EventLoop<()>, window: Window, options: Options) -> ! {
+    options.display.apply(&window);
+    match vfs::read("icon.png") {
+        Ok(bytes) => match cursor::load_icon(&bytes) {
+            Ok(icon) => window.set_window_icon(Some(icon)),
+            Err(err) => log::warn!("{}", err),
+        },
+        Err(err) => log::warn!("Failed to read the window icon: {}", err),
+    }
+
+    // Must be checked before the device exists, as RenderDoc only hooks
+    // devices created after it attached.
+    let renderdoc = renderdoc::RenderDoc::attach();
+    if let Some(renderdoc) = &renderdoc {
+        let (major, minor, patch) = renderdoc.version();
+        log::info!(
+            "RenderDoc {}.{}.{} attached, {:?} captures a frame",
+            major,
+            minor,
+            patch,
+            options.settings.key_bindings.renderdoc_capture
+        );
+    }
+
+    let (mut ctx, mut app) = match block_on(start(&window, &options)) {
+        Ok(started) => started,
+        Err(err) => {
+            log::error!("Failed to start: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let Options {
+        frame_limit,
+        mut display,
+        mut settings,
+        settings_path,
+        ..
+    } = options;
+    let keys = settings.key_bindings.clone();
+    let initial_resolution = settings.resolution;
+    let save_settings = move |settings: &Settings| {
+        if let Err(err) = settings.save(&settings_path) {
+            log::error!("{}", err);
+        }
+    };
+    let mut time = Time::default();
+    let mut pacing = pacing::Pacing::default();
+    pacing.set_frame_limit(frame_limit);
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent {
+            ref event,
+            window_id,
+        } if window_id == window.id() && app.input(event) => match event {
+            WindowEvent::CloseRequested => {
+                app.quit();
+                *control_flow = ControlFlow::Exit;
+            }
+            _ if app.should_quit() => *control_flow = ControlFlow::Exit,
+
+            WindowEvent::Focused(focused) => pacing.focus_changed(*focused),
+
+            WindowEvent::Resized(physical_size) => {
+                pacing.resized(*physical_size);
+                // Only windowed sizes are worth restoring, and minimizing
+                // reports zero. Saved on exit rather than every resize.
+                if display.mode == DisplayMode::Windowed && physical_size.width > 0 {
+                    settings.resolution = Some([physical_size.width, physical_size.height]);
+                }
+                exit_on_error(
+                    ctx.retry_out_of_memory(|ctx| block_on(ctx.resize(*physical_size))),
+                    control_flow,
+                );
+                app.resized(&ctx);
+            }
+
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => {
+                ctx.set_scale_factor(*scale_factor);
+                pacing.resized(**new_inner_size);
+                let size = **new_inner_size;
+                exit_on_error(
+                    ctx.retry_out_of_memory(|ctx| block_on(ctx.resize(size))),
+                    control_flow,
+                );
+                app.resized(&ctx);
+            }
+
+            _ => match input::pressed_key(event) {
+                Some(key) if key == keys.toggle_fullscreen && app.modifiers().alt() => {
+                    display.toggle();
+                    display.apply(&window);
+                    settings.fullscreen = display.mode;
+                    save_settings(&settings);
+                    let size = window.inner_size();
+                    exit_on_error(
+                        ctx.retry_out_of_memory(|ctx| block_on(ctx.resize(size))),
+                        control_flow,
+                    );
+                }
+
+                Some(key) if key == keys.screenshot => {
+                    let timestamp = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |time| time.as_secs());
+                    ctx.capture_frame(format!("screenshot-{}.png", timestamp));
+                }
+
+                Some(key) if key == keys.renderdoc_capture => match &renderdoc {
+                    Some(renderdoc) => {
+                        renderdoc.trigger_capture();
+                        log::info!(
+                            "RenderDoc capture {} requested",
+                            renderdoc.capture_count() + 1
+                        );
+                    }
+                    None => log::warn!("RenderDoc isn't attached; launch the app from RenderDoc"),
+                },
+
+                Some(key) if key == keys.record => {
+                    if let Some(dropped) = ctx.stop_recording() {
+                        log::info!("Stopped recording, {} frames dropped", dropped);
+                    } else {
+                        let timestamp = std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .map_or(0, |time| time.as_secs());
+                        let sink = if std::env::var_os("RECORD_VIDEO").is_some() {
+                            recorder::Sink::Ffmpeg(format!("recording-{}.mp4", timestamp).into())
+                        } else {
+                            recorder::Sink::Images(format!("recording-{}", timestamp).into())
+                        };
+                        if let Err(err) = ctx.start_recording(sink) {
+                            log::error!("Failed to start recording: {}", err);
+                        }
+                    }
+                }
+
+                Some(key) if key == keys.toggle_vsync => {
+                    let vsync = ctx.present_mode() != wgpu::PresentMode::Fifo;
+                    exit_on_error(
+                        ctx.retry_out_of_memory(|ctx| ctx.set_vsync(vsync)),
+                        control_flow,
+                    );
+                    settings.vsync = vsync;
+                    save_settings(&settings);
+                }
+
+                _ => {}
+            },
+        },
+
+        Event::DeviceEvent { ref event, .. } => app.device_input(event),
+
+        Event::LoopDestroyed if settings.resolution != initial_resolution => {
+            save_settings(&settings)
+        }
+
+        Event::RedrawRequested(_) => {
+            let _frame = logging::frame();
+            profiler::begin_frame();
+            time.set_scale(app.effective_time_scale());
+            time.tick();
+            while time.fixed_step() {
+                app.update(&time);
+            }
+            if app.should_quit() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            app.apply_cursor(&window);
+            let result = ctx.begin_frame().and_then(|mut frame| {
+                app.render(&mut ctx, &mut frame, &time)?;
+                ctx.end_frame(frame)
+            });
+            profiler::end_frame();
+            match result {
+                Ok(()) => {
+                    if let Some(report) = ctx.memory.check_growth() {
+                        log::warn!("{}", report);
+                    }
+                }
+                Err(err @ EngineError::OutOfMemory(_)) => {
+                    log::error!("{}\n{}", err, ctx.memory.report());
+                    ctx.release_transient_memory();
+                }
+                Err(EngineError::SwapChain(FrameError::Timeout)) => {}
+                Err(EngineError::SwapChain(FrameError::Outdated)) => {
+                    let size = window.inner_size();
+                    exit_on_error(
+                        ctx.retry_out_of_memory(|ctx| block_on(ctx.resize(size))),
+                        control_flow,
+                    );
+                }
+                Err(err @ EngineError::DeviceLost(_)) => {
+                    log::error!("{}, recreating the GPU context", err);
+                    let recovered =
+                        block_on(ctx.recover(Some(&window))).and_then(|()| app.recreate(&mut ctx));
+                    exit_on_error(recovered, control_flow);
+                }
+                Err(err) => exit_on_error(Err(err), control_flow),
+            }
+        }
+
+        Event::MainEventsCleared if pacing.schedule(control_flow) => {
+            window.request_redraw();
+        }
+        _ => {}
+    });
+}
+
+/// Creates the context and application for `window`.
+async fn start(window: &Window, options: &Options) -> Result<(Context, Application), EngineError> {
+    let mut ctx = Context::new(window, options.adapter_choice.clone()).await?;
+    ctx.set_present_mode(options.present_mode)?;
+    ctx.set_sample_count(options.sample_count)?;
+    let app = Application::new(&mut ctx, options)?;
+    Ok((ctx, app))
+}
+
+/// Runs the application for `frames` frames without a window, e.g. to
+/// reproduce a GPU issue on a machine without a display, and returns the
+/// last frame. Nothing sends input, so the menu stays up unless
+/// `options.scene` is set.
+pub fn run_headless(options: &Options, frames: u32) -> Result<image::RgbaImage, EngineError> {
+    let [width, height] = options.resolution.unwrap_or(HEADLESS_SIZE);
+    let mut ctx = block_on(Context::new_headless(
+        width,
+        height,
+        options.adapter_choice.clone(),
+    ))?;
+    ctx.set_sample_count(options.sample_count)?;
+    let mut app = Application::new(&mut ctx, options)?;
+    let mut time = Time::default();
+
+    for _ in 0..frames {
+        let _frame = logging::frame();
+        time.set_scale(app.effective_time_scale());
+        time.tick();
+        while time.fixed_step() {
+            app.update(&time);
+        }
+        if app.should_quit() {
+            break;
+        }
+        let result = ctx.begin_frame().and_then(|mut frame| {
+            app.render(&mut ctx, &mut frame, &time)?;
+            ctx.end_frame(frame)
+        });
+        match result {
+            Err(err @ EngineError::OutOfMemory(_)) => {
+                log::error!("{}\n{}", err, ctx.memory.report());
+                ctx.release_transient_memory();
+            }
+            result => result?,
+        }
+    }
+    ctx.read_pixels()
+}
+
+/// The save at `path`, if there is a readable one.
+fn load_save(path: &Path) -> Option<SaveGame> {
+    if !path.exists() {
+        return None;
+    }
+    match SaveGame::load(path) {
+        Ok(save) => {
+            log::info!("Loaded save game from {}", path.display());
+            Some(save)
+        }
+        Err(err) => {
+            log::error!("{}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Logs `result` and ends the main loop if it is an error.
+fn exit_on_error<E: Into<EngineError>>(result: Result<(), E>, control_flow: &mut ControlFlow) {
+    if let Err(err) = result {
+        log::error!("{}", err.into());
+        *control_flow = ControlFlow::Exit;
+    }
+}
@@ -0,0 +1,322 @@
+//! The demo's title menu, gameplay and pause screens.
+
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use winit::event::{VirtualKeyCode, WindowEvent};
+
+use crate::{
+    assets::{AssetReloaded, Handle, LoadBatch},
+    color::Color,
+    components::{Sprite, Transform, Velocity},
+    debug_font,
+    ecs::{Entity, World},
+    events::EventReader,
+    input,
+    material::Blend,
+    passes::ParticleEmitter,
+    savegame::SaveGame,
+    scene::Scene,
+    scene_graph,
+    state::{GameState, StateContext, Transition},
+    tasks::{self, TaskHandle},
+    time::{Time, FIXED_STEP},
+    InterfacePass,
+};
+
+const TEXT_SCALE: f32 = 4.0;
+
+/// Draws `text` centred horizontally, with its top at `top`.
+fn draw_centered(pass: &mut InterfacePass, top: f32, color: Color, text: &str) {
+    let left = (pass.logical_size()[0] - debug_font::text_width(text, TEXT_SCALE)) / 2.0;
+    debug_font::draw_text(pass, [left, top], TEXT_SCALE, color, text);
+}
+
+/// Shows a progress bar while a scene file loads in the background, then
+/// plays it.
+pub struct Loading {
+    path: PathBuf,
+    batch: LoadBatch,
+}
+
+impl Loading {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            batch: LoadBatch::default(),
+        }
+    }
+}
+
+impl GameState for Loading {
+    fn enter(&mut self, cx: &mut StateContext) {
+        cx.scenes.load_async(cx.loader, &self.batch, &self.path);
+    }
+
+    fn update(&mut self, cx: &mut StateContext, _time: &Time) -> Transition {
+        if !self.batch.progress().is_done() {
+            return Transition::None;
+        }
+        match cx.scenes.handle(&self.path) {
+            Some(scene) => Transition::Replace(Box::new(Gameplay::new(scene))),
+            // The loader already logged why.
+            None => Transition::Quit,
+        }
+    }
+
+    fn draw(&mut self, pass: &mut InterfacePass, _world: &World, _time: &Time) {
+        let [width, height] = pass.logical_size();
+        let palette = *pass.palette();
+        pass.draw_rect([0.0, 0.0], [width, height], palette.background);
+        draw_centered(pass, height / 3.0, palette.text, "LOADING");
+
+        let bar_width = width / 2.0;
+        let left = (width - bar_width) / 2.0;
+        let top = height / 2.0;
+        let filled = bar_width * self.batch.progress().fraction();
+        pass.draw_rect([left, top], [left + bar_width, top + 8.0], palette.track);
+        pass.draw_rect([left, top], [left + filled, top + 8.0], palette.text);
+    }
+}
+
+pub struct Menu {
+    /// What gameplay starts with.
+    pub scene: Handle<Scene>,
+    /// Where gameplay saves to; see [`Gameplay::save_to`].
+    pub save_path: Option<PathBuf>,
+    /// Continued from instead of starting the scene over.
+    pub saved: Option<SaveGame>,
+}
+
+impl GameState for Menu {
+    fn draw(&mut self, pass: &mut InterfacePass, _world: &World, _time: &Time) {
+        let [width, height] = pass.logical_size();
+        let palette = *pass.palette();
+        pass.draw_rect([0.0, 0.0], [width, height], palette.background);
+        draw_centered(pass, height / 3.0, palette.text, "NOMADS OF MYRIA");
+        let prompt = if self.saved.is_some() {
+            "SPACE TO CONTINUE"
+        } else {
+            "SPACE TO PLAY"
+        };
+        draw_centered(pass, height / 2.0, palette.text_muted, prompt);
+    }
+
+    fn input(&mut self, _cx: &mut StateContext, event: &WindowEvent) -> Transition {
+        match input::pressed_key(event) {
+            Some(VirtualKeyCode::Space) => {
+                let mut gameplay = Gameplay::new(self.scene.clone());
+                if let Some(path) = &self.save_path {
+                    gameplay.save_to(path);
+                }
+                if let Some(saved) = self.saved.take() {
+                    gameplay.resume(saved);
+                }
+                Transition::Replace(Box::new(gameplay))
+            }
+            Some(VirtualKeyCode::Escape) => Transition::Quit,
+            _ => Transition::None,
+        }
+    }
+}
+
+/// Plays a scene: entities with a velocity bounce around the window,
+/// simulated at the fixed step and drawn interpolated between steps. The
+/// first of them leaves a trail of particles. Starts over when the scene
+/// file is hot reloaded.
+pub struct Gameplay {
+    scene: Handle<Scene>,
+    /// Spawned instead of the scene on enter.
+    resumed: Option<SaveGame>,
+    save_path: Option<PathBuf>,
+    /// Spawned from the scene on enter.
+    entities: Vec<Entity>,
+    reloads: Option<EventReader<AssetReloaded<Scene>>>,
+    /// Shows "GO" for a moment after entering.
+    banner: Rc<Cell<bool>>,
+    intro: Option<TaskHandle>,
+}
+
+impl Gameplay {
+    pub fn new(scene: Handle<Scene>) -> Self {
+        Self {
+            scene,
+            resumed: None,
+            save_path: None,
+            entities: vec![],
+            reloads: None,
+            banner: Rc::default(),
+            intro: None,
+        }
+    }
+
+    /// Saves the world to `path` when gameplay exits, e.g. on quitting.
+    pub fn save_to(&mut self, path: impl AsRef<Path>) {
+        self.save_path = Some(path.as_ref().to_owned());
+    }
+
+    /// Starts from `save` instead of the scene.
+    pub fn resume(&mut self, save: SaveGame) {
+        self.resumed = Some(save);
+    }
+
+    fn spawn(&mut self, cx: &mut StateContext) {
+        self.entities = match self.resumed.take() {
+            Some(save) => save.restore(cx.world),
+            None => cx.scenes.get(&self.scene).spawn(cx.world),
+        };
+        // Not saved with the scene, so added back on resuming too.
+        let leader = self.entities.iter().find_map(|&entity| {
+            match (
+                cx.world.get::<Velocity>(entity),
+                cx.world.get::<Sprite>(entity),
+            ) {
+                (Some(_), Some(&sprite)) => Some((entity, sprite)),
+                _ => None,
+            }
+        });
+        if let Some((entity, sprite)) = leader {
+            cx.world.insert(entity, trail(sprite));
+        }
+        scene_graph::propagate(cx.world);
+    }
+
+    fn despawn(&mut self, cx: &mut StateContext) {
+        for entity in self.entities.drain(..) {
+            cx.world.despawn(entity);
+        }
+    }
+}
+
+impl GameState for Gameplay {
+    fn enter(&mut self, cx: &mut StateContext) {
+        self.reloads = Some(cx.events.reader());
+        self.spawn(cx);
+
+        let banner = self.banner.clone();
+        banner.set(true);
+        self.intro = Some(tasks::spawn(async move {
+            tasks::wait_secs(1.5).await;
+            banner.set(false);
+        }));
+    }
+
+    fn exit(&mut self, cx: &mut StateContext) {
+        if let Some(intro) = self.intro.take() {
+            intro.cancel();
+        }
+        if let Some(path) = &self.save_path {
+            match SaveGame::from_world(cx.world).save(path) {
+                Ok(()) => log::info!("Saved game to {}", path.display()),
+                Err(err) => log::error!("Failed to save game: {}", err),
+            }
+        }
+        self.despawn(cx);
+    }
+
+    fn update(&mut self, cx: &mut StateContext, _time: &Time) -> Transition {
+        if let Some(reloads) = &mut self.reloads {
+            let scene = &self.scene;
+            if cx.events.read(reloads).any(|event| event.handle == *scene) {
+                self.despawn(cx);
+                self.spawn(cx);
+            }
+        }
+
+        let step = FIXED_STEP.as_secs_f32();
+        for &entity in &self.entities {
+            let (mut velocity, size) = match (
+                cx.world.get::<Velocity>(entity),
+                cx.world.get::<Sprite>(entity),
+            ) {
+                (Some(&Velocity(velocity)), Some(sprite)) => (velocity, sprite.size),
+                _ => continue,
+            };
+            let transform = match cx.world.get_mut::<Transform>(entity) {
+                Some(transform) => transform,
+                None => continue,
+            };
+            for axis in 0..2 {
+                let bound = (cx.logical_size[axis] - size[axis]).max(0.0);
+                let position = &mut transform.translation[axis];
+                *position += velocity[axis] * step;
+                if !(0.0..=bound).contains(position) {
+                    *position = position.clamp(0.0, bound);
+                    velocity[axis] = -velocity[axis];
+                }
+            }
+            cx.world.insert(entity, Velocity(velocity));
+        }
+        Transition::None
+    }
+
+    /// Draws over the graph's white clear rather than a background of its
+    /// own, which would hide the particles.
+    fn draw(&mut self, pass: &mut InterfacePass, world: &World, time: &Time) {
+        let height = pass.logical_size()[1];
+        pass.extract(world, time.alpha());
+        if self.banner.get() {
+            let palette = *pass.palette();
+            draw_centered(pass, height / 3.0, palette.background, "GO");
+        }
+    }
+
+    fn input(&mut self, _cx: &mut StateContext, event: &WindowEvent) -> Transition {
+        match input::pressed_key(event) {
+            Some(VirtualKeyCode::Escape) | Some(VirtualKeyCode::P) => {
+                Transition::Push(Box::new(Pause))
+            }
+            _ => Transition::None,
+        }
+    }
+}
+
+/// Particles left behind the centre of a sprite, in its color.
+fn trail(sprite: Sprite) -> ParticleEmitter {
+    ParticleEmitter {
+        position: [sprite.size[0] / 2.0, sprite.size[1] / 2.0],
+        spread: 30.0,
+        drag: 1.0,
+        color: sprite.color,
+        end_color: sprite.color.with_alpha(0.0),
+        size: 6.0,
+        lifetime: 0.8,
+        rate: 120.0,
+        ..Default::default()
+    }
+}
+
+/// Dims the paused game under it.
+pub struct Pause;
+
+impl GameState for Pause {
+    fn draw(&mut self, pass: &mut InterfacePass, _world: &World, _time: &Time) {
+        let [width, height] = pass.logical_size();
+        let palette = *pass.palette();
+        pass.set_blend(Blend::ALPHA);
+        pass.draw_rect([0.0, 0.0], [width, height], palette.panel);
+        pass.set_blend(Blend::REPLACE);
+        draw_centered(pass, height / 3.0, palette.text, "PAUSED");
+        draw_centered(
+            pass,
+            height / 2.0,
+            palette.text_muted,
+            "P TO RESUME - Q TO QUIT",
+        );
+    }
+
+    fn input(&mut self, _cx: &mut StateContext, event: &WindowEvent) -> Transition {
+        match input::pressed_key(event) {
+            Some(VirtualKeyCode::Escape) | Some(VirtualKeyCode::P) => Transition::Pop,
+            Some(VirtualKeyCode::Q) => Transition::Quit,
+            _ => Transition::None,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
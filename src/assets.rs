@@ -0,0 +1,11 @@
+//! Loading of on-disk assets into GPU resources.
+
+pub mod compressed;
+pub mod loader;
+pub mod store;
+pub mod texture;
+pub mod watcher;
+
+pub use loader::{AssetFailed, AssetLoaded, AssetLoader, LoadBatch, Progress};
+pub use store::{Asset, Assets, Handle};
+pub use watcher::{AssetReloaded, FileWatcher};
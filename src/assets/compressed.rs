@@ -0,0 +1,453 @@
+//! DDS and KTX2 containers holding BC1-3 block-compressed images.
+//!
+//! wgpu 0.5 has no block-compressed texture formats, so every level is
+//! decoded to RGBA8 on the CPU before upload and takes as much VRAM as an
+//! uncompressed image would. Until the direct BCn upload path can be added
+//! these containers save disk space and load time, not VRAM.
+
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt,
+};
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDS_HEADER_END: usize = 128;
+const DDS_DX10_HEADER_END: usize = 148;
+
+const KTX2_IDENTIFIER: &[u8; 12] = &[
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+const KTX2_LEVEL_INDEX: usize = 80;
+
+#[derive(Debug)]
+pub enum ContainerError {
+    /// The file is truncated or its header is inconsistent.
+    Malformed(&'static str),
+    /// The payload isn't BC1, BC2 or BC3. Holds the FourCC, DXGI or Vulkan
+    /// format code found in the header.
+    UnsupportedFormat(u32),
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContainerError::Malformed(reason) => write!(f, "malformed container: {}", reason),
+            ContainerError::UnsupportedFormat(code) => {
+                write!(f, "unsupported compressed format {:#x}", code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BcFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+}
+
+impl BcFormat {
+    fn block_size(self) -> usize {
+        match self {
+            BcFormat::Bc1 => 8,
+            BcFormat::Bc2 | BcFormat::Bc3 => 16,
+        }
+    }
+
+    /// Bytes taken by a `width` by `height` level in this format, or `None`
+    /// if that doesn't fit in a `usize`.
+    pub fn level_size(self, width: u32, height: u32) -> Option<usize> {
+        let blocks_x = width.div_ceil(4).max(1) as usize;
+        let blocks_y = height.div_ceil(4).max(1) as usize;
+        blocks_x
+            .checked_mul(blocks_y)?
+            .checked_mul(self.block_size())
+    }
+}
+
+/// A block-compressed image with its mip levels, largest first.
+pub struct CompressedImage {
+    pub format: BcFormat,
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// Whether `adapter` can sample BC formats directly.
+///
+/// wgpu 0.5 has no block-compressed texture formats at all, so this is
+/// always `false` for now and compressed images are decoded on the CPU.
+pub fn supported(_adapter: &wgpu::Adapter) -> bool {
+    false
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ContainerError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(ContainerError::Malformed("truncated header"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, ContainerError> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(ContainerError::Malformed("truncated header"))
+}
+
+/// The `size` bytes at `offset`.
+fn slice(bytes: &[u8], offset: usize, size: usize) -> Result<Vec<u8>, ContainerError> {
+    let end = offset
+        .checked_add(size)
+        .ok_or(ContainerError::Malformed("level data out of range"))?;
+    bytes
+        .get(offset..end)
+        .map(<[u8]>::to_vec)
+        .ok_or(ContainerError::Malformed("truncated level data"))
+}
+
+fn level_extent(size: u32, level: usize) -> u32 {
+    (size >> level).max(1)
+}
+
+/// Bytes of `level` of a `width` by `height` image in `format`.
+fn level_size(
+    format: BcFormat,
+    width: u32,
+    height: u32,
+    level: usize,
+) -> Result<usize, ContainerError> {
+    format
+        .level_size(level_extent(width, level), level_extent(height, level))
+        .ok_or(ContainerError::Malformed("level too large"))
+}
+
+/// The header's level count, at most as many as halving the larger side
+/// down to 1 takes, so each level's extent stays a valid shift.
+fn clamp_level_count(level_count: u32, width: u32, height: u32) -> usize {
+    let max = 32 - width.max(height).max(1).leading_zeros();
+    level_count.clamp(1, max) as usize
+}
+
+pub fn is_dds(bytes: &[u8]) -> bool {
+    bytes.starts_with(DDS_MAGIC)
+}
+
+pub fn is_ktx2(bytes: &[u8]) -> bool {
+    bytes.starts_with(KTX2_IDENTIFIER)
+}
+
+pub fn parse_dds(bytes: &[u8]) -> Result<CompressedImage, ContainerError> {
+    if !is_dds(bytes) {
+        return Err(ContainerError::Malformed("missing DDS magic"));
+    }
+
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    let level_count = clamp_level_count(read_u32(bytes, 28)?, width, height);
+    let four_cc = read_u32(bytes, 84)?;
+
+    let (format, srgb, mut offset) = match &four_cc.to_le_bytes() {
+        b"DXT1" => (BcFormat::Bc1, false, DDS_HEADER_END),
+        b"DXT3" => (BcFormat::Bc2, false, DDS_HEADER_END),
+        b"DXT5" => (BcFormat::Bc3, false, DDS_HEADER_END),
+        b"DX10" => {
+            let dxgi_format = read_u32(bytes, DDS_HEADER_END)?;
+            let (format, srgb) = match dxgi_format {
+                71 => (BcFormat::Bc1, false),
+                72 => (BcFormat::Bc1, true),
+                74 => (BcFormat::Bc2, false),
+                75 => (BcFormat::Bc2, true),
+                77 => (BcFormat::Bc3, false),
+                78 => (BcFormat::Bc3, true),
+                _ => return Err(ContainerError::UnsupportedFormat(dxgi_format)),
+            };
+            (format, srgb, DDS_DX10_HEADER_END)
+        }
+        _ => return Err(ContainerError::UnsupportedFormat(four_cc)),
+    };
+
+    let mut levels = vec![];
+    for level in 0..level_count {
+        let size = level_size(format, width, height, level)?;
+        levels.push(slice(bytes, offset, size)?);
+        offset += size;
+    }
+
+    Ok(CompressedImage {
+        format,
+        srgb,
+        width,
+        height,
+        levels,
+    })
+}
+
+pub fn parse_ktx2(bytes: &[u8]) -> Result<CompressedImage, ContainerError> {
+    if !is_ktx2(bytes) {
+        return Err(ContainerError::Malformed("missing KTX2 identifier"));
+    }
+
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let level_count = clamp_level_count(read_u32(bytes, 40)?, width, height);
+    if read_u32(bytes, 44)? != 0 {
+        return Err(ContainerError::Malformed("supercompressed KTX2"));
+    }
+
+    let (format, srgb) = match vk_format {
+        131 | 133 => (BcFormat::Bc1, false),
+        132 | 134 => (BcFormat::Bc1, true),
+        135 => (BcFormat::Bc2, false),
+        136 => (BcFormat::Bc2, true),
+        137 => (BcFormat::Bc3, false),
+        138 => (BcFormat::Bc3, true),
+        _ => return Err(ContainerError::UnsupportedFormat(vk_format)),
+    };
+
+    let mut levels = vec![];
+    for level in 0..level_count {
+        let entry = KTX2_LEVEL_INDEX + level * 24;
+        let offset = usize::try_from(read_u64(bytes, entry)?)
+            .map_err(|_| ContainerError::Malformed("level data out of range"))?;
+        let length = read_u64(bytes, entry + 8)?;
+        let size = level_size(format, width, height, level)?;
+        if length < size as u64 {
+            return Err(ContainerError::Malformed("level smaller than its extent"));
+        }
+        levels.push(slice(bytes, offset, size)?);
+    }
+
+    Ok(CompressedImage {
+        format,
+        srgb,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn expand_565(color: u16) -> [u8; 3] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// Decodes the colour half of a BC1-3 block into `out`.
+fn decode_color(block: &[u8], four_color: bool, out: &mut [[u8; 4]; 16]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (expand_565(c0), expand_565(c1));
+
+    let mix = |a: u8, b: u8, wa: u16, wb: u16| ((a as u16 * wa + b as u16 * wb) / (wa + wb)) as u8;
+    let mut palette = [[0; 4]; 4];
+    palette[0] = [e0[0], e0[1], e0[2], 255];
+    palette[1] = [e1[0], e1[1], e1[2], 255];
+    if four_color || c0 > c1 {
+        for c in 0..3 {
+            palette[2][c] = mix(e0[c], e1[c], 2, 1);
+            palette[3][c] = mix(e0[c], e1[c], 1, 2);
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for c in 0..3 {
+            palette[2][c] = mix(e0[c], e1[c], 1, 1);
+        }
+        palette[2][3] = 255;
+    }
+
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+fn decode_block(format: BcFormat, block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = [[0; 4]; 16];
+    match format {
+        BcFormat::Bc1 => decode_color(block, false, &mut texels),
+        BcFormat::Bc2 => {
+            decode_color(&block[8..], true, &mut texels);
+            let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+            for (i, texel) in texels.iter_mut().enumerate() {
+                texel[3] = ((alpha >> (4 * i)) & 0xf) as u8 * 17;
+            }
+        }
+        BcFormat::Bc3 => {
+            decode_color(&block[8..], true, &mut texels);
+            let (a0, a1) = (block[0] as u16, block[1] as u16);
+            let mut palette = [a0 as u8, a1 as u8, 0, 0, 0, 0, 0, 255];
+            if a0 > a1 {
+                for i in 1..7 {
+                    palette[i + 1] = (((7 - i as u16) * a0 + i as u16 * a1) / 7) as u8;
+                }
+            } else {
+                for i in 1..5 {
+                    palette[i + 1] = (((5 - i as u16) * a0 + i as u16 * a1) / 5) as u8;
+                }
+            }
+            let mut bits = [0; 8];
+            bits[..6].copy_from_slice(&block[2..8]);
+            let indices = u64::from_le_bytes(bits);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                texel[3] = palette[((indices >> (3 * i)) & 7) as usize];
+            }
+        }
+    }
+    texels
+}
+
+/// Decodes one level to tightly packed RGBA8 rows.
+pub fn decode(format: BcFormat, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4).max(1);
+    let mut rgba = vec![0; width * height * 4];
+
+    for (index, block) in data.chunks_exact(format.block_size()).enumerate() {
+        let (bx, by) = (index % blocks_x * 4, index / blocks_x * 4);
+        if by >= height {
+            break;
+        }
+        for (i, texel) in decode_block(format, block).iter().enumerate() {
+            let (x, y) = (bx + i % 4, by + i / 4);
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                rgba[offset..offset + 4].copy_from_slice(texel);
+            }
+        }
+    }
+
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A BC1 block: red and blue endpoints, and the first row picking each
+    /// of the four palette entries in turn.
+    const BC1_BLOCK: [u8; 8] = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0x00, 0x00, 0x00];
+
+    fn dds(width: u32, height: u32, level_count: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; DDS_HEADER_END];
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&level_count.to_le_bytes());
+        bytes[84..88].copy_from_slice(b"DXT1");
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    /// A one-level BC1 KTX2 file whose level index points at `offset`.
+    fn ktx2(width: u32, height: u32, offset: u64, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; KTX2_LEVEL_INDEX + 24];
+        bytes[..12].copy_from_slice(KTX2_IDENTIFIER);
+        bytes[12..16].copy_from_slice(&131u32.to_le_bytes());
+        bytes[20..24].copy_from_slice(&width.to_le_bytes());
+        bytes[24..28].copy_from_slice(&height.to_le_bytes());
+        bytes[40..44].copy_from_slice(&1u32.to_le_bytes());
+        let entry = KTX2_LEVEL_INDEX;
+        bytes[entry..entry + 8].copy_from_slice(&offset.to_le_bytes());
+        bytes[entry + 8..entry + 16].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn malformed(result: Result<CompressedImage, ContainerError>) -> &'static str {
+        match result {
+            Err(ContainerError::Malformed(reason)) => reason,
+            Err(err) => panic!("expected a malformed container, got {}", err),
+            Ok(_) => panic!("expected a malformed container, got an image"),
+        }
+    }
+
+    #[test]
+    fn level_sizes() {
+        assert_eq!(BcFormat::Bc1.level_size(1, 1), Some(8));
+        assert_eq!(BcFormat::Bc3.level_size(5, 5), Some(64));
+        assert_eq!(BcFormat::Bc3.level_size(u32::MAX, u32::MAX), None);
+    }
+
+    #[test]
+    fn parses_dds() {
+        let image = parse_dds(&dds(8, 4, 2, &[BC1_BLOCK, BC1_BLOCK, BC1_BLOCK].concat())).unwrap();
+        assert_eq!(image.format, BcFormat::Bc1);
+        assert!(!image.srgb);
+        assert_eq!((image.width, image.height), (8, 4));
+        assert_eq!(
+            image.levels,
+            vec![[BC1_BLOCK, BC1_BLOCK].concat(), BC1_BLOCK.to_vec()]
+        );
+    }
+
+    #[test]
+    fn clamps_dds_level_count() {
+        // A 1x1 image has one level, whatever the header claims; shifting
+        // by the 33rd level's index used to overflow.
+        let image = parse_dds(&dds(1, 1, 33, &BC1_BLOCK)).unwrap();
+        assert_eq!(image.levels.len(), 1);
+    }
+
+    #[test]
+    fn rejects_truncated_dds() {
+        assert_eq!(
+            malformed(parse_dds(&dds(8, 8, 1, &BC1_BLOCK))),
+            "truncated level data"
+        );
+        assert_eq!(malformed(parse_dds(&DDS_MAGIC[..])), "truncated header");
+    }
+
+    #[test]
+    fn parses_ktx2() {
+        let offset = (KTX2_LEVEL_INDEX + 24) as u64;
+        let image = parse_ktx2(&ktx2(4, 4, offset, &BC1_BLOCK)).unwrap();
+        assert_eq!(image.format, BcFormat::Bc1);
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(image.levels, vec![BC1_BLOCK.to_vec()]);
+    }
+
+    #[test]
+    fn rejects_ktx2_levels_out_of_range() {
+        let past_end = (KTX2_LEVEL_INDEX + 24 + BC1_BLOCK.len()) as u64;
+        assert_eq!(
+            malformed(parse_ktx2(&ktx2(4, 4, past_end, &BC1_BLOCK))),
+            "truncated level data"
+        );
+        assert_eq!(
+            malformed(parse_ktx2(&ktx2(4, 4, u64::MAX - 4, &BC1_BLOCK))),
+            "level data out of range"
+        );
+        assert_eq!(
+            malformed(parse_ktx2(&ktx2(8, 8, 0, &BC1_BLOCK))),
+            "level smaller than its extent"
+        );
+    }
+
+    #[test]
+    fn decodes_bc1() {
+        let rgba = decode(BcFormat::Bc1, 4, 4, &BC1_BLOCK);
+        let texel = |x: usize, y: usize| &rgba[(y * 4 + x) * 4..][..4];
+        assert_eq!(texel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(texel(1, 0), [0, 0, 255, 255]);
+        assert_eq!(texel(2, 0), [170, 0, 85, 255]);
+        assert_eq!(texel(3, 0), [85, 0, 170, 255]);
+        assert_eq!(texel(3, 3), [255, 0, 0, 255]);
+
+        // Texels outside a smaller image are cropped.
+        assert_eq!(
+            decode(BcFormat::Bc1, 2, 1, &BC1_BLOCK),
+            [255, 0, 0, 255, 0, 0, 255, 255]
+        );
+    }
+}
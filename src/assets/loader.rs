@@ -0,0 +1,131 @@
+//! Reading asset files in the background.
+//!
+//! [`Assets::load_async`](super::Assets::load_async) queues a file on the
+//! [`AssetLoader`], which reads and decodes it on the [`jobs`] pool. The store's
+//! [`poll`](super::Assets::poll) then finishes it on the render thread,
+//! e.g. uploading a decoded image, and reports it to the [`LoadBatch`] it
+//! was part of and as an [`AssetLoaded`] or [`AssetFailed`] event.
+
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Condvar, Mutex},
+};
+
+use super::Handle;
+use crate::jobs;
+
+pub(super) type Job = Box<dyn FnOnce() + Send>;
+
+/// Reads asset files on the [`jobs`] pool, several at once, so they may
+/// finish in any order.
+#[derive(Default)]
+pub struct AssetLoader {
+    /// Files queued and not read yet, signalled whenever one is.
+    queued: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl AssetLoader {
+    pub(super) fn spawn(&self, job: Job) {
+        let queued = self.queued.clone();
+        *queued.0.lock().expect("loader poisoned") += 1;
+        jobs::spawn(move || {
+            // `load_async` reports decoder panics itself; this only keeps
+            // anything else from taking the pool down.
+            if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                log::error!("An asset load panicked");
+            }
+            let (count, read) = &*queued;
+            *count.lock().expect("loader poisoned") -= 1;
+            read.notify_all();
+        });
+    }
+}
+
+impl Drop for AssetLoader {
+    /// Waits for the files already queued.
+    fn drop(&mut self) {
+        let (count, read) = &*self.queued;
+        let mut count = count.lock().expect("loader poisoned");
+        while *count > 0 {
+            count = read.wait(count).expect("loader poisoned");
+        }
+    }
+}
+
+/// How far a [`LoadBatch`] is.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub total: usize,
+    pub loaded: usize,
+    pub failed: usize,
+}
+
+impl Progress {
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed == self.total
+    }
+
+    /// Finished loads as a fraction of all of them, 1 for an empty batch.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+}
+
+#[derive(Default)]
+struct BatchState {
+    progress: Progress,
+    /// Keeps the loaded assets alive while the batch is.
+    handles: Vec<Rc<usize>>,
+}
+
+/// A group of asset loads tracked together, e.g. everything a level needs,
+/// so a loading screen can show their progress. Clones share the batch.
+///
+/// Assets the batch loaded stay loaded until it is dropped, giving its
+/// owner time to take handles to them.
+#[derive(Clone, Default)]
+pub struct LoadBatch {
+    state: Rc<RefCell<BatchState>>,
+}
+
+impl LoadBatch {
+    pub fn progress(&self) -> Progress {
+        self.state.borrow().progress
+    }
+
+    pub(super) fn queued(&self) {
+        self.state.borrow_mut().progress.total += 1;
+    }
+
+    pub(super) fn loaded<T>(&self, handle: &Handle<T>) {
+        let mut state = self.state.borrow_mut();
+        state.progress.loaded += 1;
+        state.handles.push(handle.index.clone());
+    }
+
+    pub(super) fn failed(&self) {
+        self.state.borrow_mut().progress.failed += 1;
+    }
+}
+
+/// Sent by [`Assets::poll`](super::Assets::poll) once an asset loaded
+/// with `load_async` is ready.
+pub struct AssetLoaded<T> {
+    pub path: PathBuf,
+    pub handle: Handle<T>,
+}
+
+/// Sent by [`Assets::poll`](super::Assets::poll) when an asset loaded with
+/// `load_async` failed to load.
+#[derive(Clone, Debug)]
+pub struct AssetFailed {
+    pub path: PathBuf,
+    pub error: String,
+}
@@ -0,0 +1,362 @@
+//! Typed asset stores handing out reference-counted handles.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+    sync::mpsc,
+};
+
+use super::{
+    loader::{AssetFailed, AssetLoaded, AssetLoader, LoadBatch},
+    texture::{DecodedTexture, Texture, TextureError},
+    watcher::AssetReloaded,
+};
+use crate::{
+    events::EventBus,
+    scene::{Scene, SceneError},
+    Context,
+};
+
+/// Something [`Assets::load`] can read from a file, in two steps so the
+/// first can run on the [`AssetLoader`] thread.
+pub trait Asset: Sized + 'static {
+    /// What [`Asset::read`] gets out of the file, e.g. decoded pixels.
+    type Data: Send + 'static;
+    type Error: fmt::Display + Send + 'static;
+
+    /// Reads and decodes the file. Runs on any thread.
+    fn read(path: &Path) -> Result<Self::Data, Self::Error>;
+
+    /// Finishes loading on the render thread, e.g. uploading to the GPU.
+    fn create(ctx: &mut Context, path: &Path, data: Self::Data) -> Result<Self, Self::Error>;
+}
+
+impl Asset for Texture {
+    type Data = DecodedTexture;
+    type Error = TextureError;
+
+    fn read(path: &Path) -> Result<DecodedTexture, TextureError> {
+        DecodedTexture::read(path)
+    }
+
+    fn create(ctx: &mut Context, path: &Path, data: DecodedTexture) -> Result<Self, TextureError> {
+        Texture::from_decoded(ctx, &format!("texture/{}", path.display()), &data)
+    }
+}
+
+impl Asset for Scene {
+    type Data = Scene;
+    type Error = SceneError;
+
+    fn read(path: &Path) -> Result<Scene, SceneError> {
+        Scene::load(path)
+    }
+
+    fn create(_ctx: &mut Context, _path: &Path, scene: Scene) -> Result<Self, SceneError> {
+        Ok(scene)
+    }
+}
+
+/// Keeps an asset in its [`Assets`] store. Cloning is cheap; once every
+/// clone is dropped, the next [`Assets::collect`] unloads the asset.
+///
+/// A handle only refers to an asset in the store that returned it.
+pub struct Handle<T> {
+    pub(super) index: Rc<usize>,
+    _asset: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.index, &other.index)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+struct Entry<T> {
+    asset: T,
+    /// The file it was loaded from, if any.
+    path: Option<PathBuf>,
+    /// Dead once every handle is dropped.
+    handle: Weak<usize>,
+}
+
+/// Creates an asset from what the loader thread read, or says why the file
+/// couldn't be read.
+type Create<T> = Box<dyn FnOnce(&mut Context) -> Result<T, String> + Send>;
+
+/// A file the loader thread is done with.
+struct Finished<T> {
+    request: usize,
+    path: PathBuf,
+    create: Create<T>,
+}
+
+/// Assets of one type. Loading the same path twice returns the same asset
+/// while a handle to it is alive.
+pub struct Assets<T> {
+    entries: Vec<Option<Entry<T>>>,
+    free: Vec<usize>,
+    paths: HashMap<PathBuf, usize>,
+    /// Batches of the `load_async` requests still on the loader thread.
+    pending: HashMap<usize, LoadBatch>,
+    next_request: usize,
+    finished_sender: mpsc::Sender<Finished<T>>,
+    finished: mpsc::Receiver<Finished<T>>,
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        let (finished_sender, finished) = mpsc::channel();
+        Self {
+            entries: vec![],
+            free: vec![],
+            paths: HashMap::new(),
+            pending: HashMap::new(),
+            next_request: 0,
+            finished_sender,
+            finished,
+        }
+    }
+}
+
+impl<T> Assets<T> {
+    /// Stores an asset that didn't come from a file, e.g. an embedded one.
+    pub fn add(&mut self, asset: T) -> Handle<T> {
+        self.insert(asset, None)
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> &T {
+        &self.entry(handle).asset
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> &mut T {
+        let index = *handle.index;
+        &mut self.entries[index]
+            .as_mut()
+            .expect("handle from another asset store")
+            .asset
+    }
+
+    /// A handle to the asset loaded from `path`, if it is still loaded.
+    pub fn handle(&self, path: impl AsRef<Path>) -> Option<Handle<T>> {
+        let index = *self.paths.get(path.as_ref())?;
+        let index = self.entries[index].as_ref()?.handle.upgrade()?;
+        Some(Handle {
+            index,
+            _asset: PhantomData,
+        })
+    }
+
+    /// Where the asset was loaded from, if it was.
+    pub fn path(&self, handle: &Handle<T>) -> Option<&Path> {
+        self.entry(handle).path.as_deref()
+    }
+
+    /// The files the loaded assets came from, e.g. to watch them.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.paths.keys().map(PathBuf::as_path)
+    }
+
+    /// Unloads every asset without handles. Call once per frame; returns
+    /// how many were unloaded.
+    pub fn collect(&mut self) -> usize {
+        let mut unloaded = 0;
+        for (index, slot) in self.entries.iter_mut().enumerate() {
+            if matches!(slot, Some(entry) if entry.handle.strong_count() == 0) {
+                // The path may have been loaded again into another slot.
+                if let Some(path) = slot.take().and_then(|entry| entry.path) {
+                    if self.paths.get(&path) == Some(&index) {
+                        self.paths.remove(&path);
+                    }
+                }
+                self.free.push(index);
+                unloaded += 1;
+            }
+        }
+        unloaded
+    }
+
+    /// Loaded assets, including ones waiting for [`Assets::collect`].
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry(&self, handle: &Handle<T>) -> &Entry<T> {
+        self.entries[*handle.index]
+            .as_ref()
+            .expect("handle from another asset store")
+    }
+
+    fn insert(&mut self, asset: T, path: Option<PathBuf>) -> Handle<T> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.entries.push(None);
+                self.entries.len() - 1
+            }
+        };
+        let handle = Rc::new(index);
+        if let Some(path) = &path {
+            self.paths.insert(path.clone(), index);
+        }
+        self.entries[index] = Some(Entry {
+            asset,
+            path,
+            handle: Rc::downgrade(&handle),
+        });
+        Handle {
+            index: handle,
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl<T: Asset> Assets<T> {
+    /// Loads the asset at `path`, or returns the one already loaded from it.
+    pub fn load(
+        &mut self,
+        ctx: &mut Context,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<T>, T::Error> {
+        let path = path.as_ref();
+        if let Some(handle) = self.handle(path) {
+            return Ok(handle);
+        }
+        let asset = T::create(ctx, path, T::read(path)?)?;
+        Ok(self.insert(asset, Some(path.to_owned())))
+    }
+
+    /// Loads the asset at `path` again, replacing the one loaded from it
+    /// so existing handles see the new version. Returns `None` if nothing
+    /// is loaded from `path`; on errors, the old version is kept.
+    pub fn reload(
+        &mut self,
+        ctx: &mut Context,
+        path: impl AsRef<Path>,
+    ) -> Result<Option<Handle<T>>, T::Error> {
+        let path = path.as_ref();
+        let handle = match self.handle(path) {
+            Some(handle) => handle,
+            None => return Ok(None),
+        };
+        *self.get_mut(&handle) = T::create(ctx, path, T::read(path)?)?;
+        Ok(Some(handle))
+    }
+
+    /// Reloads the assets loaded from any of `changed`, e.g. the files a
+    /// [`FileWatcher`](super::FileWatcher) reported, sending an
+    /// [`AssetReloaded`] for each. Failures are logged.
+    pub fn reload_changed(
+        &mut self,
+        ctx: &mut Context,
+        changed: &[PathBuf],
+        events: &mut EventBus,
+    ) {
+        for path in changed {
+            match self.reload(ctx, path) {
+                Ok(Some(handle)) => {
+                    log::info!("Reloaded {}", path.display());
+                    events.send(AssetReloaded {
+                        path: path.clone(),
+                        handle,
+                    });
+                }
+                Ok(None) => {}
+                Err(err) => log::error!("Failed to reload {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    /// Reads the asset at `path` on `loader`'s thread and counts it in
+    /// `batch`. [`Assets::poll`] finishes it once the file was read.
+    pub fn load_async(&mut self, loader: &AssetLoader, batch: &LoadBatch, path: impl AsRef<Path>) {
+        let path = path.as_ref().to_owned();
+        batch.queued();
+        if let Some(handle) = self.handle(&path) {
+            batch.loaded(&handle);
+            return;
+        }
+
+        let request = self.next_request;
+        self.next_request += 1;
+        self.pending.insert(request, batch.clone());
+        let sender = self.finished_sender.clone();
+        loader.spawn(Box::new(move || {
+            // A decoder that panics still finishes the request, as a
+            // failure, so its batch isn't left waiting for it.
+            let read = panic::catch_unwind(AssertUnwindSafe(|| T::read(&path)));
+            let create: Create<T> = match read {
+                Ok(Ok(data)) => {
+                    let path = path.clone();
+                    Box::new(move |ctx| T::create(ctx, &path, data).map_err(|err| err.to_string()))
+                }
+                Ok(Err(err)) => {
+                    let error = err.to_string();
+                    Box::new(move |_| Err(error))
+                }
+                Err(_) => Box::new(|_| Err("decoder panicked".to_owned())),
+            };
+            // Only fails once the store is gone, when nobody is waiting.
+            let _ = sender.send(Finished {
+                request,
+                path,
+                create,
+            });
+        }));
+    }
+
+    /// Finishes the `load_async` requests the loader thread is done with,
+    /// sending an [`AssetLoaded`] or [`AssetFailed`] for each. Call once
+    /// per frame.
+    pub fn poll(&mut self, ctx: &mut Context, events: &mut EventBus) {
+        while let Ok(Finished {
+            request,
+            path,
+            create,
+        }) = self.finished.try_recv()
+        {
+            let batch = self.pending.remove(&request).unwrap_or_default();
+            // The same path may have been requested twice.
+            let result = match self.handle(&path) {
+                Some(handle) => Ok(handle),
+                None => create(ctx).map(|asset| self.insert(asset, Some(path.clone()))),
+            };
+            match result {
+                Ok(handle) => {
+                    batch.loaded(&handle);
+                    events.send(AssetLoaded { path, handle });
+                }
+                Err(error) => {
+                    log::error!("Failed to load {}: {}", path.display(), error);
+                    batch.failed();
+                    events.send(AssetFailed { path, error });
+                }
+            }
+        }
+    }
+}
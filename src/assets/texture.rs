@@ -0,0 +1,342 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use super::compressed::{self, CompressedImage, ContainerError};
+use crate::{
+    bind_group_cache::Binding,
+    color::{linear_to_srgb, srgb_to_linear},
+    gpu_mem::{self, BudgetExceeded},
+    mipmap,
+    pipeline_cache::ShaderError,
+    sampler::{Sampler, SamplerDesc},
+    validation, Context,
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// `bytes_per_row` of a buffer-to-texture copy must be a multiple of this.
+const ROW_ALIGNMENT: u32 = 256;
+
+#[derive(Debug)]
+pub enum TextureError {
+    /// There is no file at the path.
+    Missing(PathBuf),
+    Io(std::io::Error),
+    /// The file couldn't be decoded.
+    Image(image::ImageError),
+    Container(ContainerError),
+    OutOfMemory(BudgetExceeded),
+    /// The mipmap shaders failed to load.
+    Shader(ShaderError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Missing(path) => write!(f, "texture {} not found", path.display()),
+            TextureError::Io(err) => write!(f, "failed to read texture: {}", err),
+            TextureError::Image(err) => write!(f, "failed to load image: {}", err),
+            TextureError::Container(err) => write!(f, "failed to load texture: {}", err),
+            TextureError::OutOfMemory(err) => write!(f, "out of GPU memory: {}", err),
+            TextureError::Shader(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<std::io::Error> for TextureError {
+    fn from(err: std::io::Error) -> Self {
+        TextureError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(err: image::ImageError) -> Self {
+        TextureError::Image(err)
+    }
+}
+
+impl From<ContainerError> for TextureError {
+    fn from(err: ContainerError) -> Self {
+        TextureError::Container(err)
+    }
+}
+
+impl From<BudgetExceeded> for TextureError {
+    fn from(err: BudgetExceeded) -> Self {
+        TextureError::OutOfMemory(err)
+    }
+}
+
+impl From<ShaderError> for TextureError {
+    fn from(err: ShaderError) -> Self {
+        TextureError::Shader(err)
+    }
+}
+
+/// A sampled 2D texture decoded from an image, with a full mip chain (or the
+/// one stored in the file) and a default view.
+///
+/// Loaders pick [`SamplerDesc::LINEAR`]; replace `sampler` with one from
+/// `Context::create_sampler` for other filtering.
+pub struct Texture {
+    pub texture: gpu_mem::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: Sampler,
+    pub size: wgpu::Extent3d,
+}
+
+/// A texture file decoded on the CPU but not uploaded yet. Decoding is the
+/// slow part of loading, so it can happen off the render thread.
+pub enum DecodedTexture {
+    Image(image::DynamicImage),
+    Compressed(CompressedImage),
+}
+
+impl DecodedTexture {
+    /// Reads and decodes a PNG, JPEG, DDS or KTX2 file through the
+    /// [`vfs`](crate::vfs).
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        Self::decode(&read_bytes(path.as_ref())?)
+    }
+
+    /// Decodes an in-memory PNG, JPEG, DDS or KTX2 file, telling them apart
+    /// by their magic bytes.
+    pub fn decode(bytes: &[u8]) -> Result<Self, TextureError> {
+        if compressed::is_dds(bytes) {
+            return Ok(DecodedTexture::Compressed(compressed::parse_dds(bytes)?));
+        }
+        if compressed::is_ktx2(bytes) {
+            return Ok(DecodedTexture::Compressed(compressed::parse_ktx2(bytes)?));
+        }
+        Ok(DecodedTexture::Image(image::load_from_memory(bytes)?))
+    }
+}
+
+/// Reads `path` through the [`vfs`](crate::vfs), reporting a missing file
+/// as [`TextureError::Missing`].
+pub(crate) fn read_bytes(path: &Path) -> Result<Vec<u8>, TextureError> {
+    crate::vfs::read(path).map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => TextureError::Missing(path.to_owned()),
+        _ => TextureError::Io(err),
+    })
+}
+
+impl Texture {
+    /// Loads a PNG, JPEG, DDS or KTX2 file. The path doubles as the memory
+    /// label.
+    pub fn from_path(ctx: &mut Context, path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let decoded = DecodedTexture::read(path)?;
+        Self::from_decoded(ctx, &format!("texture/{}", path.display()), &decoded)
+    }
+
+    /// Loads an in-memory PNG, JPEG, DDS or KTX2 file.
+    pub fn from_bytes(ctx: &mut Context, label: &str, bytes: &[u8]) -> Result<Self, TextureError> {
+        Self::from_decoded(ctx, label, &DecodedTexture::decode(bytes)?)
+    }
+
+    pub fn from_decoded(
+        ctx: &mut Context,
+        label: &str,
+        decoded: &DecodedTexture,
+    ) -> Result<Self, TextureError> {
+        match decoded {
+            DecodedTexture::Image(image) => Self::from_image(ctx, label, image),
+            DecodedTexture::Compressed(image) => Self::from_compressed(ctx, label, image),
+        }
+    }
+
+    pub fn from_image(
+        ctx: &mut Context,
+        label: &str,
+        image: &image::DynamicImage,
+    ) -> Result<Self, TextureError> {
+        let image = image.to_rgba();
+        let (width, height) = image.dimensions();
+        Self::from_levels(ctx, label, FORMAT, width, height, &[image.into_raw()])
+    }
+
+    /// Uploads a block-compressed image.
+    ///
+    /// Until [`compressed::supported`] reports BC support, every level is
+    /// decoded to RGBA8 first, so this saves disk space but not VRAM.
+    pub fn from_compressed(
+        ctx: &mut Context,
+        label: &str,
+        image: &CompressedImage,
+    ) -> Result<Self, TextureError> {
+        let format = if image.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        let levels = image
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let width = (image.width >> level).max(1);
+                let height = (image.height >> level).max(1);
+                compressed::decode(image.format, width, height, data)
+            })
+            .collect::<Vec<_>>();
+
+        Self::from_levels(ctx, label, format, image.width, image.height, &levels)
+    }
+
+    /// Creates the texture from RGBA8 levels, largest first. A single level
+    /// gets the rest of its chain generated.
+    fn from_levels(
+        ctx: &mut Context,
+        label: &str,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        levels: &[Vec<u8>],
+    ) -> Result<Self, TextureError> {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+
+        let level_count = match levels.len() {
+            1 => mipmap::level_count(width, height),
+            count => count as u32,
+        };
+
+        let texture = ctx.memory.create_texture(
+            &ctx.device,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                array_layer_count: 1,
+                mip_level_count: level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::SAMPLED
+                    | wgpu::TextureUsage::COPY_DST
+                    | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            },
+        )?;
+        for (level, pixels) in levels.iter().enumerate() {
+            write_rgba(
+                ctx,
+                label,
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    array_layer: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                wgpu::Extent3d {
+                    width: (width >> level).max(1),
+                    height: (height >> level).max(1),
+                    depth: 1,
+                },
+                pixels,
+            )?;
+        }
+        if levels.len() == 1 {
+            mipmap::generate(ctx, &texture, format, level_count)?;
+        }
+
+        let view = texture.create_default_view();
+        let sampler = ctx.create_sampler(SamplerDesc::LINEAR);
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+            size,
+        })
+    }
+
+    /// The view and sampler, for a layout with a sampled texture at binding 0
+    /// and a sampler at binding 1.
+    pub fn bindings(&self) -> [Binding<'_>; 2] {
+        [
+            Binding::TextureView {
+                view: &self.view,
+                id: self.texture.id(),
+            },
+            Binding::Sampler {
+                sampler: &self.sampler,
+                id: self.sampler.id(),
+            },
+        ]
+    }
+}
+
+/// Copies tightly packed RGBA8 rows into the `size` region at
+/// `destination`.
+///
+/// The rows are re-padded to the copy alignment in a tracked upload buffer
+/// and the copy is submitted right away on its own encoder.
+pub fn write_rgba(
+    ctx: &mut Context,
+    label: &str,
+    destination: wgpu::TextureCopyView,
+    size: wgpu::Extent3d,
+    pixels: &[u8],
+) -> Result<(), BudgetExceeded> {
+    let _scope = validation::scope("texture/upload");
+    let row = size.width * BYTES_PER_PIXEL;
+    let bytes_per_row = row.div_ceil(ROW_ALIGNMENT) * ROW_ALIGNMENT;
+
+    let mut data = vec![0; (bytes_per_row * size.height) as usize];
+    for (src, dst) in pixels
+        .chunks(row as usize)
+        .zip(data.chunks_mut(bytes_per_row as usize))
+    {
+        dst[..row as usize].copy_from_slice(src);
+    }
+
+    let buffer = ctx.memory.create_buffer_with_data(
+        &ctx.device,
+        &format!("{}/upload", label),
+        &data,
+        wgpu::BufferUsage::COPY_SRC,
+    )?;
+
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture/upload"),
+        });
+    encoder.copy_buffer_to_texture(
+        wgpu::BufferCopyView {
+            buffer: &buffer,
+            offset: 0,
+            bytes_per_row,
+            rows_per_image: size.height,
+        },
+        destination,
+        size,
+    );
+    ctx.queue.submit(&[encoder.finish()]);
+
+    Ok(())
+}
+
+/// Multiplies the color of every pixel of the sRGB `image` by its alpha, for
+/// drawing with premultiplied blending.
+///
+/// The multiplication happens on linear values, as the GPU decodes the
+/// texels to linear before filtering and blending them.
+pub fn premultiply_alpha(image: &mut image::RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = f32::from(pixel[3]) / 255.0;
+        for channel in &mut pixel.0[..3] {
+            let linear = srgb_to_linear(f32::from(*channel) / 255.0) * alpha;
+            *channel = (linear_to_srgb(linear) * 255.0).round() as u8;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Noticing asset files changing on disk, so they can be reloaded while the
+//! application runs.
+//!
+//! [`FileWatcher`] polls the modification times of the files and
+//! directories it watches on a background thread. Polling a handful of
+//! files a few times a second costs next to nothing and behaves the same on
+//! every platform, including editors that save by replacing the file.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use super::Handle;
+
+/// How often the watched files are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Sent by [`Assets::reload_changed`](super::Assets::reload_changed) after
+/// an asset was reloaded in place. Handles to it already see the new
+/// version; this is for whoever built something from the old one.
+pub struct AssetReloaded<T> {
+    pub path: PathBuf,
+    pub handle: Handle<T>,
+}
+
+/// Reports files that changed since they were first seen. Watching a
+/// directory covers the files directly in it, including ones created later.
+pub struct FileWatcher {
+    roots: Arc<Mutex<Vec<PathBuf>>>,
+    changes: mpsc::Receiver<PathBuf>,
+    stop: Arc<AtomicBool>,
+    poller: Option<thread::JoinHandle<()>>,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        let roots = Arc::new(Mutex::new(Vec::<PathBuf>::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, changes) = mpsc::channel();
+        let poller = {
+            let roots = roots.clone();
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("file-watcher".into())
+                .spawn(move || {
+                    let mut poller = Poller::default();
+                    while !stop.load(Ordering::Relaxed) {
+                        let roots = roots.lock().expect("watcher poisoned").clone();
+                        for path in poller.poll(&roots) {
+                            if sender.send(path).is_err() {
+                                return;
+                            }
+                        }
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                })
+                .expect("failed to spawn the file watcher thread")
+        };
+        Self {
+            roots,
+            changes,
+            stop,
+            poller: Some(poller),
+        }
+    }
+}
+
+impl FileWatcher {
+    /// Starts watching a file or directory. Watching a path twice does
+    /// nothing, so this can be called for every loaded asset each frame.
+    pub fn watch(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let mut roots = self.roots.lock().expect("watcher poisoned");
+        if !roots.iter().any(|root| root == path) {
+            roots.push(path.to_owned());
+        }
+    }
+
+    /// The files that changed since the last call, each once, in order.
+    /// Files in a watched directory are joined onto the directory's path.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self.changes.try_iter().collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(poller) = self.poller.take() {
+            let _ = poller.join();
+        }
+    }
+}
+
+/// What the watcher thread remembers between polls.
+#[derive(Default)]
+struct Poller {
+    modified: HashMap<PathBuf, SystemTime>,
+    /// Roots polled at least once; their files' first times aren't changes.
+    scanned: HashSet<PathBuf>,
+}
+
+impl Poller {
+    fn poll(&mut self, roots: &[PathBuf]) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for root in roots {
+            let first = self.scanned.insert(root.clone());
+            for file in files(root) {
+                // A file being replaced may briefly not exist; it is picked
+                // up on a later poll with its new time.
+                let modified = match fs::metadata(&file).and_then(|meta| meta.modified()) {
+                    Ok(modified) => modified,
+                    Err(_) => continue,
+                };
+                let previous = self.modified.insert(file.clone(), modified);
+                if !first && previous != Some(modified) {
+                    changed.push(file);
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// `root` itself, or the files directly in it if it is a directory.
+fn files(root: &Path) -> Vec<PathBuf> {
+    if !root.is_dir() {
+        return vec![root.to_owned()];
+    }
+    fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect()
+}
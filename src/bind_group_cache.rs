@@ -0,0 +1,184 @@
+use std::{collections::HashMap, ops::Range, rc::Rc};
+
+use crate::gpu_mem::{self, GpuMemory, Kind, Tracked};
+
+/// Bind groups that haven't been requested for this many frames are dropped.
+const EVICT_AFTER_FRAMES: u64 = 120;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayoutId(usize);
+
+type LayoutKey = Vec<(u32, wgpu::ShaderStage, wgpu::BindingType)>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ResourceKey {
+    Buffer(u64, Range<wgpu::BufferAddress>),
+    TextureView(u64),
+    Sampler(u64),
+}
+
+/// A resource to bind, carrying the identity used as the cache key.
+///
+/// Views and samplers aren't tracked themselves, so they are keyed by the
+/// id of the tracked texture they view or the token kept with the sampler.
+pub enum Binding<'a> {
+    Buffer {
+        buffer: &'a gpu_mem::Buffer,
+        range: Range<wgpu::BufferAddress>,
+    },
+    TextureView {
+        view: &'a wgpu::TextureView,
+        id: u64,
+    },
+    Sampler {
+        sampler: &'a wgpu::Sampler,
+        id: u64,
+    },
+}
+
+impl Binding<'_> {
+    fn key(&self) -> ResourceKey {
+        match self {
+            Binding::Buffer { buffer, range } => ResourceKey::Buffer(buffer.id(), range.clone()),
+            Binding::TextureView { id, .. } => ResourceKey::TextureView(*id),
+            Binding::Sampler { id, .. } => ResourceKey::Sampler(*id),
+        }
+    }
+
+    fn resource(&self) -> wgpu::BindingResource<'_> {
+        match self {
+            Binding::Buffer { buffer, range } => wgpu::BindingResource::Buffer {
+                buffer,
+                range: range.clone(),
+            },
+            Binding::TextureView { view, .. } => wgpu::BindingResource::TextureView(view),
+            Binding::Sampler { sampler, .. } => wgpu::BindingResource::Sampler(sampler),
+        }
+    }
+}
+
+struct Entry {
+    bind_group: Rc<wgpu::BindGroup>,
+    last_used: u64,
+    _tracked: Tracked,
+}
+
+/// Interns bind group layouts and reuses bind groups built from the same
+/// layout and resources.
+pub struct BindGroupCache {
+    memory: GpuMemory,
+    layout_ids: HashMap<LayoutKey, LayoutId>,
+    layouts: Vec<wgpu::BindGroupLayout>,
+    /// The key of each of `layouts`.
+    layout_keys: Vec<LayoutKey>,
+    groups: HashMap<(LayoutId, Vec<ResourceKey>), Entry>,
+    frame: u64,
+}
+
+impl BindGroupCache {
+    pub fn new(memory: GpuMemory) -> Self {
+        Self {
+            memory,
+            layout_ids: HashMap::new(),
+            layouts: vec![],
+            layout_keys: vec![],
+            groups: HashMap::new(),
+            frame: 0,
+        }
+    }
+
+    /// Returns the id of a layout with `entries`, creating it on first use.
+    /// Layouts are shared by everything with the same entries, so `label`
+    /// names the first user.
+    pub fn layout_id(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> LayoutId {
+        let key = entries
+            .iter()
+            .map(|entry| (entry.binding, entry.visibility, entry.ty))
+            .collect::<LayoutKey>();
+
+        if let Some(&id) = self.layout_ids.get(&key) {
+            return id;
+        }
+
+        let id = LayoutId(self.layouts.len());
+        self.layouts.push(
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                bindings: entries,
+            }),
+        );
+        self.layout_keys.push(key.clone());
+        self.layout_ids.insert(key, id);
+        id
+    }
+
+    pub fn layout(&self, id: LayoutId) -> &wgpu::BindGroupLayout {
+        &self.layouts[id.0]
+    }
+
+    /// The entries the layout was created with.
+    pub fn layout_entries(&self, id: LayoutId) -> Vec<wgpu::BindGroupLayoutEntry> {
+        self.layout_keys[id.0]
+            .iter()
+            .map(|&(binding, visibility, ty)| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty,
+            })
+            .collect()
+    }
+
+    /// Returns a bind group for `layout` with `bindings` bound in order,
+    /// starting at binding 0. `label` is only used when a new bind group
+    /// has to be created.
+    pub fn bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        layout: LayoutId,
+        bindings: &[Binding],
+    ) -> Rc<wgpu::BindGroup> {
+        let key = (layout, bindings.iter().map(Binding::key).collect());
+        let frame = self.frame;
+        let layouts = &self.layouts;
+        let memory = &self.memory;
+
+        let entry = self.groups.entry(key).or_insert_with(|| {
+            let bindings = bindings
+                .iter()
+                .enumerate()
+                .map(|(index, binding)| wgpu::Binding {
+                    binding: index as u32,
+                    resource: binding.resource(),
+                })
+                .collect::<Vec<_>>();
+
+            Entry {
+                bind_group: Rc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(label),
+                    layout: &layouts[layout.0],
+                    bindings: &bindings,
+                })),
+                last_used: frame,
+                _tracked: memory.track_object(label, Kind::BindGroup),
+            }
+        });
+
+        entry.last_used = frame;
+        entry.bind_group.clone()
+    }
+
+    /// Advances the cache clock and drops bind groups that went unused for
+    /// a while, which also releases their hold on the bound resources.
+    pub fn maintain(&mut self) {
+        self.frame += 1;
+        let frame = self.frame;
+        self.groups
+            .retain(|_, entry| frame - entry.last_used <= EVICT_AFTER_FRAMES);
+    }
+}
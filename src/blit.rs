@@ -0,0 +1,82 @@
+use crate::{
+    pipeline_cache::{PipelineKey, ShaderError},
+    sampler::SamplerDesc,
+    validation, Context,
+};
+
+/// Records a pass that draws `source` over the whole of `destination` with
+/// a linear filter, scaling it to fit.
+///
+/// `source` must be a single-level 2D view of a sampled float texture and
+/// `destination` a render attachment in `format`.
+pub fn blit(
+    ctx: &mut Context,
+    encoder: &mut wgpu::CommandEncoder,
+    source: &wgpu::TextureView,
+    destination: &wgpu::TextureView,
+    format: wgpu::TextureFormat,
+) -> Result<(), ShaderError> {
+    let _scope = validation::scope("blit");
+    let vertex_shader = ctx.pipelines.load_shader(&ctx.device, "blit.vert")?;
+    let fragment_shader = ctx.pipelines.load_shader(&ctx.device, "blit.frag")?;
+
+    let layouts = ctx
+        .pipelines
+        .bind_group_layouts(&[vertex_shader, fragment_shader])?;
+    let layout = ctx.bind_groups.layout_id(&ctx.device, "blit", &layouts[0]);
+
+    let pipeline = ctx.pipelines.pipeline(
+        &ctx.device,
+        &ctx.bind_groups,
+        &PipelineKey {
+            vertex_shader,
+            fragment_shader: Some(fragment_shader),
+            bind_group_layouts: vec![layout],
+            vertex_layouts: vec![],
+            color_states: vec![wgpu::ColorStateDescriptor {
+                format,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: wgpu::CullMode::None,
+            index_format: wgpu::IndexFormat::Uint16,
+            sample_count: 1,
+        },
+    );
+    let sampler = ctx.create_sampler(SamplerDesc::LINEAR);
+
+    // Blit sources are usually one-off views, so the bind group is made
+    // directly rather than through the cache.
+    let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("blit"),
+        layout: ctx.bind_groups.layout(layout),
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source),
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler),
+            },
+        ],
+    });
+
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: destination,
+            resolve_target: None,
+            load_op: wgpu::LoadOp::Clear,
+            store_op: wgpu::StoreOp::Store,
+            clear_color: wgpu::Color::TRANSPARENT,
+        }],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, &bind_group, &[]);
+    pass.draw(0..3, 0..1);
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::{collections::HashMap, ops::Deref};
+
+use crate::{
+    frame::{FrameContext, PerFrame},
+    gpu_mem::{self, BudgetExceeded, GpuMemory},
+};
+
+const MIN_SIZE_CLASS: wgpu::BufferAddress = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct PoolKey {
+    size: wgpu::BufferAddress,
+    usage: wgpu::BufferUsage,
+}
+
+impl PoolKey {
+    fn new(size: wgpu::BufferAddress, usage: wgpu::BufferUsage) -> Self {
+        Self {
+            size: size.max(MIN_SIZE_CLASS).next_power_of_two(),
+            usage: usage | wgpu::BufferUsage::COPY_DST,
+        }
+    }
+}
+
+/// A buffer on loan from a [`BufferPool`]. Hand it back with
+/// [`BufferPool::release`] instead of dropping it.
+pub struct PooledBuffer {
+    buffer: gpu_mem::Buffer,
+    key: PoolKey,
+}
+
+impl PooledBuffer {
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.key.size
+    }
+
+    pub fn usage(&self) -> wgpu::BufferUsage {
+        self.key.usage
+    }
+
+    pub fn buffer(&self) -> &gpu_mem::Buffer {
+        &self.buffer
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+/// Recycles buffers by power-of-two size class and usage.
+///
+/// Released buffers are held back until the GPU has finished the frame that
+/// last used them, as reported by the [`FrameContext`].
+pub struct BufferPool {
+    free: HashMap<PoolKey, Vec<gpu_mem::Buffer>>,
+    retired: Vec<PooledBuffer>,
+    in_flight: PerFrame<Vec<PooledBuffer>>,
+}
+
+impl BufferPool {
+    pub fn new(frames: &FrameContext) -> Self {
+        Self {
+            free: HashMap::new(),
+            retired: vec![],
+            in_flight: PerFrame::new(frames, |_| vec![]),
+        }
+    }
+
+    /// Returns a buffer of at least `size` bytes usable as `usage`.
+    ///
+    /// `label` names the borrower in memory reports.
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        memory: &GpuMemory,
+        label: &str,
+        size: wgpu::BufferAddress,
+        usage: wgpu::BufferUsage,
+    ) -> Result<PooledBuffer, BudgetExceeded> {
+        let key = PoolKey::new(size, usage);
+
+        let buffer = match self.free.get_mut(&key).and_then(Vec::pop) {
+            Some(buffer) => {
+                memory.relabel(&buffer, label);
+                buffer
+            }
+            None => memory.create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size: key.size,
+                    usage: key.usage,
+                },
+            )?,
+        };
+
+        Ok(PooledBuffer { buffer, key })
+    }
+
+    /// Gives a buffer back to the pool. It becomes available again once the
+    /// current frame has completed on the GPU.
+    pub fn release(&mut self, buffer: PooledBuffer) {
+        self.retired.push(buffer);
+    }
+
+    /// Swaps `buffer` for a larger one from the pool if it can't hold `size`
+    /// bytes. The contents are not preserved.
+    pub fn grow(
+        &mut self,
+        device: &wgpu::Device,
+        memory: &GpuMemory,
+        buffer: &mut PooledBuffer,
+        size: wgpu::BufferAddress,
+    ) -> Result<(), BudgetExceeded> {
+        if buffer.size() < size {
+            let label = memory.label(buffer.buffer());
+            let new = self.acquire(device, memory, &label, size, buffer.usage())?;
+            self.release(std::mem::replace(buffer, new));
+        }
+        Ok(())
+    }
+
+    /// Hands the buffers released this frame over to the current frame slot.
+    /// Call once per frame right after `queue.submit`.
+    pub fn submitted(&mut self, frames: &FrameContext) {
+        self.in_flight.get_mut(frames).append(&mut self.retired);
+    }
+
+    /// Makes the buffers of a completed frame slot reusable. Call right after
+    /// [`FrameContext::begin`].
+    pub fn reclaim(&mut self, frames: &FrameContext, memory: &GpuMemory) {
+        for PooledBuffer { buffer, key } in self.in_flight.get_mut(frames).drain(..) {
+            memory.relabel(&buffer, "pool/free");
+            self.free.entry(key).or_default().push(buffer);
+        }
+    }
+
+    /// Drops every free buffer, keeping only those on loan or still in flight.
+    pub fn trim(&mut self) {
+        self.free.clear();
+    }
+}
@@ -0,0 +1,253 @@
+//! Colors, kept linear so blending and interpolating them is physically
+//! sensible.
+//!
+//! The swapchain and textures are sRGB: the GPU encodes what shaders write
+//! and decodes what they sample, and blends the linear values in between.
+//! Colors are picked in sRGB, though, e.g. from an image editor, so a
+//! [`Color`] is made with [`Color::srgb`], which converts, and its
+//! components are the linear values vertex colors and clear colors want.
+//! [`Color::linear`] is for values that already are, e.g. computed light.
+//! Scenes and other files people edit store colors as sRGB, and can write
+//! them as hex, like CSS: `"#1a1a26"`.
+//!
+//! What the interface draws with is picked by role from a [`Palette`]
+//! rather than spelt out at every call, so screens and debug panels agree
+//! and can be restyled in one place.
+
+use std::{fmt, str::FromStr};
+
+/// A linear RGBA color, with straight alpha.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Self = Self::linear(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Self = Self::linear(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Self = Self::linear(1.0, 1.0, 1.0, 1.0);
+    pub const RED: Self = Self::linear(1.0, 0.0, 0.0, 1.0);
+    pub const GREEN: Self = Self::linear(0.0, 1.0, 0.0, 1.0);
+    pub const BLUE: Self = Self::linear(0.0, 0.0, 1.0, 1.0);
+    pub const YELLOW: Self = Self::linear(1.0, 1.0, 0.0, 1.0);
+    pub const CYAN: Self = Self::linear(0.0, 1.0, 1.0, 1.0);
+    pub const MAGENTA: Self = Self::linear(1.0, 0.0, 1.0, 1.0);
+    /// sRGB 50% gray, i.e. halfway between black and white to the eye.
+    pub const GRAY: Self = Self::linear(0.214_041_14, 0.214_041_14, 0.214_041_14, 1.0);
+
+    pub const fn linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Converts sRGB components, as color pickers show them, to linear.
+    /// Alpha is linear either way.
+    pub fn srgb(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::linear(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    /// [`Color::srgb`] from an `[r, g, b, a]` array.
+    pub fn from_srgb(srgb: [f32; 4]) -> Self {
+        let [r, g, b, a] = srgb;
+        Self::srgb(r, g, b, a)
+    }
+
+    /// The sRGB components, as [`Color::srgb`] takes them.
+    pub fn to_srgb(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    /// 8-bit sRGB components, as in image files.
+    pub fn srgb8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let unit = |value: u8| f32::from(value) / 255.0;
+        Self::srgb(unit(r), unit(g), unit(b), unit(a))
+    }
+
+    /// Hue in degrees, saturation and value, all of the sRGB color, as
+    /// color pickers show them.
+    pub fn hsv(hue: f32, saturation: f32, value: f32, a: f32) -> Self {
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = value - chroma;
+        Self::srgb(r + m, g + m, b + m, a)
+    }
+
+    /// The hue in degrees, saturation and value, as [`Color::hsv`] takes
+    /// them.
+    pub fn to_hsv(self) -> [f32; 3] {
+        let [r, g, b, _] = self.to_srgb();
+        let max = r.max(g).max(b);
+        let chroma = max - r.min(g).min(b);
+        let hue = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / chroma + 2.0)
+        } else {
+            60.0 * ((r - g) / chroma + 4.0)
+        };
+        let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+        [hue, saturation, max]
+    }
+
+    /// Interpolates in linear space, `t` of the way to `other`.
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        let mix = |from: f32, to: f32| from + (to - from) * t;
+        Self::linear(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    /// The linear components, for vertex data.
+    pub fn to_linear(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+}
+
+/// `#rrggbbaa` in sRGB, or `#rrggbb` when opaque.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b, a] = self
+            .to_srgb()
+            .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        write!(f, "#{:02x}{:02x}{:02x}", r, g, b)?;
+        if a != 0xff {
+            write!(f, "{:02x}", a)?;
+        }
+        Ok(())
+    }
+}
+
+/// A string that isn't a hex color.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseColorError(String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid color `{}`, expected #rgb, #rgba, #rrggbb or #rrggbbaa",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Parses sRGB hex: `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(hex: &str) -> Result<Self, ParseColorError> {
+        let error = || ParseColorError(hex.to_owned());
+        let digits = hex.strip_prefix('#').ok_or_else(error)?;
+        if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(error());
+        }
+        let digit = |index: usize| u8::from_str_radix(&digits[index..=index], 16).unwrap();
+        let byte = |index: usize| u8::from_str_radix(&digits[index..index + 2], 16).unwrap();
+        let [r, g, b, a] = match digits.len() {
+            3 | 4 => {
+                let a = if digits.len() == 4 {
+                    digit(3) * 0x11
+                } else {
+                    0xff
+                };
+                [digit(0) * 0x11, digit(1) * 0x11, digit(2) * 0x11, a]
+            }
+            6 | 8 => {
+                let a = if digits.len() == 8 { byte(6) } else { 0xff };
+                [byte(0), byte(2), byte(4), a]
+            }
+            _ => return Err(error()),
+        };
+        Ok(Self::srgb8(r, g, b, a))
+    }
+}
+
+/// The colors the interface is drawn with, by role.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Palette {
+    /// Behind full-screen menus.
+    pub background: Color,
+    /// Translucent, behind panels drawn over the game.
+    pub panel: Color,
+    /// The unfilled part of bars and gauges.
+    pub track: Color,
+    pub text: Color,
+    /// Prompts and other secondary text.
+    pub text_muted: Color,
+    /// Within budget, e.g. frame times.
+    pub good: Color,
+    pub warning: Color,
+    pub bad: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: Color::srgb(0.1, 0.1, 0.15, 1.0),
+            panel: Color::BLACK.with_alpha(0.6),
+            track: Color::srgb(0.3, 0.3, 0.35, 1.0),
+            text: Color::WHITE,
+            text_muted: Color::srgb(0.7, 0.7, 0.7, 1.0),
+            good: Color::srgb(0.3, 0.9, 0.3, 1.0),
+            warning: Color::srgb(0.9, 0.8, 0.2, 1.0),
+            bad: Color::srgb(0.9, 0.3, 0.2, 1.0),
+        }
+    }
+}
+
+/// A clear color for an sRGB target, which takes linear values too.
+impl From<Color> for wgpu::Color {
+    fn from(color: Color) -> Self {
+        wgpu::Color {
+            r: f64::from(color.r),
+            g: f64::from(color.g),
+            b: f64::from(color.b),
+            a: f64::from(color.a),
+        }
+    }
+}
+
+/// Decodes one sRGB component, as `srgb_to_linear` in `common.wgsl` does.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes one linear component, as `linear_to_srgb` in `common.wgsl` does.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
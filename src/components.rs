@@ -0,0 +1,76 @@
+//! Components the engine knows how to simulate and draw.
+
+use nalgebra as na;
+
+use crate::{color::Color, ecs::Entity};
+
+/// Placement relative to the entity's [`Parent`], or to the interface's
+/// logical pixels for entities without one.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: na::Vector3<f32>,
+    pub rotation: na::UnitQuaternion<f32>,
+    pub scale: na::Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: na::Vector3::zeros(),
+            rotation: na::UnitQuaternion::identity(),
+            scale: na::Vector3::repeat(1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(x: f32, y: f32) -> Self {
+        Self {
+            translation: na::Vector3::new(x, y, 0.0),
+            ..Default::default()
+        }
+    }
+
+    /// Scales, then rotates, then translates.
+    pub fn matrix(&self) -> na::Matrix4<f32> {
+        na::Isometry3::from_parts(self.translation.into(), self.rotation).to_homogeneous()
+            * na::Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+}
+
+/// Makes the entity's [`Transform`] relative to another entity's.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+/// The combined transform of an entity and its parents, written by
+/// [`scene_graph::propagate`](crate::scene_graph::propagate) every update.
+/// `previous` is the last update's, so drawing can interpolate between them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlobalTransform {
+    pub matrix: na::Matrix4<f32>,
+    pub previous: na::Matrix4<f32>,
+}
+
+impl GlobalTransform {
+    /// Where `point` ends up `alpha` of the way from the previous update to
+    /// this one.
+    pub fn transform_point(&self, point: [f32; 2], alpha: f32) -> [f32; 2] {
+        let point = na::Point3::new(point[0], point[1], 0.0);
+        let previous = self.previous.transform_point(&point);
+        let current = self.matrix.transform_point(&point);
+        let point = previous + (current - previous) * alpha;
+        [point.x, point.y]
+    }
+}
+
+/// Logical pixels per second.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Velocity(pub [f32; 2]);
+
+/// A filled rectangle the interface pass draws from the origin of the
+/// entity's [`GlobalTransform`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub size: [f32; 2],
+    pub color: Color,
+}
@@ -0,0 +1,175 @@
+//! Compute shaders, dispatched into an encoder the way render passes draw
+//! into one. Passes in the [`RenderGraph`](crate::render_graph::RenderGraph)
+//! dispatch from [`Pass::prepare`](crate::passes::Pass::prepare), which
+//! records into the frame's encoder before any pass draws, so what they
+//! compute is ready for every draw that frame.
+
+use std::rc::Rc;
+
+use crate::{
+    bind_group_cache::{Binding, LayoutId},
+    pipeline_cache::{ComputePipelineKey, ShaderError, ShaderSource, ShaderVariant},
+    profiler, validation, Context,
+};
+
+/// Where the shader of a [`ComputePass`] is loaded from.
+#[derive(Clone, Debug)]
+enum Shader {
+    Variant(ShaderVariant),
+    Source(ShaderSource),
+}
+
+/// How many workgroups a dispatch runs.
+enum Dispatch<'a> {
+    Direct([u32; 3]),
+    /// Read from a buffer at an offset.
+    Indirect(&'a wgpu::Buffer, wgpu::BufferAddress),
+}
+
+/// A compute shader with the bind group layouts reflected from it, ready
+/// to dispatch. The pipeline is looked up in the cache on every dispatch,
+/// so hot reloading the shader takes effect on the next one.
+pub struct ComputePass {
+    shader: Shader,
+    key: ComputePipelineKey,
+    workgroup_size: [u32; 3],
+}
+
+impl ComputePass {
+    /// Loads `shader`, e.g. `"particles.comp"`, from the assets.
+    pub fn new(ctx: &mut Context, shader: impl Into<ShaderVariant>) -> Result<Self, ShaderError> {
+        Self::load(ctx, Shader::Variant(shader.into()))
+    }
+
+    /// Compiles the shader from `source`; see
+    /// [`PipelineCache::load_source`](crate::pipeline_cache::PipelineCache::load_source).
+    pub fn from_source(ctx: &mut Context, source: ShaderSource) -> Result<Self, ShaderError> {
+        Self::load(ctx, Shader::Source(source))
+    }
+
+    fn load(ctx: &mut Context, shader: Shader) -> Result<Self, ShaderError> {
+        let (name, id) = match &shader {
+            Shader::Variant(variant) => (
+                variant.name,
+                ctx.pipelines.load_variant(&ctx.device, *variant)?,
+            ),
+            Shader::Source(source) => {
+                (source.name, ctx.pipelines.load_source(&ctx.device, source)?)
+            }
+        };
+        let bind_group_layouts = ctx
+            .pipelines
+            .bind_group_layouts(&[id])?
+            .iter()
+            .map(|entries| ctx.bind_groups.layout_id(&ctx.device, name, entries))
+            .collect();
+        let key = ComputePipelineKey {
+            shader: id,
+            bind_group_layouts,
+        };
+        ctx.pipelines.validate_compute(&key, &ctx.bind_groups)?;
+        let workgroup_size = ctx.pipelines.interface(id).workgroup_size;
+        Ok(Self {
+            shader,
+            key,
+            workgroup_size,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match &self.shader {
+            Shader::Variant(variant) => variant.name,
+            Shader::Source(source) => source.name,
+        }
+    }
+
+    /// The layout of bind group `group`, as the shader uses it.
+    pub fn layout(&self, group: u32) -> LayoutId {
+        self.key.bind_group_layouts[group as usize]
+    }
+
+    /// A bind group of `bindings` for group `group`, through the cache.
+    pub fn bind_group(
+        &self,
+        ctx: &mut Context,
+        group: u32,
+        bindings: &[Binding],
+    ) -> Rc<wgpu::BindGroup> {
+        ctx.bind_groups
+            .bind_group(&ctx.device, self.name(), self.layout(group), bindings)
+    }
+
+    /// Invocations per workgroup along each axis, as the shader declares.
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// The fewest workgroups covering `invocations` along each axis.
+    pub fn workgroups(&self, invocations: [u32; 3]) -> [u32; 3] {
+        let count = |axis: usize| invocations[axis].div_ceil(self.workgroup_size[axis].max(1));
+        [count(0), count(1), count(2)]
+    }
+
+    /// Records a dispatch of `workgroups` with `bind_groups` bound from
+    /// group 0 up.
+    pub fn dispatch(
+        &self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_groups: &[&wgpu::BindGroup],
+        workgroups: [u32; 3],
+    ) {
+        self.record(ctx, encoder, bind_groups, Dispatch::Direct(workgroups));
+    }
+
+    /// Records a dispatch whose workgroup counts are read from `buffer` at
+    /// `offset`, three `u32`s, e.g. as written by an earlier dispatch.
+    pub fn dispatch_indirect(
+        &self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_groups: &[&wgpu::BindGroup],
+        buffer: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    ) {
+        self.record(
+            ctx,
+            encoder,
+            bind_groups,
+            Dispatch::Indirect(buffer, offset),
+        );
+    }
+
+    fn record(
+        &self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_groups: &[&wgpu::BindGroup],
+        dispatch: Dispatch,
+    ) {
+        let name = self.name();
+        let _scope = validation::scope(name);
+        let _profile = profiler::scope(name);
+        let pipeline = ctx
+            .pipelines
+            .compute_pipeline(&ctx.device, &ctx.bind_groups, &self.key);
+        let mut pass = encoder.begin_compute_pass();
+        pass.set_pipeline(&pipeline);
+        for (group, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(group as u32, bind_group, &[]);
+            ctx.stats.bind_group();
+        }
+        match dispatch {
+            Dispatch::Direct([x, y, z]) => pass.dispatch(x, y, z),
+            Dispatch::Indirect(buffer, offset) => pass.dispatch_indirect(buffer, offset),
+        }
+        ctx.stats.dispatch();
+    }
+
+    /// Loads the shader again on the context's current device, e.g. after
+    /// `Context::recover`. Bind groups have to be made again.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), ShaderError> {
+        *self = Self::load(ctx, self.shader.clone())?;
+        Ok(())
+    }
+}
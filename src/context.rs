@@ -0,0 +1,669 @@
+//! The GPU context: device, output and the caches and per-frame
+//! resources shared by every pass.
+
+use std::path::{Path, PathBuf};
+
+use winit::window::Window;
+
+use crate::{
+    adapter::{self, AdapterChoice},
+    bind_group_cache::BindGroupCache,
+    blit, buffer_pool,
+    color::Color,
+    error::{EngineError, FrameError, ReadbackError},
+    frame, gpu_mem,
+    pipeline_cache::PipelineCache,
+    readback, recorder,
+    render_target::{DepthBuffer, MultisampleBuffer},
+    sampler::{self, SamplerCache, SamplerDesc},
+    staging,
+    stats::RenderStats,
+    validation,
+};
+
+const FRAMES_IN_FLIGHT: usize = 2;
+const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 1 << 16;
+
+/// Parses `fifo`, `mailbox` or `immediate`, as taken by `--present-mode`
+/// and `PRESENT_MODE`.
+pub fn parse_present_mode(value: &str) -> Option<wgpu::PresentMode> {
+    match value {
+        "fifo" => Some(wgpu::PresentMode::Fifo),
+        "mailbox" => Some(wgpu::PresentMode::Mailbox),
+        "immediate" => Some(wgpu::PresentMode::Immediate),
+        _ => None,
+    }
+}
+
+/// Swap chain formats `SURFACE_FORMAT` can name, sRGB ones first.
+const SURFACE_FORMATS: &[(&str, wgpu::TextureFormat)] = &[
+    ("bgra8-srgb", wgpu::TextureFormat::Bgra8UnormSrgb),
+    ("rgba8-srgb", wgpu::TextureFormat::Rgba8UnormSrgb),
+    ("bgra8", wgpu::TextureFormat::Bgra8Unorm),
+    ("rgba8", wgpu::TextureFormat::Rgba8Unorm),
+];
+
+/// Picks the swap chain format: the one named by `SURFACE_FORMAT`, or the
+/// first of [`SURFACE_FORMATS`] otherwise.
+///
+/// wgpu 0.5 can't list the formats a surface supports, and creating a swap
+/// chain with an unsupported one panics inside wgpu, so surfaces that only
+/// expose RGBA need the override.
+fn surface_format() -> wgpu::TextureFormat {
+    let requested = match std::env::var("SURFACE_FORMAT") {
+        Ok(requested) => requested,
+        Err(_) => return SURFACE_FORMATS[0].1,
+    };
+    match SURFACE_FORMATS.iter().find(|(name, _)| *name == requested) {
+        Some(&(_, format)) => format,
+        None => {
+            log::warn!("Unknown SURFACE_FORMAT {:?}", requested);
+            SURFACE_FORMATS[0].1
+        }
+    }
+}
+
+/// Creates the depth buffer and, when multisampling, the color buffer that
+/// is resolved into the swap chain frame.
+fn create_attachments(
+    device: &wgpu::Device,
+    memory: &gpu_mem::GpuMemory,
+    sc_desc: &wgpu::SwapChainDescriptor,
+    sample_count: u32,
+) -> Result<(DepthBuffer, Option<MultisampleBuffer>), gpu_mem::BudgetExceeded> {
+    let depth = DepthBuffer::new(
+        device,
+        memory,
+        "context/depth",
+        sc_desc.width,
+        sc_desc.height,
+        sample_count,
+    )?;
+
+    let multisample = if sample_count > 1 {
+        Some(MultisampleBuffer::new(
+            device,
+            memory,
+            "context/multisample",
+            sc_desc.width,
+            sc_desc.height,
+            sc_desc.format,
+            sample_count,
+        )?)
+    } else {
+        None
+    };
+
+    Ok((depth, multisample))
+}
+
+/// Where frames end up: a window's swap chain, or a texture that can be
+/// read back when running headless.
+enum Output {
+    Window {
+        surface: wgpu::Surface,
+        swap_chain: wgpu::SwapChain,
+    },
+    Headless(gpu_mem::Texture),
+}
+
+/// The texture a frame is drawn into.
+pub enum FrameTarget {
+    Swap(wgpu::SwapChainOutput),
+    Offscreen(wgpu::TextureView),
+    /// A frame being captured or recorded. Swap chain images can't be
+    /// copied from, so it is drawn into `texture` and blitted to `output` on
+    /// present. `path` is set for single-frame captures.
+    Captured {
+        output: wgpu::SwapChainOutput,
+        texture: gpu_mem::Texture,
+        view: wgpu::TextureView,
+        path: Option<PathBuf>,
+    },
+}
+
+impl FrameTarget {
+    pub fn view(&self) -> &wgpu::TextureView {
+        match self {
+            FrameTarget::Swap(output) => &output.view,
+            FrameTarget::Offscreen(view) | FrameTarget::Captured { view, .. } => view,
+        }
+    }
+}
+
+/// A frame being recorded, from [`Context::begin_frame`] to
+/// [`Context::end_frame`]. Every pass records into the same encoder, which
+/// is submitted once at the end.
+pub struct Frame {
+    pub target: FrameTarget,
+    pub encoder: wgpu::CommandEncoder,
+}
+
+impl Frame {
+    pub fn view(&self) -> &wgpu::TextureView {
+        self.target.view()
+    }
+}
+
+fn save_capture(image: &image::RgbaImage, path: &Path) {
+    match image.save(path) {
+        Ok(()) => log::info!("Saved screenshot to {}", path.display()),
+        Err(err) => log::error!("Failed to save screenshot to {}: {}", path.display(), err),
+    }
+}
+
+/// A color texture standing in for a swap chain image described by
+/// `sc_desc`, which can also be sampled and read back. Headless contexts
+/// render into one, as do captured frames.
+fn create_offscreen(
+    device: &wgpu::Device,
+    memory: &gpu_mem::GpuMemory,
+    label: &str,
+    sc_desc: &wgpu::SwapChainDescriptor,
+) -> Result<gpu_mem::Texture, gpu_mem::BudgetExceeded> {
+    memory.create_texture(
+        device,
+        &wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth: 1,
+            },
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: sc_desc.format,
+            usage: sc_desc.usage | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC,
+        },
+    )
+}
+
+pub struct Context {
+    output: Output,
+    pub adapter: wgpu::Adapter,
+    adapter_choice: AdapterChoice,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub sc_desc: wgpu::SwapChainDescriptor,
+    pub depth: DepthBuffer,
+    pub multisample: Option<MultisampleBuffer>,
+    sample_count: u32,
+    capture: Option<PathBuf>,
+    recorder: Option<recorder::Recorder>,
+    pub frames: frame::FrameContext,
+    pub staging: staging::StagingBelt,
+    pub buffer_pool: buffer_pool::BufferPool,
+    pub memory: gpu_mem::GpuMemory,
+    pub bind_groups: BindGroupCache,
+    pub pipelines: PipelineCache,
+    pub samplers: SamplerCache,
+    /// Counts for the frame being recorded; passes add their draws and
+    /// uploads here.
+    pub stats: RenderStats,
+    last_stats: RenderStats,
+
+    pub size: winit::dpi::PhysicalSize<u32>,
+    scale_factor: f64,
+}
+
+impl Context {
+    /// Starts with vsync and without multisampling; see
+    /// [`Context::set_present_mode`] and [`Context::set_sample_count`].
+    pub async fn new(window: &Window, adapter_choice: AdapterChoice) -> Result<Self, EngineError> {
+        let surface = wgpu::Surface::create(window);
+        let mut ctx = Self::create(Some(surface), window.inner_size(), adapter_choice).await?;
+        ctx.scale_factor = window.scale_factor();
+        Ok(ctx)
+    }
+
+    /// A context without a window, rendering every frame into an offscreen
+    /// texture that [`Context::read_pixels`] copies back. Meant for
+    /// server-side rendering and tests on machines without a display.
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        adapter_choice: AdapterChoice,
+    ) -> Result<Self, EngineError> {
+        Self::create(
+            None,
+            winit::dpi::PhysicalSize::new(width, height),
+            adapter_choice,
+        )
+        .await
+    }
+
+    async fn create(
+        surface: Option<wgpu::Surface>,
+        size: winit::dpi::PhysicalSize<u32>,
+        adapter_choice: AdapterChoice,
+    ) -> Result<Self, EngineError> {
+        let adapter = adapter::select(&adapter_choice, surface.as_ref()).await?;
+
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                extensions: wgpu::Extensions {
+                    anisotropic_filtering: false,
+                },
+                limits: Default::default(),
+            })
+            .await;
+
+        let sc_desc = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            format: surface_format(),
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+
+        let memory = gpu_mem::GpuMemory::new(
+            std::env::var("GPU_MEMORY_BUDGET")
+                .ok()
+                .and_then(|budget| budget.parse().ok()),
+        );
+
+        let output = match surface {
+            Some(surface) => Output::Window {
+                swap_chain: device.create_swap_chain(&surface, &sc_desc),
+                surface,
+            },
+            None => Output::Headless(create_offscreen(
+                &device,
+                &memory,
+                "context/offscreen",
+                &sc_desc,
+            )?),
+        };
+
+        let sample_count = 1;
+        let (depth, multisample) = create_attachments(&device, &memory, &sc_desc, sample_count)?;
+
+        let frames = frame::FrameContext::new(&device, &memory, FRAMES_IN_FLIGHT)?;
+        let staging = staging::StagingBelt::new(STAGING_CHUNK_SIZE);
+        let buffer_pool = buffer_pool::BufferPool::new(&frames);
+
+        Ok(Self {
+            output,
+            adapter,
+            adapter_choice,
+            device,
+            queue,
+            sc_desc,
+            depth,
+            multisample,
+            sample_count,
+            capture: None,
+            recorder: None,
+            frames,
+            staging,
+            buffer_pool,
+            bind_groups: BindGroupCache::new(memory.clone()),
+            samplers: SamplerCache::new(memory.clone()),
+            memory,
+            pipelines: PipelineCache::new(),
+            stats: RenderStats::default(),
+            last_stats: RenderStats::default(),
+
+            size,
+            scale_factor: 1.0,
+        })
+    }
+
+    /// Replaces the adapter, device and everything created from them after
+    /// the device was lost. `window` is the one the context was created for,
+    /// or `None` if it is headless. Settings such as the adapter choice and
+    /// sample count carry over; passes must then rebuild their own resources.
+    ///
+    /// On failure the context is left as it was, still unusable.
+    pub async fn recover(&mut self, window: Option<&Window>) -> Result<(), EngineError> {
+        let sample_count = self.sample_count;
+        let present_mode = self.present_mode();
+        let scale_factor = self.scale_factor;
+        let surface = window.map(wgpu::Surface::create);
+        *self = Self::create(surface, self.size, self.adapter_choice.clone()).await?;
+        self.scale_factor = scale_factor;
+        self.set_sample_count(sample_count)?;
+        self.set_present_mode(present_mode)?;
+        Ok(())
+    }
+
+    /// Physical pixels per logical pixel, as reported by the window.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Call on `ScaleFactorChanged`, before resizing to the new inner size.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
+
+    /// The output size in logical pixels, which keep the same physical size
+    /// across monitors with different scale factors.
+    pub fn logical_size(&self) -> winit::dpi::LogicalSize<f32> {
+        self.size.to_logical(self.scale_factor)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self.output, Output::Headless(_))
+    }
+
+    pub async fn resize(
+        &mut self,
+        new_size: winit::dpi::PhysicalSize<u32>,
+    ) -> Result<(), gpu_mem::BudgetExceeded> {
+        self.size = new_size;
+        self.sc_desc.width = new_size.width;
+        self.sc_desc.height = new_size.height;
+
+        // A minimized window has no area to present to; keep the old swap
+        // chain until it is restored. `begin_frame` reports it outdated.
+        if new_size.width == 0 || new_size.height == 0 {
+            return Ok(());
+        }
+
+        self.recreate_output()?;
+        self.recreate_attachments()
+    }
+
+    /// Rebuilds the swap chain, or the offscreen texture, from `sc_desc`.
+    pub fn recreate_output(&mut self) -> Result<(), gpu_mem::BudgetExceeded> {
+        match &mut self.output {
+            Output::Window {
+                surface,
+                swap_chain,
+            } => *swap_chain = self.device.create_swap_chain(surface, &self.sc_desc),
+            Output::Headless(texture) => {
+                *texture = create_offscreen(
+                    &self.device,
+                    &self.memory,
+                    "context/offscreen",
+                    &self.sc_desc,
+                )?
+            }
+        }
+        Ok(())
+    }
+
+    /// Acquires the next frame and starts recording it.
+    pub fn begin_frame(&mut self) -> Result<Frame, EngineError> {
+        let target = self.acquire_target()?;
+        self.frames.begin(&self.device)?;
+        self.buffer_pool.reclaim(&self.frames, &self.memory);
+
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("frame"),
+            });
+        Ok(Frame { target, encoder })
+    }
+
+    /// Submits everything recorded into `frame` and presents it.
+    pub fn end_frame(&mut self, frame: Frame) -> Result<(), EngineError> {
+        let Frame {
+            target,
+            mut encoder,
+        } = frame;
+        self.frames.end(&mut encoder);
+
+        self.staging.finish();
+        self.queue.submit(&[encoder.finish()]);
+        self.staging.recall();
+        self.buffer_pool.submitted(&self.frames);
+        self.frames.submitted();
+        self.bind_groups.maintain();
+
+        // Staging chunks are only remapped (and dropped resources reclaimed)
+        // once the device is polled.
+        self.device.poll(wgpu::Maintain::Poll);
+
+        self.present(target)
+    }
+
+    fn acquire_target(&mut self) -> Result<FrameTarget, EngineError> {
+        if self.sc_desc.width == 0 || self.sc_desc.height == 0 {
+            return Err(FrameError::Outdated.into());
+        }
+        match &mut self.output {
+            Output::Window { swap_chain, .. } => {
+                let output = swap_chain
+                    .get_next_texture()
+                    .map_err(|wgpu::TimeOut| FrameError::Timeout)?;
+                Ok(if self.capture.is_some() || self.recorder.is_some() {
+                    let texture = create_offscreen(
+                        &self.device,
+                        &self.memory,
+                        "context/capture",
+                        &self.sc_desc,
+                    )?;
+                    let view = texture.create_default_view();
+                    FrameTarget::Captured {
+                        output,
+                        texture,
+                        view,
+                        path: self.capture.take(),
+                    }
+                } else {
+                    FrameTarget::Swap(output)
+                })
+            }
+            Output::Headless(texture) => Ok(FrameTarget::Offscreen(texture.create_default_view())),
+        }
+    }
+
+    /// Finishes `target` after its commands were submitted, presenting it
+    /// if it came from the swap chain and saving it if it was captured.
+    fn present(&mut self, target: FrameTarget) -> Result<(), EngineError> {
+        self.stats.bytes_uploaded += self.staging.take_written();
+        self.last_stats = std::mem::take(&mut self.stats);
+
+        match target {
+            FrameTarget::Swap(_) => {}
+            FrameTarget::Offscreen(_) => {
+                if self.capture.is_some() || self.recorder.is_some() {
+                    let image = self.read_pixels()?;
+                    let path = self.capture.take();
+                    self.captured(image, path);
+                }
+            }
+            FrameTarget::Captured {
+                output,
+                texture,
+                view,
+                path,
+            } => {
+                let _scope = validation::scope("capture");
+                let mut encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("capture"),
+                        });
+                let format = self.sc_desc.format;
+                blit::blit(self, &mut encoder, &view, &output.view, format)?;
+                self.queue.submit(&[encoder.finish()]);
+
+                let image = readback::read_rgba(
+                    self,
+                    &texture,
+                    format,
+                    self.sc_desc.width,
+                    self.sc_desc.height,
+                )?;
+                self.captured(image, path);
+            }
+        }
+        Ok(())
+    }
+
+    /// What the last presented frame submitted.
+    pub fn frame_stats(&self) -> RenderStats {
+        self.last_stats
+    }
+
+    fn captured(&mut self, image: image::RgbaImage, path: Option<PathBuf>) {
+        if let Some(path) = path {
+            save_capture(&image, &path);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push(image);
+        }
+    }
+
+    /// Saves the next presented frame to `path` as a PNG.
+    pub fn capture_frame(&mut self, path: impl Into<PathBuf>) {
+        self.capture = Some(path.into());
+    }
+
+    /// Sends every presented frame to `sink` until `stop_recording`.
+    ///
+    /// Each frame is read back synchronously, which costs some frame rate
+    /// while recording; encoding happens on the recorder's thread.
+    pub fn start_recording(&mut self, sink: recorder::Sink) -> std::io::Result<()> {
+        self.recorder = Some(recorder::Recorder::start(sink)?);
+        Ok(())
+    }
+
+    /// Stops recording, waiting for queued frames to be written. Returns the
+    /// number of frames dropped because the writer fell behind.
+    pub fn stop_recording(&mut self) -> Option<usize> {
+        self.recorder.take().map(|recorder| {
+            let dropped = recorder.dropped();
+            recorder.stop();
+            dropped
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Copies the last frame of a headless context back as RGBA.
+    ///
+    /// Fails with [`ReadbackError::Windowed`] if the context renders to a
+    /// window.
+    pub fn read_pixels(&self) -> Result<image::RgbaImage, EngineError> {
+        match &self.output {
+            Output::Headless(texture) => readback::read_rgba(
+                self,
+                texture,
+                self.sc_desc.format,
+                self.sc_desc.width,
+                self.sc_desc.height,
+            ),
+            Output::Window { .. } => Err(ReadbackError::Windowed.into()),
+        }
+    }
+
+    fn recreate_attachments(&mut self) -> Result<(), gpu_mem::BudgetExceeded> {
+        // Drop the old buffers first so they don't count against the budget
+        // while their replacements are created.
+        self.multisample = None;
+        let (depth, multisample) =
+            create_attachments(&self.device, &self.memory, &self.sc_desc, self.sample_count)?;
+        self.depth = depth;
+        self.multisample = multisample;
+        Ok(())
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Switches multisampling on (`count > 1`) or off. Pipelines pick the
+    /// new count up from `sample_count` the next time they are looked up.
+    pub fn set_sample_count(&mut self, count: u32) -> Result<(), gpu_mem::BudgetExceeded> {
+        if count != self.sample_count {
+            self.sample_count = count;
+            self.recreate_attachments()?;
+        }
+        Ok(())
+    }
+
+    /// Format of the swap chain frames, which pipelines drawing to them must
+    /// use for their color state.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.sc_desc.format
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.sc_desc.present_mode
+    }
+
+    /// Rebuilds the swap chain with `mode`.
+    ///
+    /// wgpu 0.5 can't tell which modes a surface supports; it quietly falls
+    /// back to `Fifo` when the requested one isn't, so `present_mode` keeps
+    /// reporting the request.
+    pub fn set_present_mode(
+        &mut self,
+        mode: wgpu::PresentMode,
+    ) -> Result<(), gpu_mem::BudgetExceeded> {
+        if mode == self.sc_desc.present_mode {
+            return Ok(());
+        }
+        self.sc_desc.present_mode = mode;
+        if self.sc_desc.width != 0 && self.sc_desc.height != 0 {
+            self.recreate_output()?;
+        }
+        Ok(())
+    }
+
+    /// Toggles between `Fifo` and `Immediate`.
+    pub fn set_vsync(&mut self, vsync: bool) -> Result<(), gpu_mem::BudgetExceeded> {
+        self.set_present_mode(if vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::Immediate
+        })
+    }
+
+    /// Color attachment for drawing to `frame`, through the multisample
+    /// buffer when there is one.
+    pub fn color_attachment<'a>(
+        &'a self,
+        frame: &'a wgpu::TextureView,
+        load_op: wgpu::LoadOp,
+        clear_color: Color,
+    ) -> wgpu::RenderPassColorAttachmentDescriptor<'a> {
+        let (attachment, resolve_target) = match &self.multisample {
+            Some(multisample) => (multisample.view(), Some(frame)),
+            None => (frame, None),
+        };
+        wgpu::RenderPassColorAttachmentDescriptor {
+            attachment,
+            resolve_target,
+            load_op,
+            store_op: wgpu::StoreOp::Store,
+            clear_color: clear_color.into(),
+        }
+    }
+
+    pub fn create_sampler(&mut self, desc: SamplerDesc) -> sampler::Sampler {
+        self.samplers.get(&self.device, desc)
+    }
+
+    /// Frees pooled and idle staging memory after an allocation failure.
+    pub fn release_transient_memory(&mut self) {
+        self.device.poll(wgpu::Maintain::Wait);
+        self.buffer_pool.trim();
+        self.staging.trim();
+        self.device.poll(wgpu::Maintain::Poll);
+    }
+
+    /// Runs `f`, and once more after `release_transient_memory` if it ran
+    /// out of memory.
+    pub fn retry_out_of_memory<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, gpu_mem::BudgetExceeded>,
+    ) -> Result<T, gpu_mem::BudgetExceeded> {
+        match f(self) {
+            Err(_) => {
+                self.release_transient_memory();
+                f(self)
+            }
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Frustum culling on the GPU, for worlds with more objects than are
+//! worth testing on the CPU every frame.
+//!
+//! Objects are queued with a box around them and the instance they are
+//! drawn with, grouped into draws of one mesh each. A compute shader tests
+//! the boxes against the camera frustum and packs the instances of those
+//! that survive into a buffer of its own, writing each draw's instance
+//! count into an indirect buffer, so the draws are recorded without the
+//! CPU knowing what was culled and draw nothing for what was.
+
+use std::ops::Range;
+
+use nalgebra as na;
+
+use crate::{
+    bind_group_cache::Binding,
+    buffer_pool::PooledBuffer,
+    compute::ComputePass,
+    dynamic_buffer::DynamicBuffer,
+    error::EngineError,
+    gpu_mem::BudgetExceeded,
+    gpu_struct,
+    indirect_buffer::{DrawIndexedArgs, IndirectBuffer},
+    passes::Vertex,
+    Context,
+};
+
+gpu_struct! {
+    std430;
+    /// The space a camera sees, as the planes bounding it, facing in. An
+    /// object is culled if its box is entirely behind any of them.
+    #[derive(Debug)]
+    pub struct Frustum {
+        planes: [na::Vector4<f32>; 6],
+    }
+}
+
+impl Frustum {
+    /// The frustum of `view_projection`, which takes positions to wgpu's
+    /// clip space, with depth from 0 to 1.
+    pub fn new(view_projection: &na::Matrix4<f32>) -> Self {
+        let mut frustum = Self::sides(view_projection);
+        let row = |i: usize| view_projection.row(i).transpose();
+        frustum.planes[4] = row(2);
+        frustum.planes[5] = row(3) - row(2);
+        frustum
+    }
+
+    /// The frustum of `view_projection` without its near and far planes,
+    /// for 2D, where depth is only for ordering.
+    pub fn sides(view_projection: &na::Matrix4<f32>) -> Self {
+        let row = |i: usize| view_projection.row(i).transpose();
+        Self {
+            planes: [
+                row(3) + row(0),
+                row(3) - row(0),
+                row(3) + row(1),
+                row(3) - row(1),
+                na::Vector4::zeros(),
+                na::Vector4::zeros(),
+            ],
+        }
+    }
+}
+
+gpu_struct! {
+    std430;
+    /// `Object` in `cull.comp.wgsl`.
+    #[derive(Debug)]
+    struct CullObject {
+        min: na::Vector3<f32>,
+        draw: u32,
+        max: na::Vector3<f32>,
+        frustum: u32,
+    }
+}
+
+gpu_struct! {
+    std140;
+    /// `Params` in `cull.comp.wgsl`.
+    #[derive(Debug)]
+    struct CullParams {
+        objects: u32,
+        instance_words: u32,
+        _pad0: u32,
+        _pad1: u32,
+    }
+}
+
+/// Objects to cull on the GPU and draw as instances of `I`, and the draws
+/// culling them leaves; see the [module docs](crate::culling).
+///
+/// Each draw is one indirect draw of its mesh, so a draw whose objects are
+/// all culled still costs a draw call, of no instances.
+pub struct FrustumCulling<I: Vertex> {
+    label: String,
+    shader: ComputePass,
+    params: PooledBuffer,
+    objects: DynamicBuffer<CullObject>,
+    instances: DynamicBuffer<I>,
+    frustums: DynamicBuffer<Frustum>,
+    /// The instances that survived, packed by draw.
+    culled: PooledBuffer,
+    draws: IndirectBuffer<DrawIndexedArgs>,
+    /// Of the last draw begun, for the objects pushed after it.
+    frustum: u32,
+}
+
+impl<I: Vertex> FrustumCulling<I> {
+    pub fn new(ctx: &mut Context, label: &str, capacity: usize) -> Result<Self, EngineError> {
+        debug_assert_eq!(std::mem::size_of::<I>() % 4, 0);
+        let shader = ComputePass::new(ctx, "cull.comp")?;
+        let id = ctx.pipelines.load_shader(&ctx.device, "cull.comp")?;
+        ctx.pipelines.check_buffer::<CullParams>(id, 0, 0)?;
+
+        let storage = wgpu::BufferUsage::STORAGE;
+        Ok(Self {
+            label: label.to_owned(),
+            shader,
+            params: Self::acquire_params(ctx, label)?,
+            objects: DynamicBuffer::new(ctx, &format!("{}/objects", label), storage, capacity)?,
+            instances: DynamicBuffer::new(ctx, &format!("{}/instances", label), storage, capacity)?,
+            frustums: DynamicBuffer::new(ctx, &format!("{}/frustums", label), storage, 1)?,
+            culled: Self::acquire_culled(ctx, label, capacity)?,
+            draws: IndirectBuffer::new(ctx, &format!("{}/draws", label), 1)?,
+            frustum: 0,
+        })
+    }
+
+    fn acquire_params(ctx: &mut Context, label: &str) -> Result<PooledBuffer, BudgetExceeded> {
+        ctx.buffer_pool.acquire(
+            &ctx.device,
+            &ctx.memory,
+            &format!("{}/params", label),
+            std::mem::size_of::<CullParams>() as wgpu::BufferAddress,
+            wgpu::BufferUsage::UNIFORM,
+        )
+    }
+
+    fn acquire_culled(
+        ctx: &mut Context,
+        label: &str,
+        capacity: usize,
+    ) -> Result<PooledBuffer, BudgetExceeded> {
+        ctx.buffer_pool.acquire(
+            &ctx.device,
+            &ctx.memory,
+            &format!("{}/culled", label),
+            (capacity.max(1) * std::mem::size_of::<I>()) as wgpu::BufferAddress,
+            wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::VERTEX,
+        )
+    }
+
+    /// Starts a draw of `args`, whose instance count and first instance
+    /// are ignored, of the objects pushed from now on, culled against
+    /// frustum `frustum` of those passed to [`FrustumCulling::cull`].
+    /// Returns its index among the draws.
+    pub fn begin_draw(&mut self, args: DrawIndexedArgs, frustum: u32) -> u32 {
+        self.frustum = frustum;
+        self.draws.push(DrawIndexedArgs {
+            instance_count: 0,
+            first_instance: self.instances.len() as u32,
+            ..args
+        })
+    }
+
+    /// Adds an object to the last draw begun, drawn as `instance` unless
+    /// the box from `min` to `max` is outside the draw's frustum.
+    pub fn push(&mut self, instance: I, min: na::Point3<f32>, max: na::Point3<f32>) {
+        debug_assert!(!self.draws.is_empty(), "no draw begun");
+        self.objects.push(CullObject {
+            min: min.coords,
+            draw: self.draws.len() as u32 - 1,
+            max: max.coords,
+            frustum: self.frustum,
+        });
+        self.instances.push(instance);
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+        self.instances.clear();
+        self.draws.clear();
+    }
+
+    /// How many draws were begun.
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// How many objects were pushed, culled or not.
+    pub fn objects(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// The draws as begun, before culling fills in their instances.
+    pub fn draws(&self) -> &[DrawIndexedArgs] {
+        self.draws.as_slice()
+    }
+
+    /// Uploads the objects and records the dispatch culling them against
+    /// `frustums`, which the draws index.
+    pub fn cull(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        frustums: &[Frustum],
+    ) -> Result<(), EngineError> {
+        self.draws.upload(ctx, encoder)?;
+        if self.objects.is_empty() {
+            return Ok(());
+        }
+        self.objects.upload(ctx, encoder)?;
+        self.instances.upload(ctx, encoder)?;
+        self.frustums.clear();
+        self.frustums.extend_from_slice(frustums);
+        self.frustums.upload(ctx, encoder)?;
+        ctx.buffer_pool.grow(
+            &ctx.device,
+            &ctx.memory,
+            &mut self.culled,
+            (self.instances.len() * std::mem::size_of::<I>()) as wgpu::BufferAddress,
+        )?;
+
+        let params = CullParams {
+            objects: self.objects.len() as u32,
+            instance_words: (std::mem::size_of::<I>() / 4) as u32,
+            _pad0: 0,
+            _pad1: 0,
+        };
+        ctx.staging.write_buffer(
+            &ctx.device,
+            &ctx.memory,
+            encoder,
+            self.params.buffer(),
+            0,
+            bytemuck::bytes_of(&params),
+        )?;
+
+        let bind_group = self.shader.bind_group(
+            ctx,
+            0,
+            &[
+                Binding::Buffer {
+                    buffer: self.params.buffer(),
+                    range: 0..self.params.size(),
+                },
+                self.objects.binding(),
+                self.frustums.binding(),
+                self.instances.binding(),
+                Binding::Buffer {
+                    buffer: self.culled.buffer(),
+                    range: 0..self.culled.size(),
+                },
+                self.draws.binding(),
+            ],
+        );
+        let workgroups = self.shader.workgroups([params.objects, 1, 1]);
+        self.shader
+            .dispatch(ctx, encoder, &[&bind_group], workgroups);
+        Ok(())
+    }
+
+    /// Binds the instances that survived to vertex slot `slot`.
+    pub fn bind<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, slot: u32) {
+        pass.set_vertex_buffer(slot, self.culled.buffer(), 0, 0);
+    }
+
+    /// Records the draws in `draws`, in order, with the mesh buffers and
+    /// the instances bound.
+    pub fn multi_draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, draws: Range<u32>) {
+        self.draws.multi_draw(pass, draws);
+    }
+
+    /// Moves the buffers to the context's current device and loads the
+    /// shader again, keeping the objects. Cull again before drawing.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), EngineError> {
+        self.shader.recreate(ctx)?;
+        self.params = Self::acquire_params(ctx, &self.label)?;
+        self.culled = Self::acquire_culled(ctx, &self.label, self.instances.len())?;
+        self.objects.recreate(ctx)?;
+        self.instances.recreate(ctx)?;
+        self.frustums.recreate(ctx)?;
+        self.draws.recreate(ctx)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+use winit::window::{CursorIcon, Icon, Window};
+
+use crate::{
+    gpu_mem::BudgetExceeded,
+    texture_atlas::{TextureAtlas, UvRect},
+    Context,
+};
+
+/// A cursor image stored in a [`TextureAtlas`]. `hotspot` is the pixel,
+/// from the image's top-left corner, that sits under the pointer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CursorImage {
+    pub uv: UvRect,
+    pub size: [u32; 2],
+    pub hotspot: [u32; 2],
+}
+
+impl CursorImage {
+    /// Adds `image` to `atlas`, or returns `None` if it is full.
+    pub fn new(
+        ctx: &mut Context,
+        atlas: &mut TextureAtlas,
+        image: &image::RgbaImage,
+        hotspot: [u32; 2],
+    ) -> Result<Option<Self>, BudgetExceeded> {
+        let (width, height) = image.dimensions();
+        Ok(atlas.insert(ctx, image)?.map(|uv| Self {
+            uv,
+            size: [width, height],
+            hotspot,
+        }))
+    }
+}
+
+/// What the mouse pointer looks like.
+///
+/// winit 0.22 can only show the platform's own cursors, so custom images
+/// hide the system cursor and are drawn by the interface pass instead.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CursorStyle {
+    System(CursorIcon),
+    Custom(CursorImage),
+    Hidden,
+}
+
+impl Default for CursorStyle {
+    fn default() -> Self {
+        CursorStyle::System(CursorIcon::Default)
+    }
+}
+
+impl CursorStyle {
+    /// Updates the system cursor on `window` to match.
+    pub fn apply(&self, window: &Window) {
+        match self {
+            CursorStyle::System(icon) => {
+                window.set_cursor_icon(*icon);
+                window.set_cursor_visible(true);
+            }
+            CursorStyle::Custom(_) | CursorStyle::Hidden => window.set_cursor_visible(false),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IconError {
+    Image(image::ImageError),
+    Icon(winit::window::BadIcon),
+}
+
+impl fmt::Display for IconError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IconError::Image(err) => write!(f, "failed to decode icon: {}", err),
+            IconError::Icon(err) => write!(f, "invalid icon: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for IconError {}
+
+/// Decodes an embedded PNG or other image file into a window icon.
+pub fn load_icon(bytes: &[u8]) -> Result<Icon, IconError> {
+    let image = image::load_from_memory(bytes)
+        .map_err(IconError::Image)?
+        .to_rgba();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).map_err(IconError::Icon)
+}
+
+/// Relative mouse mode: the cursor is grabbed by the window and hidden,
+/// and raw `DeviceEvent::MouseMotion` deltas are collected instead of
+/// positions, as camera controllers need.
+///
+/// The platform releases the grab when the window loses focus; it is taken
+/// again once focus returns.
+pub struct PointerLock {
+    locked: bool,
+    focused: bool,
+    /// Whether the window currently holds the grab.
+    grabbed: bool,
+    delta: [f64; 2],
+}
+
+impl Default for PointerLock {
+    fn default() -> Self {
+        // New windows normally start focused; winit doesn't always say so.
+        Self {
+            locked: false,
+            focused: true,
+            grabbed: false,
+            delta: [0.0, 0.0],
+        }
+    }
+}
+
+impl PointerLock {
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Takes effect on the next [`PointerLock::apply`].
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+        self.delta = [0.0, 0.0];
+    }
+
+    pub fn focus_changed(&mut self, focused: bool) {
+        self.focused = focused;
+        if !focused {
+            self.grabbed = false;
+        }
+    }
+
+    /// Adds raw motion, if locked.
+    pub fn motion(&mut self, (dx, dy): (f64, f64)) {
+        if self.locked && self.grabbed {
+            self.delta[0] += dx;
+            self.delta[1] += dy;
+        }
+    }
+
+    /// Motion since the last call, in the device's own units.
+    pub fn take_delta(&mut self) -> [f64; 2] {
+        std::mem::replace(&mut self.delta, [0.0, 0.0])
+    }
+
+    /// Grabs or releases the cursor to match. A failed grab, e.g. on
+    /// platforms without support, is reported and retried on the next call.
+    pub fn apply(&mut self, window: &Window) {
+        let grab = self.locked && self.focused;
+        if grab == self.grabbed {
+            return;
+        }
+        match window.set_cursor_grab(grab) {
+            Ok(()) => self.grabbed = grab,
+            Err(err) => {
+                log::warn!("Failed to grab the cursor: {}", err);
+                self.locked = false;
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use winit::{
+    monitor::{MonitorHandle, VideoMode},
+    window::{Fullscreen, Window},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    Windowed,
+    /// A borderless window covering the monitor at its desktop resolution.
+    Borderless,
+    /// Exclusive fullscreen in a specific video mode.
+    Exclusive,
+}
+
+impl DisplayMode {
+    /// Parses `windowed`, `borderless` or `exclusive`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "windowed" => Some(DisplayMode::Windowed),
+            "borderless" => Some(DisplayMode::Borderless),
+            "exclusive" => Some(DisplayMode::Exclusive),
+            _ => None,
+        }
+    }
+}
+
+/// How the window is shown. `monitor` indexes `Window::available_monitors`
+/// and defaults to the one the window is on; `video_mode` is a
+/// `WIDTHxHEIGHT@HZ` string and defaults to the monitor's largest mode.
+#[derive(Clone, Debug)]
+pub struct DisplaySettings {
+    pub mode: DisplayMode,
+    /// The fullscreen mode Alt+Enter switches to from windowed.
+    pub fullscreen_mode: DisplayMode,
+    pub monitor: Option<usize>,
+    pub video_mode: Option<String>,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            mode: DisplayMode::Windowed,
+            fullscreen_mode: DisplayMode::Borderless,
+            monitor: None,
+            video_mode: None,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// Switches between windowed and `fullscreen_mode`.
+    pub fn toggle(&mut self) {
+        self.mode = match self.mode {
+            DisplayMode::Windowed => self.fullscreen_mode,
+            _ => DisplayMode::Windowed,
+        };
+    }
+
+    /// Applies the settings to `window`. Exclusive mode falls back to
+    /// borderless when the monitor reports no matching video mode.
+    ///
+    /// The window's size changes, so the context must be resized afterwards.
+    pub fn apply(&self, window: &Window) {
+        let monitor = self
+            .monitor
+            .and_then(|index| window.available_monitors().nth(index))
+            .unwrap_or_else(|| window.current_monitor());
+
+        let fullscreen = match self.mode {
+            DisplayMode::Windowed => None,
+            DisplayMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            DisplayMode::Exclusive => Some(match self.pick_video_mode(&monitor) {
+                Some(video_mode) => Fullscreen::Exclusive(video_mode),
+                None => {
+                    log::warn!("No video mode matches {:?}", self.video_mode);
+                    Fullscreen::Borderless(monitor)
+                }
+            }),
+        };
+        window.set_fullscreen(fullscreen);
+    }
+
+    fn pick_video_mode(&self, monitor: &MonitorHandle) -> Option<VideoMode> {
+        match &self.video_mode {
+            Some(wanted) => monitor
+                .video_modes()
+                .find(|video_mode| describe_video_mode(video_mode) == *wanted),
+            None => monitor.video_modes().max_by_key(|video_mode| {
+                let size = video_mode.size();
+                (size.width * size.height, video_mode.refresh_rate())
+            }),
+        }
+    }
+}
+
+fn describe_video_mode(video_mode: &VideoMode) -> String {
+    let size = video_mode.size();
+    format!(
+        "{}x{}@{}",
+        size.width,
+        size.height,
+        video_mode.refresh_rate()
+    )
+}
+
+/// Lines describing every monitor and its video modes, for
+/// `--list-displays`.
+pub fn describe(window: &Window) -> Vec<String> {
+    let mut lines = vec![];
+    for (index, monitor) in window.available_monitors().enumerate() {
+        let size = monitor.size();
+        lines.push(format!(
+            "{}: {} ({}x{})",
+            index,
+            monitor.name().unwrap_or_else(|| "unnamed".into()),
+            size.width,
+            size.height
+        ));
+        let mut modes = monitor
+            .video_modes()
+            .map(|video_mode| describe_video_mode(&video_mode))
+            .collect::<Vec<_>>();
+        modes.sort();
+        modes.dedup();
+        lines.extend(modes.into_iter().map(|mode| format!("    {}", mode)));
+    }
+    lines
+}
@@ -0,0 +1,107 @@
+use crate::{
+    bind_group_cache::Binding, buffer_pool::PooledBuffer, error::EngineError,
+    gpu_mem::BudgetExceeded, Context,
+};
+
+/// CPU-side `Vec<T>` mirrored into a pooled GPU buffer.
+///
+/// The GPU buffer is swapped for the next size class whenever the CPU data
+/// outgrows it, so capacity grows geometrically.
+pub struct DynamicBuffer<T: bytemuck::Pod> {
+    label: String,
+    data: Vec<T>,
+    buffer: PooledBuffer,
+}
+
+impl<T: bytemuck::Pod> DynamicBuffer<T> {
+    pub fn new(
+        ctx: &mut Context,
+        label: &str,
+        usage: wgpu::BufferUsage,
+        capacity: usize,
+    ) -> Result<Self, BudgetExceeded> {
+        let buffer = ctx.buffer_pool.acquire(
+            &ctx.device,
+            &ctx.memory,
+            label,
+            (capacity * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+            usage,
+        )?;
+
+        Ok(Self {
+            label: label.to_owned(),
+            data: Vec::with_capacity(capacity),
+            buffer,
+        })
+    }
+
+    /// Replaces the GPU buffer with one from the context's current device,
+    /// keeping the CPU data. Upload again before drawing.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded> {
+        self.buffer = ctx.buffer_pool.acquire(
+            &ctx.device,
+            &ctx.memory,
+            &self.label,
+            self.buffer.size(),
+            self.buffer.usage(),
+        )?;
+        Ok(())
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.data.push(value);
+    }
+
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        self.data.extend_from_slice(values);
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// The whole GPU buffer, to bind for a shader, e.g. as a storage
+    /// buffer. It can be longer than the data.
+    pub fn binding(&self) -> Binding<'_> {
+        Binding::Buffer {
+            buffer: self.buffer.buffer(),
+            range: 0..self.buffer.size(),
+        }
+    }
+
+    /// Grows the GPU buffer if needed and records a copy of the CPU data
+    /// into it through the staging belt.
+    pub fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError> {
+        let bytes: &[u8] = bytemuck::cast_slice(&self.data);
+
+        ctx.buffer_pool.grow(
+            &ctx.device,
+            &ctx.memory,
+            &mut self.buffer,
+            bytes.len() as wgpu::BufferAddress,
+        )?;
+
+        ctx.staging
+            .write_buffer(&ctx.device, &ctx.memory, encoder, &self.buffer, 0, bytes)
+    }
+}
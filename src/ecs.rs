@@ -0,0 +1,186 @@
+//! A minimal entity-component store.
+//!
+//! Entities are ids with a generation, so a stale id never refers to an
+//! entity spawned into its slot later. Each component type is stored in its
+//! own column indexed by entity slot, which keeps lookups a bounds check
+//! and makes iterating one type cheap; joins look the other types up per
+//! entity.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+trait Column {
+    fn remove(&mut self, index: usize);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> Column for Vec<Option<T>> {
+    fn remove(&mut self, index: usize) {
+        if let Some(slot) = self.get_mut(index) {
+            *slot = None;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct World {
+    /// Current generation of each slot; odd while the slot is in use.
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    columns: HashMap<TypeId, Box<dyn Column>>,
+}
+
+impl World {
+    pub fn spawn(&mut self) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                self.generations.len() as u32 - 1
+            }
+        };
+        let generation = &mut self.generations[index as usize];
+        *generation += 1;
+        Entity {
+            index,
+            generation: *generation,
+        }
+    }
+
+    /// Removes `entity` and its components. Returns whether it was alive.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        for column in self.columns.values_mut() {
+            column.remove(entity.index as usize);
+        }
+        self.generations[entity.index as usize] += 1;
+        self.free.push(entity.index);
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index as usize) == Some(&entity.generation)
+    }
+
+    /// Every entity alive, in slot order.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.generations
+            .iter()
+            .enumerate()
+            .filter(|(_, &generation)| generation % 2 == 1)
+            .map(|(index, &generation)| Entity {
+                index: index as u32,
+                generation,
+            })
+    }
+
+    /// Adds `component` to `entity`, replacing one of the same type. Does
+    /// nothing if the entity was despawned.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        let column = self.column_mut::<T>();
+        let index = entity.index as usize;
+        if column.len() <= index {
+            column.resize_with(index + 1, || None);
+        }
+        column[index] = Some(component);
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.column_mut::<T>()
+            .get_mut(entity.index as usize)
+            .and_then(Option::take)
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.column::<T>()?.get(entity.index as usize)?.as_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.column_mut::<T>()
+            .get_mut(entity.index as usize)?
+            .as_mut()
+    }
+
+    /// Every entity with a `T`, in slot order.
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        let generations = &self.generations;
+        self.column::<T>()
+            .into_iter()
+            .flat_map(|column| column.iter().enumerate())
+            .filter_map(move |(index, component)| {
+                let entity = Entity {
+                    index: index as u32,
+                    generation: generations[index],
+                };
+                Some((entity, component.as_ref()?))
+            })
+    }
+
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        let generations = &self.generations;
+        self.columns
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|column| column.as_any_mut().downcast_mut::<Vec<Option<T>>>())
+            .into_iter()
+            .flat_map(|column| column.iter_mut().enumerate())
+            .filter_map(move |(index, component)| {
+                let entity = Entity {
+                    index: index as u32,
+                    generation: generations[index],
+                };
+                Some((entity, component.as_mut()?))
+            })
+    }
+
+    /// Every entity with both an `A` and a `B`.
+    pub fn query2<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> {
+        self.query::<A>()
+            .filter_map(move |(entity, a)| Some((entity, a, self.get::<B>(entity)?)))
+    }
+
+    fn column<T: 'static>(&self) -> Option<&Vec<Option<T>>> {
+        self.columns
+            .get(&TypeId::of::<T>())
+            .and_then(|column| column.as_any().downcast_ref())
+    }
+
+    fn column_mut<T: 'static>(&mut self) -> &mut Vec<Option<T>> {
+        self.columns
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<Option<T>>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .expect("component column stored under another type")
+    }
+}
@@ -0,0 +1,171 @@
+use std::{fmt, path::PathBuf};
+
+use crate::{
+    adapter::NoAdapter, assets::texture::TextureError, gpu_mem::BudgetExceeded,
+    pipeline_cache::ShaderError, render_graph::GraphError, scene::SceneError,
+};
+
+/// Why no swap chain frame could be acquired.
+#[derive(Debug)]
+pub enum FrameError {
+    /// The GPU didn't hand out an image in time. Skip the frame.
+    Timeout,
+    /// The swap chain no longer matches the window, e.g. while it is
+    /// minimized. Resize the context to the window's current size.
+    ///
+    /// Lost and suboptimal swap chains are reconfigured by wgpu itself and
+    /// never reach the application.
+    Outdated,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Timeout => write!(f, "timed out acquiring a swap chain frame"),
+            FrameError::Outdated => write!(f, "swap chain is outdated"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// Why a texture couldn't be copied back to the CPU.
+#[derive(Debug)]
+pub enum ReadbackError {
+    /// The context renders to a window, whose frames can't be read back.
+    Windowed,
+    /// Only the 8-bit RGBA and BGRA formats can be read back.
+    UnsupportedFormat(wgpu::TextureFormat),
+}
+
+impl fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadbackError::Windowed => write!(f, "can't read back a windowed context"),
+            ReadbackError::UnsupportedFormat(format) => {
+                write!(f, "can't read back {:?} textures", format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+/// The GPU device stopped working, e.g. after a driver reset. Everything
+/// created from it has to be rebuilt; see `Context::recover`.
+#[derive(Copy, Clone, Debug)]
+pub struct DeviceLost;
+
+impl fmt::Display for DeviceLost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GPU device lost")
+    }
+}
+
+impl std::error::Error for DeviceLost {}
+
+/// Everything that can go wrong setting up or running the renderer.
+///
+/// The main loop decides what to do with each: memory and swap chain
+/// problems are handled and the frame skipped, a lost device is recovered
+/// from, and anything else ends the program.
+#[derive(Debug)]
+pub enum EngineError {
+    /// No adapter matched the request, e.g. on machines without a GPU.
+    NoAdapter(NoAdapter),
+    /// A built-in shader isn't valid SPIR-V.
+    Shader(ShaderError),
+    SwapChain(FrameError),
+    /// An asset file doesn't exist.
+    AssetMissing(PathBuf),
+    /// An asset exists but couldn't be loaded.
+    Asset(TextureError),
+    /// An allocation was refused. The frame was dropped and the caller is
+    /// expected to release memory (see `Context::release_transient_memory`)
+    /// before trying again.
+    OutOfMemory(BudgetExceeded),
+    DeviceLost(DeviceLost),
+    /// The passes don't form a valid render graph.
+    Graph(GraphError),
+    /// A built-in scene doesn't parse.
+    Scene(SceneError),
+    Readback(ReadbackError),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::NoAdapter(err) => err.fmt(f),
+            EngineError::Shader(err) => err.fmt(f),
+            EngineError::SwapChain(err) => err.fmt(f),
+            EngineError::AssetMissing(path) => write!(f, "asset {} not found", path.display()),
+            EngineError::Asset(err) => err.fmt(f),
+            EngineError::OutOfMemory(err) => write!(f, "out of GPU memory: {}", err),
+            EngineError::DeviceLost(err) => err.fmt(f),
+            EngineError::Graph(err) => err.fmt(f),
+            EngineError::Scene(err) => err.fmt(f),
+            EngineError::Readback(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<NoAdapter> for EngineError {
+    fn from(err: NoAdapter) -> Self {
+        EngineError::NoAdapter(err)
+    }
+}
+
+impl From<ShaderError> for EngineError {
+    fn from(err: ShaderError) -> Self {
+        EngineError::Shader(err)
+    }
+}
+
+impl From<TextureError> for EngineError {
+    fn from(err: TextureError) -> Self {
+        match err {
+            TextureError::Missing(path) => EngineError::AssetMissing(path),
+            TextureError::OutOfMemory(err) => EngineError::OutOfMemory(err),
+            TextureError::Shader(err) => EngineError::Shader(err),
+            err => EngineError::Asset(err),
+        }
+    }
+}
+
+impl From<BudgetExceeded> for EngineError {
+    fn from(err: BudgetExceeded) -> Self {
+        EngineError::OutOfMemory(err)
+    }
+}
+
+impl From<FrameError> for EngineError {
+    fn from(err: FrameError) -> Self {
+        EngineError::SwapChain(err)
+    }
+}
+
+impl From<DeviceLost> for EngineError {
+    fn from(err: DeviceLost) -> Self {
+        EngineError::DeviceLost(err)
+    }
+}
+
+impl From<GraphError> for EngineError {
+    fn from(err: GraphError) -> Self {
+        EngineError::Graph(err)
+    }
+}
+
+impl From<SceneError> for EngineError {
+    fn from(err: SceneError) -> Self {
+        EngineError::Scene(err)
+    }
+}
+
+impl From<ReadbackError> for EngineError {
+    fn from(err: ReadbackError) -> Self {
+        EngineError::Readback(err)
+    }
+}
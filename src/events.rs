@@ -0,0 +1,142 @@
+//! Typed event queues, so subsystems can tell each other about things
+//! (the window resized, a button was clicked) without holding references
+//! to each other.
+//!
+//! Events are kept for two calls to [`Events::update`], so every reader
+//! that reads once per update sees each event exactly once, whether it
+//! runs before or after the writer. [`EventBus`] holds one queue per event
+//! type.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+pub struct Events<T> {
+    /// Id of the first event in `previous`; ids count every event sent.
+    base: usize,
+    previous: Vec<T>,
+    current: Vec<T>,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            base: 0,
+            previous: vec![],
+            current: vec![],
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    /// Drops the events sent before the previous update. Call once per
+    /// update, e.g. per fixed step.
+    pub fn update(&mut self) {
+        self.base += self.previous.len();
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// A reader that sees events sent from now on.
+    pub fn reader(&self) -> EventReader<T> {
+        EventReader {
+            next: self.end(),
+            _events: PhantomData,
+        }
+    }
+
+    /// The events `reader` hasn't seen yet, oldest first. Events dropped by
+    /// `update` before the reader got to them are skipped.
+    pub fn read<'a>(&'a self, reader: &mut EventReader<T>) -> impl Iterator<Item = &'a T> {
+        let skip = reader.next.saturating_sub(self.base);
+        reader.next = self.end();
+        self.previous.iter().chain(&self.current).skip(skip)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.previous.is_empty() && self.current.is_empty()
+    }
+
+    fn end(&self) -> usize {
+        self.base + self.previous.len() + self.current.len()
+    }
+}
+
+/// Where one reader is in an [`Events`] queue.
+pub struct EventReader<T> {
+    next: usize,
+    _events: PhantomData<fn() -> T>,
+}
+
+trait Queue {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> Queue for Events<T> {
+    fn update(&mut self) {
+        Events::update(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// One [`Events`] queue per event type, created on first use.
+#[derive(Default)]
+pub struct EventBus {
+    queues: HashMap<TypeId, Box<dyn Queue>>,
+}
+
+impl EventBus {
+    pub fn send<T: 'static>(&mut self, event: T) {
+        self.events_mut::<T>().send(event);
+    }
+
+    pub fn reader<T: 'static>(&mut self) -> EventReader<T> {
+        self.events_mut::<T>().reader()
+    }
+
+    pub fn read<'a, T: 'static>(
+        &'a self,
+        reader: &mut EventReader<T>,
+    ) -> impl Iterator<Item = &'a T> {
+        self.events::<T>()
+            .map(|events| events.read(reader))
+            .into_iter()
+            .flatten()
+    }
+
+    pub fn events<T: 'static>(&self) -> Option<&Events<T>> {
+        self.queues
+            .get(&TypeId::of::<T>())
+            .and_then(|queue| queue.as_any().downcast_ref())
+    }
+
+    pub fn events_mut<T: 'static>(&mut self) -> &mut Events<T> {
+        self.queues
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Events::<T>::default()))
+            .as_any_mut()
+            .downcast_mut()
+            .expect("event queue stored under another type")
+    }
+
+    /// Updates every queue; see [`Events::update`].
+    pub fn update(&mut self) {
+        for queue in self.queues.values_mut() {
+            queue.update();
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::task::noop_waker_ref;
+
+use crate::{
+    error::DeviceLost,
+    gpu_mem::{self, BudgetExceeded, GpuMemory},
+};
+
+const FENCE_SIZE: wgpu::BufferAddress = 4;
+
+type MapFuture =
+    Pin<Box<dyn Future<Output = Result<wgpu::BufferReadMapping, wgpu::BufferAsyncErr>>>>;
+
+/// wgpu doesn't expose fences, so completion is detected by copying into a
+/// tiny readable buffer at the end of the frame and mapping it after submit:
+/// the map only resolves once the GPU has executed that copy.
+struct Fence {
+    pending: Option<MapFuture>,
+    buffer: gpu_mem::Buffer,
+}
+
+impl Fence {
+    fn new(device: &wgpu::Device, memory: &GpuMemory) -> Result<Self, BudgetExceeded> {
+        Ok(Self {
+            pending: None,
+            buffer: memory.create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("frame/fence"),
+                    size: FENCE_SIZE,
+                    usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+                },
+            )?,
+        })
+    }
+
+    /// A failed map means the device is gone: it's the only way wgpu 0.5
+    /// reports a lost context.
+    fn is_signaled(&mut self) -> Result<bool, DeviceLost> {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match self
+            .pending
+            .as_mut()
+            .map(|future| future.as_mut().poll(&mut cx))
+        {
+            None => Ok(true),
+            Some(Poll::Ready(result)) => {
+                self.pending = None;
+                result.map(|_| true).map_err(|_| DeviceLost)
+            }
+            Some(Poll::Pending) => Ok(false),
+        }
+    }
+
+    fn wait(&mut self, device: &wgpu::Device) -> Result<(), DeviceLost> {
+        device.poll(wgpu::Maintain::Poll);
+        if !self.is_signaled()? {
+            device.poll(wgpu::Maintain::Wait);
+            if let Some(future) = self.pending.take() {
+                futures::executor::block_on(future).map_err(|_| DeviceLost)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tracks a fixed number of frames that may be executing on the GPU at once.
+pub struct FrameContext {
+    fence_source: gpu_mem::Buffer,
+    fences: Vec<Fence>,
+    index: usize,
+    frame_number: u64,
+}
+
+impl FrameContext {
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &GpuMemory,
+        frames_in_flight: usize,
+    ) -> Result<Self, BudgetExceeded> {
+        assert!(frames_in_flight > 0);
+
+        let fence_source = memory.create_buffer_with_data(
+            device,
+            "frame/fence-source",
+            bytemuck::cast_slice(&[0u32]),
+            wgpu::BufferUsage::COPY_SRC,
+        )?;
+
+        Ok(Self {
+            fence_source,
+            fences: (0..frames_in_flight)
+                .map(|_| Fence::new(device, memory))
+                .collect::<Result<_, _>>()?,
+            index: 0,
+            frame_number: 0,
+        })
+    }
+
+    /// Moves to the next frame slot, blocking until the GPU has finished the
+    /// frame that last used it. Returns the slot index.
+    pub fn begin(&mut self, device: &wgpu::Device) -> Result<usize, DeviceLost> {
+        self.frame_number += 1;
+        self.index = (self.frame_number % self.fences.len() as u64) as usize;
+        self.fences[self.index].wait(device)?;
+        Ok(self.index)
+    }
+
+    /// Records the fence signal for the current frame. Must be the last
+    /// command recorded on the frame's final encoder.
+    pub fn end(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_buffer_to_buffer(
+            &self.fence_source,
+            0,
+            &self.fences[self.index].buffer,
+            0,
+            FENCE_SIZE,
+        );
+    }
+
+    /// Arms the fence for the current frame. Call right after `queue.submit`.
+    pub fn submitted(&mut self) {
+        let fence = &mut self.fences[self.index];
+        fence.pending = Some(Box::pin(fence.buffer.map_read(0, FENCE_SIZE)));
+    }
+
+    pub fn count(&self) -> usize {
+        self.fences.len()
+    }
+
+    pub fn current(&self) -> usize {
+        self.index
+    }
+}
+
+/// One instance of `T` per in-flight frame, selected by the current slot.
+pub struct PerFrame<T> {
+    items: Vec<T>,
+}
+
+impl<T> PerFrame<T> {
+    pub fn new(frames: &FrameContext, mut f: impl FnMut(usize) -> T) -> Self {
+        Self {
+            items: (0..frames.count()).map(&mut f).collect(),
+        }
+    }
+
+    pub fn get_mut(&mut self, frames: &FrameContext) -> &mut T {
+        &mut self.items[frames.current()]
+    }
+}
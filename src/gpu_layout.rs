@@ -0,0 +1,253 @@
+//! Structs shared with shaders, laid out the way the GPU reads them.
+//!
+//! Rust's `#[repr(C)]` aligns fields by their Rust types, which for a
+//! vector or matrix of floats is 4 bytes. Shaders align a `vec3` or
+//! `mat4x4` to 16, so a struct that happens to line up works until someone
+//! adds a field in the wrong place, and then the shader silently reads
+//! garbage. [`gpu_struct!`](crate::gpu_struct) declares such a struct and
+//! fails to compile if a field isn't where the shader expects it, leaving
+//! the padding to be spelt out as fields:
+//!
+//! ```ignore
+//! gpu_struct! {
+//!     std140;
+//!     struct Light {
+//!         position: na::Vector3<f32>,
+//!         radius: f32,
+//!         color: na::Vector3<f32>,
+//!         _pad: f32,
+//!     }
+//! }
+//! ```
+//!
+//! Vectors are nalgebra's, and arrays are shader arrays, so `[f32; 3]` is
+//! an `array<f32, 3>` rather than a `vec3<f32>`. `Matrix3` has no GPU
+//! layout matching its own and isn't supported; use a `Matrix4`. Structs
+//! can't nest.
+//!
+//! [`GpuStruct::check`] compares the fields against a shader's reflected
+//! buffer as well, which catches the shader and the struct disagreeing.
+
+use nalgebra as na;
+
+use crate::shader_reflection::BufferLayout;
+
+#[doc(hidden)]
+pub use bytemuck;
+
+/// The rules a buffer's contents follow.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Uniform buffers: arrays and structs are aligned to 16 bytes.
+    Std140,
+    /// Storage buffers.
+    Std430,
+}
+
+/// A type a [`gpu_struct!`](crate::gpu_struct) field can have.
+///
+/// # Safety
+///
+/// The type must be plain data without padding, so a struct of them made
+/// without implicit padding is `bytemuck::Pod`.
+pub unsafe trait GpuType: Copy + 'static {
+    /// Alignment under std430.
+    const ALIGN: usize;
+    /// Alignment under std140.
+    const UNIFORM_ALIGN: usize = Self::ALIGN;
+    /// Whether the Rust layout of the type is the std430 one.
+    const STD430: bool = true;
+    /// Whether the Rust layout of the type is the std140 one.
+    const STD140: bool = true;
+}
+
+macro_rules! gpu_types {
+    ($($ty:ty => $align:expr),* $(,)?) => {
+        $(unsafe impl GpuType for $ty {
+            const ALIGN: usize = $align;
+        })*
+    };
+}
+
+gpu_types! {
+    f32 => 4,
+    i32 => 4,
+    u32 => 4,
+    na::Vector2<f32> => 8,
+    na::Vector2<i32> => 8,
+    na::Vector2<u32> => 8,
+    na::Vector3<f32> => 16,
+    na::Vector3<i32> => 16,
+    na::Vector3<u32> => 16,
+    na::Vector4<f32> => 16,
+    na::Vector4<i32> => 16,
+    na::Vector4<u32> => 16,
+    na::Matrix4<f32> => 16,
+}
+
+unsafe impl GpuType for na::Matrix2<f32> {
+    const ALIGN: usize = 8;
+    /// std140 pads each column to 16 bytes.
+    const STD140: bool = false;
+}
+
+unsafe impl<T: GpuType, const N: usize> GpuType for [T; N] {
+    const ALIGN: usize = T::ALIGN;
+    const UNIFORM_ALIGN: usize = max(T::UNIFORM_ALIGN, 16);
+    // Elements are a multiple of their alignment apart on the GPU, but
+    // `size_of` apart in Rust, e.g. 16 and 12 for a `Vector3`.
+    const STD430: bool = T::STD430 && std::mem::size_of::<T>().is_multiple_of(T::ALIGN);
+    const STD140: bool = T::STD140 && std::mem::size_of::<T>().is_multiple_of(Self::UNIFORM_ALIGN);
+}
+
+/// A field of a [`GpuStruct`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// A struct declared with [`gpu_struct!`](crate::gpu_struct).
+pub trait GpuStruct: bytemuck::Pod {
+    const LAYOUT: Layout;
+    const FIELDS: &'static [Field];
+
+    /// Checks that `buffer`, as a shader declared it, has the fields of
+    /// this struct at the same offsets and sizes, and is no bigger.
+    fn check(buffer: &BufferLayout) -> Result<(), String> {
+        let size = std::mem::size_of::<Self>();
+        if buffer.size as usize > size {
+            return Err(format!(
+                "the shader's buffer is {} bytes, but the struct only {}",
+                buffer.size, size
+            ));
+        }
+        for member in &buffer.members {
+            let name = member.name.as_deref().unwrap_or("?");
+            let field = Self::FIELDS
+                .iter()
+                .find(|field| field.offset == member.offset as usize)
+                .ok_or_else(|| {
+                    format!(
+                        "the shader's `{}` at offset {} lines up with no field",
+                        name, member.offset
+                    )
+                })?;
+            if field.size != member.size as usize {
+                return Err(format!(
+                    "the shader's `{}` is {} bytes, but the `{}` field {}",
+                    name, member.size, field.name, field.size
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The alignment of `T` under `layout`.
+pub const fn align<T: GpuType>(layout: Layout) -> usize {
+    match layout {
+        Layout::Std140 => T::UNIFORM_ALIGN,
+        Layout::Std430 => T::ALIGN,
+    }
+}
+
+/// Whether the Rust layout of `T` is its `layout` one.
+pub const fn fits<T: GpuType>(layout: Layout) -> bool {
+    match layout {
+        Layout::Std140 => T::STD140,
+        Layout::Std430 => T::STD430,
+    }
+}
+
+/// The alignment of a struct whose fields align to at most `align`.
+pub const fn struct_align(layout: Layout, align: usize) -> usize {
+    match layout {
+        Layout::Std140 => max(align, 16),
+        Layout::Std430 => align,
+    }
+}
+
+pub const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Declares a `#[repr(C)]` struct laid out by the `std140` or `std430`
+/// rules, failing to compile if any field is misaligned or the struct
+/// needs padding at the end. See the [module docs](crate::gpu_layout).
+#[macro_export]
+macro_rules! gpu_struct {
+    (
+        $layout:ident;
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        #[derive(Copy, Clone)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        // Sound as the checks below rule out padding.
+        unsafe impl $crate::gpu_layout::bytemuck::Zeroable for $name {}
+        unsafe impl $crate::gpu_layout::bytemuck::Pod for $name {}
+
+        impl $crate::gpu_layout::GpuStruct for $name {
+            const LAYOUT: $crate::gpu_layout::Layout = $crate::gpu_struct!(@layout $layout);
+            const FIELDS: &'static [$crate::gpu_layout::Field] = &[$(
+                $crate::gpu_layout::Field {
+                    name: stringify!($field),
+                    offset: ::std::mem::offset_of!($name, $field),
+                    size: ::std::mem::size_of::<$ty>(),
+                }
+            ),*];
+        }
+
+        const _: () = {
+            use $crate::gpu_layout as layout;
+            let rules: layout::Layout = $crate::gpu_struct!(@layout $layout);
+            let mut end = 0;
+            let mut align = 1;
+            $(
+                if !layout::fits::<$ty>(rules) {
+                    panic!(concat!(
+                        "`", stringify!($name), ".", stringify!($field), "` has a type whose ",
+                        stringify!($layout), " layout differs from its Rust one",
+                    ));
+                }
+                let offset = ::std::mem::offset_of!($name, $field);
+                if offset != end {
+                    panic!(concat!(
+                        "`", stringify!($name), "` has padding before `", stringify!($field), "`",
+                    ));
+                }
+                if !offset.is_multiple_of(layout::align::<$ty>(rules)) {
+                    panic!(concat!(
+                        "`", stringify!($name), ".", stringify!($field), "` is misaligned for ",
+                        stringify!($layout), "; add padding fields before it",
+                    ));
+                }
+                end = offset + ::std::mem::size_of::<$ty>();
+                align = layout::max(align, layout::align::<$ty>(rules));
+            )*
+            if ::std::mem::size_of::<$name>() != end
+                || !end.is_multiple_of(layout::struct_align(rules, align))
+            {
+                panic!(concat!(
+                    "`", stringify!($name), "`'s size isn't a multiple of its ",
+                    stringify!($layout), " alignment; add padding fields at the end",
+                ));
+            }
+        };
+    };
+    (@layout std140) => { $crate::gpu_layout::Layout::Std140 };
+    (@layout std430) => { $crate::gpu_layout::Layout::Std430 };
+}
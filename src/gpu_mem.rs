@@ -0,0 +1,449 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    Vertex,
+    Index,
+    Uniform,
+    Staging,
+    Texture,
+    Other,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::Vertex,
+        Category::Index,
+        Category::Uniform,
+        Category::Staging,
+        Category::Texture,
+        Category::Other,
+    ];
+
+    fn from_usage(usage: wgpu::BufferUsage) -> Self {
+        if usage.contains(wgpu::BufferUsage::VERTEX) {
+            Category::Vertex
+        } else if usage.contains(wgpu::BufferUsage::INDEX) {
+            Category::Index
+        } else if usage.contains(wgpu::BufferUsage::UNIFORM) {
+            Category::Uniform
+        } else if usage.intersects(wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::MAP_WRITE) {
+            Category::Staging
+        } else {
+            Category::Other
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    Buffer,
+    Texture,
+    Sampler,
+    BindGroup,
+}
+
+/// A report is produced by [`GpuMemory::check_growth`] whenever the total
+/// grows past the previous high-water mark by this much.
+const GROWTH_THRESHOLD: wgpu::BufferAddress = 16 << 20;
+
+/// Approximate size in bytes of a texture described by `desc`, including
+/// every mip level, array layer and sample.
+fn texture_size(desc: &wgpu::TextureDescriptor) -> wgpu::BufferAddress {
+    use wgpu::TextureFormat::*;
+
+    let texel: wgpu::BufferAddress = match desc.format {
+        R8Unorm | R8Snorm | R8Uint | R8Sint => 1,
+        R16Uint | R16Sint | R16Float | Rg8Unorm | Rg8Snorm | Rg8Uint | Rg8Sint => 2,
+        Rgba16Uint | Rgba16Sint | Rgba16Float | Rg32Uint | Rg32Sint | Rg32Float => 8,
+        Rgba32Uint | Rgba32Sint | Rgba32Float => 16,
+        _ => 4,
+    };
+
+    let mut size = 0;
+    let (mut width, mut height) = (desc.size.width, desc.size.height);
+    for _ in 0..desc.mip_level_count.max(1) {
+        size += width as wgpu::BufferAddress * height as wgpu::BufferAddress * texel;
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
+
+    size * desc.size.depth.max(1) as wgpu::BufferAddress
+        * desc.array_layer_count.max(1) as wgpu::BufferAddress
+        * desc.sample_count.max(1) as wgpu::BufferAddress
+}
+
+#[derive(Clone, Debug)]
+struct Allocation {
+    label: String,
+    size: wgpu::BufferAddress,
+    category: Category,
+    kind: Kind,
+}
+
+impl Allocation {
+    /// Labels follow a `subsystem/name` scheme.
+    fn subsystem(&self) -> &str {
+        self.label.split('/').next().unwrap_or(&self.label)
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    allocations: HashMap<u64, Allocation>,
+    totals: HashMap<Category, wgpu::BufferAddress>,
+    next_id: u64,
+    budget: Option<wgpu::BufferAddress>,
+    high_water: wgpu::BufferAddress,
+}
+
+impl Inner {
+    fn total(&self) -> wgpu::BufferAddress {
+        self.totals.values().sum()
+    }
+
+    fn reserve(
+        &mut self,
+        label: Option<&str>,
+        size: wgpu::BufferAddress,
+        category: Category,
+        kind: Kind,
+    ) -> Result<u64, BudgetExceeded> {
+        if let Some(budget) = self.budget {
+            let allocated = self.total();
+            if allocated + size > budget {
+                return Err(BudgetExceeded {
+                    requested: size,
+                    allocated,
+                    budget,
+                });
+            }
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        *self.totals.entry(category).or_default() += size;
+        self.allocations.insert(
+            id,
+            Allocation {
+                label: label.unwrap_or("unlabeled").to_owned(),
+                size,
+                category,
+                kind,
+            },
+        );
+
+        Ok(id)
+    }
+
+    fn free(&mut self, id: u64) {
+        if let Some(allocation) = self.allocations.remove(&id) {
+            *self.totals.entry(allocation.category).or_default() -= allocation.size;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BudgetExceeded {
+    pub requested: wgpu::BufferAddress,
+    pub allocated: wgpu::BufferAddress,
+    pub budget: wgpu::BufferAddress,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GPU memory budget exceeded: requested {} bytes with {} of {} bytes allocated",
+            self.requested, self.allocated, self.budget
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Keeps the tracker informed when the wrapped resource is dropped.
+pub struct Tracked {
+    id: u64,
+    memory: Arc<Mutex<Inner>>,
+}
+
+impl Tracked {
+    /// Identifies the tracked resource for as long as it is alive.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.memory.lock().unwrap().free(self.id);
+    }
+}
+
+/// A `wgpu::Buffer` whose size is accounted for by [`GpuMemory`].
+pub struct Buffer {
+    buffer: wgpu::Buffer,
+    _tracked: Tracked,
+}
+
+impl Buffer {
+    /// Identifies this buffer for as long as it is alive.
+    pub fn id(&self) -> u64 {
+        self._tracked.id
+    }
+}
+
+impl Deref for Buffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+/// A `wgpu::Texture` whose size is accounted for by [`GpuMemory`].
+pub struct Texture {
+    texture: wgpu::Texture,
+    _tracked: Tracked,
+}
+
+impl Texture {
+    /// Identifies this texture (and views of it) for as long as it is alive.
+    pub fn id(&self) -> u64 {
+        self._tracked.id
+    }
+}
+
+impl Deref for Texture {
+    type Target = wgpu::Texture;
+
+    fn deref(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+}
+
+/// Records every buffer and texture created through it, grouped by
+/// [`Category`], and optionally refuses allocations past a budget.
+#[derive(Clone, Default)]
+pub struct GpuMemory {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl GpuMemory {
+    pub fn new(budget: Option<wgpu::BufferAddress>) -> Self {
+        let memory = Self::default();
+        memory.inner.lock().unwrap().budget = budget;
+        memory
+    }
+
+    fn track(
+        &self,
+        label: Option<&str>,
+        size: wgpu::BufferAddress,
+        category: Category,
+        kind: Kind,
+    ) -> Result<Tracked, BudgetExceeded> {
+        let id = self
+            .inner
+            .lock()
+            .unwrap()
+            .reserve(label, size, category, kind)?;
+        Ok(Tracked {
+            id,
+            memory: self.inner.clone(),
+        })
+    }
+
+    /// Total bytes currently allocated.
+    pub fn allocated(&self) -> wgpu::BufferAddress {
+        self.inner.lock().unwrap().total()
+    }
+
+    pub fn budget(&self) -> Option<wgpu::BufferAddress> {
+        self.inner.lock().unwrap().budget
+    }
+
+    /// Counts a resource that doesn't own memory of its own, such as a bind
+    /// group, for as long as the returned token is alive.
+    pub fn track_object(&self, label: &str, kind: Kind) -> Tracked {
+        self.track(Some(label), 0, Category::Other, kind)
+            .expect("zero-sized allocations never exceed the budget")
+    }
+
+    /// Changes the label a buffer is reported under, e.g. when a pooled
+    /// buffer is handed to a different subsystem.
+    pub fn relabel(&self, buffer: &Buffer, label: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(allocation) = inner.allocations.get_mut(&buffer.id()) {
+            allocation.label = label.to_owned();
+        }
+    }
+
+    pub fn label(&self, buffer: &Buffer) -> String {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .allocations
+            .get(&buffer.id())
+            .map_or_else(String::new, |allocation| allocation.label.clone())
+    }
+
+    /// Returns a report if the total grew well past the last high-water
+    /// mark, so steady growth (i.e. a leak) shows up without polling.
+    pub fn check_growth(&self) -> Option<MemoryReport> {
+        let grown = {
+            let mut inner = self.inner.lock().unwrap();
+            let total = inner.total();
+            if total > inner.high_water + GROWTH_THRESHOLD {
+                inner.high_water = total;
+                true
+            } else {
+                false
+            }
+        };
+
+        if grown {
+            Some(self.report())
+        } else {
+            None
+        }
+    }
+
+    pub fn create_buffer(
+        &self,
+        device: &wgpu::Device,
+        desc: &wgpu::BufferDescriptor,
+    ) -> Result<Buffer, BudgetExceeded> {
+        let tracked = self.track(
+            desc.label,
+            desc.size,
+            Category::from_usage(desc.usage),
+            Kind::Buffer,
+        )?;
+        Ok(Buffer {
+            buffer: device.create_buffer(desc),
+            _tracked: tracked,
+        })
+    }
+
+    pub fn create_buffer_with_data(
+        &self,
+        device: &wgpu::Device,
+        label: &str,
+        data: &[u8],
+        usage: wgpu::BufferUsage,
+    ) -> Result<Buffer, BudgetExceeded> {
+        let tracked = self.track(
+            Some(label),
+            data.len() as wgpu::BufferAddress,
+            Category::from_usage(usage),
+            Kind::Buffer,
+        )?;
+        Ok(Buffer {
+            buffer: device.create_buffer_with_data(data, usage),
+            _tracked: tracked,
+        })
+    }
+
+    pub fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        desc: &wgpu::TextureDescriptor,
+    ) -> Result<Texture, BudgetExceeded> {
+        let tracked = self.track(
+            desc.label,
+            texture_size(desc),
+            Category::Texture,
+            Kind::Texture,
+        )?;
+        Ok(Texture {
+            texture: device.create_texture(desc),
+            _tracked: tracked,
+        })
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        let inner = self.inner.lock().unwrap();
+        let mut largest: Vec<_> = inner.allocations.values().cloned().collect();
+        largest.sort_by_key(|a| std::cmp::Reverse(a.size));
+        largest.truncate(REPORT_LARGEST);
+
+        let mut live = HashMap::<(String, Kind), usize>::new();
+        for allocation in inner.allocations.values() {
+            *live
+                .entry((allocation.subsystem().to_owned(), allocation.kind))
+                .or_default() += 1;
+        }
+        let mut live: Vec<_> = live
+            .into_iter()
+            .map(|((subsystem, kind), count)| (subsystem, kind, count))
+            .collect();
+        live.sort();
+
+        MemoryReport {
+            live,
+            totals: Category::ALL
+                .iter()
+                .map(|&category| (category, inner.totals.get(&category).copied().unwrap_or(0)))
+                .collect(),
+            count: inner.allocations.len(),
+            largest: largest
+                .into_iter()
+                .map(|a| (a.label, a.category, a.size))
+                .collect(),
+            budget: inner.budget,
+        }
+    }
+}
+
+const REPORT_LARGEST: usize = 8;
+
+/// Snapshot of the tracked allocations, printable for debugging.
+#[derive(Clone, Debug)]
+pub struct MemoryReport {
+    pub live: Vec<(String, Kind, usize)>,
+    pub totals: Vec<(Category, wgpu::BufferAddress)>,
+    pub count: usize,
+    pub largest: Vec<(String, Category, wgpu::BufferAddress)>,
+    pub budget: Option<wgpu::BufferAddress>,
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total: wgpu::BufferAddress = self.totals.iter().map(|(_, size)| size).sum();
+        match self.budget {
+            Some(budget) => writeln!(
+                f,
+                "GPU memory: {} bytes in {} allocations (budget {} bytes)",
+                total, self.count, budget
+            )?,
+            None => writeln!(
+                f,
+                "GPU memory: {} bytes in {} allocations",
+                total, self.count
+            )?,
+        }
+
+        for (category, size) in &self.totals {
+            writeln!(f, "  {:?}: {} bytes", category, size)?;
+        }
+
+        writeln!(f, "  live resources:")?;
+        for (subsystem, kind, count) in &self.live {
+            writeln!(f, "    {}: {} {:?}", subsystem, count, kind)?;
+        }
+
+        writeln!(f, "  largest:")?;
+        for (label, category, size) in &self.largest {
+            writeln!(f, "    {} ({:?}): {} bytes", label, category, size)?;
+        }
+
+        Ok(())
+    }
+}
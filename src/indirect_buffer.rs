@@ -0,0 +1,148 @@
+use std::{mem, ops::Range};
+
+use crate::{
+    bind_group_cache::Binding, dynamic_buffer::DynamicBuffer, error::EngineError,
+    gpu_mem::BudgetExceeded, Context,
+};
+
+/// The arguments of one draw read from a buffer, laid out as the GPU reads
+/// them.
+pub trait IndirectArgs: bytemuck::Pod {
+    /// Records the draw whose arguments are at `offset` in `buffer`.
+    fn draw<'a>(
+        pass: &mut wgpu::RenderPass<'a>,
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    );
+}
+
+/// The arguments of `RenderPass::draw_indirect`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DrawArgs {
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    pub first_vertex: u32,
+    pub first_instance: u32,
+}
+
+unsafe impl bytemuck::Pod for DrawArgs {}
+unsafe impl bytemuck::Zeroable for DrawArgs {}
+
+impl IndirectArgs for DrawArgs {
+    fn draw<'a>(
+        pass: &mut wgpu::RenderPass<'a>,
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    ) {
+        pass.draw_indirect(buffer, offset);
+    }
+}
+
+/// The arguments of `RenderPass::draw_indexed_indirect`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DrawIndexedArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+unsafe impl bytemuck::Pod for DrawIndexedArgs {}
+unsafe impl bytemuck::Zeroable for DrawIndexedArgs {}
+
+impl IndirectArgs for DrawIndexedArgs {
+    fn draw<'a>(
+        pass: &mut wgpu::RenderPass<'a>,
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    ) {
+        pass.draw_indexed_indirect(buffer, offset);
+    }
+}
+
+/// Draw arguments built on the CPU and mirrored into a buffer the GPU
+/// reads them from, so a run of draws sharing their state is recorded as
+/// one call per draw without anything set in between. The buffer is also
+/// a storage buffer, for compute shaders to write the arguments instead.
+///
+/// wgpu 0.5 has no multi-draw, so [`IndirectBuffer::multi_draw`] records
+/// the draws one by one; a wgpu with it can record them in one call
+/// without changing callers.
+pub struct IndirectBuffer<A: IndirectArgs> {
+    args: DynamicBuffer<A>,
+}
+
+impl<A: IndirectArgs> IndirectBuffer<A> {
+    pub fn new(ctx: &mut Context, label: &str, capacity: usize) -> Result<Self, BudgetExceeded> {
+        Ok(Self {
+            args: DynamicBuffer::new(
+                ctx,
+                label,
+                wgpu::BufferUsage::INDIRECT | wgpu::BufferUsage::STORAGE,
+                capacity,
+            )?,
+        })
+    }
+
+    /// Adds the arguments of a draw, returning its index.
+    pub fn push(&mut self, args: A) -> u32 {
+        self.args.push(args);
+        self.args.len() as u32 - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.args.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[A] {
+        self.args.as_slice()
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        self.args.buffer()
+    }
+
+    /// The buffer, to bind as a storage buffer for a compute shader to
+    /// write the arguments.
+    pub fn binding(&self) -> Binding<'_> {
+        self.args.binding()
+    }
+
+    /// Records the draw at `index`, with whatever is bound.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, index: u32) {
+        let offset = index as wgpu::BufferAddress * mem::size_of::<A>() as wgpu::BufferAddress;
+        A::draw(pass, self.args.buffer(), offset);
+    }
+
+    /// Records the draws in `draws`, in order, with whatever is bound.
+    pub fn multi_draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, draws: Range<u32>) {
+        for index in draws {
+            self.draw(pass, index);
+        }
+    }
+
+    pub fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError> {
+        self.args.upload(ctx, encoder)
+    }
+
+    /// Moves the buffer to the context's current device, keeping the
+    /// arguments. Upload again before drawing.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded> {
+        self.args.recreate(ctx)
+    }
+}
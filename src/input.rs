@@ -0,0 +1,67 @@
+//! Keyboard and pointer state tracked from window events.
+
+use winit::event::{
+    DeviceEvent, ElementState, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent,
+};
+
+use crate::cursor::PointerLock;
+
+#[derive(Default)]
+pub struct Input {
+    /// Pointer position in window pixels, while it is over the window.
+    cursor_position: Option<[f32; 2]>,
+    modifiers: ModifiersState,
+    pointer_lock: PointerLock,
+}
+
+impl Input {
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some([position.x as f32, position.y as f32]);
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor_position = None,
+            WindowEvent::ModifiersChanged(state) => self.modifiers = *state,
+            WindowEvent::Focused(focused) => self.pointer_lock.focus_changed(*focused),
+            _ => {}
+        }
+    }
+
+    pub fn device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.pointer_lock.motion(*delta);
+        }
+    }
+
+    pub fn cursor_position(&self) -> Option<[f32; 2]> {
+        self.cursor_position
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    pub fn pointer_lock(&self) -> &PointerLock {
+        &self.pointer_lock
+    }
+
+    pub fn pointer_lock_mut(&mut self) -> &mut PointerLock {
+        &mut self.pointer_lock
+    }
+}
+
+/// The key `event` presses, if it is a key press.
+pub fn pressed_key(event: &WindowEvent) -> Option<VirtualKeyCode> {
+    match event {
+        WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode,
+                    ..
+                },
+            ..
+        } => *virtual_keycode,
+        _ => None,
+    }
+}
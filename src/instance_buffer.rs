@@ -0,0 +1,73 @@
+use std::ops::Range;
+
+use crate::{
+    dynamic_buffer::DynamicBuffer, error::EngineError, gpu_mem::BudgetExceeded, passes::Vertex,
+    Context,
+};
+
+/// Per-instance attributes of `I`, mirrored into a vertex buffer that is
+/// stepped once per instance, so one draw of a mesh can place thousands of
+/// copies of it.
+///
+/// `I::desc` must use `InputStepMode::Instance` and shader locations after
+/// those of the vertices it is drawn with.
+pub struct InstanceBuffer<I: Vertex> {
+    instances: DynamicBuffer<I>,
+}
+
+impl<I: Vertex> InstanceBuffer<I> {
+    pub fn new(ctx: &mut Context, label: &str, capacity: usize) -> Result<Self, BudgetExceeded> {
+        debug_assert_eq!(I::desc().step_mode, wgpu::InputStepMode::Instance);
+        Ok(Self {
+            instances: DynamicBuffer::new(ctx, label, wgpu::BufferUsage::VERTEX, capacity)?,
+        })
+    }
+
+    /// Adds `instances`, returning the range to draw them with.
+    pub fn extend(&mut self, instances: &[I]) -> Range<u32> {
+        let start = self.instances.len() as u32;
+        self.instances.extend_from_slice(instances);
+        start..self.instances.len() as u32
+    }
+
+    /// Adds one instance, returning its index.
+    pub fn push(&mut self, instance: I) -> u32 {
+        self.instances.push(instance);
+        self.instances.len() as u32 - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[I] {
+        self.instances.as_slice()
+    }
+
+    /// Binds the instances to vertex slot `slot`.
+    pub fn bind<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, slot: u32) {
+        pass.set_vertex_buffer(slot, self.instances.buffer(), 0, 0);
+    }
+
+    pub fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError> {
+        self.instances.upload(ctx, encoder)
+    }
+
+    /// Moves the buffer to the context's current device, keeping the
+    /// instances. Upload again before drawing.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded> {
+        self.instances.recreate(ctx)
+    }
+}
@@ -0,0 +1,90 @@
+//! A work-stealing thread pool for engine work that splits into
+//! independent pieces, e.g. decoding assets or culling sprites.
+//!
+//! Jobs either run detached with [`spawn`], or inside a [`scope`] that
+//! returns once every job spawned in it is done. Scoped jobs can borrow
+//! from the caller, so the main loop can fan work out over a frame's data
+//! and join on it before recording render commands:
+//!
+//! ```ignore
+//! let mut visible = vec![vec![]; chunks.len()];
+//! jobs::scope(|s| {
+//!     for (chunk, visible) in chunks.iter().zip(&mut visible) {
+//!         s.spawn(move |_| *visible = cull(chunk));
+//!     }
+//! });
+//! ```
+//!
+//! There is one pool per process, started on first use with one thread
+//! per core besides the main thread, or as many as [`init`] asked for.
+
+use std::sync::OnceLock;
+
+pub use rayon::Scope;
+
+static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+
+/// Starts the pool with `threads` workers, e.g. from a command line flag.
+/// Returns `false` if the pool was already running, with its own count.
+pub fn init(threads: usize) -> bool {
+    let mut started = false;
+    POOL.get_or_init(|| {
+        started = true;
+        build(threads)
+    });
+    started
+}
+
+fn build(threads: usize) -> rayon::ThreadPool {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .thread_name(|index| format!("job-{}", index))
+        .build()
+        .expect("failed to start the job threads")
+}
+
+fn pool() -> &'static rayon::ThreadPool {
+    POOL.get_or_init(|| {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        build(cores - 1)
+    })
+}
+
+/// How many threads run jobs.
+pub fn threads() -> usize {
+    pool().current_num_threads()
+}
+
+/// Runs `job` on the pool without waiting for it.
+pub fn spawn(job: impl FnOnce() + Send + 'static) {
+    pool().spawn(job);
+}
+
+/// Calls `body` with a [`Scope`] to spawn jobs into, and returns once they
+/// all finished. A job that panics makes this panic too.
+pub fn scope<'scope, R: Send>(body: impl FnOnce(&Scope<'scope>) -> R + Send + 'scope) -> R {
+    pool().scope(body)
+}
+
+/// `map` applied to `items` in chunks of `chunk_size` across the pool, with
+/// the results in the order of the items. Small inputs are mapped on the
+/// calling thread, where splitting them would cost more than it saves.
+pub fn map_chunks<T: Sync, R: Send>(
+    items: &[T],
+    chunk_size: usize,
+    map: impl Fn(&[T]) -> Vec<R> + Sync,
+) -> Vec<R> {
+    let chunk_size = chunk_size.max(1);
+    if items.len() <= chunk_size {
+        return map(items);
+    }
+    let chunks: Vec<&[T]> = items.chunks(chunk_size).collect();
+    let mut results: Vec<Vec<R>> = chunks.iter().map(|_| vec![]).collect();
+    let map = &map;
+    scope(|s| {
+        for (chunk, result) in chunks.iter().zip(&mut results) {
+            s.spawn(move |_| *result = map(chunk));
+        }
+    });
+    results.into_iter().flatten().collect()
+}
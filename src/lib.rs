@@ -0,0 +1,71 @@
+//! A small wgpu renderer: a [`Context`] owning the device and its shared
+//! caches, the passes drawing into it, and the demo [`app`] built on both.
+
+// wgpu 0.5 only drives native backends through wgpu-native; WebGPU support
+// needs a newer wgpu, so fail early with a clear message instead of deep
+// inside its dependencies.
+#[cfg(target_arch = "wasm32")]
+compile_error!("the web target requires a wgpu release with a WebGPU backend (0.6 or later)");
+
+pub mod adapter;
+pub mod app;
+pub mod assets;
+pub mod bind_group_cache;
+pub mod blit;
+pub mod buffer_pool;
+pub mod color;
+pub mod components;
+pub mod compute;
+pub mod context;
+pub mod culling;
+pub mod cursor;
+pub mod debug_font;
+pub mod display;
+pub mod dynamic_buffer;
+pub mod ecs;
+pub mod error;
+pub mod events;
+pub mod frame;
+pub mod gpu_layout;
+pub mod gpu_mem;
+pub mod indirect_buffer;
+pub mod input;
+pub mod instance_buffer;
+pub mod jobs;
+pub mod logging;
+pub mod material;
+pub mod mesh_arena;
+pub mod mipmap;
+pub mod overlay;
+pub mod pacing;
+pub mod passes;
+pub mod pipeline_cache;
+pub mod profiler;
+pub mod push_constants;
+pub mod readback;
+pub mod recorder;
+pub mod render_graph;
+pub mod render_target;
+pub mod renderdoc;
+pub mod rng;
+pub mod sampler;
+pub mod savegame;
+pub mod scene;
+pub mod scene_graph;
+pub mod settings;
+pub mod shader_compiler;
+pub mod shader_reflection;
+pub mod shapes;
+pub mod staging;
+pub mod state;
+pub mod stats;
+pub mod tasks;
+pub mod texture_atlas;
+pub mod texture_streaming;
+pub mod time;
+pub mod timers;
+pub mod validation;
+pub mod vfs;
+
+pub use context::{Context, Frame, FrameTarget};
+pub use passes::{InterfaceInstance, InterfacePass, InterfaceVertex, Vertex};
@@ -0,0 +1,195 @@
+//! Logging for the engine and its dependencies, through the `log` crate.
+//!
+//! Levels are set per module with a `RUST_LOG`-style filter such as
+//! `info,wgpu_core=warn,minimal_error::pacing=debug`; the longest matching
+//! module prefix wins. Records go to stderr and optionally a file, tagged
+//! with the time since startup, the frame they were logged in (see
+//! [`frame`]) and the current [`validation::scope`].
+
+use std::{
+    fs::File,
+    io::{self, LineWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use log::{LevelFilter, Metadata, Record};
+
+use crate::validation;
+
+/// Per-module levels.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for Filter {
+    /// Info for this crate, warnings and errors from everything else.
+    fn default() -> Self {
+        Self {
+            default: LevelFilter::Warn,
+            modules: vec![(crate_name().to_owned(), LevelFilter::Info)],
+        }
+    }
+}
+
+impl Filter {
+    /// Parses comma-separated `level` or `module=level` entries. A bare
+    /// level applies to every module not listed, this crate included;
+    /// entries that don't parse are skipped.
+    pub fn parse(spec: &str) -> Self {
+        let mut filter = Self::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((module, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        filter.set(module.trim(), level);
+                    }
+                }
+                None => {
+                    if let Ok(level) = entry.parse() {
+                        filter.default = level;
+                        filter.modules.retain(|(module, _)| module != crate_name());
+                    }
+                }
+            }
+        }
+        filter
+    }
+
+    /// Sets the level of `module` and everything under it.
+    pub fn set(&mut self, module: &str, level: LevelFilter) {
+        self.modules.retain(|(name, _)| name != module);
+        self.modules.push((module.to_owned(), level));
+    }
+
+    pub fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// Where records go. [`Config::from_env`] reads `RUST_LOG` and `LOG_FILE`.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub filter: Filter,
+    /// Also append records to this file.
+    pub file: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            filter: std::env::var("RUST_LOG")
+                .map(|spec| Filter::parse(&spec))
+                .unwrap_or_default(),
+            file: std::env::var_os("LOG_FILE").map(PathBuf::from),
+        }
+    }
+}
+
+struct Logger {
+    filter: Filter,
+    start: Instant,
+    file: Option<Mutex<LineWriter<File>>>,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) || validation::report(record) {
+            return;
+        }
+
+        let mut line = format!(
+            "{:>10.3} {:<5} ",
+            self.start.elapsed().as_secs_f64(),
+            record.level()
+        );
+        match FRAME.load(Ordering::Relaxed) {
+            0 => {}
+            frame => line.push_str(&format!("[frame {}] ", frame)),
+        }
+        if let Some(scope) = validation::current_scope() {
+            line.push_str(&format!("[{}] ", scope));
+        }
+        line.push_str(&format!("{}: {}", record.target(), record.args()));
+
+        eprintln!("{}", line);
+        if let Some(file) = &self.file {
+            let _ = writeln!(file.lock().unwrap(), "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Installs the logger. Call once at startup; later calls do nothing.
+/// Fails only if the log file can't be opened.
+pub fn install(config: Config) -> io::Result<()> {
+    let file = match &config.file {
+        Some(path) => Some(Mutex::new(LineWriter::new(
+            File::options().create(true).append(true).open(path)?,
+        ))),
+        None => None,
+    };
+    let max_level = config.filter.max_level();
+    let logger = Logger {
+        filter: config.filter,
+        start: Instant::now(),
+        file,
+    };
+    if log::set_logger(Box::leak(Box::new(logger))).is_ok() {
+        log::set_max_level(max_level);
+    }
+    Ok(())
+}
+
+fn crate_name() -> &'static str {
+    module_path!().split("::").next().unwrap_or_default()
+}
+
+/// The frame being rendered, counting from 1; 0 outside of frames.
+static FRAME: AtomicU64 = AtomicU64::new(0);
+static FRAME_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Tags records with a new frame number until the guard drops.
+pub fn frame() -> FrameSpan {
+    let frame = FRAME_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    FRAME.store(frame, Ordering::Relaxed);
+    FrameSpan(())
+}
+
+pub struct FrameSpan(());
+
+impl Drop for FrameSpan {
+    fn drop(&mut self) {
+        FRAME.store(0, Ordering::Relaxed);
+    }
+}
@@ -1,397 +1,375 @@
-use nalgebra as na;
-use winit::{
-    event::*,
-    event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
-};
-
-pub struct Context {
-    pub surface: wgpu::Surface,
-    pub adapter: wgpu::Adapter,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
-    pub sc_desc: wgpu::SwapChainDescriptor,
-    pub swap_chain: wgpu::SwapChain,
+use std::path::PathBuf;
 
-    pub size: winit::dpi::PhysicalSize<u32>,
+use minimal_error::{
+    adapter::{self, AdapterChoice},
+    app, context, display, jobs, logging,
+    settings::{self, Settings},
+    validation, vfs,
+};
+use winit::{dpi::PhysicalSize, event_loop::EventLoop, window::WindowBuilder};
+
+const USAGE: &str = "\
+Usage: minimal-error [OPTIONS]
+
+Display:
+    --windowed                Start in a window
+    --fullscreen              Start fullscreen, borderless unless configured
+    --display MODE            windowed, borderless or exclusive
+    --monitor INDEX           Monitor to go fullscreen on
+    --video-mode WxH@HZ       Video mode for exclusive fullscreen
+    --resolution WxH          Window size in physical pixels
+    --list-displays           Print the monitors and video modes
+
+GPU:
+    --adapter NAME            high-performance, low-power, an index or part of a name
+    --backend LIST            vulkan, metal, dx12, dx11, gl, primary, secondary or all
+    --list-adapters           Print the adapters on the selected backends
+    --present-mode MODE       fifo, mailbox or immediate
+    --fps-limit FPS           Cap the frame rate
+    --jobs COUNT              Worker threads for engine jobs (default: cores - 1)
+
+Running:
+    --scene PATH              Play a scene file instead of showing the menu
+    --hot-reload              Reload scenes and shaders when their files change
+    --no-hot-reload           Don't, which is the default in release builds
+    --mount PATH              Mount a directory or archive over the assets
+    --assets DIR              Read the engine's own assets from DIR, e.g. to
+                              hot reload them in a build that embeds them
+    --pack DIR OUTPUT         Pack a directory into an archive, then exit
+    --seed SEED               Seed the random streams, to reproduce a run
+    --save PATH               Save and resume the game from PATH instead of
+                              save.ron
+    --no-save                 Neither resume nor save the game
+    --headless                Render offscreen without a window, then exit
+    --frames COUNT            Frames a headless run renders (default 60)
+    --output PATH             Save a headless run's last frame as an image
+
+Logging:
+    -v, --verbose             Log debug messages from the engine
+    --log FILTER              Per-module levels, e.g. info,wgpu_core=warn
+    --log-file PATH           Also append the log to a file
+
+Settings are read from config.toml, or the file CONFIG_FILE names. The
+engine's assets are read from ASSET_DIR if set.
+";
+
+fn parse_resolution(value: &str) -> Option<[u32; 2]> {
+    let (width, height) = value.split_once('x')?;
+    Some([width.parse().ok()?, height.parse().ok()?])
 }
 
-impl Context {
-    pub async fn new(window: &Window) -> Self {
-        let size = window.inner_size();
-
-        let surface = wgpu::Surface::create(window);
-
-        let adapter = wgpu::Adapter::request(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-            },
-            wgpu::BackendBit::PRIMARY,
-        )
-        .await
-        .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                extensions: wgpu::Extensions {
-                    anisotropic_filtering: false,
-                },
-                limits: Default::default(),
-            })
-            .await;
-
-        let sc_desc = wgpu::SwapChainDescriptor {
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-        };
-
-        let swap_chain = device.create_swap_chain(&surface, &sc_desc);
-
-        Self {
-            surface,
-            adapter,
-            device,
-            queue,
-            sc_desc,
-            swap_chain,
-
-            size,
+/// Mounts the loose files in the working directory and the shipped archive,
+/// if there is one, then `overlays` on top. Debug builds put the loose files
+/// above the archive, since they are the ones being edited.
+fn mount_assets(overlays: &[PathBuf]) {
+    let loose = PathBuf::from(".");
+    let pak = PathBuf::from(vfs::DEFAULT_PAK);
+    let base = match (pak.is_file(), cfg!(debug_assertions)) {
+        (false, _) => vec![loose],
+        (true, true) => vec![pak, loose],
+        (true, false) => vec![loose, pak],
+    };
+    for path in base.iter().chain(overlays) {
+        match vfs::mount(path) {
+            Ok(()) => log::debug!("Mounted {}", path.display()),
+            Err(err) => log::error!("Failed to mount {}: {}", path.display(), err),
         }
     }
-
-    pub async fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        self.size = new_size;
-        self.sc_desc.width = new_size.width;
-        self.sc_desc.height = new_size.height;
-        self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
-    }
 }
 
-pub trait Vertex: bytemuck::Pod + bytemuck::Zeroable {
-    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a>;
+/// What the command line asks for, besides running the game.
+enum Command {
+    Run,
+    Help,
+    ListAdapters,
+    Pack { dir: String, output: String },
 }
-pub struct InterfacePass {
-    pipeline: wgpu::RenderPipeline,
-    uniforms_bind_group: wgpu::BindGroup,
-    camera: na::Orthographic3<f32>,
 
-    pub vertices: Vec<InterfaceVertex>,
-    pub indices: Vec<u32>,
+/// Everything the command line sets.
+struct Cli {
+    command: Command,
+    options: app::Options,
+    log_config: logging::Config,
+    headless: bool,
+    headless_frames: u32,
+    headless_output: Option<PathBuf>,
+    list_displays: bool,
+    mounts: Vec<PathBuf>,
+    unknown_args: Vec<String>,
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-pub struct InterfaceVertex {
-    pub pos: [f32; 2],
-    pub color: [f32; 4],
-    pub uv: [f32; 2],
-    pub index: u32,
+/// The value following `flag`.
+fn next_value(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+    what: &str,
+) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} needs {}", flag, what))
 }
 
-unsafe impl bytemuck::Pod for InterfaceVertex {}
-unsafe impl bytemuck::Zeroable for InterfaceVertex {}
-
-impl Vertex for InterfaceVertex {
-    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
-        use std::mem;
+/// The number following `flag`.
+fn next_number<T: std::str::FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+    what: &str,
+) -> Result<T, String> {
+    let value = next_value(args, flag, "a value")?;
+    value
+        .parse()
+        .map_err(|_| format!("{} must be {}, not {:?}", flag, what, value))
+}
 
-        wgpu::VertexBufferDescriptor {
-            stride: mem::size_of::<Self>() as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttributeDescriptor {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: 8,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float4,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: 24,
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float2,
-                },
-                wgpu::VertexAttributeDescriptor {
-                    offset: 32,
-                    shader_location: 3,
-                    format: wgpu::VertexFormat::Uint,
-                },
-            ],
+/// Parses `args` on top of the options read from the environment. Stops at
+/// the first argument that runs a command of its own, e.g. `--help`.
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+    options: app::Options,
+    log_config: logging::Config,
+) -> Result<Cli, String> {
+    let mut cli = Cli {
+        command: Command::Run,
+        options,
+        log_config,
+        headless: false,
+        headless_frames: 60,
+        headless_output: None,
+        list_displays: false,
+        mounts: vec![],
+        unknown_args: vec![],
+    };
+    let options = &mut cli.options;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                cli.command = Command::Help;
+                break;
+            }
+            "--list-adapters" => {
+                cli.command = Command::ListAdapters;
+                break;
+            }
+            "--adapter" => {
+                options.adapter_choice =
+                    AdapterChoice::parse(&next_value(&mut args, &arg, "a value")?);
+            }
+            "--backend" => {
+                let value = next_value(&mut args, &arg, "a value")?;
+                let backends = adapter::parse_backends(&value).ok_or_else(|| {
+                    format!(
+                        "--backend must list vulkan, metal, dx12, dx11, gl, primary, secondary or all, not {:?}",
+                        value
+                    )
+                })?;
+                adapter::set_backends(backends);
+            }
+            "--present-mode" => {
+                let value = next_value(&mut args, &arg, "a value")?;
+                options.present_mode = context::parse_present_mode(&value).ok_or_else(|| {
+                    format!(
+                        "--present-mode must be fifo, mailbox or immediate, not {:?}",
+                        value
+                    )
+                })?;
+            }
+            "--fps-limit" => options.frame_limit = Some(next_number(&mut args, &arg, "a number")?),
+            "--jobs" => {
+                jobs::init(next_number(&mut args, &arg, "a number")?);
+            }
+            "--list-displays" => cli.list_displays = true,
+            "--display" => {
+                let value = next_value(&mut args, &arg, "a value")?;
+                options.display.mode = display::DisplayMode::parse(&value).ok_or_else(|| {
+                    format!(
+                        "--display must be windowed, borderless or exclusive, not {:?}",
+                        value
+                    )
+                })?;
+                if options.display.mode != display::DisplayMode::Windowed {
+                    options.display.fullscreen_mode = options.display.mode;
+                }
+            }
+            "--windowed" => options.display.mode = display::DisplayMode::Windowed,
+            "--fullscreen" => options.display.mode = options.display.fullscreen_mode,
+            "--resolution" => {
+                let value = next_value(&mut args, &arg, "a value")?;
+                options.resolution = Some(parse_resolution(&value).ok_or_else(|| {
+                    format!("--resolution must be WIDTHxHEIGHT, not {:?}", value)
+                })?);
+            }
+            "--scene" => options.scene = Some(next_value(&mut args, &arg, "a path")?.into()),
+            "--seed" => options.seed = Some(next_number(&mut args, &arg, "a number")?),
+            "--save" => options.save_path = Some(next_value(&mut args, &arg, "a path")?.into()),
+            "--no-save" => options.save_path = None,
+            "--hot-reload" => options.hot_reload = true,
+            "--no-hot-reload" => options.hot_reload = false,
+            "--assets" => vfs::set_source(vfs::Source::Directory(
+                next_value(&mut args, &arg, "a directory")?.into(),
+            )),
+            "--mount" => cli
+                .mounts
+                .push(next_value(&mut args, &arg, "a path")?.into()),
+            "--pack" => {
+                let dir = next_value(&mut args, &arg, "a directory")?;
+                let output = next_value(&mut args, &arg, "an output path")?;
+                cli.command = Command::Pack { dir, output };
+                break;
+            }
+            "--headless" => cli.headless = true,
+            "--frames" => cli.headless_frames = next_number(&mut args, &arg, "a number")?,
+            "--output" => cli.headless_output = Some(next_value(&mut args, &arg, "a path")?.into()),
+            "-v" | "--verbose" => cli
+                .log_config
+                .filter
+                .set(env!("CARGO_CRATE_NAME"), log::LevelFilter::Debug),
+            "--monitor" => {
+                options.display.monitor = Some(next_number(&mut args, &arg, "an index")?)
+            }
+            "--video-mode" => {
+                options.display.video_mode = Some(next_value(&mut args, &arg, "a value")?);
+            }
+            "--log" => {
+                cli.log_config.filter =
+                    logging::Filter::parse(&next_value(&mut args, &arg, "a filter")?);
+            }
+            "--log-file" => {
+                cli.log_config.file = Some(next_value(&mut args, &arg, "a path")?.into())
+            }
+            _ => cli.unknown_args.push(arg),
         }
     }
+    Ok(cli)
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug)]
-struct VertexUniforms {
-    camera: na::Matrix4<f32>,
-    transform: na::Matrix4<f32>,
-}
-
-unsafe impl bytemuck::Pod for VertexUniforms {}
-unsafe impl bytemuck::Zeroable for VertexUniforms {}
-
-impl InterfacePass {
-    fn new(ctx: &mut Context) -> Self {
-        let vs_data = include_bytes!("shader/interface.vert.spv");
-        let fs_data = include_bytes!("shader/interface.frag.spv");
-
-        let vs_data = wgpu::read_spirv(std::io::Cursor::new(&vs_data[..])).unwrap();
-        let fs_data = wgpu::read_spirv(std::io::Cursor::new(&fs_data[..])).unwrap();
-
-        let vs_module = ctx.device.create_shader_module(&vs_data);
-        let fs_module = ctx.device.create_shader_module(&fs_data);
-
-        let camera = na::Orthographic3::new(0.0, 1.0, 0.0, 1.0, 10.0, 100.0);
-
-        let uniforms = VertexUniforms {
-            camera: camera.as_matrix().to_owned(),
-            transform: na::Matrix4::identity(),
-        };
-
-        let uniforms = ctx.device.create_buffer_with_data(
-            bytemuck::cast_slice(&[uniforms]),
-            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-        );
-
-        let uniforms_bind_group_layout =
-            ctx.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    label: None,
-                    bindings: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStage::VERTEX,
-                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
-                    }],
-                });
-
-        let uniforms_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &uniforms_bind_group_layout,
-            bindings: &[wgpu::Binding {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer {
-                    buffer: &uniforms,
-                    range: 0..(std::mem::size_of::<VertexUniforms>() as wgpu::BufferAddress),
-                },
-            }],
-        });
-
-        let layout = ctx
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                bind_group_layouts: &[&uniforms_bind_group_layout],
-            });
-
-        let pipeline = ctx
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                layout: &layout,
-                vertex_stage: wgpu::ProgrammableStageDescriptor {
-                    module: &vs_module,
-                    entry_point: "main",
-                },
-                fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                    module: &fs_module,
-                    entry_point: "main",
-                }),
-                rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: wgpu::CullMode::Back,
-                    depth_bias: 0,
-                    depth_bias_slope_scale: 0.0,
-                    depth_bias_clamp: 0.0,
-                }),
-                color_states: &[wgpu::ColorStateDescriptor {
-                    format: ctx.sc_desc.format,
-                    color_blend: wgpu::BlendDescriptor::REPLACE,
-                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-                primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-                depth_stencil_state: None,
-                vertex_state: wgpu::VertexStateDescriptor {
-                    index_format: wgpu::IndexFormat::Uint32,
-                    vertex_buffers: &[InterfaceVertex::desc()],
-                },
-                sample_count: 1,
-                sample_mask: !0,
-                alpha_to_coverage_enabled: false,
-            });
-
-        Self {
-            pipeline,
-            uniforms_bind_group,
-            camera,
-            vertices: vec![],
-            indices: vec![],
-        }
+fn main() {
+    let log_config = logging::Config::from_env();
+    let settings_path = std::env::var_os("CONFIG_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| settings::DEFAULT_PATH.into());
+    let (settings, settings_error) = match Settings::load(&settings_path) {
+        Ok(settings) => (settings, None),
+        Err(err) => (Settings::default(), Some(err)),
+    };
+    let mut options = app::Options::new(settings, settings_path);
+    if let Some(dir) = std::env::var_os("ASSET_DIR") {
+        vfs::set_source(vfs::Source::Directory(dir.into()));
     }
-
-    fn update(&mut self) {}
-
-    fn render(&self, ctx: &mut Context) {
-        let vertex_buffer = ctx.device.create_buffer_with_data(
-            bytemuck::cast_slice(&self.vertices),
-            wgpu::BufferUsage::VERTEX,
-        );
-
-        let index_buffer = ctx.device.create_buffer_with_data(
-            bytemuck::cast_slice(&self.indices),
-            wgpu::BufferUsage::INDEX,
-        );
-
-        let frame = ctx
-            .swap_chain
-            .get_next_texture()
-            .expect("Timeout getting texture");
-
-        let mut encoder = ctx
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
-        {
-            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.view,
-                    resolve_target: None,
-                    load_op: wgpu::LoadOp::Load,
-                    store_op: wgpu::StoreOp::Store,
-                    clear_color: wgpu::Color {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        a: 0.0,
-                    },
-                }],
-                depth_stencil_attachment: None,
-            });
-
-            pass.set_pipeline(&self.pipeline);
-            pass.set_bind_group(0, &self.uniforms_bind_group, &[]);
-            pass.set_vertex_buffer(0, &vertex_buffer, 0, 0);
-            pass.set_index_buffer(&index_buffer, 0, 0);
-            pass.draw_indexed(0..self.indices.len() as u32, 0, 0..1);
-        }
-
-        ctx.queue.submit(&[encoder.finish()]);
+    if let Ok(value) = std::env::var("GPU_ADAPTER") {
+        options.adapter_choice = AdapterChoice::parse(&value);
     }
-}
-
-pub struct Application {
-    interface_pass: InterfacePass,
-}
-
-impl Application {
-    pub fn new(ctx: &mut Context) -> Self {
-        let mut interface_pass = InterfacePass::new(ctx);
-
-        interface_pass.vertices = vec![
-            InterfaceVertex {
-                pos: [0.0, 0.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-                uv: [0.0, 0.0],
-                index: 0,
-            },
-            InterfaceVertex {
-                pos: [1.0, 0.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-                uv: [0.0, 0.0],
-                index: 0,
-            },
-            InterfaceVertex {
-                pos: [1.0, 1.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-                uv: [0.0, 0.0],
-                index: 0,
-            },
-            InterfaceVertex {
-                pos: [0.0, 1.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-                uv: [0.0, 0.0],
-                index: 0,
-            },
-        ];
-
-        interface_pass.indices = vec![0, 1, 3, 1, 2, 3];
-
-        Self { interface_pass }
+    if let Some(mode) = std::env::var("PRESENT_MODE")
+        .ok()
+        .and_then(|mode| context::parse_present_mode(&mode))
+    {
+        options.present_mode = mode;
     }
-
-    pub fn update(&mut self, ctx: &mut Context) {
-        self.interface_pass.update();
+    if let Some(samples) = std::env::var("MSAA_SAMPLES")
+        .ok()
+        .and_then(|samples| samples.parse().ok())
+    {
+        options.sample_count = samples;
     }
-
-    pub fn render(&self, ctx: &mut Context) {
-        self.interface_pass.render(ctx);
+    options.frame_limit = std::env::var("FPS_LIMIT")
+        .ok()
+        .and_then(|fps| fps.parse().ok());
+    if let Some(backends) = std::env::var("WGPU_BACKEND")
+        .ok()
+        .and_then(|value| adapter::parse_backends(&value))
+    {
+        adapter::set_backends(backends);
     }
-
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
-        true
+    let Cli {
+        command,
+        mut options,
+        log_config,
+        headless,
+        headless_frames,
+        headless_output,
+        list_displays,
+        mounts,
+        unknown_args,
+    } = match parse_args(std::env::args().skip(1), options, log_config) {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprint!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    match command {
+        Command::Run => {}
+        Command::Help => {
+            print!("{}", USAGE);
+            return;
+        }
+        Command::ListAdapters => {
+            for (index, (_, info)) in adapter::enumerate().iter().enumerate() {
+                println!("{}", adapter::describe(index, info));
+            }
+            return;
+        }
+        Command::Pack { dir, output } => {
+            match vfs::pak::write(&dir, &output) {
+                Ok(count) => println!("Packed {} files into {}", count, output),
+                Err(err) => {
+                    eprintln!("Failed to pack {}: {}", dir, err);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
     }
-}
-
-fn main() {
-    use futures::executor::block_on;
-
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
-        .with_title("Nomads of Myria")
-        .build(&event_loop)
-        .unwrap();
-
-    let mut ctx = block_on(Context::new(&window));
-    let mut app = Application::new(&mut ctx);
 
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
-        match event {
-            Event::WindowEvent {
-                ref event,
-                window_id,
-            } if window_id == window.id() => {
-                if app.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-
-                        WindowEvent::KeyboardInput { input, .. } => match input {
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            } => *control_flow = ControlFlow::Exit,
-                            _ => {}
-                        },
-
-                        WindowEvent::Resized(physical_size) => {
-                            block_on(ctx.resize(*physical_size));
-                        }
-
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            block_on(ctx.resize(**new_inner_size));
-                        }
-
-                        _ => {}
+    if let Err(err) = logging::install(log_config) {
+        eprintln!("Failed to open the log file: {}", err);
+    }
+    validation::install();
+    if let Some(err) = settings_error {
+        log::warn!("{}, using the defaults", err);
+    }
+    for arg in unknown_args {
+        log::warn!("Ignoring unknown argument {:?}", arg);
+    }
+    mount_assets(&mounts);
+
+    if headless {
+        // So the frames don't depend on a save an earlier run left behind.
+        options.save_path = None;
+        match app::run_headless(&options, headless_frames) {
+            Ok(image) => {
+                if let Some(path) = headless_output {
+                    match image.save(&path) {
+                        Ok(()) => log::info!("Saved the last frame to {}", path.display()),
+                        Err(err) => log::error!("Failed to save {}: {}", path.display(), err),
                     }
                 }
             }
-
-            Event::RedrawRequested(_) => {
-                app.update(&mut ctx);
-                app.render(&mut ctx);
+            Err(err) => {
+                log::error!("Headless run failed: {}", err);
+                std::process::exit(1);
             }
+        }
+        return;
+    }
 
-            Event::MainEventsCleared => {
-                window.request_redraw();
-            }
-            _ => {}
+    let event_loop = EventLoop::new();
+    let mut builder = WindowBuilder::new().with_title("Nomads of Myria");
+    if let Some([width, height]) = options.resolution {
+        builder = builder.with_inner_size(PhysicalSize::new(width, height));
+    }
+    let window = match builder.build(&event_loop) {
+        Ok(window) => window,
+        Err(err) => {
+            log::error!("Failed to create the window: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if list_displays {
+        for line in display::describe(&window) {
+            println!("{}", line);
         }
-    });
+        return;
+    }
+
+    app::run(event_loop, window, options)
 }
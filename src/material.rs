@@ -0,0 +1,243 @@
+//! Materials: how a draw is shaded, as opposed to the geometry it shades.
+//!
+//! A [`Material`] picks the shaders, what is bound for them at bind group
+//! 1, and how its output blends with what is already drawn. Passes keep
+//! theirs in [`Materials`] and refer to them by [`MaterialId`], which is
+//! also what they sort and batch draws by: consecutive draws with one
+//! material share a pipeline and bind group.
+//!
+//! Besides variants of a pass's own shaders, materials can be of a
+//! [`MaterialType`] defined outside the engine, with shaders, a vertex
+//! format and parameters of its own, which passes that support them
+//! register and then draw like their own.
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    bind_group_cache::LayoutId, pipeline_cache::ShaderSource, shader_compiler::ShaderFeatures,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(usize);
+
+/// A [`MaterialType`] registered with a pass.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialTypeId(pub(crate) usize);
+
+/// A kind of material with shaders of its own.
+///
+/// Its vertex shader gets the drawing pass's camera and the draw's
+/// transform as `CameraUniforms` from `common.wgsl`, at binding 0 of group
+/// 0; group 1 is laid out as `params`. The vertex format is the one of the
+/// meshes the type is registered with.
+#[derive(Clone, Debug)]
+pub struct MaterialType {
+    pub vertex_shader: ShaderSource,
+    pub fragment_shader: ShaderSource,
+    pub params: Vec<wgpu::BindGroupLayoutEntry>,
+}
+
+/// Which shaders a material draws with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MaterialShader {
+    /// The pass's own shaders, in the variant with these features.
+    Builtin(ShaderFeatures),
+    Custom(MaterialTypeId),
+}
+
+/// How a material's color and alpha combine with the target's.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Blend {
+    pub color: wgpu::BlendDescriptor,
+    pub alpha: wgpu::BlendDescriptor,
+}
+
+impl Blend {
+    /// Overwrites the target, ignoring alpha.
+    pub const REPLACE: Self = Self {
+        color: wgpu::BlendDescriptor::REPLACE,
+        alpha: wgpu::BlendDescriptor::REPLACE,
+    };
+
+    /// Covers the target by the source's alpha, for translucency.
+    pub const ALPHA: Self = Self {
+        color: wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+    };
+
+    /// Covers the target by the source's alpha, with the source's color
+    /// already multiplied by it. Filtering and overlapping translucency
+    /// then come out without dark fringes.
+    pub const PREMULTIPLIED: Self = Self {
+        color: wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        },
+    };
+
+    /// Adds the source, weighted by its alpha, for glows and light.
+    pub const ADDITIVE: Self = Self {
+        color: wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: Self::KEEP_ALPHA,
+    };
+
+    /// Multiplies the target by the source color, ignoring alpha, for
+    /// shadows and tints.
+    pub const MULTIPLY: Self = Self {
+        color: wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::DstColor,
+            dst_factor: wgpu::BlendFactor::Zero,
+            operation: wgpu::BlendOperation::Add,
+        },
+        alpha: Self::KEEP_ALPHA,
+    };
+
+    /// Leaves the target's alpha as it is.
+    const KEEP_ALPHA: wgpu::BlendDescriptor = wgpu::BlendDescriptor {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+
+    /// Whether drawing with this hides everything behind it, so draws can
+    /// be reordered under a depth test without changing the result.
+    pub fn is_opaque(&self) -> bool {
+        *self == Self::REPLACE
+    }
+}
+
+/// What a material binds at bind group 1.
+#[derive(Clone, Debug)]
+pub enum MaterialParams {
+    /// The drawing pass's texture atlas.
+    Atlas,
+    /// A bind group of its own, e.g. a texture or a uniform block of
+    /// parameters, made with `layout`.
+    BindGroup {
+        layout: LayoutId,
+        bind_group: Rc<wgpu::BindGroup>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub shader: MaterialShader,
+    pub params: MaterialParams,
+    pub blend: Blend,
+}
+
+impl Material {
+    /// The atlas, tinted by the vertex color: what the interface draws
+    /// with unless told otherwise.
+    pub fn atlas() -> Self {
+        Self {
+            shader: MaterialShader::Builtin(
+                ShaderFeatures::TEXTURED | ShaderFeatures::VERTEX_COLOR,
+            ),
+            params: MaterialParams::Atlas,
+            blend: Blend::REPLACE,
+        }
+    }
+
+    /// The atlas with premultiplied alpha, for an atlas storing its images
+    /// premultiplied.
+    pub fn atlas_premultiplied() -> Self {
+        Self {
+            shader: MaterialShader::Builtin(
+                ShaderFeatures::TEXTURED
+                    | ShaderFeatures::VERTEX_COLOR
+                    | ShaderFeatures::PREMULTIPLIED,
+            ),
+            params: MaterialParams::Atlas,
+            blend: Blend::PREMULTIPLIED,
+        }
+    }
+
+    /// A material of the custom type `ty`, binding `bind_group`, made with
+    /// the type's layout.
+    pub fn custom(ty: MaterialTypeId, layout: LayoutId, bind_group: Rc<wgpu::BindGroup>) -> Self {
+        Self {
+            shader: MaterialShader::Custom(ty),
+            params: MaterialParams::BindGroup { layout, bind_group },
+            blend: Blend::REPLACE,
+        }
+    }
+}
+
+/// The materials of one pass.
+#[derive(Default)]
+pub struct Materials {
+    materials: Vec<Material>,
+    /// The material each one made by `with_blend` is a copy of.
+    bases: HashMap<MaterialId, MaterialId>,
+    /// The copies of a material with other blending, by base and blend.
+    blended: HashMap<(MaterialId, Blend), MaterialId>,
+}
+
+impl Materials {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    pub fn get(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    /// Changes take effect from the next frame's draws with it. Copies
+    /// made by [`Materials::with_blend`] aren't changed along with it.
+    pub fn get_mut(&mut self, id: MaterialId) -> &mut Material {
+        &mut self.materials[id.0]
+    }
+
+    /// `id`, but blended with `blend`: the material itself if it already
+    /// is, otherwise a copy, added the first time it is asked for. Copies
+    /// of copies are of the original, so asking for its blend returns it.
+    pub fn with_blend(&mut self, id: MaterialId, blend: Blend) -> MaterialId {
+        let base = self.bases.get(&id).copied().unwrap_or(id);
+        if self.get(base).blend == blend {
+            return base;
+        }
+        if let Some(&blended) = self.blended.get(&(base, blend.clone())) {
+            return blended;
+        }
+        let material = Material {
+            blend: blend.clone(),
+            ..self.get(base).clone()
+        };
+        let blended = self.add(material);
+        self.bases.insert(blended, base);
+        self.blended.insert((base, blend), blended);
+        blended
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+}
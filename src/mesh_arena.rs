@@ -0,0 +1,244 @@
+use std::{any::Any, ops::Range};
+
+use crate::{
+    dynamic_buffer::DynamicBuffer, error::EngineError, gpu_mem::BudgetExceeded,
+    indirect_buffer::DrawIndexedArgs, Context,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MeshId(usize);
+
+#[derive(Clone, Debug)]
+struct Mesh {
+    vertices: Range<u32>,
+    indices: Range<u32>,
+}
+
+/// Packs many small meshes into one shared vertex buffer and one shared
+/// index buffer. Indices are stored relative to their own mesh and drawn
+/// with a base-vertex offset.
+///
+/// Removed meshes leave holes that are reclaimed by [`MeshArena::compact`],
+/// which runs automatically once more than half of the arena is dead.
+pub struct MeshArena<V: bytemuck::Pod> {
+    vertices: DynamicBuffer<V>,
+    indices: DynamicBuffer<u32>,
+    meshes: Vec<Option<Mesh>>,
+    free_ids: Vec<usize>,
+    dead_vertices: u32,
+    dirty: bool,
+}
+
+impl<V: bytemuck::Pod> MeshArena<V> {
+    pub fn new(
+        ctx: &mut Context,
+        subsystem: &str,
+        vertex_capacity: usize,
+        index_capacity: usize,
+    ) -> Result<Self, BudgetExceeded> {
+        Ok(Self {
+            vertices: DynamicBuffer::new(
+                ctx,
+                &format!("{}/arena vertices", subsystem),
+                wgpu::BufferUsage::VERTEX,
+                vertex_capacity,
+            )?,
+            indices: DynamicBuffer::new(
+                ctx,
+                &format!("{}/arena indices", subsystem),
+                wgpu::BufferUsage::INDEX,
+                index_capacity,
+            )?,
+            meshes: vec![],
+            free_ids: vec![],
+            dead_vertices: 0,
+            dirty: false,
+        })
+    }
+
+    pub fn insert(&mut self, vertices: &[V], indices: &[u32]) -> MeshId {
+        let first_vertex = self.vertices.len() as u32;
+        let first_index = self.indices.len() as u32;
+
+        self.vertices.extend_from_slice(vertices);
+        self.indices.extend_from_slice(indices);
+        self.dirty = true;
+
+        let mesh = Mesh {
+            vertices: first_vertex..first_vertex + vertices.len() as u32,
+            indices: first_index..first_index + indices.len() as u32,
+        };
+
+        match self.free_ids.pop() {
+            Some(id) => {
+                self.meshes[id] = Some(mesh);
+                MeshId(id)
+            }
+            None => {
+                self.meshes.push(Some(mesh));
+                MeshId(self.meshes.len() - 1)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: MeshId) {
+        if let Some(mesh) = self.meshes.get_mut(id.0).and_then(Option::take) {
+            self.dead_vertices += mesh.vertices.end - mesh.vertices.start;
+            self.free_ids.push(id.0);
+        }
+
+        if self.dead_vertices as usize * 2 > self.vertices.len() {
+            self.compact();
+        }
+    }
+
+    /// Rewrites the shared buffers without the holes left by removed meshes.
+    pub fn compact(&mut self) {
+        let old_vertices = self.vertices.as_slice().to_vec();
+        let old_indices = self.indices.as_slice().to_vec();
+
+        self.vertices.clear();
+        self.indices.clear();
+
+        for mesh in self.meshes.iter_mut().flatten() {
+            let first_vertex = self.vertices.len() as u32;
+            let first_index = self.indices.len() as u32;
+
+            self.vertices.extend_from_slice(
+                &old_vertices[mesh.vertices.start as usize..mesh.vertices.end as usize],
+            );
+            self.indices.extend_from_slice(
+                &old_indices[mesh.indices.start as usize..mesh.indices.end as usize],
+            );
+
+            mesh.vertices = first_vertex..first_vertex + (mesh.vertices.end - mesh.vertices.start);
+            mesh.indices = first_index..first_index + (mesh.indices.end - mesh.indices.start);
+        }
+
+        self.dead_vertices = 0;
+        self.dirty = true;
+    }
+
+    /// Moves the shared buffers to the context's current device. Meshes keep
+    /// their ids and are uploaded again on the next [`MeshArena::upload`].
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded> {
+        self.vertices.recreate(ctx)?;
+        self.indices.recreate(ctx)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Uploads the shared buffers if any mesh changed since the last call.
+    pub fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError> {
+        if self.dirty {
+            self.vertices.upload(ctx, encoder)?;
+            self.indices.upload(ctx, encoder)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Binds the shared buffers to vertex slot `slot`. Must be called before
+    /// [`MeshArena::draw`].
+    pub fn bind<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, slot: u32) {
+        pass.set_vertex_buffer(slot, self.vertices.buffer(), 0, 0);
+        pass.set_index_buffer(self.indices.buffer(), 0, 0);
+    }
+
+    /// How many indices drawing `id` draws, or 0 if it was removed.
+    pub fn index_count(&self, id: MeshId) -> u32 {
+        match self.meshes.get(id.0) {
+            Some(Some(mesh)) => mesh.indices.end - mesh.indices.start,
+            _ => 0,
+        }
+    }
+
+    pub fn draw(&self, pass: &mut wgpu::RenderPass, id: MeshId, instances: Range<u32>) {
+        if let Some(Some(mesh)) = self.meshes.get(id.0) {
+            pass.draw_indexed(mesh.indices.clone(), mesh.vertices.start as i32, instances);
+        }
+    }
+
+    /// The arguments of an indirect draw of `instances` of `id`, with the
+    /// shared buffers bound, or `None` if it was removed.
+    pub fn indirect_args(&self, id: MeshId, instances: Range<u32>) -> Option<DrawIndexedArgs> {
+        let mesh = self.meshes.get(id.0)?.as_ref()?;
+        Some(DrawIndexedArgs {
+            index_count: mesh.indices.end - mesh.indices.start,
+            instance_count: instances.end - instances.start,
+            first_index: mesh.indices.start,
+            base_vertex: mesh.vertices.start as i32,
+            first_instance: instances.start,
+        })
+    }
+
+    /// The indices of `id`, relative to its first vertex, or none if it was
+    /// removed.
+    pub fn indices(&self, id: MeshId) -> &[u32] {
+        match self.meshes.get(id.0) {
+            Some(Some(mesh)) => {
+                &self.indices.as_slice()[mesh.indices.start as usize..mesh.indices.end as usize]
+            }
+            _ => &[],
+        }
+    }
+
+    /// The vertex buffer, and the offset into it of the vertices of `id`,
+    /// for drawing the mesh with other indices.
+    pub fn vertices(&self, id: MeshId) -> (&wgpu::Buffer, i32) {
+        let base = match self.meshes.get(id.0) {
+            Some(Some(mesh)) => mesh.vertices.start as i32,
+            _ => 0,
+        };
+        (self.vertices.buffer(), base)
+    }
+}
+
+/// A [`MeshArena`] of any vertex type, for passes drawing meshes in formats
+/// they don't know themselves.
+pub trait Meshes {
+    fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError>;
+    fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded>;
+    fn bind<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, slot: u32);
+    fn draw(&self, pass: &mut wgpu::RenderPass, id: MeshId, instances: Range<u32>);
+    fn index_count(&self, id: MeshId) -> u32;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<V: bytemuck::Pod> Meshes for MeshArena<V> {
+    fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError> {
+        MeshArena::upload(self, ctx, encoder)
+    }
+
+    fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded> {
+        MeshArena::recreate(self, ctx)
+    }
+
+    fn bind<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, slot: u32) {
+        MeshArena::bind(self, pass, slot)
+    }
+
+    fn draw(&self, pass: &mut wgpu::RenderPass, id: MeshId, instances: Range<u32>) {
+        MeshArena::draw(self, pass, id, instances)
+    }
+
+    fn index_count(&self, id: MeshId) -> u32 {
+        MeshArena::index_count(self, id)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
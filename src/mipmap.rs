@@ -0,0 +1,50 @@
+use crate::{blit, pipeline_cache::ShaderError, validation, Context};
+
+/// Number of levels in a full mip chain for a `width` by `height` texture.
+pub fn level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Fills mip levels `1..level_count` of `texture` by repeatedly blitting
+/// each level into the next.
+///
+/// The texture must be 2D, single-layer and created with
+/// `OUTPUT_ATTACHMENT | SAMPLED` usage, with level 0 already uploaded.
+pub fn generate(
+    ctx: &mut Context,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    level_count: u32,
+) -> Result<(), ShaderError> {
+    if level_count < 2 {
+        return Ok(());
+    }
+    let _scope = validation::scope("mipmap");
+
+    let views = (0..level_count)
+        .map(|level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                format,
+                dimension: wgpu::TextureViewDimension::D2,
+                aspect: wgpu::TextureAspect::All,
+                base_mip_level: level,
+                level_count: 1,
+                base_array_layer: 0,
+                array_layer_count: 1,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("mipmap"),
+        });
+
+    for pair in views.windows(2) {
+        blit::blit(ctx, &mut encoder, &pair[0], &pair[1], format)?;
+    }
+
+    ctx.queue.submit(&[encoder.finish()]);
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{debug_font, material::Blend, passes::Layer, stats::RenderStats, InterfacePass};
+
+/// Frame intervals kept for the graph.
+const HISTORY: usize = 120;
+/// Frame time at the top of the graph.
+const GRAPH_SPAN: Duration = Duration::from_micros(33_333);
+const GRAPH_HEIGHT: f32 = 40.0;
+/// Logical pixels per font pixel.
+const TEXT_SCALE: f32 = 2.0;
+const PADDING: f32 = 6.0;
+const GRAPH_WIDTH: f32 = HISTORY as f32 * 2.0;
+
+/// FPS, frame-time history and draw counts in the top-right corner, drawn
+/// with the interface pass.
+///
+/// Frame times are the frame clock's deltas, so they include pacing and
+/// present waits, unlike the profiler's frames. They are recorded while
+/// hidden too, so the graph is full when shown.
+pub struct DebugOverlay {
+    visible: bool,
+    frame_times: VecDeque<Duration>,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            frame_times: VecDeque::with_capacity(HISTORY),
+        }
+    }
+}
+
+impl DebugOverlay {
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Call once per frame with `Time::delta`.
+    pub fn frame(&mut self, delta: Duration) {
+        if self.frame_times.len() == HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta);
+    }
+
+    /// Average over the last half second of frames.
+    pub fn fps(&self) -> f32 {
+        let mut total = Duration::default();
+        let mut frames = 0;
+        for &time in self.frame_times.iter().rev() {
+            total += time;
+            frames += 1;
+            if total >= Duration::from_millis(500) {
+                break;
+            }
+        }
+        if frames == 0 {
+            0.0
+        } else {
+            frames as f32 / total.as_secs_f32()
+        }
+    }
+
+    /// Queues the overlay into `pass` if visible, showing `stats`, normally
+    /// `Context::frame_stats`.
+    pub fn draw(&self, pass: &mut InterfacePass, stats: &RenderStats) {
+        if !self.visible {
+            return;
+        }
+
+        let last = self.frame_times.back().copied().unwrap_or_default();
+        let lines = [
+            format!(
+                "{:.0} FPS {:.2} MS",
+                self.fps(),
+                last.as_secs_f32() * 1000.0
+            ),
+            format!(
+                "DRAWS {} DISPATCH {} BINDS {}",
+                stats.draw_calls, stats.dispatches, stats.bind_group_switches
+            ),
+            format!("VERTS {} IDX {}", stats.vertices, stats.indices),
+            format!("UPLOAD {:.1} KB", stats.bytes_uploaded as f32 / 1024.0),
+        ];
+
+        let line_height = (debug_font::GLYPH_HEIGHT + 2) as f32 * TEXT_SCALE;
+        let text_height = lines.len() as f32 * line_height;
+        let text_width = lines
+            .iter()
+            .map(|line| debug_font::text_width(line, TEXT_SCALE))
+            .fold(GRAPH_WIDTH, f32::max);
+        let width = text_width + 2.0 * PADDING;
+        let origin = [(pass.logical_size()[0] - width - 8.0).max(0.0), 8.0];
+        let height = PADDING * 3.0 + text_height + GRAPH_HEIGHT;
+        let palette = *pass.palette();
+        let layer = pass.layer();
+        pass.set_layer(Layer::OVERLAY);
+        pass.set_blend(Blend::ALPHA);
+        pass.draw_rect(
+            origin,
+            [origin[0] + width, origin[1] + height],
+            palette.panel,
+        );
+        pass.set_blend(Blend::REPLACE);
+
+        let left = origin[0] + PADDING;
+        for (index, line) in lines.iter().enumerate() {
+            let top = origin[1] + PADDING + index as f32 * line_height;
+            debug_font::draw_text(pass, [left, top], TEXT_SCALE, palette.text, line);
+        }
+
+        // One bar per frame, newest on the right, coloured by how it
+        // compares with 60 and 30 Hz.
+        let bottom = origin[1] + PADDING * 2.0 + text_height + GRAPH_HEIGHT;
+        let skip = HISTORY - self.frame_times.len();
+        for (index, &time) in self.frame_times.iter().enumerate() {
+            let x = left + (skip + index) as f32 * 2.0;
+            let fraction = (time.as_secs_f32() / GRAPH_SPAN.as_secs_f32()).min(1.0);
+            let color = if time <= Duration::from_micros(16_667) {
+                palette.good
+            } else if time <= GRAPH_SPAN {
+                palette.warning
+            } else {
+                palette.bad
+            };
+            pass.draw_rect(
+                [x, bottom - (fraction * GRAPH_HEIGHT).max(1.0)],
+                [x + 2.0, bottom],
+                color,
+            );
+        }
+
+        // The 60 Hz budget.
+        let budget = bottom - GRAPH_HEIGHT / 2.0;
+        pass.draw_rect(
+            [left, budget],
+            [left + GRAPH_WIDTH, budget + 1.0],
+            palette.text.with_alpha(0.5),
+        );
+        pass.set_layer(layer);
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+use winit::event_loop::ControlFlow;
+
+/// Redraw rate while the window is visible but not focused.
+const UNFOCUSED_FPS: u32 = 10;
+
+/// How long before a frame is due the loop stops waiting on the OS, whose
+/// timers are too coarse for high limits, and spins instead.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// Decides when the main loop redraws, so a hidden or background window
+/// doesn't keep a core and the GPU busy.
+///
+/// Focused windows redraw continuously, or at the frame limit if one is
+/// set, unfocused ones at [`UNFOCUSED_FPS`] at most, and minimized ones not
+/// at all until they are restored. The limit is enforced on the CPU,
+/// independently of the present mode.
+///
+/// winit 0.22 doesn't report occlusion, so a window covered by another one
+/// only slows down once it loses focus.
+pub struct Pacing {
+    focused: bool,
+    minimized: bool,
+    frame_limit: Option<u32>,
+    next_frame: Instant,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Self {
+            focused: true,
+            minimized: false,
+            frame_limit: None,
+            next_frame: Instant::now(),
+        }
+    }
+}
+
+impl Pacing {
+    pub fn focus_changed(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Call on every resize; a zero size means the window was minimized.
+    pub fn resized(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        self.minimized = size.width == 0 || size.height == 0;
+    }
+
+    pub fn frame_limit(&self) -> Option<u32> {
+        self.frame_limit
+    }
+
+    /// Caps the frame rate at `fps`, or lifts the cap with `None`.
+    pub fn set_frame_limit(&mut self, fps: Option<u32>) {
+        self.frame_limit = fps.filter(|&fps| fps > 0);
+    }
+
+    /// Whether the loop is throttled below its normal rate.
+    pub fn is_throttled(&self) -> bool {
+        self.minimized || !self.focused
+    }
+
+    /// Called once the event queue is drained. Returns whether to request a
+    /// redraw now, and sets `control_flow` to wake the loop for the next one
+    /// unless it is exiting.
+    pub fn schedule(&mut self, control_flow: &mut ControlFlow) -> bool {
+        if *control_flow == ControlFlow::Exit {
+            return false;
+        }
+
+        if self.minimized {
+            *control_flow = ControlFlow::Wait;
+            return false;
+        }
+
+        let limit = self.frame_limit.map(|fps| Duration::from_secs(1) / fps);
+        let interval = if self.focused {
+            match limit {
+                Some(interval) => interval,
+                None => {
+                    *control_flow = ControlFlow::Poll;
+                    return true;
+                }
+            }
+        } else {
+            let unfocused = Duration::from_secs(1) / UNFOCUSED_FPS;
+            limit.map_or(unfocused, |interval| interval.max(unfocused))
+        };
+
+        if Instant::now() + SPIN_MARGIN < self.next_frame {
+            *control_flow = ControlFlow::WaitUntil(self.next_frame - SPIN_MARGIN);
+            return false;
+        }
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+
+        // After a stall, start over from now rather than rushing through
+        // the missed frames.
+        self.next_frame = (self.next_frame + interval).max(Instant::now());
+        *control_flow = ControlFlow::WaitUntil(self.next_frame - SPIN_MARGIN);
+        true
+    }
+}
@@ -0,0 +1,65 @@
+//! The render passes, scheduled and recorded by the
+//! [`RenderGraph`](crate::render_graph::RenderGraph).
+
+mod interface;
+mod layer;
+mod particles;
+mod sprites;
+
+use crate::{
+    ecs::World,
+    error::EngineError,
+    render_graph::{Attachments, Output, FRAME},
+    time::Time,
+    Context,
+};
+
+pub use interface::{InterfaceInstance, InterfacePass, InterfaceVertex, Rect, View};
+pub use layer::Layer;
+pub use particles::{Particle, ParticleEmitter, ParticlePass};
+pub use sprites::{SpritePass, SpriteTextureId, SpriteVertex};
+
+pub trait Vertex: bytemuck::Pod + bytemuck::Zeroable {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a>;
+}
+
+/// A pass drawing into the frame or a transient attachment. Every frame,
+/// [`RenderGraph::render`](crate::render_graph::RenderGraph::render) calls `prepare` on each pass and then `record`
+/// on each, in schedule order.
+pub trait Pass {
+    /// Names the pass in graph errors.
+    fn name(&self) -> &'static str;
+
+    /// Attachments this pass samples.
+    fn reads(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// The attachment this pass draws into.
+    fn writes(&self) -> &'static str {
+        FRAME
+    }
+
+    /// Takes what this pass draws from `world`. The application calls it
+    /// on the passes it was given, before rendering; passes fed some other
+    /// way keep the default, which does nothing.
+    fn extract(&mut self, _world: &World, _time: &Time) {}
+
+    /// Uploads this frame's data through `encoder` and looks up what
+    /// `record` binds, including the attachments it reads. Render
+    /// statistics are counted here, as `record` only sees the context
+    /// immutably.
+    fn prepare(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        attachments: &Attachments,
+    ) -> Result<(), EngineError>;
+
+    /// Records this pass's drawing into `output`, the attachment it writes.
+    fn record(&self, ctx: &Context, encoder: &mut wgpu::CommandEncoder, output: &Output);
+
+    /// Rebuilds every GPU resource on the context's current device, e.g.
+    /// after `Context::recover`.
+    fn recreate(&mut self, ctx: &mut Context) -> Result<(), EngineError>;
+}
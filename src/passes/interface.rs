@@ -0,0 +1,1998 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    rc::Rc,
+};
+
+use nalgebra as na;
+
+use crate::{
+    bind_group_cache::LayoutId,
+    color::{Color, Palette},
+    components::{GlobalTransform, Sprite},
+    culling::{Frustum, FrustumCulling},
+    dynamic_buffer::DynamicBuffer,
+    ecs::World,
+    error::EngineError,
+    gpu_mem, gpu_struct,
+    indirect_buffer::{DrawIndexedArgs, IndirectBuffer},
+    instance_buffer::InstanceBuffer,
+    jobs,
+    material::{
+        Blend, Material, MaterialId, MaterialParams, MaterialShader, MaterialType, MaterialTypeId,
+        Materials,
+    },
+    mesh_arena::{MeshArena, MeshId, Meshes},
+    pipeline_cache::{PipelineKey, ShaderVariant, VertexLayout},
+    profiler,
+    push_constants::PushBlock,
+    render_graph::{Attachments, Output},
+    render_target::{self, RenderTarget},
+    sampler::SamplerDesc,
+    shader_compiler::ShaderFeatures,
+    shapes::{Path, Tessellation},
+    texture_atlas::{TextureAtlas, UvRect},
+    validation, Context,
+};
+
+use super::{Layer, Pass, Vertex};
+
+/// Interface coordinates are logical pixels from the top-left corner of the
+/// window, with y pointing down. With a virtual size set, they are pixels
+/// of a canvas of that size instead, scaled to fit the window and centred,
+/// with black bars along the sides it doesn't fill.
+///
+/// Quads queued with the `draw_*` methods are drawn with the current
+/// [`Material`], and meshes from [`InterfacePass::meshes_mut`] with the one
+/// they are queued with. Draws are painted by [`Layer`], set with
+/// [`InterfacePass::set_layer`], and within a layer in the order they were
+/// queued, merging consecutive ones with the same material. The depth test
+/// doesn't change that order: quads are flat, at depth 0, so they pass it
+/// wherever they overlap, and only meshes placed in depth are sorted by
+/// it. With [`InterfacePass::set_depth_prepass`] opaque draws are also
+/// drawn once before, writing depth only, so heavy meshes shade each pixel
+/// once.
+///
+/// Materials can also be of a [`MaterialType`] registered with
+/// [`InterfacePass::register_material_type`], whose meshes live in an
+/// arena of the type's own, at [`InterfacePass::type_meshes_mut`].
+///
+/// Draws can be clipped to a rectangle with [`InterfacePass::set_clip`],
+/// e.g. for scrolling containers.
+///
+/// Draws can be masked to arbitrary shapes, e.g. a circular minimap: what
+/// is queued between [`InterfacePass::begin_mask`] and
+/// [`InterfacePass::end_mask`] isn't drawn but marks the stencil, and what
+/// is queued after is drawn only where it is marked, until
+/// [`InterfacePass::unmask`]. Masks nest, each within the last.
+///
+/// Draws can also go into a [`View`] set with [`InterfacePass::set_view`]:
+/// a part of the interface with a camera of its own, drawn through a
+/// viewport, e.g. one player's half of a split screen or one of an
+/// editor's views of a scene.
+///
+/// Many copies of one mesh can be drawn at once with
+/// [`InterfacePass::draw_instances`], each placed, tinted and textured by
+/// an [`InterfaceInstance`] from [`InterfacePass::instances_mut`]. With
+/// [`InterfacePass::draw_culled`], the instances outside the camera are
+/// culled on the GPU instead of drawn.
+///
+/// In wireframe mode the edges of every triangle are drawn as white lines
+/// on top, except for meshes of custom types and instances. wgpu 0.5
+/// can't rasterize polygons as lines, so the pass makes line lists of the
+/// indices.
+pub struct InterfacePass {
+    /// The pipeline of the atlas material; the others' differ in the
+    /// fragment shader, group 1 layout and blending.
+    pipeline_key: PipelineKey,
+    /// Of the window, as last set.
+    window_size: [f32; 2],
+    virtual_size: Option<[f32; 2]>,
+    palette: Palette,
+    camera: na::Orthographic3<f32>,
+    transform: na::Matrix4<f32>,
+    /// Camera and transform, once for the quads and once per mesh draw.
+    uniforms: PushBlock<VertexUniforms>,
+    atlas: TextureAtlas,
+    materials: Materials,
+    atlas_material: MaterialId,
+    material: MaterialId,
+    clip: Option<Rect>,
+    layer: Layer,
+    /// The draws marking each mask queued so far this frame, innermost
+    /// last.
+    masks: Vec<Vec<Draw>>,
+    /// Where the draws of a mask still being queued start.
+    mask_start: Option<usize>,
+    /// Whether any draw this frame uses the stencil, so every pipeline
+    /// needs a depth-stencil state and the pass the depth attachment.
+    stencil_used: bool,
+    /// Set this frame, by index.
+    views: Vec<View>,
+    view: Option<usize>,
+    draws: Vec<Draw>,
+    meshes: MeshArena<InterfaceVertex>,
+    /// A white quad from (0, 0) to (1, 1) in `meshes`, for instanced quads.
+    unit_quad: MeshId,
+    /// Per-instance attributes of this frame's instanced draws.
+    instances: InstanceBuffer<InterfaceInstance>,
+    /// The arguments of this frame's instanced draws, so runs of them
+    /// sharing their state are recorded without setting it in between.
+    indirect: IndirectBuffer<DrawIndexedArgs>,
+    /// The instances of this frame's culled draws, and those draws.
+    culling: FrustumCulling<InterfaceInstance>,
+    /// By `MaterialTypeId`.
+    types: Vec<CustomType>,
+    /// Pipeline keys already checked against their shaders.
+    validated: HashSet<PipelineKey>,
+    depth_prepass: bool,
+    wireframe: bool,
+    /// Line list indices of the edges drawn this frame in wireframe mode.
+    edges: DynamicBuffer<u32>,
+    prepared: Option<Prepared>,
+
+    pub vertices: DynamicBuffer<InterfaceVertex>,
+    pub indices: DynamicBuffer<u32>,
+}
+
+/// A draw queued this frame.
+#[derive(Clone)]
+struct Draw {
+    material: MaterialId,
+    /// Drawn in place of the material's bind group, for render targets.
+    bind_group: Option<Rc<wgpu::BindGroup>>,
+    clip: Option<Rect>,
+    layer: Layer,
+    /// In `InterfacePass::views`.
+    view: Option<usize>,
+    stencil: Stencil,
+    geometry: Geometry,
+}
+
+/// How a draw uses the stencil, which counts the masks a pixel is in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Stencil {
+    Off,
+    /// Drawn where the pixel is in `level` masks.
+    Inside(u32),
+    /// Marks a mask within `level` others, without drawing.
+    Add(u32),
+    /// Unmarks a mask within `level` others, without drawing.
+    Remove(u32),
+}
+
+impl Stencil {
+    /// What the stencil is compared with.
+    fn reference(self) -> u32 {
+        match self {
+            Stencil::Off => 0,
+            Stencil::Inside(level) | Stencil::Add(level) => level,
+            Stencil::Remove(level) => level + 1,
+        }
+    }
+
+    /// Whether color is written.
+    fn draws(self) -> bool {
+        matches!(self, Stencil::Off | Stencil::Inside(_))
+    }
+
+    /// `state` with this use of the stencil. Pixels covered twice by a
+    /// mask are only counted once, as the first pass makes the test
+    /// fail. Masks don't write depth either, as they aren't drawn.
+    fn apply(self, state: &mut wgpu::DepthStencilStateDescriptor) {
+        let operation = |pass_op| wgpu::StencilStateFaceDescriptor {
+            compare: wgpu::CompareFunction::Equal,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+        let (face, write_mask) = match self {
+            Stencil::Off => return,
+            Stencil::Inside(_) => (operation(wgpu::StencilOperation::Keep), 0),
+            Stencil::Add(_) => (operation(wgpu::StencilOperation::IncrementClamp), !0),
+            Stencil::Remove(_) => (operation(wgpu::StencilOperation::DecrementClamp), !0),
+        };
+        state.stencil_front = face.clone();
+        state.stencil_back = face;
+        state.stencil_read_mask = !0;
+        state.stencil_write_mask = write_mask;
+        if !self.draws() {
+            state.depth_write_enabled = false;
+        }
+    }
+}
+
+/// A rectangle, in interface coordinates unless said otherwise.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Rect {
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self { min, max }
+    }
+
+    /// The part of both, empty if they don't overlap.
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let min = [self.min[0].max(other.min[0]), self.min[1].max(other.min[1])];
+        let max = [self.max[0].min(other.max[0]), self.max[1].min(other.max[1])];
+        Rect {
+            min,
+            max: [max[0].max(min[0]), max[1].max(min[1])],
+        }
+    }
+}
+
+/// A part of the interface drawn with a camera and transform of its own in
+/// place of the pass's.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct View {
+    /// Where it shows, in interface coordinates. Its camera's view volume
+    /// is stretched to fill it.
+    pub rect: Rect,
+    pub camera: na::Orthographic3<f32>,
+    /// Applied to quads, as the pass transform is outside views.
+    pub transform: na::Matrix4<f32>,
+}
+
+impl View {
+    /// Showing logical pixels from its top-left corner at their size, as
+    /// the interface does.
+    pub fn new(rect: Rect) -> Self {
+        Self {
+            rect,
+            camera: logical_camera(rect.max[0] - rect.min[0], rect.max[1] - rect.min[1]),
+            transform: na::Matrix4::identity(),
+        }
+    }
+
+    pub fn size(&self) -> [f32; 2] {
+        [
+            self.rect.max[0] - self.rect.min[0],
+            self.rect.max[1] - self.rect.min[1],
+        ]
+    }
+}
+
+#[derive(Clone)]
+enum Geometry {
+    /// Indices queued with the `draw_*` methods, drawn with the pass
+    /// transform.
+    Quads(Range<u32>),
+    /// A mesh from the arena, drawn with its own transform.
+    Mesh(MeshId, na::Matrix4<f32>),
+    /// Instances of a mesh from the pass's arena, drawn with the pass
+    /// transform.
+    Instances(MeshId, Range<u32>),
+    /// A draw in `InterfacePass::culling`, drawn with the pass transform.
+    Culled(u32),
+}
+
+/// A [`MaterialType`] registered with the pass.
+struct CustomType {
+    /// Kept to load the shaders again on a new device.
+    desc: MaterialType,
+    vertex_layout: VertexLayout,
+    /// The layout of its params, at group 1.
+    layout: LayoutId,
+    /// Its pipeline with the pass's blending and depth state.
+    pipeline_key: PipelineKey,
+    /// A `MeshArena` of the vertex type it was registered with.
+    meshes: Box<dyn Meshes>,
+}
+
+/// What [`Pass::prepare`] looked up for [`Pass::record`].
+struct Prepared {
+    uniforms: Rc<wgpu::BindGroup>,
+    /// In drawing order.
+    batches: Vec<Batch>,
+    /// Around a virtual canvas, drawn onto the whole window first.
+    bars: Option<Batch>,
+    /// In wireframe mode.
+    wireframe: Option<Wireframe>,
+}
+
+/// The edges of the batches, drawn after them.
+struct Wireframe {
+    pipeline: Rc<wgpu::RenderPipeline>,
+    batches: Vec<EdgeBatch>,
+}
+
+struct EdgeBatch {
+    uniforms: wgpu::DynamicOffset,
+    view: Option<usize>,
+    /// Of the pass's own meshes, or the quads if `None`.
+    mesh: Option<MeshId>,
+    /// In `InterfacePass::edges`.
+    indices: Range<u32>,
+}
+
+/// Draws that share a pipeline, bind groups and geometry buffers.
+struct Batch {
+    pipeline: Rc<wgpu::RenderPipeline>,
+    /// At slot 1.
+    bind_group: Rc<wgpu::BindGroup>,
+    /// The dynamic offset of the uniforms.
+    uniforms: wgpu::DynamicOffset,
+    clip: Option<Rect>,
+    view: Option<usize>,
+    stencil: Stencil,
+    /// Writing only depth, for opaque batches with the depth pre-pass on.
+    prepass: Option<Rc<wgpu::RenderPipeline>>,
+    geometry: BatchGeometry,
+}
+
+enum BatchGeometry {
+    Quads(Range<u32>),
+    /// From the arena of the custom type, or the pass's own.
+    Mesh(Option<MaterialTypeId>, MeshId),
+    /// Instanced draws of meshes from the pass's own arena, by their
+    /// arguments in `InterfacePass::indirect`.
+    Indirect(Range<u32>),
+    /// The same, by the draws in `InterfacePass::culling`.
+    Culled(Range<u32>),
+}
+
+impl BatchGeometry {
+    /// Which geometry buffers it is drawn from.
+    fn buffers(&self) -> Option<Option<MaterialTypeId>> {
+        match self {
+            BatchGeometry::Quads(_) => None,
+            BatchGeometry::Mesh(ty, _) => Some(*ty),
+            BatchGeometry::Indirect(_) | BatchGeometry::Culled(_) => Some(None),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct InterfaceVertex {
+    pub pos: [f32; 2],
+    /// Linear, as from [`Color::to_linear`].
+    pub color: [f32; 4],
+    pub uv: [f32; 2],
+    pub index: u32,
+}
+
+unsafe impl bytemuck::Pod for InterfaceVertex {}
+unsafe impl bytemuck::Zeroable for InterfaceVertex {}
+
+/// Where and how one copy of an instanced mesh is drawn: its vertices are
+/// moved by `transform`, tinted by `color` and have their uvs mapped into
+/// `uv`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct InterfaceInstance {
+    /// The columns of a 2D affine transform: where the mesh's x and y
+    /// axes end up, then its origin.
+    pub transform: [[f32; 2]; 3],
+    /// Linear, as from [`Color::to_linear`].
+    pub color: [f32; 4],
+    /// The corners the mesh's uvs of 0 and 1 map to.
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    /// The atlas layer, replacing the vertices'.
+    pub index: u32,
+}
+
+unsafe impl bytemuck::Pod for InterfaceInstance {}
+unsafe impl bytemuck::Zeroable for InterfaceInstance {}
+
+impl InterfaceInstance {
+    /// The atlas image at `uv` stretched between `min` and `max`, when
+    /// drawn with [`InterfacePass::unit_quad`].
+    pub fn rect(uv: UvRect, min: [f32; 2], max: [f32; 2], color: Color) -> Self {
+        Self {
+            transform: [[max[0] - min[0], 0.0], [0.0, max[1] - min[1]], min],
+            color: color.to_linear(),
+            uv_min: uv.min,
+            uv_max: uv.max,
+            index: uv.layer,
+        }
+    }
+}
+
+impl Vertex for InterfaceInstance {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 8,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 16,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 24,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 40,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 56,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Uint,
+                },
+            ],
+        }
+    }
+}
+
+impl Vertex for InterfaceVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 8,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 24,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 32,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint,
+                },
+            ],
+        }
+    }
+}
+
+gpu_struct! {
+    std140;
+    /// `CameraUniforms` in `common.wgsl`.
+    #[derive(Debug)]
+    pub(super) struct VertexUniforms {
+        pub(super) camera: na::Matrix4<f32>,
+        pub(super) transform: na::Matrix4<f32>,
+    }
+}
+
+const INITIAL_VERTEX_CAPACITY: usize = 1024;
+const INITIAL_INDEX_CAPACITY: usize = 1536;
+const INITIAL_MESH_VERTEX_CAPACITY: usize = 1024;
+const INITIAL_MESH_INDEX_CAPACITY: usize = 1536;
+const INITIAL_EDGE_CAPACITY: usize = 2048;
+const INITIAL_INSTANCE_CAPACITY: usize = 1024;
+const INITIAL_INDIRECT_CAPACITY: usize = 64;
+const ATLAS_SIZE: u32 = 1024;
+const ATLAS_LAYERS: u32 = 4;
+/// Sprites `extract` transforms and culls per job.
+const CULL_CHUNK_SIZE: usize = 512;
+
+pub(super) fn logical_camera(width: f32, height: f32) -> na::Orthographic3<f32> {
+    na::Orthographic3::new(0.0, width, height, 0.0, 10.0, 100.0)
+}
+
+impl InterfacePass {
+    /// Shaders, uniforms and pipeline key, i.e. everything tied to the
+    /// device besides the atlas and geometry buffers.
+    fn device_resources(
+        ctx: &mut Context,
+        atlas_layout: LayoutId,
+    ) -> Result<(PipelineKey, PushBlock<VertexUniforms>), EngineError> {
+        let vertex_shader = ctx.pipelines.load_shader(&ctx.device, "interface.vert")?;
+        let fragment_shader = ctx.pipelines.load_variant(
+            &ctx.device,
+            ShaderVariant::new(
+                "interface.frag",
+                ShaderFeatures::TEXTURED | ShaderFeatures::VERTEX_COLOR,
+            ),
+        )?;
+
+        // Group 0 is the uniforms, at a dynamic offset per draw; the atlas
+        // brings its own layout for group 1. `validate` checks both below.
+        let uniforms = PushBlock::new(ctx, "interface/uniforms", wgpu::ShaderStage::VERTEX)?;
+
+        let pipeline_key = PipelineKey {
+            vertex_shader,
+            fragment_shader: Some(fragment_shader),
+            bind_group_layouts: vec![uniforms.layout(), atlas_layout],
+            vertex_layouts: vec![VertexLayout::from_desc(&InterfaceVertex::desc())],
+            color_states: vec![wgpu::ColorStateDescriptor {
+                format: ctx.surface_format(),
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            // The y-down camera flips winding, and 2D quads have no back.
+            cull_mode: wgpu::CullMode::None,
+            index_format: wgpu::IndexFormat::Uint32,
+            sample_count: ctx.sample_count(),
+        };
+        ctx.pipelines.validate(&pipeline_key, &ctx.bind_groups)?;
+        ctx.pipelines
+            .check_buffer::<VertexUniforms>(vertex_shader, 0, 0)?;
+
+        Ok((pipeline_key, uniforms))
+    }
+
+    /// The shaders and params layout of `ty`, as the pipeline key of the
+    /// pass with those swapped in, and the layout.
+    fn type_resources(
+        &self,
+        ctx: &mut Context,
+        ty: &MaterialType,
+        vertex_layout: &VertexLayout,
+    ) -> Result<(PipelineKey, LayoutId), EngineError> {
+        let vertex_shader = ctx.pipelines.load_source(&ctx.device, &ty.vertex_shader)?;
+        let fragment_shader = ctx
+            .pipelines
+            .load_source(&ctx.device, &ty.fragment_shader)?;
+        let layout = ctx
+            .bind_groups
+            .layout_id(&ctx.device, ty.vertex_shader.name, &ty.params);
+
+        let mut pipeline_key = self.pipeline_key.clone();
+        pipeline_key.vertex_shader = vertex_shader;
+        pipeline_key.fragment_shader = Some(fragment_shader);
+        pipeline_key.bind_group_layouts[1] = layout;
+        pipeline_key.vertex_layouts = vec![vertex_layout.clone()];
+        ctx.pipelines.validate(&pipeline_key, &ctx.bind_groups)?;
+        ctx.pipelines
+            .check_buffer::<VertexUniforms>(vertex_shader, 0, 0)?;
+
+        Ok((pipeline_key, layout))
+    }
+
+    pub fn new(ctx: &mut Context) -> Result<Self, EngineError> {
+        let logical_size = ctx.logical_size();
+
+        let atlas = TextureAtlas::new(
+            ctx,
+            "interface",
+            ATLAS_SIZE,
+            ATLAS_LAYERS,
+            SamplerDesc::LINEAR,
+        )?;
+
+        let (pipeline_key, uniforms) = Self::device_resources(ctx, atlas.layout())?;
+
+        let vertices = DynamicBuffer::new(
+            ctx,
+            "interface/vertices",
+            wgpu::BufferUsage::VERTEX,
+            INITIAL_VERTEX_CAPACITY,
+        )?;
+        let indices = DynamicBuffer::new(
+            ctx,
+            "interface/indices",
+            wgpu::BufferUsage::INDEX,
+            INITIAL_INDEX_CAPACITY,
+        )?;
+        let mut meshes = MeshArena::new(
+            ctx,
+            "interface",
+            INITIAL_MESH_VERTEX_CAPACITY,
+            INITIAL_MESH_INDEX_CAPACITY,
+        )?;
+        let white = Color::WHITE.to_linear();
+        let unit_quad = meshes.insert(
+            &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]].map(|corner| InterfaceVertex {
+                pos: corner,
+                color: white,
+                uv: corner,
+                index: 0,
+            }),
+            &[0, 1, 3, 1, 2, 3],
+        );
+        let instances = InstanceBuffer::new(ctx, "interface/instances", INITIAL_INSTANCE_CAPACITY)?;
+        let indirect = IndirectBuffer::new(ctx, "interface/indirect", INITIAL_INDIRECT_CAPACITY)?;
+        let culling = FrustumCulling::new(ctx, "interface/culling", INITIAL_INSTANCE_CAPACITY)?;
+        let edges = DynamicBuffer::new(
+            ctx,
+            "interface/edges",
+            wgpu::BufferUsage::INDEX,
+            INITIAL_EDGE_CAPACITY,
+        )?;
+
+        let mut materials = Materials::new();
+        let atlas_material = materials.add(Material::atlas());
+
+        Ok(Self {
+            pipeline_key,
+            window_size: [logical_size.width, logical_size.height],
+            virtual_size: None,
+            palette: Palette::default(),
+            camera: logical_camera(logical_size.width, logical_size.height),
+            transform: na::Matrix4::identity(),
+            uniforms,
+            atlas,
+            materials,
+            atlas_material,
+            material: atlas_material,
+            clip: None,
+            layer: Layer::HUD,
+            masks: vec![],
+            mask_start: None,
+            stencil_used: false,
+            views: vec![],
+            view: None,
+            draws: vec![],
+            meshes,
+            unit_quad,
+            instances,
+            indirect,
+            culling,
+            types: vec![],
+            validated: HashSet::new(),
+            depth_prepass: false,
+            wireframe: false,
+            edges,
+            prepared: None,
+            vertices,
+            indices,
+        })
+    }
+
+    pub fn update(&mut self) {}
+
+    /// The colors whatever draws into the pass picks from.
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn atlas_mut(&mut self) -> &mut TextureAtlas {
+        &mut self.atlas
+    }
+
+    /// Adds a material to draw with. It is checked against the shaders the
+    /// first time something is drawn with it.
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materials.add(material)
+    }
+
+    pub fn materials(&self) -> &Materials {
+        &self.materials
+    }
+
+    pub fn materials_mut(&mut self) -> &mut Materials {
+        &mut self.materials
+    }
+
+    /// The material quads are drawn with by default.
+    pub fn atlas_material(&self) -> MaterialId {
+        self.atlas_material
+    }
+
+    /// Registers a material type drawing meshes of `V`, checking its
+    /// shaders against the vertex format and the uniforms the pass binds.
+    pub fn register_material_type<V: Vertex>(
+        &mut self,
+        ctx: &mut Context,
+        ty: MaterialType,
+    ) -> Result<MaterialTypeId, EngineError> {
+        let vertex_layout = VertexLayout::from_desc(&V::desc());
+        let (pipeline_key, layout) = self.type_resources(ctx, &ty, &vertex_layout)?;
+        let meshes = MeshArena::<V>::new(
+            ctx,
+            ty.vertex_shader.name,
+            INITIAL_MESH_VERTEX_CAPACITY,
+            INITIAL_MESH_INDEX_CAPACITY,
+        )?;
+        self.types.push(CustomType {
+            desc: ty,
+            vertex_layout,
+            layout,
+            pipeline_key,
+            meshes: Box::new(meshes),
+        });
+        Ok(MaterialTypeId(self.types.len() - 1))
+    }
+
+    /// The layout to make bind groups of params of `ty` with.
+    pub fn type_layout(&self, ty: MaterialTypeId) -> LayoutId {
+        self.types[ty.0].layout
+    }
+
+    /// Meshes kept across frames for materials of `ty`, which must have
+    /// been registered with `V`.
+    pub fn type_meshes_mut<V: Vertex>(&mut self, ty: MaterialTypeId) -> &mut MeshArena<V> {
+        self.types[ty.0]
+            .meshes
+            .as_any_mut()
+            .downcast_mut()
+            .expect("material type registered with another vertex type")
+    }
+
+    /// Draws the quads queued from now on with `material`, until the next
+    /// call or [`InterfacePass::clear`]. Materials of custom types only
+    /// draw meshes.
+    pub fn set_material(&mut self, material: MaterialId) {
+        assert!(
+            matches!(
+                self.materials.get(material).shader,
+                MaterialShader::Builtin(_)
+            ),
+            "quads can't be drawn with a custom material type"
+        );
+        self.material = material;
+    }
+
+    pub fn material(&self) -> MaterialId {
+        self.material
+    }
+
+    /// Whether the atlas stores its images premultiplied and the atlas
+    /// material draws them with [`Blend::PREMULTIPLIED`], so overlapping
+    /// translucent quads and antialiased edges don't darken. Other
+    /// materials, including copies of the atlas one made before, keep
+    /// their shaders and blending.
+    pub fn set_premultiplied(
+        &mut self,
+        ctx: &mut Context,
+        premultiplied: bool,
+    ) -> Result<(), gpu_mem::BudgetExceeded> {
+        self.atlas.set_premultiplied(ctx, premultiplied)?;
+        *self.materials.get_mut(self.atlas_material) = if premultiplied {
+            Material::atlas_premultiplied()
+        } else {
+            Material::atlas()
+        };
+        Ok(())
+    }
+
+    /// Draws the quads queued from now on with the current material, but
+    /// blended with `blend`, until the material changes again.
+    pub fn set_blend(&mut self, blend: Blend) {
+        self.material = self.materials.with_blend(self.material, blend);
+    }
+
+    /// Meshes kept across frames, for [`InterfacePass::draw_mesh`].
+    pub fn meshes_mut(&mut self) -> &mut MeshArena<InterfaceVertex> {
+        &mut self.meshes
+    }
+
+    /// Queues `mesh` from [`InterfacePass::meshes_mut`], or from
+    /// [`InterfacePass::type_meshes_mut`] if `material` is of a custom
+    /// type, drawn with `material` and `transform` in place of the pass
+    /// or view transform.
+    pub fn draw_mesh(&mut self, mesh: MeshId, material: MaterialId, transform: na::Matrix4<f32>) {
+        self.draws.push(Draw {
+            material,
+            bind_group: None,
+            clip: self.clip,
+            layer: self.layer,
+            view: self.view,
+            stencil: self.stencil(),
+            geometry: Geometry::Mesh(mesh, transform),
+        });
+    }
+
+    /// Instances for [`InterfacePass::draw_instances`], dropped by
+    /// [`InterfacePass::clear`].
+    pub fn instances_mut(&mut self) -> &mut InstanceBuffer<InterfaceInstance> {
+        &mut self.instances
+    }
+
+    /// The quad in [`InterfacePass::meshes_mut`] that
+    /// [`InterfaceInstance::rect`] places.
+    pub fn unit_quad(&self) -> MeshId {
+        self.unit_quad
+    }
+
+    /// Queues `instances` from [`InterfacePass::instances_mut`] of `mesh`
+    /// from [`InterfacePass::meshes_mut`], in one draw with the current
+    /// material and the pass or view transform. Consecutive instanced
+    /// draws with the same material and clip are recorded back to back,
+    /// from an indirect buffer.
+    pub fn draw_instances(&mut self, mesh: MeshId, instances: Range<u32>) {
+        self.draws.push(Draw {
+            material: self.material,
+            bind_group: None,
+            clip: self.clip,
+            layer: self.layer,
+            view: self.view,
+            stencil: self.stencil(),
+            geometry: Geometry::Instances(mesh, instances),
+        });
+    }
+
+    /// Queues `instances` of `mesh` from [`InterfacePass::meshes_mut`] like
+    /// [`InterfacePass::draw_instances`], except that each is only drawn if
+    /// its bounds, in the coordinates it is placed in, are in view. That
+    /// is tested on the GPU, so large worlds can queue everything they
+    /// have without culling it themselves; the clip isn't taken into
+    /// account.
+    pub fn draw_culled(&mut self, mesh: MeshId, instances: &[(InterfaceInstance, Rect)]) {
+        let args = match self.meshes.indirect_args(mesh, 0..0) {
+            Some(args) => args,
+            None => return,
+        };
+        // Frustum 0 is the pass camera's, then one per view.
+        let frustum = self.view.map_or(0, |view| view as u32 + 1);
+        let draw = self.culling.begin_draw(args, frustum);
+        for (instance, bounds) in instances {
+            let [min, max] = [bounds.min, bounds.max].map(|[x, y]| na::Point3::new(x, y, 0.0));
+            self.culling.push(*instance, min, max);
+        }
+        self.draws.push(Draw {
+            material: self.material,
+            bind_group: None,
+            clip: self.clip,
+            layer: self.layer,
+            view: self.view,
+            stencil: self.stencil(),
+            geometry: Geometry::Culled(draw),
+        });
+    }
+
+    /// Clips what is queued from now on to `clip`, in interface coordinates
+    /// regardless of the pass transform, or stops clipping. Nested
+    /// containers can intersect their bounds with [`InterfacePass::clip`].
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+
+    pub fn clip(&self) -> Option<Rect> {
+        self.clip
+    }
+
+    /// Paints what is queued from now on in `layer`, [`Layer::HUD`] until
+    /// set. A mask and what it masks are drawn in the order they were
+    /// queued, so they have to be in one layer.
+    pub fn set_layer(&mut self, layer: Layer) {
+        self.layer = layer;
+    }
+
+    pub fn layer(&self) -> Layer {
+        self.layer
+    }
+
+    /// Starts a mask: what is queued until [`InterfacePass::end_mask`]
+    /// marks its shape, within the current mask if any, rather than being
+    /// drawn.
+    pub fn begin_mask(&mut self) {
+        assert!(self.mask_start.is_none(), "a mask is already being queued");
+        self.mask_start = Some(self.draws.len());
+    }
+
+    /// Draws what is queued from now on only inside the mask begun last.
+    pub fn end_mask(&mut self) {
+        let start = self.mask_start.take().expect("no mask is being queued");
+        self.masks.push(self.draws[start..].to_vec());
+    }
+
+    /// Removes the innermost mask, by queuing its shape again to unmark
+    /// it, so what is queued from now on is drawn inside the one before,
+    /// if any.
+    pub fn unmask(&mut self) {
+        let mask = self.masks.pop().expect("no mask to remove");
+        let stencil = Stencil::Remove(self.masks.len() as u32);
+        self.draws
+            .extend(mask.into_iter().map(|draw| Draw { stencil, ..draw }));
+    }
+
+    /// How draws queued now use the stencil.
+    fn stencil(&self) -> Stencil {
+        let level = self.masks.len() as u32;
+        if self.mask_start.is_some() {
+            Stencil::Add(level)
+        } else if level > 0 {
+            Stencil::Inside(level)
+        } else {
+            Stencil::Off
+        }
+    }
+
+    /// Draws what is queued from now on into `view`, or back onto the
+    /// interface itself. Clips stay in interface coordinates, and are
+    /// intersected with the view's rect.
+    pub fn set_view(&mut self, view: Option<View>) {
+        self.view = view.map(|view| {
+            self.views.push(view);
+            self.views.len() - 1
+        });
+    }
+
+    /// The view set with [`InterfacePass::set_view`], if any.
+    pub fn view(&self) -> Option<&View> {
+        self.view.map(|view| &self.views[view])
+    }
+
+    /// Drops this frame's draws, instances, masks and views and resets the
+    /// material to the atlas one, the clip to none and the layer to the HUD.
+    /// Meshes stay in the arena.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.instances.clear();
+        self.culling.clear();
+        self.draws.clear();
+        self.views.clear();
+        self.material = self.atlas_material;
+        self.clip = None;
+        self.layer = Layer::HUD;
+        self.masks.clear();
+        self.mask_start = None;
+        self.view = None;
+    }
+
+    /// Queues a quad filled with `color` between `min` and `max`.
+    pub fn draw_rect(&mut self, min: [f32; 2], max: [f32; 2], color: Color) {
+        let white = self.atlas.white_uv();
+        let uv = UvRect {
+            layer: 0,
+            min: white,
+            max: white,
+        };
+        self.draw_image(uv, min, max, color);
+    }
+
+    /// Queues every entity with a [`Sprite`] and a [`GlobalTransform`],
+    /// drawn `alpha` of the way between its last two updates. Sprites
+    /// entirely outside the interface, or the current view, are skipped.
+    ///
+    /// Sprites are transformed here rather than through the transform
+    /// uniform, so they all stay in one draw. Large worlds are transformed
+    /// and culled on the [`jobs`] pool.
+    pub fn extract(&mut self, world: &World, alpha: f32) {
+        let white = self.atlas.white_uv();
+        let uv = UvRect {
+            layer: 0,
+            min: white,
+            max: white,
+        };
+        let sprites: Vec<(Sprite, GlobalTransform)> = world
+            .query2::<Sprite, GlobalTransform>()
+            .map(|(_, &sprite, &global)| (sprite, global))
+            .collect();
+        let (view, [view_width, view_height]) = match self.view() {
+            Some(view) => (view.transform, view.size()),
+            None => (self.transform, self.logical_size()),
+        };
+        let visible = jobs::map_chunks(&sprites, CULL_CHUNK_SIZE, |chunk| {
+            chunk
+                .iter()
+                .filter_map(|(sprite, global)| {
+                    let [width, height] = sprite.size;
+                    let corners = [[0.0, 0.0], [width, 0.0], [width, height], [0.0, height]]
+                        .map(|corner| global.transform_point(corner, alpha));
+                    let mut min = [f32::INFINITY; 2];
+                    let mut max = [f32::NEG_INFINITY; 2];
+                    for corner in &corners {
+                        let point =
+                            view.transform_point(&na::Point3::new(corner[0], corner[1], 0.0));
+                        min = [min[0].min(point.x), min[1].min(point.y)];
+                        max = [max[0].max(point.x), max[1].max(point.y)];
+                    }
+                    let inside = max[0] >= 0.0
+                        && max[1] >= 0.0
+                        && min[0] <= view_width
+                        && min[1] <= view_height;
+                    Some((corners, sprite.color)).filter(|_| inside)
+                })
+                .collect()
+        });
+        for (corners, color) in visible {
+            self.draw_quad(uv, corners, color);
+        }
+    }
+
+    /// Queues a quad showing the atlas image at `uv` between `min` and `max`.
+    pub fn draw_image(&mut self, uv: UvRect, min: [f32; 2], max: [f32; 2], color: Color) {
+        let corners = [
+            [min[0], min[1]],
+            [max[0], min[1]],
+            [max[0], max[1]],
+            [min[0], max[1]],
+        ];
+        self.draw_quad(uv, corners, color);
+    }
+
+    /// Queues the atlas image at `uv` between `min` and `max` as a nine-slice:
+    /// `border` is the left, top, right and bottom edges of the image in
+    /// texels, drawn at that size in interface units, so only the middle
+    /// stretches and the corners keep their shape. Borders wider or taller
+    /// than the quad are shrunk to meet in the middle.
+    pub fn draw_nine_slice(
+        &mut self,
+        uv: UvRect,
+        border: [f32; 4],
+        min: [f32; 2],
+        max: [f32; 2],
+        color: Color,
+    ) {
+        let texel = 1.0 / self.atlas.size() as f32;
+        // Where the slices start and end along an axis, on screen and in
+        // the atlas.
+        let edges = |axis: usize| {
+            let [start, end] = [border[axis], border[axis + 2]];
+            let length = (max[axis] - min[axis]).max(0.0);
+            // NaN, for no borders and no length, takes the 1.
+            let fit = (length / (start + end)).min(1.0);
+            let [start, end] = [start * fit, end * fit];
+            (
+                [min[axis], min[axis] + start, max[axis] - end, max[axis]],
+                [
+                    uv.min[axis],
+                    uv.min[axis] + border[axis] * texel,
+                    uv.max[axis] - border[axis + 2] * texel,
+                    uv.max[axis],
+                ],
+            )
+        };
+        let (xs, us) = edges(0);
+        let (ys, vs) = edges(1);
+        for row in 0..3 {
+            for column in 0..3 {
+                if xs[column] >= xs[column + 1] || ys[row] >= ys[row + 1] {
+                    continue;
+                }
+                let slice = UvRect {
+                    layer: uv.layer,
+                    min: [us[column], vs[row]],
+                    max: [us[column + 1], vs[row + 1]],
+                };
+                self.draw_image(
+                    slice,
+                    [xs[column], ys[row]],
+                    [xs[column + 1], ys[row + 1]],
+                    color,
+                );
+            }
+        }
+    }
+
+    /// Queues the atlas image at `uv` mapped onto `corners`, clockwise from
+    /// the one showing its top-left.
+    pub fn draw_quad(&mut self, uv: UvRect, corners: [[f32; 2]; 4], color: Color) {
+        let base = self.vertices.len() as u32;
+        let uvs = [
+            uv.min,
+            [uv.max[0], uv.min[1]],
+            uv.max,
+            [uv.min[0], uv.max[1]],
+        ];
+        for (&pos, &tex) in corners.iter().zip(&uvs) {
+            self.vertices.push(InterfaceVertex {
+                pos,
+                color: color.to_linear(),
+                uv: tex,
+                index: uv.layer,
+            });
+        }
+        self.push_quad(base, None);
+    }
+
+    /// Queues `path` filled with `color`; see [`shapes`](crate::shapes) for
+    /// what fills.
+    pub fn fill_path(&mut self, path: &Path, color: Color) {
+        self.draw_tessellation(&path.fill(), color);
+    }
+
+    /// Queues a line `width` wide along `path`, in `color`.
+    pub fn stroke_path(&mut self, path: &Path, width: f32, color: Color) {
+        self.draw_tessellation(&path.stroke(width), color);
+    }
+
+    /// Queues the triangles of `tessellation`, filled with `color`.
+    pub fn draw_tessellation(&mut self, tessellation: &Tessellation, color: Color) {
+        if tessellation.indices.is_empty() {
+            return;
+        }
+        let base = self.vertices.len() as u32;
+        let white = self.atlas.white_uv();
+        let color = color.to_linear();
+        for &pos in &tessellation.positions {
+            self.vertices.push(InterfaceVertex {
+                pos,
+                color,
+                uv: white,
+                index: 0,
+            });
+        }
+        let indices: Vec<u32> = tessellation.indices.iter().map(|&i| base + i).collect();
+        self.push_triangles(&indices, None);
+    }
+
+    /// Queues a quad showing `target` between `min` and `max`, with the
+    /// current material but the target in place of what it binds.
+    pub fn draw_target(
+        &mut self,
+        ctx: &mut Context,
+        target: &RenderTarget,
+        min: [f32; 2],
+        max: [f32; 2],
+        color: Color,
+    ) {
+        let base = self.vertices.len() as u32;
+        for &(pos, uv) in &[
+            ([min[0], min[1]], [0.0, 0.0]),
+            ([max[0], min[1]], [1.0, 0.0]),
+            ([max[0], max[1]], [1.0, 1.0]),
+            ([min[0], max[1]], [0.0, 1.0]),
+        ] {
+            self.vertices.push(InterfaceVertex {
+                pos,
+                color: color.to_linear(),
+                uv,
+                index: 0,
+            });
+        }
+        let layout = self.params_layout(self.material);
+        self.push_quad(base, Some(target.bind_group(ctx, layout)));
+    }
+
+    /// Queues the indices of the quad whose vertices start at `base`,
+    /// extending the last draw if it has the same material.
+    fn push_quad(&mut self, base: u32, bind_group: Option<Rc<wgpu::BindGroup>>) {
+        self.push_triangles(
+            &[base, base + 1, base + 3, base + 1, base + 2, base + 3],
+            bind_group,
+        );
+    }
+
+    /// Queues `indices`, three per triangle, extending the last draw if it
+    /// has the same material.
+    fn push_triangles(&mut self, indices: &[u32], bind_group: Option<Rc<wgpu::BindGroup>>) {
+        let start = self.indices.len() as u32;
+        self.indices.extend_from_slice(indices);
+        let end = self.indices.len() as u32;
+        let stencil = self.stencil();
+        if bind_group.is_none() {
+            if let Some(Draw {
+                material,
+                bind_group: None,
+                clip,
+                layer,
+                view,
+                stencil: last_stencil,
+                geometry: Geometry::Quads(range),
+            }) = self.draws.last_mut()
+            {
+                if *material == self.material
+                    && *clip == self.clip
+                    && *layer == self.layer
+                    && *view == self.view
+                    && *last_stencil == stencil
+                    && range.end == start
+                {
+                    range.end = end;
+                    return;
+                }
+            }
+        }
+        self.draws.push(Draw {
+            material: self.material,
+            bind_group,
+            clip: self.clip,
+            layer: self.layer,
+            view: self.view,
+            stencil,
+            geometry: Geometry::Quads(start..end),
+        });
+    }
+
+    /// Whether geometry is depth-tested against `Context::depth`, for
+    /// meshes placed in depth. Off by default. Quads are at depth 0 and
+    /// always pass, so they are painted in submission order either way.
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.pipeline_key.depth_stencil_state = if enabled {
+            Some(render_target::depth_stencil_state())
+        } else {
+            None
+        };
+    }
+
+    /// Whether opaque draws are drawn into depth alone first, then shaded
+    /// only where they are in front, with the depth test on. Saves
+    /// shading overdrawn pixels at the cost of drawing the geometry twice.
+    pub fn set_depth_prepass(&mut self, enabled: bool) {
+        self.depth_prepass = enabled;
+    }
+
+    pub fn depth_prepass(&self) -> bool {
+        self.depth_prepass
+    }
+
+    /// Whether triangle edges are drawn over everything, to inspect
+    /// tessellation.
+    pub fn set_wireframe(&mut self, enabled: bool) {
+        self.wireframe = enabled;
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Fits the camera to a `width` by `height` logical-pixel window. Does
+    /// nothing if it already is, so it can be called every frame with
+    /// `Context::logical_size`. Replaces a camera set with `set_camera`.
+    pub fn set_logical_size(&mut self, width: f32, height: f32) {
+        if self.window_size != [width, height] {
+            self.window_size = [width, height];
+            if self.virtual_size.is_none() {
+                self.set_camera(logical_camera(width, height));
+            }
+        }
+    }
+
+    /// The size of the interface: the virtual one if set, otherwise the
+    /// window's.
+    pub fn logical_size(&self) -> [f32; 2] {
+        self.virtual_size.unwrap_or(self.window_size)
+    }
+
+    /// Lays the interface out on a canvas of `size`, e.g. 1920 by 1080,
+    /// letterboxed in the window, or on the window itself. Replaces a
+    /// camera set with `set_camera`.
+    pub fn set_virtual_size(&mut self, size: Option<[f32; 2]>) {
+        self.virtual_size = size;
+        let [width, height] = self.logical_size();
+        self.set_camera(logical_camera(width, height));
+    }
+
+    pub fn virtual_size(&self) -> Option<[f32; 2]> {
+        self.virtual_size
+    }
+
+    /// Where the interface shows in the window, in the window's logical
+    /// pixels.
+    pub fn viewport(&self) -> Rect {
+        let [x, y, width, height] = fit(self.logical_size(), self.window_size);
+        Rect::new([x, y], [x + width, y + height])
+    }
+
+    /// The interface coordinates of `point` in the window's logical pixels,
+    /// e.g. the cursor position.
+    pub fn to_interface(&self, point: [f32; 2]) -> [f32; 2] {
+        let viewport = self.viewport();
+        let [width, height] = self.logical_size();
+        [
+            (point[0] - viewport.min[0]) * width / (viewport.max[0] - viewport.min[0]),
+            (point[1] - viewport.min[1]) * height / (viewport.max[1] - viewport.min[1]),
+        ]
+    }
+
+    pub fn camera(&self) -> &na::Orthographic3<f32> {
+        &self.camera
+    }
+
+    pub fn set_camera(&mut self, camera: na::Orthographic3<f32>) {
+        self.camera = camera;
+    }
+
+    pub fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: na::Matrix4<f32>) {
+        self.transform = transform;
+    }
+
+    /// The layout `material` binds at group 1 with.
+    fn params_layout(&self, material: MaterialId) -> LayoutId {
+        match &self.materials.get(material).params {
+            MaterialParams::Atlas => self.atlas.layout(),
+            MaterialParams::BindGroup { layout, .. } => *layout,
+        }
+    }
+
+    /// The depth-stencil state of every pipeline this frame: the depth
+    /// test's if on, otherwise one for the stencil alone if anything is
+    /// masked.
+    fn depth_stencil_state(&self) -> Option<wgpu::DepthStencilStateDescriptor> {
+        match &self.pipeline_key.depth_stencil_state {
+            Some(state) => Some(state.clone()),
+            None if self.stencil_used => Some(render_target::stencil_only_state()),
+            None => None,
+        }
+    }
+
+    /// The pipeline drawing with `material` using the stencil as
+    /// `stencil` says, checked against its shaders the first time.
+    fn material_pipeline(
+        &mut self,
+        ctx: &mut Context,
+        material: MaterialId,
+        stencil: Stencil,
+        instanced: bool,
+    ) -> Result<Rc<wgpu::RenderPipeline>, EngineError> {
+        let key = self.material_key(ctx, material, stencil, instanced)?;
+        self.pipeline(ctx, key)
+    }
+
+    /// The depth-only and shading pipelines of opaque `material` under
+    /// the depth pre-pass. The second tests against the depth the first
+    /// wrote, without writing it again.
+    fn prepass_pipelines(
+        &mut self,
+        ctx: &mut Context,
+        material: MaterialId,
+        instanced: bool,
+    ) -> Result<(Rc<wgpu::RenderPipeline>, Rc<wgpu::RenderPipeline>), EngineError> {
+        let key = self.material_key(ctx, material, Stencil::Off, instanced)?;
+        let mut depth = key.clone();
+        depth.color_states[0].write_mask = wgpu::ColorWrite::empty();
+        let mut shaded = key;
+        if let Some(state) = &mut shaded.depth_stencil_state {
+            state.depth_write_enabled = false;
+        }
+        Ok((self.pipeline(ctx, depth)?, self.pipeline(ctx, shaded)?))
+    }
+
+    /// The pipeline of `key`, checked against its shaders the first time.
+    fn pipeline(
+        &mut self,
+        ctx: &mut Context,
+        key: PipelineKey,
+    ) -> Result<Rc<wgpu::RenderPipeline>, EngineError> {
+        if !self.validated.contains(&key) {
+            ctx.pipelines.validate(&key, &ctx.bind_groups)?;
+            self.validated.insert(key.clone());
+        }
+        Ok(ctx.pipelines.pipeline(&ctx.device, &ctx.bind_groups, &key))
+    }
+
+    /// The key of the pipeline drawing with `material` using the stencil
+    /// as `stencil` says, and instances if `instanced`.
+    fn material_key(
+        &mut self,
+        ctx: &mut Context,
+        material: MaterialId,
+        stencil: Stencil,
+        instanced: bool,
+    ) -> Result<PipelineKey, EngineError> {
+        let depth_stencil_state = self.depth_stencil_state();
+        let layout = self.params_layout(material);
+        let material = self.materials.get(material);
+        let mut key = match material.shader {
+            MaterialShader::Builtin(features) => {
+                let mut key = self.pipeline_key.clone();
+                key.fragment_shader =
+                    Some(ctx.pipelines.load_variant(
+                        &ctx.device,
+                        ShaderVariant::new("interface.frag", features),
+                    )?);
+                if instanced {
+                    key.vertex_shader = ctx.pipelines.load_variant(
+                        &ctx.device,
+                        ShaderVariant::new("interface.vert", ShaderFeatures::INSTANCED),
+                    )?;
+                    key.vertex_layouts
+                        .push(VertexLayout::from_desc(&InterfaceInstance::desc()));
+                }
+                key
+            }
+            MaterialShader::Custom(ty) => {
+                let mut key = self.types[ty.0].pipeline_key.clone();
+                key.sample_count = self.pipeline_key.sample_count;
+                key
+            }
+        };
+        key.bind_group_layouts[1] = layout;
+        key.color_states[0].color_blend = material.blend.color.clone();
+        key.color_states[0].alpha_blend = material.blend.alpha.clone();
+        if !stencil.draws() {
+            key.color_states[0].write_mask = wgpu::ColorWrite::empty();
+        }
+        key.depth_stencil_state = depth_stencil_state;
+        if let Some(state) = &mut key.depth_stencil_state {
+            stencil.apply(state);
+        }
+        Ok(key)
+    }
+
+    /// Queues black quads covering the window around the viewport, with
+    /// a camera of the window's own.
+    fn queue_bars(
+        &mut self,
+        ctx: &mut Context,
+        atlas: Rc<wgpu::BindGroup>,
+    ) -> Result<Batch, EngineError> {
+        let [width, height] = self.window_size;
+        let viewport = self.viewport();
+        let white = self.atlas.white_uv();
+        let start = self.indices.len() as u32;
+        for (min, max) in [
+            ([0.0, 0.0], [viewport.min[0], height]),
+            ([viewport.max[0], 0.0], [width, height]),
+            ([0.0, 0.0], [width, viewport.min[1]]),
+            ([0.0, viewport.max[1]], [width, height]),
+        ] {
+            if max[0] <= min[0] || max[1] <= min[1] {
+                continue;
+            }
+            let base = self.vertices.len() as u32;
+            for pos in [min, [max[0], min[1]], max, [min[0], max[1]]] {
+                self.vertices.push(InterfaceVertex {
+                    pos,
+                    color: Color::BLACK.to_linear(),
+                    uv: white,
+                    index: 0,
+                });
+            }
+            self.indices.extend_from_slice(&[
+                base,
+                base + 1,
+                base + 3,
+                base + 1,
+                base + 2,
+                base + 3,
+            ]);
+        }
+        let uniforms = self.uniforms.push(VertexUniforms {
+            camera: logical_camera(width, height).to_homogeneous(),
+            transform: na::Matrix4::identity(),
+        });
+        Ok(Batch {
+            pipeline: self.material_pipeline(ctx, self.atlas_material, Stencil::Off, false)?,
+            bind_group: atlas,
+            uniforms,
+            clip: None,
+            view: None,
+            stencil: Stencil::Off,
+            prepass: None,
+            geometry: BatchGeometry::Quads(start..self.indices.len() as u32),
+        })
+    }
+
+    /// The pipeline drawing edges on top of everything in white.
+    fn wireframe_pipeline(
+        &mut self,
+        ctx: &mut Context,
+    ) -> Result<Rc<wgpu::RenderPipeline>, EngineError> {
+        let mut key = self.pipeline_key.clone();
+        key.fragment_shader = Some(ctx.pipelines.load_variant(
+            &ctx.device,
+            ShaderVariant::new("interface.frag", ShaderFeatures::NONE),
+        )?);
+        key.bind_group_layouts.truncate(1);
+        key.depth_stencil_state = self.depth_stencil_state();
+        key.primitive_topology = wgpu::PrimitiveTopology::LineList;
+        // The depth state must match the attachment's, so the test is
+        // kept but always passes.
+        if let Some(state) = &mut key.depth_stencil_state {
+            state.depth_write_enabled = false;
+            state.depth_compare = wgpu::CompareFunction::Always;
+        }
+        self.pipeline(ctx, key)
+    }
+
+    /// Queues the edges of the batches drawn from the pass's own buffers,
+    /// as line lists.
+    fn edge_batches(&mut self, batches: &[Batch]) -> Vec<EdgeBatch> {
+        self.edges.clear();
+        let mut edges = vec![];
+        for batch in batches {
+            let (mesh, triangles) = match &batch.geometry {
+                BatchGeometry::Quads(range) => (
+                    None,
+                    &self.indices.as_slice()[range.start as usize..range.end as usize],
+                ),
+                BatchGeometry::Mesh(None, mesh) => (Some(*mesh), self.meshes.indices(*mesh)),
+                BatchGeometry::Mesh(Some(_), _)
+                | BatchGeometry::Indirect(_)
+                | BatchGeometry::Culled(_) => continue,
+            };
+            let start = self.edges.len() as u32;
+            for triangle in triangles.chunks_exact(3) {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                self.edges.extend_from_slice(&[a, b, b, c, c, a]);
+            }
+            edges.push(EdgeBatch {
+                uniforms: batch.uniforms,
+                view: batch.view,
+                mesh,
+                indices: start..self.edges.len() as u32,
+            });
+        }
+        edges
+    }
+
+    /// The arena meshes of `ty`, or of the pass itself, are in.
+    fn meshes(&self, ty: Option<MaterialTypeId>) -> &dyn Meshes {
+        match ty {
+            Some(ty) => &*self.types[ty.0].meshes,
+            None => &self.meshes,
+        }
+    }
+
+    /// Streams the uniforms and the current geometry into their GPU
+    /// buffers.
+    fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError> {
+        let _scope = profiler::scope("interface/upload");
+        self.uniforms.upload(ctx, encoder)?;
+        self.vertices.upload(ctx, encoder)?;
+        self.indices.upload(ctx, encoder)?;
+        self.meshes.upload(ctx, encoder)?;
+        self.edges.upload(ctx, encoder)?;
+        self.instances.upload(ctx, encoder)?;
+        self.indirect.upload(ctx, encoder)?;
+        for ty in &mut self.types {
+            ty.meshes.upload(ctx, encoder)?;
+        }
+        Ok(())
+    }
+}
+
+/// The largest rectangle of the aspect of `size` that fits in `into`,
+/// centred, as x, y, width and height.
+fn fit(size: [f32; 2], into: [f32; 2]) -> [f32; 4] {
+    let scale = (into[0] / size[0]).min(into[1] / size[1]);
+    let [width, height] = [size[0] * scale, size[1] * scale];
+    [
+        (into[0] - width) / 2.0,
+        (into[1] - height) / 2.0,
+        width,
+        height,
+    ]
+}
+
+/// `rect` in a view of `view_size` shown at `viewport`, as x, y, width and
+/// height in pixels like `viewport`.
+fn pixel_rect(rect: &Rect, view_size: [f32; 2], viewport: [f32; 4]) -> [f32; 4] {
+    let pixel =
+        |value: f32, axis: usize| viewport[axis] + value * viewport[axis + 2] / view_size[axis];
+    let min = [pixel(rect.min[0], 0), pixel(rect.min[1], 1)];
+    let max = [pixel(rect.max[0], 0), pixel(rect.max[1], 1)];
+    [min[0], min[1], max[0] - min[0], max[1] - min[1]]
+}
+
+/// The scissor rectangle, as x, y, width and height in pixels, covering
+/// `clip` in a view of `view_size` shown at `viewport`, in pixels too.
+fn scissor_rect(clip: &Rect, view_size: [f32; 2], viewport: [f32; 4]) -> [u32; 4] {
+    let [x, y, width, height] = pixel_rect(clip, view_size, viewport);
+    let clamp =
+        |value: f32, axis: usize| value.clamp(viewport[axis], viewport[axis] + viewport[axis + 2]);
+    let min = [clamp(x, 0).floor(), clamp(y, 1).floor()];
+    let max = [clamp(x + width, 0).ceil(), clamp(y + height, 1).ceil()];
+    [
+        min[0] as u32,
+        min[1] as u32,
+        (max[0] - min[0]).max(0.0) as u32,
+        (max[1] - min[1]).max(0.0) as u32,
+    ]
+}
+
+impl Pass for InterfacePass {
+    fn name(&self) -> &'static str {
+        "interface"
+    }
+
+    fn prepare(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        _attachments: &Attachments,
+    ) -> Result<(), EngineError> {
+        let _scope = validation::scope("interface");
+        let _profile = profiler::scope("interface/prepare");
+        self.pipeline_key.sample_count = ctx.sample_count();
+        self.stencil_used = self.draws.iter().any(|draw| draw.stencil != Stencil::Off);
+
+        // Draws are painted by layer, and within one in the order they
+        // were queued, which the stable sort keeps. That holds under the
+        // depth test too: quads all sit at depth 0 and pass it whatever
+        // they overlap, so reordering them would swap what is on top.
+        let mut order: Vec<usize> = (0..self.draws.len()).collect();
+        let depth_test = self.pipeline_key.depth_stencil_state.is_some();
+        let draws = &self.draws;
+        order.sort_by_key(|&index| draws[index].layer);
+
+        self.uniforms.clear();
+        let quad_uniforms = self.uniforms.push(VertexUniforms {
+            camera: self.camera.to_homogeneous(),
+            transform: self.transform,
+        });
+        let (views, uniforms) = (&self.views, &mut self.uniforms);
+        let view_uniforms: Vec<wgpu::DynamicOffset> = views
+            .iter()
+            .map(|view| {
+                uniforms.push(VertexUniforms {
+                    camera: view.camera.to_homogeneous(),
+                    transform: view.transform,
+                })
+            })
+            .collect();
+        let atlas = self.atlas.bind_group(ctx);
+        let mut pipelines: HashMap<(MaterialId, Stencil, bool), Rc<wgpu::RenderPipeline>> =
+            HashMap::new();
+        let mut prepass_pipelines: HashMap<(MaterialId, bool), (Rc<wgpu::RenderPipeline>, _)> =
+            HashMap::new();
+        let mut batches: Vec<Batch> = vec![];
+        self.indirect.clear();
+        for index in order {
+            let draw = &self.draws[index];
+            let (material, clip, view, stencil) =
+                (draw.material, draw.clip, draw.view, draw.stencil);
+            let geometry = draw.geometry.clone();
+            let instanced = matches!(geometry, Geometry::Instances(..) | Geometry::Culled(_));
+            let bind_group = match (&draw.bind_group, &self.materials.get(material).params) {
+                (Some(bind_group), _) => bind_group.clone(),
+                (None, MaterialParams::Atlas) => atlas.clone(),
+                (None, MaterialParams::BindGroup { bind_group, .. }) => bind_group.clone(),
+            };
+            let prepassed = self.depth_prepass
+                && depth_test
+                && stencil == Stencil::Off
+                && self.materials.get(material).blend.is_opaque();
+            let (prepass, pipeline) = if prepassed {
+                let (depth, shaded) = match prepass_pipelines.get(&(material, instanced)) {
+                    Some(pipelines) => pipelines.clone(),
+                    None => {
+                        let pipelines = self.prepass_pipelines(ctx, material, instanced)?;
+                        prepass_pipelines.insert((material, instanced), pipelines.clone());
+                        pipelines
+                    }
+                };
+                (Some(depth), shaded)
+            } else {
+                let pipeline = match pipelines.get(&(material, stencil, instanced)) {
+                    Some(pipeline) => pipeline.clone(),
+                    None => {
+                        let pipeline = self.material_pipeline(ctx, material, stencil, instanced)?;
+                        pipelines.insert((material, stencil, instanced), pipeline.clone());
+                        pipeline
+                    }
+                };
+                (None, pipeline)
+            };
+            let (uniforms, geometry) = match geometry {
+                Geometry::Quads(range) => {
+                    let uniforms = view.map_or(quad_uniforms, |view| view_uniforms[view]);
+                    (uniforms, BatchGeometry::Quads(range))
+                }
+                Geometry::Mesh(mesh, transform) => {
+                    let ty = match self.materials.get(material).shader {
+                        MaterialShader::Builtin(_) => None,
+                        MaterialShader::Custom(ty) => Some(ty),
+                    };
+                    let camera = view.map_or(self.camera, |view| self.views[view].camera);
+                    let uniforms = self.uniforms.push(VertexUniforms {
+                        camera: camera.to_homogeneous(),
+                        transform,
+                    });
+                    (uniforms, BatchGeometry::Mesh(ty, mesh))
+                }
+                Geometry::Instances(mesh, instances) => {
+                    let args = match self.meshes.indirect_args(mesh, instances) {
+                        Some(args) => args,
+                        None => continue,
+                    };
+                    let uniforms = view.map_or(quad_uniforms, |view| view_uniforms[view]);
+                    let draw = self.indirect.push(args);
+                    (uniforms, BatchGeometry::Indirect(draw..draw + 1))
+                }
+                Geometry::Culled(draw) => {
+                    let uniforms = view.map_or(quad_uniforms, |view| view_uniforms[view]);
+                    (uniforms, BatchGeometry::Culled(draw..draw + 1))
+                }
+            };
+            // Runs of quads, and of instanced draws, with the same state
+            // are drawn as one batch.
+            if let Some(last) = batches.last_mut() {
+                if let (BatchGeometry::Quads(last_range), BatchGeometry::Quads(range))
+                | (BatchGeometry::Indirect(last_range), BatchGeometry::Indirect(range))
+                | (BatchGeometry::Culled(last_range), BatchGeometry::Culled(range)) =
+                    (&mut last.geometry, &geometry)
+                {
+                    if Rc::ptr_eq(&last.pipeline, &pipeline)
+                        && Rc::ptr_eq(&last.bind_group, &bind_group)
+                        && last.uniforms == uniforms
+                        && last.clip == clip
+                        && last.view == view
+                        && last.stencil == stencil
+                        && last_range.end == range.start
+                    {
+                        last_range.end = range.end;
+                        continue;
+                    }
+                }
+            }
+            batches.push(Batch {
+                pipeline,
+                bind_group,
+                uniforms,
+                clip,
+                view,
+                stencil,
+                prepass,
+                geometry,
+            });
+        }
+        let bars = match self.virtual_size {
+            Some(_) => Some(self.queue_bars(ctx, atlas)?),
+            None => None,
+        };
+        let wireframe = if self.wireframe {
+            Some(Wireframe {
+                pipeline: self.wireframe_pipeline(ctx)?,
+                batches: self.edge_batches(&batches),
+            })
+        } else {
+            None
+        };
+        self.upload(ctx, encoder)?;
+        if !self.culling.is_empty() {
+            let frustum = |camera: &na::Orthographic3<f32>, transform| {
+                Frustum::sides(&(camera.to_homogeneous() * transform))
+            };
+            let frustums: Vec<Frustum> = std::iter::once(frustum(&self.camera, self.transform))
+                .chain(
+                    self.views
+                        .iter()
+                        .map(|view| frustum(&view.camera, view.transform)),
+                )
+                .collect();
+            self.culling.cull(ctx, encoder, &frustums)?;
+        }
+
+        let uniforms = self.uniforms.bind_group(ctx);
+        ctx.stats.bind_group();
+        ctx.stats.vertices += self.vertices.len() as u32;
+        let prepassed = batches.iter().filter(|batch| batch.prepass.is_some());
+        for batch in batches.iter().chain(&bars).chain(prepassed) {
+            ctx.stats.bind_group();
+            match &batch.geometry {
+                BatchGeometry::Quads(range) => ctx.stats.draw_indexed(range.end - range.start),
+                BatchGeometry::Mesh(ty, mesh) => {
+                    ctx.stats.draw_indexed(self.meshes(*ty).index_count(*mesh))
+                }
+                BatchGeometry::Indirect(draws) => {
+                    let args = &self.indirect.as_slice()[draws.start as usize..draws.end as usize];
+                    for args in args {
+                        ctx.stats
+                            .draw_indexed(args.index_count * args.instance_count);
+                    }
+                }
+                // How many instances survive is only known on the GPU.
+                BatchGeometry::Culled(draws) => {
+                    for _ in draws.clone() {
+                        ctx.stats.draw_indexed(0);
+                    }
+                }
+            }
+        }
+
+        if let Some(wireframe) = &wireframe {
+            for batch in &wireframe.batches {
+                ctx.stats.bind_group();
+                ctx.stats
+                    .draw_indexed(batch.indices.end - batch.indices.start);
+            }
+        }
+
+        self.prepared = Some(Prepared {
+            uniforms,
+            batches,
+            bars,
+            wireframe,
+        });
+        Ok(())
+    }
+
+    fn record(&self, ctx: &Context, encoder: &mut wgpu::CommandEncoder, output: &Output) {
+        let _scope = validation::scope("interface");
+        let _profile = profiler::scope("interface/record");
+        let prepared = match &self.prepared {
+            Some(prepared) => prepared,
+            None => return,
+        };
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[output.color_attachment(
+                ctx,
+                wgpu::LoadOp::Load,
+                Color::TRANSPARENT,
+            )],
+            depth_stencil_attachment: self
+                .depth_stencil_state()
+                .and_then(|_| output.depth_attachment(ctx)),
+        });
+
+        // Pipelines and geometry buffers are only switched when they
+        // change between batches.
+        let mut pipeline: Option<&Rc<wgpu::RenderPipeline>> = None;
+        let mut buffers = None;
+        let size = output.size(ctx);
+        if let Some(bars) = &prepared.bars {
+            pass.set_pipeline(&bars.pipeline);
+            pipeline = Some(&bars.pipeline);
+            pass.set_bind_group(0, &prepared.uniforms, &[bars.uniforms]);
+            pass.set_bind_group(1, &bars.bind_group, &[]);
+            pass.set_vertex_buffer(0, self.vertices.buffer(), 0, 0);
+            pass.set_index_buffer(self.indices.buffer(), 0, 0);
+            buffers = Some(None);
+            if let BatchGeometry::Quads(range) = &bars.geometry {
+                pass.draw_indexed(range.clone(), 0, 0..1);
+            }
+        }
+        let view_size = self.logical_size();
+        let viewport = fit(view_size, [size[0] as f32, size[1] as f32]);
+        if prepared.bars.is_some() {
+            let [x, y, width, height] = viewport;
+            pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        }
+        // The pixels a view is drawn into, or the interface if `None`.
+        let view_viewport = |view: Option<usize>| {
+            view.map_or(viewport, |view| {
+                pixel_rect(&self.views[view].rect, view_size, viewport)
+            })
+        };
+        let full = [0, 0, size[0], size[1]];
+        let unclipped = Rect::new([0.0, 0.0], view_size);
+        let mut scissor = full;
+        let mut current_view = None;
+        let mut reference = 0;
+        // Whether the culled instances are bound, if any are.
+        let mut instances_bound = None;
+        // The depth pre-pass goes first, as the opaque batches it draws
+        // come first anyway.
+        let prepass = prepared
+            .batches
+            .iter()
+            .filter_map(|batch| Some((batch, batch.prepass.as_ref()?)));
+        let shaded = prepared
+            .batches
+            .iter()
+            .map(|batch| (batch, &batch.pipeline));
+        for (batch, batch_pipeline) in prepass.chain(shaded) {
+            let mut clip = batch.clip.unwrap_or(unclipped);
+            if let Some(view) = batch.view {
+                clip = clip.intersection(&self.views[view].rect);
+            }
+            let clipped = scissor_rect(&clip, view_size, viewport);
+            if clipped[2] == 0 || clipped[3] == 0 {
+                continue;
+            }
+            if clipped != scissor {
+                pass.set_scissor_rect(clipped[0], clipped[1], clipped[2], clipped[3]);
+                scissor = clipped;
+            }
+            if batch.view != current_view {
+                let [x, y, width, height] = view_viewport(batch.view);
+                pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                current_view = batch.view;
+            }
+            if pipeline.is_none_or(|pipeline| !Rc::ptr_eq(pipeline, batch_pipeline)) {
+                pass.set_pipeline(batch_pipeline);
+                pipeline = Some(batch_pipeline);
+            }
+            if batch.stencil.reference() != reference {
+                pass.set_stencil_reference(batch.stencil.reference());
+                reference = batch.stencil.reference();
+            }
+            pass.set_bind_group(0, &prepared.uniforms, &[batch.uniforms]);
+            pass.set_bind_group(1, &batch.bind_group, &[]);
+            if buffers != Some(batch.geometry.buffers()) {
+                match batch.geometry.buffers() {
+                    Some(ty) => self.meshes(ty).bind(&mut pass, 0),
+                    None => {
+                        pass.set_vertex_buffer(0, self.vertices.buffer(), 0, 0);
+                        pass.set_index_buffer(self.indices.buffer(), 0, 0);
+                    }
+                }
+                buffers = Some(batch.geometry.buffers());
+            }
+            match &batch.geometry {
+                BatchGeometry::Quads(range) => pass.draw_indexed(range.clone(), 0, 0..1),
+                BatchGeometry::Mesh(ty, mesh) => self.meshes(*ty).draw(&mut pass, *mesh, 0..1),
+                BatchGeometry::Indirect(draws) => {
+                    if instances_bound != Some(false) {
+                        self.instances.bind(&mut pass, 1);
+                        instances_bound = Some(false);
+                    }
+                    self.indirect.multi_draw(&mut pass, draws.clone())
+                }
+                BatchGeometry::Culled(draws) => {
+                    if instances_bound != Some(true) {
+                        self.culling.bind(&mut pass, 1);
+                        instances_bound = Some(true);
+                    }
+                    self.culling.multi_draw(&mut pass, draws.clone())
+                }
+            }
+        }
+
+        if let Some(wireframe) = &prepared.wireframe {
+            if scissor != full {
+                pass.set_scissor_rect(0, 0, size[0], size[1]);
+            }
+            pass.set_pipeline(&wireframe.pipeline);
+            pass.set_index_buffer(self.edges.buffer(), 0, 0);
+            for batch in &wireframe.batches {
+                if batch.view != current_view {
+                    let [x, y, width, height] = view_viewport(batch.view);
+                    pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                    current_view = batch.view;
+                }
+                pass.set_bind_group(0, &prepared.uniforms, &[batch.uniforms]);
+                let base_vertex = match batch.mesh {
+                    Some(mesh) => {
+                        let (vertices, base_vertex) = self.meshes.vertices(mesh);
+                        pass.set_vertex_buffer(0, vertices, 0, 0);
+                        base_vertex
+                    }
+                    None => {
+                        pass.set_vertex_buffer(0, self.vertices.buffer(), 0, 0);
+                        0
+                    }
+                };
+                pass.draw_indexed(batch.indices.clone(), base_vertex, 0..1);
+            }
+        }
+    }
+
+    /// Rebuilds every GPU resource on the context's current device, e.g.
+    /// after `Context::recover`. Camera, transform, geometry, meshes,
+    /// materials, material types and atlas contents are kept; materials
+    /// with bind groups of their own need them made again by whoever added
+    /// them.
+    fn recreate(&mut self, ctx: &mut Context) -> Result<(), EngineError> {
+        self.atlas.recreate(ctx)?;
+
+        let depth_stencil_state = self.pipeline_key.depth_stencil_state.take();
+        let (pipeline_key, uniforms) = Self::device_resources(ctx, self.atlas.layout())?;
+        self.pipeline_key = pipeline_key;
+        self.pipeline_key.depth_stencil_state = depth_stencil_state;
+        self.uniforms = uniforms;
+        self.validated.clear();
+
+        self.vertices.recreate(ctx)?;
+        self.indices.recreate(ctx)?;
+        self.edges.recreate(ctx)?;
+        self.instances.recreate(ctx)?;
+        self.indirect.recreate(ctx)?;
+        self.culling.recreate(ctx)?;
+        self.meshes.recreate(ctx)?;
+        for index in 0..self.types.len() {
+            let ty = &self.types[index];
+            let (pipeline_key, layout) = self.type_resources(ctx, &ty.desc, &ty.vertex_layout)?;
+            let ty = &mut self.types[index];
+            ty.pipeline_key = pipeline_key;
+            ty.layout = layout;
+            ty.meshes.recreate(ctx)?;
+        }
+        // Render target quads hold bind groups of the old device.
+        self.draws.retain(|draw| draw.bind_group.is_none());
+        for mask in &mut self.masks {
+            mask.retain(|draw| draw.bind_group.is_none());
+        }
+        self.prepared = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+//! The layers 2D draws are painted in, shared by the interface and sprite
+//! passes.
+
+/// Where a 2D draw goes in the order its pass paints: everything in a
+/// layer is painted over everything in the layers below it, and within a
+/// layer in the order it was queued.
+///
+/// The named layers leave room between them for a finer order, through
+/// [`Layer::offset`], e.g. `Layer::WORLD.offset(row)` to paint the rows of
+/// a map from the top down whatever order they are queued in. Layers only
+/// order the draws of one pass; passes are painted in the order the
+/// render graph schedules them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Layer(pub i32);
+
+impl Layer {
+    pub const BACKGROUND: Self = Self(-2 << 16);
+    /// Where sprites go unless told otherwise.
+    pub const WORLD: Self = Self(-1 << 16);
+    pub const EFFECTS: Self = Self(0);
+    /// Where the interface goes unless told otherwise.
+    pub const HUD: Self = Self(1 << 16);
+    pub const OVERLAY: Self = Self(2 << 16);
+
+    /// The layer `by` above this one, or below if negative.
+    pub fn offset(self, by: i32) -> Self {
+        Self(self.0.saturating_add(by))
+    }
+}
@@ -0,0 +1,488 @@
+use std::rc::Rc;
+
+use nalgebra as na;
+
+use crate::{
+    bind_group_cache::Binding,
+    buffer_pool::PooledBuffer,
+    color::Color,
+    components::GlobalTransform,
+    compute::ComputePass,
+    ecs::World,
+    error::EngineError,
+    gpu_struct,
+    material::Blend,
+    pipeline_cache::{PipelineKey, VertexLayout},
+    profiler,
+    push_constants::PushBlock,
+    render_graph::{Attachments, Output},
+    time::Time,
+    validation, Context,
+};
+
+use super::{
+    interface::{logical_camera, VertexUniforms},
+    Pass, Vertex,
+};
+
+gpu_struct! {
+    std430;
+    /// One particle as the simulation stores it, and the per-instance
+    /// attributes it is drawn with. Dead once `age` reaches `lifetime`. The
+    /// color is linear, from the emitter's colors by age.
+    #[derive(Debug)]
+    pub struct Particle {
+        pub position: na::Vector2<f32>,
+        pub velocity: na::Vector2<f32>,
+        pub color: na::Vector4<f32>,
+        pub size: f32,
+        pub age: f32,
+        pub lifetime: f32,
+        _pad: f32,
+    }
+}
+
+impl Vertex for Particle {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        wgpu::VertexBufferDescriptor {
+            stride: std::mem::size_of::<Particle>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 16,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 32,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float,
+                },
+                // Age and lifetime.
+                wgpu::VertexAttributeDescriptor {
+                    offset: 36,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float2,
+                },
+            ],
+        }
+    }
+}
+
+gpu_struct! {
+    std140;
+    /// `Params` in `particles.comp.wgsl`.
+    #[derive(Debug)]
+    struct ParticleParams {
+        position: na::Vector2<f32>,
+        velocity: na::Vector2<f32>,
+        gravity: na::Vector2<f32>,
+        spread: f32,
+        drag: f32,
+        color: na::Vector4<f32>,
+        end_color: na::Vector4<f32>,
+        size: f32,
+        lifetime: f32,
+        dt: f32,
+        seed: u32,
+        spawn_start: u32,
+        spawn_count: u32,
+        capacity: u32,
+        reset: u32,
+    }
+}
+
+/// Where and how a [`ParticlePass`] spawns particles, and how they move.
+/// Positions and sizes are in the units of the pass's camera, times in
+/// seconds.
+///
+/// Also a component: the pass follows the first entity that has one, see
+/// [`ParticlePass::extract`]. Its position is then relative to the
+/// entity's [`GlobalTransform`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParticleEmitter {
+    pub position: [f32; 2],
+    /// The velocity particles start with, before `spread`.
+    pub velocity: [f32; 2],
+    /// The most speed added in a random direction at spawn.
+    pub spread: f32,
+    pub gravity: [f32; 2],
+    /// The fraction of velocity lost per second.
+    pub drag: f32,
+    /// Particles fade from `color` at spawn to `end_color` at death.
+    pub color: Color,
+    pub end_color: Color,
+    pub size: f32,
+    /// The average lifetime; each particle's is within a quarter of it.
+    pub lifetime: f32,
+    /// Particles spawned per second.
+    pub rate: f32,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0],
+            velocity: [0.0, 0.0],
+            spread: 100.0,
+            gravity: [0.0, 0.0],
+            drag: 0.0,
+            color: Color::WHITE,
+            end_color: Color::WHITE.with_alpha(0.0),
+            size: 4.0,
+            lifetime: 1.0,
+            rate: 0.0,
+        }
+    }
+}
+
+/// Shaders, buffers and pipeline key, i.e. everything tied to the device.
+struct DeviceResources {
+    simulation: ComputePass,
+    pipeline_key: PipelineKey,
+    uniforms: PushBlock<VertexUniforms>,
+    params: PooledBuffer,
+    particles: PooledBuffer,
+}
+
+impl DeviceResources {
+    fn new(ctx: &mut Context, capacity: u32) -> Result<Self, EngineError> {
+        let simulation = ComputePass::new(ctx, "particles.comp")?;
+        let compute_shader = ctx.pipelines.load_shader(&ctx.device, "particles.comp")?;
+        ctx.pipelines
+            .check_buffer::<ParticleParams>(compute_shader, 0, 0)?;
+
+        let vertex_shader = ctx.pipelines.load_shader(&ctx.device, "particles.vert")?;
+        let fragment_shader = ctx.pipelines.load_shader(&ctx.device, "particles.frag")?;
+        let uniforms = PushBlock::new(ctx, "particles/uniforms", wgpu::ShaderStage::VERTEX)?;
+        let pipeline_key = PipelineKey {
+            vertex_shader,
+            fragment_shader: Some(fragment_shader),
+            bind_group_layouts: vec![uniforms.layout()],
+            vertex_layouts: vec![VertexLayout::from_desc(&Particle::desc())],
+            color_states: vec![wgpu::ColorStateDescriptor {
+                format: ctx.surface_format(),
+                color_blend: Blend::PREMULTIPLIED.color,
+                alpha_blend: Blend::PREMULTIPLIED.alpha,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: wgpu::CullMode::None,
+            index_format: wgpu::IndexFormat::Uint32,
+            sample_count: ctx.sample_count(),
+        };
+        ctx.pipelines.validate(&pipeline_key, &ctx.bind_groups)?;
+        ctx.pipelines
+            .check_buffer::<VertexUniforms>(vertex_shader, 0, 0)?;
+
+        let params = ctx.buffer_pool.acquire(
+            &ctx.device,
+            &ctx.memory,
+            "particles/params",
+            std::mem::size_of::<ParticleParams>() as wgpu::BufferAddress,
+            wgpu::BufferUsage::UNIFORM,
+        )?;
+        let particles = ctx.buffer_pool.acquire(
+            &ctx.device,
+            &ctx.memory,
+            "particles",
+            capacity.max(1) as wgpu::BufferAddress
+                * std::mem::size_of::<Particle>() as wgpu::BufferAddress,
+            wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::VERTEX,
+        )?;
+
+        Ok(Self {
+            simulation,
+            pipeline_key,
+            uniforms,
+            params,
+            particles,
+        })
+    }
+}
+
+/// What `record` binds, looked up in `prepare`.
+struct Prepared {
+    pipeline: Rc<wgpu::RenderPipeline>,
+    uniforms: Rc<wgpu::BindGroup>,
+    offset: wgpu::DynamicOffset,
+}
+
+/// Particles simulated entirely on the GPU: a compute shader spawns, moves
+/// and kills them in a storage buffer, which is then bound as an instance
+/// buffer to draw them, so their number costs the CPU nothing.
+///
+/// The buffer holds a fixed number of particles, used as a ring: spawning
+/// replaces the oldest, whether or not it is dead yet. Particles are
+/// drawn as soft round dots, blended over the frame in buffer order.
+///
+/// The simulation advances by the time given to [`ParticlePass::update`]
+/// since the last frame, and stands still without it, unless the pass
+/// follows an emitter in the world.
+pub struct ParticlePass {
+    capacity: u32,
+    emitter: ParticleEmitter,
+    /// Whether `emitter` was taken from the world last frame.
+    following: bool,
+    /// `None` tracks the window's logical size.
+    camera: Option<na::Orthographic3<f32>>,
+    transform: na::Matrix4<f32>,
+    gpu: DeviceResources,
+    /// Seconds to simulate in the next `prepare`.
+    dt: f32,
+    /// Fractional particles carried over to the next frame's spawn.
+    spawn_backlog: f32,
+    spawn_count: u32,
+    spawn_start: u32,
+    frame: u32,
+    /// Whether the particle buffer still holds what it was pooled with.
+    reset: bool,
+    prepared: Option<Prepared>,
+}
+
+impl ParticlePass {
+    /// A pass simulating up to `capacity` particles at once, in the
+    /// window's logical pixels until [`ParticlePass::set_camera`] says
+    /// otherwise.
+    pub fn new(ctx: &mut Context, capacity: u32) -> Result<Self, EngineError> {
+        let gpu = DeviceResources::new(ctx, capacity)?;
+        Ok(Self {
+            capacity,
+            emitter: ParticleEmitter::default(),
+            following: false,
+            camera: None,
+            transform: na::Matrix4::identity(),
+            gpu,
+            dt: 0.0,
+            spawn_backlog: 0.0,
+            spawn_count: 0,
+            spawn_start: 0,
+            frame: 0,
+            reset: true,
+            prepared: None,
+        })
+    }
+
+    /// How many particles can be alive at once.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn emitter(&self) -> &ParticleEmitter {
+        &self.emitter
+    }
+
+    /// Changes the emitter for particles spawned from the next frame on.
+    /// Particles already alive keep their positions and velocities but
+    /// take the new gravity, drag and colors.
+    pub fn emitter_mut(&mut self) -> &mut ParticleEmitter {
+        &mut self.emitter
+    }
+
+    /// Advances the simulation by `dt` seconds in the next frame, spawning
+    /// at the emitter's rate over that time.
+    pub fn update(&mut self, dt: f32) {
+        self.dt += dt;
+        self.spawn_backlog += self.emitter.rate * dt;
+        let spawned = self.spawn_backlog.floor();
+        self.spawn_backlog -= spawned;
+        self.spawn(spawned as u32);
+    }
+
+    /// Spawns `count` particles at once in the next frame.
+    pub fn burst(&mut self, count: u32) {
+        self.spawn(count);
+    }
+
+    fn spawn(&mut self, count: u32) {
+        self.spawn_count = self.spawn_count.saturating_add(count).min(self.capacity);
+    }
+
+    /// Kills every particle in the next frame.
+    pub fn clear(&mut self) {
+        self.reset = true;
+        self.spawn_count = 0;
+    }
+
+    /// The camera set with [`ParticlePass::set_camera`], if any.
+    pub fn camera(&self) -> Option<&na::Orthographic3<f32>> {
+        self.camera.as_ref()
+    }
+
+    /// Draws through `camera`, or, with `None`, in the window's logical
+    /// pixels whatever its size.
+    pub fn set_camera(&mut self, camera: Option<na::Orthographic3<f32>>) {
+        self.camera = camera;
+    }
+
+    pub fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: na::Matrix4<f32>) {
+        self.transform = transform;
+    }
+}
+
+impl Pass for ParticlePass {
+    fn name(&self) -> &'static str {
+        "particles"
+    }
+
+    /// Follows the first entity with a [`ParticleEmitter`] and a
+    /// [`GlobalTransform`]: takes its emitter, moved to where the entity is
+    /// drawn, and advances by the frame's scaled time, so particles stop
+    /// with a paused game. Particles are cleared when the entity goes
+    /// away. Without one the pass is left to [`ParticlePass::update`].
+    fn extract(&mut self, world: &World, time: &Time) {
+        let followed = world
+            .query2::<ParticleEmitter, GlobalTransform>()
+            .next()
+            .map(|(_, &emitter, global)| {
+                (
+                    emitter,
+                    global.transform_point(emitter.position, time.alpha()),
+                )
+            });
+        match followed {
+            Some((emitter, position)) => {
+                self.emitter = ParticleEmitter {
+                    position,
+                    ..emitter
+                };
+                self.following = true;
+                self.update(time.delta_seconds() * time.scale());
+            }
+            None if self.following => {
+                self.following = false;
+                self.clear();
+            }
+            None => {}
+        }
+    }
+
+    fn prepare(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        _attachments: &Attachments,
+    ) -> Result<(), EngineError> {
+        let _scope = validation::scope("particles");
+        let _profile = profiler::scope("particles/prepare");
+        self.gpu.pipeline_key.sample_count = ctx.sample_count();
+
+        let emitter = &self.emitter;
+        let params = ParticleParams {
+            position: emitter.position.into(),
+            velocity: emitter.velocity.into(),
+            gravity: emitter.gravity.into(),
+            spread: emitter.spread,
+            drag: emitter.drag,
+            color: emitter.color.to_linear().into(),
+            end_color: emitter.end_color.to_linear().into(),
+            size: emitter.size,
+            lifetime: emitter.lifetime,
+            dt: self.dt,
+            seed: self.frame,
+            spawn_start: self.spawn_start,
+            spawn_count: self.spawn_count,
+            capacity: self.capacity,
+            reset: self.reset as u32,
+        };
+        ctx.staging.write_buffer(
+            &ctx.device,
+            &ctx.memory,
+            encoder,
+            self.gpu.params.buffer(),
+            0,
+            bytemuck::bytes_of(&params),
+        )?;
+
+        let bind_group = self.gpu.simulation.bind_group(
+            ctx,
+            0,
+            &[
+                Binding::Buffer {
+                    buffer: self.gpu.params.buffer(),
+                    range: 0..self.gpu.params.size(),
+                },
+                Binding::Buffer {
+                    buffer: self.gpu.particles.buffer(),
+                    range: 0..self.gpu.particles.size(),
+                },
+            ],
+        );
+        let workgroups = self.gpu.simulation.workgroups([self.capacity, 1, 1]);
+        self.gpu
+            .simulation
+            .dispatch(ctx, encoder, &[&bind_group], workgroups);
+
+        self.spawn_start = (self.spawn_start + self.spawn_count) % self.capacity.max(1);
+        self.spawn_count = 0;
+        self.dt = 0.0;
+        self.frame = self.frame.wrapping_add(1);
+        self.reset = false;
+
+        let camera = self.camera.unwrap_or_else(|| {
+            let size = ctx.logical_size();
+            logical_camera(size.width, size.height)
+        });
+        self.gpu.uniforms.clear();
+        let offset = self.gpu.uniforms.push(VertexUniforms {
+            camera: camera.to_homogeneous(),
+            transform: self.transform,
+        });
+        self.gpu.uniforms.upload(ctx, encoder)?;
+        let uniforms = self.gpu.uniforms.bind_group(ctx);
+        let pipeline =
+            ctx.pipelines
+                .pipeline(&ctx.device, &ctx.bind_groups, &self.gpu.pipeline_key);
+        ctx.stats.bind_group();
+        ctx.stats.draw(6 * self.capacity);
+
+        self.prepared = Some(Prepared {
+            pipeline,
+            uniforms,
+            offset,
+        });
+        Ok(())
+    }
+
+    fn record(&self, ctx: &Context, encoder: &mut wgpu::CommandEncoder, output: &Output) {
+        let _scope = validation::scope("particles");
+        let _profile = profiler::scope("particles/record");
+        let prepared = match &self.prepared {
+            Some(prepared) => prepared,
+            None => return,
+        };
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[output.color_attachment(
+                ctx,
+                wgpu::LoadOp::Load,
+                Color::TRANSPARENT,
+            )],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&prepared.pipeline);
+        pass.set_bind_group(0, &prepared.uniforms, &[prepared.offset]);
+        pass.set_vertex_buffer(0, self.gpu.particles.buffer(), 0, 0);
+        pass.draw(0..6, 0..self.capacity);
+    }
+
+    /// Rebuilds every GPU resource on the context's current device, e.g.
+    /// after `Context::recover`. The emitter, camera and transform are
+    /// kept, but the particles alive are lost with the old device.
+    fn recreate(&mut self, ctx: &mut Context) -> Result<(), EngineError> {
+        self.gpu = DeviceResources::new(ctx, self.capacity)?;
+        self.reset = true;
+        self.prepared = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,455 @@
+use std::{ops::Range, rc::Rc};
+
+use nalgebra as na;
+
+use crate::{
+    assets::texture::Texture,
+    bind_group_cache::LayoutId,
+    color::Color,
+    dynamic_buffer::DynamicBuffer,
+    error::EngineError,
+    material::Blend,
+    pipeline_cache::{PipelineKey, VertexLayout},
+    profiler,
+    push_constants::PushBlock,
+    render_graph::{Attachments, Output},
+    validation, Context,
+};
+
+use super::{
+    interface::{logical_camera, VertexUniforms},
+    Layer, Pass, Rect, Vertex,
+};
+
+const INITIAL_SPRITE_CAPACITY: usize = 1024;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SpriteVertex {
+    pub pos: [f32; 2],
+    pub uv: [f32; 2],
+    /// Linear, as from [`Color::to_linear`].
+    pub color: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for SpriteVertex {}
+unsafe impl bytemuck::Zeroable for SpriteVertex {}
+
+impl Vertex for SpriteVertex {
+    fn desc<'a>() -> wgpu::VertexBufferDescriptor<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferDescriptor {
+            stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttributeDescriptor {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 8,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float2,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: 16,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float4,
+                },
+            ],
+        }
+    }
+}
+
+/// A texture added to a [`SpritePass`], e.g. a sprite sheet or one page of
+/// an atlas.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpriteTextureId(usize);
+
+struct SpriteTexture {
+    bind_group: Rc<wgpu::BindGroup>,
+    size: [f32; 2],
+}
+
+/// A sprite queued this frame, placed already.
+struct QueuedSprite {
+    layer: Layer,
+    texture: SpriteTextureId,
+    corners: [[f32; 2]; 4],
+    uv: Rect,
+    color: [f32; 4],
+}
+
+/// Sprites sharing a texture, drawn in one call.
+struct Batch {
+    bind_group: Rc<wgpu::BindGroup>,
+    indices: Range<u32>,
+}
+
+/// What `record` binds, looked up in `prepare`.
+struct Prepared {
+    pipeline: Rc<wgpu::RenderPipeline>,
+    uniforms: Rc<wgpu::BindGroup>,
+    offset: wgpu::DynamicOffset,
+    batches: Vec<Batch>,
+}
+
+/// Textured quads for the game world, drawn with a camera of their own and
+/// apart from the interface, which is usually drawn over them.
+///
+/// Sprites are painted by [`Layer`], set with [`SpritePass::set_layer`],
+/// and within a layer in the order they were queued, all from one vertex
+/// buffer, with runs of them showing the same texture drawn in one call.
+/// Sprites that don't overlap can be sorted by texture within their layer
+/// with [`SpritePass::set_sort_by_texture`], so each texture is bound once
+/// per layer.
+pub struct SpritePass {
+    pipeline_key: PipelineKey,
+    uniforms: PushBlock<VertexUniforms>,
+    texture_layout: LayoutId,
+    camera: na::Orthographic3<f32>,
+    transform: na::Matrix4<f32>,
+    textures: Vec<SpriteTexture>,
+    sprites: Vec<QueuedSprite>,
+    layer: Layer,
+    sort_by_texture: bool,
+    vertices: DynamicBuffer<SpriteVertex>,
+    indices: DynamicBuffer<u32>,
+    prepared: Option<Prepared>,
+}
+
+impl SpritePass {
+    /// Shaders, uniforms, pipeline key and the layout of the textures.
+    fn device_resources(
+        ctx: &mut Context,
+    ) -> Result<(PipelineKey, PushBlock<VertexUniforms>, LayoutId), EngineError> {
+        let vertex_shader = ctx.pipelines.load_shader(&ctx.device, "sprite.vert")?;
+        let fragment_shader = ctx.pipelines.load_shader(&ctx.device, "sprite.frag")?;
+
+        let uniforms = PushBlock::new(ctx, "sprites/uniforms", wgpu::ShaderStage::VERTEX)?;
+        let texture_layout = ctx.bind_groups.layout_id(
+            &ctx.device,
+            "sprites/texture",
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::D2,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+        );
+
+        let pipeline_key = PipelineKey {
+            vertex_shader,
+            fragment_shader: Some(fragment_shader),
+            bind_group_layouts: vec![uniforms.layout(), texture_layout],
+            vertex_layouts: vec![VertexLayout::from_desc(&SpriteVertex::desc())],
+            color_states: vec![wgpu::ColorStateDescriptor {
+                format: ctx.surface_format(),
+                color_blend: Blend::ALPHA.color,
+                alpha_blend: Blend::ALPHA.alpha,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            // Sprites can be mirrored by their rects.
+            cull_mode: wgpu::CullMode::None,
+            index_format: wgpu::IndexFormat::Uint32,
+            sample_count: ctx.sample_count(),
+        };
+        ctx.pipelines.validate(&pipeline_key, &ctx.bind_groups)?;
+        ctx.pipelines
+            .check_buffer::<VertexUniforms>(vertex_shader, 0, 0)?;
+
+        Ok((pipeline_key, uniforms, texture_layout))
+    }
+
+    /// A pass in interface coordinates until [`SpritePass::set_camera`]
+    /// says otherwise.
+    pub fn new(ctx: &mut Context) -> Result<Self, EngineError> {
+        let logical_size = ctx.logical_size();
+        let (pipeline_key, uniforms, texture_layout) = Self::device_resources(ctx)?;
+        let vertices = DynamicBuffer::new(
+            ctx,
+            "sprites/vertices",
+            wgpu::BufferUsage::VERTEX,
+            INITIAL_SPRITE_CAPACITY * 4,
+        )?;
+        let indices = DynamicBuffer::new(
+            ctx,
+            "sprites/indices",
+            wgpu::BufferUsage::INDEX,
+            INITIAL_SPRITE_CAPACITY * 6,
+        )?;
+        Ok(Self {
+            pipeline_key,
+            uniforms,
+            texture_layout,
+            camera: logical_camera(logical_size.width, logical_size.height),
+            transform: na::Matrix4::identity(),
+            textures: vec![],
+            sprites: vec![],
+            layer: Layer::WORLD,
+            sort_by_texture: false,
+            vertices,
+            indices,
+            prepared: None,
+        })
+    }
+
+    /// Makes `texture` drawable by the pass, with its own sampler.
+    pub fn add_texture(&mut self, ctx: &mut Context, texture: &Texture) -> SpriteTextureId {
+        let texture = self.sprite_texture(ctx, texture);
+        self.textures.push(texture);
+        SpriteTextureId(self.textures.len() - 1)
+    }
+
+    /// Replaces the texture at `id`, e.g. with a sharper version or one on
+    /// a new device.
+    pub fn set_texture(&mut self, ctx: &mut Context, id: SpriteTextureId, texture: &Texture) {
+        self.textures[id.0] = self.sprite_texture(ctx, texture);
+    }
+
+    fn sprite_texture(&self, ctx: &mut Context, texture: &Texture) -> SpriteTexture {
+        let bind_group = ctx.bind_groups.bind_group(
+            &ctx.device,
+            "sprites/texture",
+            self.texture_layout,
+            &texture.bindings(),
+        );
+        SpriteTexture {
+            bind_group,
+            size: [texture.size.width as f32, texture.size.height as f32],
+        }
+    }
+
+    /// Queues the pixels of `texture` in `source` stretched over
+    /// `destination` and turned by `rotation` radians about its centre,
+    /// tinted by `tint`. A source with its corners swapped mirrors the
+    /// sprite.
+    pub fn draw(
+        &mut self,
+        texture: SpriteTextureId,
+        source: Rect,
+        destination: Rect,
+        rotation: f32,
+        tint: Color,
+    ) {
+        let size = self.textures[texture.0].size;
+        let uv = Rect::new(
+            [source.min[0] / size[0], source.min[1] / size[1]],
+            [source.max[0] / size[0], source.max[1] / size[1]],
+        );
+        let (min, max) = (destination.min, destination.max);
+        let center = na::Point2::new((min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0);
+        let turn = na::Rotation2::new(rotation);
+        let corners = [
+            [min[0], min[1]],
+            [max[0], min[1]],
+            [max[0], max[1]],
+            [min[0], max[1]],
+        ]
+        .map(|[x, y]| {
+            let corner = center + turn * (na::Point2::new(x, y) - center);
+            [corner.x, corner.y]
+        });
+        self.sprites.push(QueuedSprite {
+            layer: self.layer,
+            texture,
+            corners,
+            uv,
+            color: tint.to_linear(),
+        });
+    }
+
+    /// Drops this frame's sprites and resets the layer to the world.
+    /// Textures stay added.
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+        self.layer = Layer::WORLD;
+    }
+
+    /// Paints sprites queued from now on in `layer`, [`Layer::WORLD`] until
+    /// set.
+    pub fn set_layer(&mut self, layer: Layer) {
+        self.layer = layer;
+    }
+
+    pub fn layer(&self) -> Layer {
+        self.layer
+    }
+
+    /// Whether sprites are sorted by texture within their layer before
+    /// drawing, so each is bound once per layer. Sprites of one texture
+    /// keep their order, but overlapping sprites of different ones may
+    /// swap.
+    pub fn set_sort_by_texture(&mut self, enabled: bool) {
+        self.sort_by_texture = enabled;
+    }
+
+    pub fn sort_by_texture(&self) -> bool {
+        self.sort_by_texture
+    }
+
+    pub fn camera(&self) -> &na::Orthographic3<f32> {
+        &self.camera
+    }
+
+    pub fn set_camera(&mut self, camera: na::Orthographic3<f32>) {
+        self.camera = camera;
+    }
+
+    pub fn transform(&self) -> &na::Matrix4<f32> {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: na::Matrix4<f32>) {
+        self.transform = transform;
+    }
+}
+
+impl Pass for SpritePass {
+    fn name(&self) -> &'static str {
+        "sprites"
+    }
+
+    fn prepare(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+        _attachments: &Attachments,
+    ) -> Result<(), EngineError> {
+        let _scope = validation::scope("sprites");
+        let _profile = profiler::scope("sprites/prepare");
+        self.pipeline_key.sample_count = ctx.sample_count();
+
+        // The sort is stable, so sprites keep the order they were queued
+        // in within their layer, and texture if sorting by it.
+        let mut order: Vec<usize> = (0..self.sprites.len()).collect();
+        let (sprites, sort_by_texture) = (&self.sprites, self.sort_by_texture);
+        order.sort_by_key(|&index| {
+            let sprite = &sprites[index];
+            (
+                sprite.layer,
+                Some(sprite.texture).filter(|_| sort_by_texture),
+            )
+        });
+
+        self.vertices.clear();
+        self.indices.clear();
+        let mut batches: Vec<(SpriteTextureId, Range<u32>)> = vec![];
+        for index in order {
+            let sprite = &self.sprites[index];
+            let base = self.vertices.len() as u32;
+            let Rect { min, max } = sprite.uv;
+            let uvs = [
+                [min[0], min[1]],
+                [max[0], min[1]],
+                [max[0], max[1]],
+                [min[0], max[1]],
+            ];
+            for (pos, uv) in sprite.corners.iter().zip(uvs) {
+                self.vertices.push(SpriteVertex {
+                    pos: *pos,
+                    uv,
+                    color: sprite.color,
+                });
+            }
+            let start = self.indices.len() as u32;
+            self.indices
+                .extend_from_slice(&[0, 1, 2, 0, 2, 3].map(|index| base + index));
+            let end = self.indices.len() as u32;
+            match batches.last_mut() {
+                Some((texture, indices)) if *texture == sprite.texture => indices.end = end,
+                _ => batches.push((sprite.texture, start..end)),
+            }
+        }
+
+        self.uniforms.clear();
+        let offset = self.uniforms.push(VertexUniforms {
+            camera: self.camera.to_homogeneous(),
+            transform: self.transform,
+        });
+        self.uniforms.upload(ctx, encoder)?;
+        self.vertices.upload(ctx, encoder)?;
+        self.indices.upload(ctx, encoder)?;
+
+        let uniforms = self.uniforms.bind_group(ctx);
+        let pipeline = ctx
+            .pipelines
+            .pipeline(&ctx.device, &ctx.bind_groups, &self.pipeline_key);
+        ctx.stats.bind_group();
+        ctx.stats.vertices += self.vertices.len() as u32;
+        let batches = batches
+            .into_iter()
+            .map(|(texture, indices)| {
+                ctx.stats.bind_group();
+                ctx.stats.draw_indexed(indices.end - indices.start);
+                Batch {
+                    bind_group: self.textures[texture.0].bind_group.clone(),
+                    indices,
+                }
+            })
+            .collect();
+
+        self.prepared = Some(Prepared {
+            pipeline,
+            uniforms,
+            offset,
+            batches,
+        });
+        Ok(())
+    }
+
+    fn record(&self, ctx: &Context, encoder: &mut wgpu::CommandEncoder, output: &Output) {
+        let _scope = validation::scope("sprites");
+        let _profile = profiler::scope("sprites/record");
+        let prepared = match &self.prepared {
+            Some(prepared) if !prepared.batches.is_empty() => prepared,
+            _ => return,
+        };
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[output.color_attachment(
+                ctx,
+                wgpu::LoadOp::Load,
+                Color::TRANSPARENT,
+            )],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&prepared.pipeline);
+        pass.set_bind_group(0, &prepared.uniforms, &[prepared.offset]);
+        pass.set_vertex_buffer(0, self.vertices.buffer(), 0, 0);
+        pass.set_index_buffer(self.indices.buffer(), 0, 0);
+        for batch in &prepared.batches {
+            pass.set_bind_group(1, &batch.bind_group, &[]);
+            pass.draw_indexed(batch.indices.clone(), 0, 0..1);
+        }
+    }
+
+    /// Rebuilds every GPU resource on the context's current device, e.g.
+    /// after `Context::recover`. Camera, transform and queued sprites are
+    /// kept; textures need setting again with [`SpritePass::set_texture`]
+    /// by whoever added them.
+    fn recreate(&mut self, ctx: &mut Context) -> Result<(), EngineError> {
+        let (pipeline_key, uniforms, texture_layout) = Self::device_resources(ctx)?;
+        self.pipeline_key = pipeline_key;
+        self.uniforms = uniforms;
+        self.texture_layout = texture_layout;
+        self.vertices.recreate(ctx)?;
+        self.indices.recreate(ctx)?;
+        self.prepared = None;
+        Ok(())
+    }
+}
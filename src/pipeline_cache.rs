@@ -0,0 +1,627 @@
+use std::{collections::HashMap, fmt, io, path::PathBuf, rc::Rc};
+
+use crate::{
+    bind_group_cache::{BindGroupCache, LayoutId},
+    gpu_layout::GpuStruct,
+    shader_compiler::{self, CompileError, ShaderFeatures, ENTRY_POINT},
+    shader_reflection::{self, ShaderInterface},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderId(usize);
+
+/// A shader compiled with some of the features its source checks for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderVariant {
+    pub name: &'static str,
+    pub features: ShaderFeatures,
+}
+
+impl ShaderVariant {
+    pub fn new(name: &'static str, features: ShaderFeatures) -> Self {
+        Self { name, features }
+    }
+}
+
+impl From<&'static str> for ShaderVariant {
+    fn from(name: &'static str) -> Self {
+        Self::new(name, ShaderFeatures::NONE)
+    }
+}
+
+impl fmt::Display for ShaderVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&shader_compiler::variant_name(self.name, self.features))
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The shader couldn't be read, or isn't valid SPIR-V.
+    Io {
+        name: &'static str,
+        error: io::Error,
+    },
+    Compile(CompileError),
+    /// The shader doesn't fit the layouts a pipeline was built with, or
+    /// its interface couldn't be read.
+    Interface {
+        name: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { name, error } => {
+                write!(f, "failed to load shader {}: {}", name, error)
+            }
+            ShaderError::Compile(err) => write!(f, "failed to compile shader {}", err),
+            ShaderError::Interface { name, message } => {
+                write!(f, "shader {} doesn't match its pipeline: {}", name, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<CompileError> for ShaderError {
+    fn from(err: CompileError) -> Self {
+        ShaderError::Compile(err)
+    }
+}
+
+/// Where [`PipelineCache::load_shader`] reads the WGSL source of the shader
+/// `name` from, relative to the asset source.
+pub fn shader_path(name: &str) -> PathBuf {
+    format!("shaders/{}.wgsl", name).into()
+}
+
+/// Where [`PipelineCache::load_shader`] reads `variant` from if its source
+/// isn't there, e.g. in builds that embed compiled shaders.
+pub fn compiled_shader_path(variant: ShaderVariant) -> PathBuf {
+    format!("shaders/{}.spv", variant).into()
+}
+
+/// The WGSL source of a shader, handed over in code rather than read from
+/// [`shader_path`], e.g. by code outside the engine. Includes are still
+/// read from beside where its file would be.
+#[derive(Clone, Debug)]
+pub struct ShaderSource {
+    pub name: &'static str,
+    pub wgsl: String,
+}
+
+/// A shader compiled from WGSL.
+pub struct CompiledShader {
+    pub spirv: Vec<u8>,
+    /// The source and the files it includes, relative to the asset source.
+    pub files: Vec<PathBuf>,
+}
+
+/// Compiles `variant` from the WGSL `source` of its shader to SPIR-V,
+/// reading the includes through the [`vfs`](crate::vfs).
+pub fn compile_shader(
+    variant: ShaderVariant,
+    source: Vec<u8>,
+) -> Result<CompiledShader, ShaderError> {
+    let name = variant.name;
+    let source = String::from_utf8(source).map_err(|err| ShaderError::Io {
+        name,
+        error: io::Error::new(io::ErrorKind::InvalidData, err),
+    })?;
+    let file = shader_path(name);
+    let mut files = vec![file.clone()];
+    let mut include = |path: &str| {
+        files.push(path.into());
+        crate::vfs::read_to_string(path).map_err(|err| err.to_string())
+    };
+    let compiled = shader_compiler::compile(
+        &file.to_string_lossy(),
+        &source,
+        variant.features,
+        &mut include,
+    )?;
+    Ok(CompiledShader {
+        spirv: compiled.spirv,
+        files,
+    })
+}
+
+/// Owned, hashable form of a `wgpu::VertexBufferDescriptor`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub stride: wgpu::BufferAddress,
+    pub step_mode: wgpu::InputStepMode,
+    pub attributes: Vec<wgpu::VertexAttributeDescriptor>,
+}
+
+impl VertexLayout {
+    pub fn from_desc(desc: &wgpu::VertexBufferDescriptor) -> Self {
+        Self {
+            stride: desc.stride,
+            step_mode: desc.step_mode,
+            attributes: desc.attributes.to_vec(),
+        }
+    }
+}
+
+/// Everything that distinguishes one render pipeline variant from another.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub vertex_shader: ShaderId,
+    pub fragment_shader: Option<ShaderId>,
+    pub bind_group_layouts: Vec<LayoutId>,
+    pub vertex_layouts: Vec<VertexLayout>,
+    pub color_states: Vec<wgpu::ColorStateDescriptor>,
+    pub depth_stencil_state: Option<wgpu::DepthStencilStateDescriptor>,
+    pub primitive_topology: wgpu::PrimitiveTopology,
+    pub cull_mode: wgpu::CullMode,
+    pub index_format: wgpu::IndexFormat,
+    pub sample_count: u32,
+}
+
+/// Everything that distinguishes one compute pipeline from another.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ComputePipelineKey {
+    pub shader: ShaderId,
+    pub bind_group_layouts: Vec<LayoutId>,
+}
+
+/// Loads shader modules once and builds render and compute pipelines on
+/// first request.
+#[derive(Default)]
+pub struct PipelineCache {
+    shader_ids: HashMap<ShaderVariant, ShaderId>,
+    shaders: Vec<wgpu::ShaderModule>,
+    /// Reflected from each of `shaders`.
+    interfaces: Vec<ShaderInterface>,
+    /// What each shader loaded from source was compiled from.
+    files: HashMap<ShaderVariant, Vec<PathBuf>>,
+    layouts: HashMap<Vec<LayoutId>, wgpu::PipelineLayout>,
+    pipelines: HashMap<PipelineKey, Rc<wgpu::RenderPipeline>>,
+    compute_pipelines: HashMap<ComputePipelineKey, Rc<wgpu::ComputePipeline>>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the id of the SPIR-V shader registered as `variant`, e.g.
+    /// just a name, creating the module from `spirv` the first time.
+    pub fn shader(
+        &mut self,
+        device: &wgpu::Device,
+        variant: impl Into<ShaderVariant>,
+        spirv: &[u8],
+    ) -> Result<ShaderId, ShaderError> {
+        let variant = variant.into();
+        if let Some(&id) = self.shader_ids.get(&variant) {
+            return Ok(id);
+        }
+        let name = variant.name;
+
+        let data = wgpu::read_spirv(io::Cursor::new(spirv))
+            .map_err(|error| ShaderError::Io { name, error })?;
+        let interface = reflect(name, spirv)?;
+
+        let id = ShaderId(self.shaders.len());
+        self.shaders.push(device.create_shader_module(&data));
+        self.interfaces.push(interface);
+        self.shader_ids.insert(variant, id);
+        Ok(id)
+    }
+
+    /// Replaces the module registered as `variant` with `spirv`, e.g. after
+    /// its file changed, and rebuilds the pipelines that use it, so the
+    /// next lookups return the new ones. Returns how many were rebuilt, or
+    /// `None` if `variant` isn't registered.
+    ///
+    /// Invalid SPIR-V, or a shader that no longer fits the layouts of the
+    /// pipelines using it, leaves the old module and its pipelines in
+    /// place. Passes holding on to an old pipeline keep drawing with it
+    /// until they look it up again.
+    pub fn reload_shader(
+        &mut self,
+        device: &wgpu::Device,
+        bind_groups: &BindGroupCache,
+        variant: ShaderVariant,
+        spirv: &[u8],
+    ) -> Result<Option<usize>, ShaderError> {
+        let id = match self.shader_ids.get(&variant) {
+            Some(&id) => id,
+            None => return Ok(None),
+        };
+        let name = variant.name;
+        let data = wgpu::read_spirv(io::Cursor::new(spirv))
+            .map_err(|error| ShaderError::Io { name, error })?;
+        let interface = reflect(name, spirv)?;
+
+        let affected: Vec<PipelineKey> = self
+            .pipelines
+            .keys()
+            .filter(|key| key.vertex_shader == id || key.fragment_shader == Some(id))
+            .cloned()
+            .collect();
+        let affected_compute: Vec<ComputePipelineKey> = self
+            .compute_pipelines
+            .keys()
+            .filter(|key| key.shader == id)
+            .cloned()
+            .collect();
+        for key in &affected {
+            check(name, &interface, key, bind_groups)?;
+        }
+        for key in &affected_compute {
+            check_bindings(name, &interface, &key.bind_group_layouts, bind_groups)?;
+        }
+        self.shaders[id.0] = device.create_shader_module(&data);
+        self.interfaces[id.0] = interface;
+        for key in &affected {
+            let pipeline = self.build(device, bind_groups, key);
+            self.pipelines.insert(key.clone(), pipeline);
+        }
+        for key in &affected_compute {
+            let pipeline = self.build_compute(device, bind_groups, key);
+            self.compute_pipelines.insert(key.clone(), pipeline);
+        }
+        Ok(Some(affected.len() + affected_compute.len()))
+    }
+
+    /// Like [`PipelineCache::shader`], compiling the module from
+    /// [`shader_path`] through the [`vfs`](crate::vfs) the first time, or
+    /// reading it precompiled from [`compiled_shader_path`] if there is no
+    /// source.
+    pub fn load_shader(
+        &mut self,
+        device: &wgpu::Device,
+        name: &'static str,
+    ) -> Result<ShaderId, ShaderError> {
+        self.load_variant(device, ShaderVariant::from(name))
+    }
+
+    /// Like [`PipelineCache::load_shader`] for a variant of the shader,
+    /// which is compiled the first time it is asked for. Variants are
+    /// cached separately, even ones that come out the same because the
+    /// source doesn't check for some of their features.
+    pub fn load_variant(
+        &mut self,
+        device: &wgpu::Device,
+        variant: ShaderVariant,
+    ) -> Result<ShaderId, ShaderError> {
+        if let Some(&id) = self.shader_ids.get(&variant) {
+            return Ok(id);
+        }
+        let name = variant.name;
+        let spirv = match crate::vfs::read(shader_path(name)) {
+            Ok(source) => {
+                let compiled = compile_shader(variant, source)?;
+                self.files.insert(variant, compiled.files);
+                compiled.spirv
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                read_compiled(variant).map_err(|error| ShaderError::Io { name, error })?
+            }
+            Err(error) => return Err(ShaderError::Io { name, error }),
+        };
+        self.shader(device, variant, &spirv)
+    }
+
+    /// Like [`PipelineCache::shader`], compiling the module from `source`
+    /// the first time. Such shaders aren't hot reloaded.
+    pub fn load_source(
+        &mut self,
+        device: &wgpu::Device,
+        source: &ShaderSource,
+    ) -> Result<ShaderId, ShaderError> {
+        let variant = ShaderVariant::from(source.name);
+        if let Some(&id) = self.shader_ids.get(&variant) {
+            return Ok(id);
+        }
+        let compiled = compile_shader(variant, source.wgsl.clone().into_bytes())?;
+        self.shader(device, variant, &compiled.spirv)
+    }
+
+    /// Compiles `variant` from its source again and reloads it as
+    /// [`PipelineCache::reload_shader`] does, e.g. after the source or one
+    /// of its includes changed.
+    pub fn recompile_shader(
+        &mut self,
+        device: &wgpu::Device,
+        bind_groups: &BindGroupCache,
+        variant: ShaderVariant,
+    ) -> Result<Option<usize>, ShaderError> {
+        if !self.shader_ids.contains_key(&variant) {
+            return Ok(None);
+        }
+        let name = variant.name;
+        let source =
+            crate::vfs::read(shader_path(name)).map_err(|error| ShaderError::Io { name, error })?;
+        let compiled = compile_shader(variant, source)?;
+        let rebuilt = self.reload_shader(device, bind_groups, variant, &compiled.spirv)?;
+        // A shader that fails keeps the files of its last good version,
+        // which always include its source.
+        self.files.insert(variant, compiled.files);
+        Ok(rebuilt)
+    }
+
+    /// The files `variant` was compiled from, its source first, or nothing
+    /// if it was loaded precompiled.
+    pub fn shader_files(&self, variant: ShaderVariant) -> &[PathBuf] {
+        self.files.get(&variant).map_or(&[], Vec::as_slice)
+    }
+
+    /// What the shader binds and takes as vertex input.
+    pub fn interface(&self, id: ShaderId) -> &ShaderInterface {
+        &self.interfaces[id.0]
+    }
+
+    /// The bind group layouts the stages `shaders` of one pipeline need,
+    /// from group 0 up, as reflected from the shaders themselves.
+    pub fn bind_group_layouts(
+        &self,
+        shaders: &[ShaderId],
+    ) -> Result<Vec<Vec<wgpu::BindGroupLayoutEntry>>, ShaderError> {
+        let interfaces: Vec<&ShaderInterface> =
+            shaders.iter().map(|&id| self.interface(id)).collect();
+        shader_reflection::bind_group_layouts(&interfaces).map_err(|message| {
+            ShaderError::Interface {
+                name: self.name(shaders[0]),
+                message,
+            }
+        })
+    }
+
+    /// Checks that `T` is laid out like the buffer the shader `id` binds at
+    /// `binding` of `group`, so what is written to one is what the shader
+    /// reads.
+    pub fn check_buffer<T: GpuStruct>(
+        &self,
+        id: ShaderId,
+        group: u32,
+        binding: u32,
+    ) -> Result<(), ShaderError> {
+        let name = self.name(id);
+        let buffer =
+            self.interface(id)
+                .buffer(group, binding)
+                .ok_or_else(|| ShaderError::Interface {
+                    name,
+                    message: format!("no buffer at binding {} of group {}", binding, group),
+                })?;
+        T::check(buffer).map_err(|message| ShaderError::Interface { name, message })
+    }
+
+    /// Checks `key`'s vertex and bind group layouts against what its
+    /// shaders use, which wgpu 0.5 doesn't, so a mismatch is an error here
+    /// rather than garbage or a driver crash on the first draw.
+    pub fn validate(
+        &self,
+        key: &PipelineKey,
+        bind_groups: &BindGroupCache,
+    ) -> Result<(), ShaderError> {
+        for id in std::iter::once(key.vertex_shader).chain(key.fragment_shader) {
+            check(self.name(id), self.interface(id), key, bind_groups)?;
+        }
+        Ok(())
+    }
+
+    fn name(&self, id: ShaderId) -> &'static str {
+        self.shader_ids
+            .iter()
+            .find(|(_, &other)| other == id)
+            .map_or("?", |(variant, _)| variant.name)
+    }
+
+    /// The registered shader variants, in no particular order.
+    pub fn shader_variants(&self) -> impl Iterator<Item = ShaderVariant> + '_ {
+        self.shader_ids.keys().copied()
+    }
+
+    pub fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        bind_groups: &BindGroupCache,
+        key: &PipelineKey,
+    ) -> Rc<wgpu::RenderPipeline> {
+        if let Some(pipeline) = self.pipelines.get(key) {
+            return pipeline.clone();
+        }
+        let pipeline = self.build(device, bind_groups, key);
+        self.pipelines.insert(key.clone(), pipeline.clone());
+        pipeline
+    }
+
+    /// Checks `key`'s bind group layouts against what its shader uses, as
+    /// [`PipelineCache::validate`] does for render pipelines.
+    pub fn validate_compute(
+        &self,
+        key: &ComputePipelineKey,
+        bind_groups: &BindGroupCache,
+    ) -> Result<(), ShaderError> {
+        check_bindings(
+            self.name(key.shader),
+            self.interface(key.shader),
+            &key.bind_group_layouts,
+            bind_groups,
+        )
+    }
+
+    pub fn compute_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        bind_groups: &BindGroupCache,
+        key: &ComputePipelineKey,
+    ) -> Rc<wgpu::ComputePipeline> {
+        if let Some(pipeline) = self.compute_pipelines.get(key) {
+            return pipeline.clone();
+        }
+        let pipeline = self.build_compute(device, bind_groups, key);
+        self.compute_pipelines.insert(key.clone(), pipeline.clone());
+        pipeline
+    }
+
+    fn build_compute(
+        &mut self,
+        device: &wgpu::Device,
+        bind_groups: &BindGroupCache,
+        key: &ComputePipelineKey,
+    ) -> Rc<wgpu::ComputePipeline> {
+        let layout = pipeline_layout(
+            &mut self.layouts,
+            device,
+            bind_groups,
+            &key.bind_group_layouts,
+        );
+        Rc::new(
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                layout,
+                compute_stage: wgpu::ProgrammableStageDescriptor {
+                    module: &self.shaders[key.shader.0],
+                    entry_point: ENTRY_POINT,
+                },
+            }),
+        )
+    }
+
+    fn build(
+        &mut self,
+        device: &wgpu::Device,
+        bind_groups: &BindGroupCache,
+        key: &PipelineKey,
+    ) -> Rc<wgpu::RenderPipeline> {
+        let layout = pipeline_layout(
+            &mut self.layouts,
+            device,
+            bind_groups,
+            &key.bind_group_layouts,
+        );
+
+        let vertex_buffers = key
+            .vertex_layouts
+            .iter()
+            .map(|layout| wgpu::VertexBufferDescriptor {
+                stride: layout.stride,
+                step_mode: layout.step_mode,
+                attributes: &layout.attributes,
+            })
+            .collect::<Vec<_>>();
+
+        let shaders = &self.shaders;
+        Rc::new(
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                layout,
+                vertex_stage: wgpu::ProgrammableStageDescriptor {
+                    module: &shaders[key.vertex_shader.0],
+                    entry_point: "main",
+                },
+                fragment_stage: key
+                    .fragment_shader
+                    .map(|id| wgpu::ProgrammableStageDescriptor {
+                        module: &shaders[id.0],
+                        entry_point: "main",
+                    }),
+                rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: key.cull_mode,
+                    depth_bias: 0,
+                    depth_bias_slope_scale: 0.0,
+                    depth_bias_clamp: 0.0,
+                }),
+                color_states: &key.color_states,
+                primitive_topology: key.primitive_topology,
+                depth_stencil_state: key.depth_stencil_state.clone(),
+                vertex_state: wgpu::VertexStateDescriptor {
+                    index_format: key.index_format,
+                    vertex_buffers: &vertex_buffers,
+                },
+                sample_count: key.sample_count,
+                sample_mask: !0,
+                alpha_to_coverage_enabled: false,
+            }),
+        )
+    }
+}
+
+/// The pipeline layout of `bind_group_layouts`, created the first time.
+fn pipeline_layout<'a>(
+    layouts: &'a mut HashMap<Vec<LayoutId>, wgpu::PipelineLayout>,
+    device: &wgpu::Device,
+    bind_groups: &BindGroupCache,
+    bind_group_layouts: &[LayoutId],
+) -> &'a wgpu::PipelineLayout {
+    layouts
+        .entry(bind_group_layouts.to_vec())
+        .or_insert_with(|| {
+            let layouts = bind_group_layouts
+                .iter()
+                .map(|&id| bind_groups.layout(id))
+                .collect::<Vec<_>>();
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts: &layouts,
+            })
+        })
+}
+
+/// Reads `variant` precompiled. The build only compiles variants in the
+/// features their source checks for, which come out the same as all the
+/// others, so this falls back to the variant with the most of `variant`'s
+/// features that there is a file for.
+fn read_compiled(variant: ShaderVariant) -> io::Result<Vec<u8>> {
+    let mut subsets: Vec<ShaderFeatures> = variant.features.subsets().collect();
+    subsets.sort_by_key(|features| std::cmp::Reverse(features.names().count()));
+    for features in subsets {
+        match crate::vfs::read(compiled_shader_path(ShaderVariant::new(
+            variant.name,
+            features,
+        ))) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            result => return result,
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no compiled variant of {}", variant),
+    ))
+}
+
+fn reflect(name: &'static str, spirv: &[u8]) -> Result<ShaderInterface, ShaderError> {
+    ShaderInterface::reflect(spirv, ENTRY_POINT)
+        .map_err(|message| ShaderError::Interface { name, message })
+}
+
+/// Checks the shader `name` with `interface` against the layouts of `key`.
+fn check(
+    name: &'static str,
+    interface: &ShaderInterface,
+    key: &PipelineKey,
+    bind_groups: &BindGroupCache,
+) -> Result<(), ShaderError> {
+    interface
+        .check_vertex_layouts(&key.vertex_layouts)
+        .map_err(|message| ShaderError::Interface { name, message })?;
+    check_bindings(name, interface, &key.bind_group_layouts, bind_groups)
+}
+
+/// Checks the bindings of the shader `name` with `interface` against
+/// `bind_group_layouts`, from group 0 up.
+fn check_bindings(
+    name: &'static str,
+    interface: &ShaderInterface,
+    bind_group_layouts: &[LayoutId],
+    bind_groups: &BindGroupCache,
+) -> Result<(), ShaderError> {
+    let error = |message| ShaderError::Interface { name, message };
+    for binding in &interface.bindings {
+        let layout = bind_group_layouts
+            .get(binding.group as usize)
+            .ok_or_else(|| error(format!("no layout for bind group {}", binding.group)))?;
+        interface
+            .check_bind_group_layout(binding.group, &bind_groups.layout_entries(*layout))
+            .map_err(error)?;
+    }
+    Ok(())
+}
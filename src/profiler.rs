@@ -0,0 +1,231 @@
+//! A lightweight CPU scope profiler for the main thread.
+//!
+//! Scopes opened with [`scope`] between [`begin_frame`] and [`end_frame`]
+//! are recorded with their nesting depth, and the last [`HISTORY`] frames
+//! are kept for the in-app viewer. Nothing is recorded, and scopes cost a
+//! thread-local lookup, while the profiler is disabled.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{color::Color, material::Blend, passes::Layer, InterfacePass};
+
+/// Frames kept in the history.
+pub const HISTORY: usize = 120;
+
+#[derive(Clone, Debug)]
+pub struct ScopeRecord {
+    pub name: &'static str,
+    pub depth: u32,
+    /// Offset from the start of the frame.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FrameProfile {
+    pub duration: Duration,
+    /// Scopes in the order they were opened.
+    pub scopes: Vec<ScopeRecord>,
+}
+
+#[derive(Default)]
+struct Profiler {
+    enabled: bool,
+    frame_start: Option<Instant>,
+    current: FrameProfile,
+    /// Indices into `current.scopes` of the scopes still open.
+    open: Vec<usize>,
+    history: VecDeque<FrameProfile>,
+}
+
+thread_local! {
+    static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::default());
+}
+
+pub fn is_enabled() -> bool {
+    PROFILER.with(|profiler| profiler.borrow().enabled)
+}
+
+/// Turns recording on or off. Turning it off clears the history.
+pub fn set_enabled(enabled: bool) {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        profiler.enabled = enabled;
+        if !enabled {
+            profiler.frame_start = None;
+            profiler.history.clear();
+        }
+    });
+}
+
+pub fn begin_frame() {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        if profiler.enabled {
+            profiler.frame_start = Some(Instant::now());
+            profiler.current = FrameProfile::default();
+            profiler.open.clear();
+        }
+    });
+}
+
+pub fn end_frame() {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        if let Some(start) = profiler.frame_start.take() {
+            let mut frame = std::mem::take(&mut profiler.current);
+            frame.duration = start.elapsed();
+            if profiler.history.len() == HISTORY {
+                profiler.history.pop_front();
+            }
+            profiler.history.push_back(frame);
+        }
+    });
+}
+
+/// Records the time until the guard drops as `name`, nested in any scope
+/// that is already open.
+pub fn scope(name: &'static str) -> Scope {
+    PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        let start = match profiler.frame_start {
+            Some(start) => start,
+            None => return Scope { recording: false },
+        };
+        let depth = profiler.open.len() as u32;
+        let index = profiler.current.scopes.len();
+        profiler.current.scopes.push(ScopeRecord {
+            name,
+            depth,
+            start: start.elapsed(),
+            duration: Duration::default(),
+        });
+        profiler.open.push(index);
+        Scope { recording: true }
+    })
+}
+
+/// The most recently completed frame.
+pub fn last_frame() -> Option<FrameProfile> {
+    PROFILER.with(|profiler| profiler.borrow().history.back().cloned())
+}
+
+/// Durations of the frames in the history, oldest first.
+pub fn frame_times() -> Vec<Duration> {
+    PROFILER.with(|profiler| {
+        profiler
+            .borrow()
+            .history
+            .iter()
+            .map(|frame| frame.duration)
+            .collect()
+    })
+}
+
+pub struct Scope {
+    recording: bool,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if !self.recording {
+            return;
+        }
+        PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            let start = match profiler.frame_start {
+                Some(start) => start,
+                None => return,
+            };
+            if let Some(index) = profiler.open.pop() {
+                let record = &mut profiler.current.scopes[index];
+                record.duration = start.elapsed() - record.start;
+            }
+        });
+    }
+}
+
+/// Height of one nesting level in the viewer, in logical pixels.
+const ROW_HEIGHT: f32 = 12.0;
+/// Frame time spanning the full width of the viewer.
+const VIEW_SPAN: Duration = Duration::from_micros(16_667);
+
+/// Draws the last frame as a flame graph with its top-left corner at
+/// `origin`: one row per nesting level, bars scaled so `width` is a 60 Hz
+/// frame. Bars are coloured by scope name; [`log_last_frame`] lists them
+/// with their names and timings.
+pub fn draw(pass: &mut InterfacePass, origin: [f32; 2], width: f32) {
+    let frame = match last_frame() {
+        Some(frame) => frame,
+        None => return,
+    };
+
+    let rows = frame
+        .scopes
+        .iter()
+        .map(|scope| scope.depth + 1)
+        .max()
+        .unwrap_or(0);
+    let height = rows.max(1) as f32 * ROW_HEIGHT;
+    let palette = *pass.palette();
+    let layer = pass.layer();
+    pass.set_layer(Layer::OVERLAY);
+    pass.set_blend(Blend::ALPHA);
+    pass.draw_rect(
+        origin,
+        [origin[0] + width, origin[1] + height],
+        palette.panel,
+    );
+    pass.set_blend(Blend::REPLACE);
+
+    let scale = width / VIEW_SPAN.as_secs_f32();
+    let x = |offset: Duration| origin[0] + (offset.as_secs_f32() * scale).min(width);
+    for scope in &frame.scopes {
+        let top = origin[1] + scope.depth as f32 * ROW_HEIGHT;
+        let min = [x(scope.start), top + 1.0];
+        // Keep very short scopes visible.
+        let max = [
+            x(scope.start + scope.duration).max(min[0] + 1.0),
+            top + ROW_HEIGHT - 1.0,
+        ];
+        pass.draw_rect(min, max, scope_color(scope.name));
+    }
+
+    // The frame marker: where the frame ended relative to the 60 Hz budget.
+    let end = x(frame.duration);
+    pass.draw_rect(
+        [end - 1.0, origin[1]],
+        [end + 1.0, origin[1] + height],
+        palette.text,
+    );
+    pass.set_layer(layer);
+}
+
+/// Logs the last frame's scopes, indented by depth.
+pub fn log_last_frame() {
+    if let Some(frame) = last_frame() {
+        let mut text = format!("frame: {:.2} ms", frame.duration.as_secs_f64() * 1000.0);
+        for scope in &frame.scopes {
+            text.push_str(&format!(
+                "\n{:indent$}{}: {:.3} ms",
+                "",
+                scope.name,
+                scope.duration.as_secs_f64() * 1000.0,
+                indent = 2 + scope.depth as usize * 2
+            ));
+        }
+        log::info!("{}", text);
+    }
+}
+
+/// A stable, fairly saturated colour derived from `name`.
+fn scope_color(name: &str) -> Color {
+    let hash = name.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    Color::hsv((hash % 360) as f32, 0.6, 0.9, 1.0)
+}
@@ -0,0 +1,123 @@
+use std::{marker::PhantomData, rc::Rc};
+
+use crate::{
+    bind_group_cache::{Binding, LayoutId},
+    buffer_pool::PooledBuffer,
+    error::EngineError,
+    gpu_mem::BudgetExceeded,
+    Context,
+};
+
+/// Minimum alignment of dynamic uniform buffer offsets guaranteed by every
+/// backend.
+const DYNAMIC_OFFSET_ALIGNMENT: usize = 256;
+
+const INITIAL_SLOTS: usize = 64;
+
+/// Whether the device can take per-draw data as real push constants.
+///
+/// wgpu 0.5 exposes no push-constant extension, so this is always `false`
+/// for now and [`PushBlock`] falls back to a dynamic uniform buffer.
+pub fn supported(_adapter: &wgpu::Adapter) -> bool {
+    false
+}
+
+/// A small POD block of per-draw data.
+///
+/// Each [`PushBlock::push`] appends one value and returns the dynamic offset
+/// to pass to `set_bind_group` for the draw that uses it. The block binds as
+/// a single uniform buffer at binding 0 of its own bind group.
+pub struct PushBlock<T: bytemuck::Pod> {
+    layout: LayoutId,
+    data: Vec<u8>,
+    buffer: PooledBuffer,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> PushBlock<T> {
+    fn stride() -> usize {
+        let size = std::mem::size_of::<T>();
+        size.div_ceil(DYNAMIC_OFFSET_ALIGNMENT) * DYNAMIC_OFFSET_ALIGNMENT
+    }
+
+    pub fn new(
+        ctx: &mut Context,
+        label: &str,
+        visibility: wgpu::ShaderStage,
+    ) -> Result<Self, BudgetExceeded> {
+        let layout = ctx.bind_groups.layout_id(
+            &ctx.device,
+            label,
+            &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
+            }],
+        );
+
+        let buffer = ctx.buffer_pool.acquire(
+            &ctx.device,
+            &ctx.memory,
+            label,
+            (INITIAL_SLOTS * Self::stride()) as wgpu::BufferAddress,
+            wgpu::BufferUsage::UNIFORM,
+        )?;
+
+        Ok(Self {
+            layout,
+            data: vec![],
+            buffer,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn layout(&self) -> LayoutId {
+        self.layout
+    }
+
+    /// Queues `value` for this frame and returns its dynamic offset.
+    pub fn push(&mut self, value: T) -> wgpu::DynamicOffset {
+        let offset = self.data.len();
+        self.data.extend_from_slice(bytemuck::bytes_of(&value));
+        self.data.resize(offset + Self::stride(), 0);
+        offset as wgpu::DynamicOffset
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    pub fn upload(
+        &mut self,
+        ctx: &mut Context,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<(), EngineError> {
+        ctx.buffer_pool.grow(
+            &ctx.device,
+            &ctx.memory,
+            &mut self.buffer,
+            self.data.len() as wgpu::BufferAddress,
+        )?;
+
+        ctx.staging.write_buffer(
+            &ctx.device,
+            &ctx.memory,
+            encoder,
+            &self.buffer,
+            0,
+            &self.data,
+        )
+    }
+
+    pub fn bind_group(&self, ctx: &mut Context) -> Rc<wgpu::BindGroup> {
+        ctx.bind_groups.bind_group(
+            &ctx.device,
+            "push block",
+            self.layout,
+            &[Binding::Buffer {
+                buffer: self.buffer.buffer(),
+                range: 0..std::mem::size_of::<T>() as wgpu::BufferAddress,
+            }],
+        )
+    }
+}
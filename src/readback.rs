@@ -0,0 +1,83 @@
+use crate::{
+    error::{DeviceLost, EngineError, ReadbackError},
+    validation, Context,
+};
+
+/// `bytes_per_row` of a texture-to-buffer copy must be a multiple of this.
+const ROW_ALIGNMENT: u32 = 256;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Copies the `width` by `height` level 0 of `texture` back to the CPU,
+/// blocking until the GPU is done with it.
+///
+/// `format` must be one of the 8-bit RGBA or BGRA formats, or this fails
+/// with [`ReadbackError::UnsupportedFormat`]; BGRA is swizzled so the image
+/// is always RGBA. The texture needs `COPY_SRC` usage.
+pub fn read_rgba(
+    ctx: &Context,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> Result<image::RgbaImage, EngineError> {
+    let _scope = validation::scope("readback");
+    let swizzle = match format {
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        _ => return Err(ReadbackError::UnsupportedFormat(format).into()),
+    };
+    let row = width * BYTES_PER_PIXEL;
+    let bytes_per_row = row.div_ceil(ROW_ALIGNMENT) * ROW_ALIGNMENT;
+    let size = (bytes_per_row * height) as wgpu::BufferAddress;
+
+    let buffer = ctx.memory.create_buffer(
+        &ctx.device,
+        &wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        },
+    )?;
+
+    let mut encoder = ctx
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("readback"),
+        });
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView {
+            buffer: &buffer,
+            offset: 0,
+            bytes_per_row,
+            rows_per_image: height,
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+    );
+    ctx.queue.submit(&[encoder.finish()]);
+
+    let mapping = buffer.map_read(0, size);
+    ctx.device.poll(wgpu::Maintain::Wait);
+    let mapping = futures::executor::block_on(mapping).map_err(|_| DeviceLost)?;
+
+    let mut pixels = Vec::with_capacity((row * height) as usize);
+    for padded in mapping.as_slice().chunks(bytes_per_row as usize) {
+        pixels.extend_from_slice(&padded[..row as usize]);
+    }
+    if swizzle {
+        for pixel in pixels.chunks_exact_mut(BYTES_PER_PIXEL as usize) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Ok(image::RgbaImage::from_raw(width, height, pixels).unwrap())
+}
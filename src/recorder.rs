@@ -0,0 +1,135 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    thread,
+};
+
+/// Frames queued for the writer thread before new ones get dropped.
+const QUEUE_LENGTH: usize = 8;
+const FRAME_RATE: u32 = 60;
+
+/// Where a [`Recorder`] writes frames.
+#[derive(Clone, Debug)]
+pub enum Sink {
+    /// Numbered PNGs in this directory, which is created if needed.
+    Images(PathBuf),
+    /// A video encoded by piping raw frames to `ffmpeg`, which must be on the
+    /// `PATH`.
+    Ffmpeg(PathBuf),
+}
+
+/// Writes a sequence of frames on a background thread, so a slow disk or
+/// encoder only drops frames rather than stalling rendering.
+pub struct Recorder {
+    sender: Option<mpsc::SyncSender<image::RgbaImage>>,
+    writer: Option<thread::JoinHandle<()>>,
+    dropped: usize,
+}
+
+impl Recorder {
+    pub fn start(sink: Sink) -> io::Result<Self> {
+        if let Sink::Images(dir) = &sink {
+            fs::create_dir_all(dir)?;
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+        let writer = thread::Builder::new()
+            .name("recorder".into())
+            .spawn(move || {
+                if let Err(err) = write_frames(&sink, receiver) {
+                    log::error!("Recording to {:?} failed: {}", sink, err);
+                }
+            })?;
+
+        Ok(Self {
+            sender: Some(sender),
+            writer: Some(writer),
+            dropped: 0,
+        })
+    }
+
+    /// Queues `frame`, dropping it if the writer is behind.
+    pub fn push(&mut self, frame: image::RgbaImage) {
+        if let Some(sender) = &self.sender {
+            if sender.try_send(frame).is_err() {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Frames that were dropped because the writer couldn't keep up.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Waits for queued frames to be written.
+    pub fn stop(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+fn write_frames(sink: &Sink, frames: mpsc::Receiver<image::RgbaImage>) -> io::Result<()> {
+    let mut ffmpeg: Option<Child> = None;
+    let mut size = None;
+
+    for (index, frame) in frames.iter().enumerate() {
+        // Encoders need a fixed size; frames after a resize are skipped.
+        if *size.get_or_insert(frame.dimensions()) != frame.dimensions() {
+            continue;
+        }
+
+        match sink {
+            Sink::Images(dir) => frame
+                .save(dir.join(format!("frame-{:06}.png", index)))
+                .map_err(|err| io::Error::other(err.to_string()))?,
+            Sink::Ffmpeg(path) => {
+                let child = match &mut ffmpeg {
+                    Some(child) => child,
+                    None => ffmpeg.insert(spawn_ffmpeg(path, frame.dimensions())?),
+                };
+                child.stdin.as_mut().unwrap().write_all(&frame)?;
+            }
+        }
+    }
+
+    if let Some(mut child) = ffmpeg {
+        drop(child.stdin.take());
+        child.wait()?;
+    }
+    Ok(())
+}
+
+fn spawn_ffmpeg(path: &Path, (width, height): (u32, u32)) -> io::Result<Child> {
+    Command::new("ffmpeg")
+        .args([
+            "-loglevel",
+            "error",
+            "-y",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &FRAME_RATE.to_string(), "-i", "-"])
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+}
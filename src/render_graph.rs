@@ -0,0 +1,290 @@
+//! Orders passes by the attachments they read and write, and owns the
+//! transient attachments between them.
+//!
+//! The graph only records: the caller begins the [`Frame`] it records
+//! into and ends it once everything drawing into it has.
+//!
+//! Every pass writes one attachment: [`FRAME`], the frame being presented,
+//! or one declared with [`RenderGraph::declare`], which the graph allocates
+//! at a fraction of the frame size and can be sampled by later passes. A
+//! pass runs after every pass writing an attachment it reads; passes
+//! writing the same attachment keep the order they were given in.
+//!
+//! Passes record on the calling thread, one after the other, into the
+//! frame's encoder. wgpu 0.5 makes `CommandEncoder` neither `Send` nor
+//! `Sync`, as it allocates from the creating thread's command pool, so
+//! passes can't record on [`jobs`](crate::jobs) threads until wgpu is
+//! upgraded. CPU work that doesn't touch an encoder can run there before
+//! recording, as sprite culling does while the interface is built.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    color::Color, error::EngineError, gpu_mem::BudgetExceeded, passes::Pass, profiler,
+    render_target::RenderTarget, validation, Context, Frame,
+};
+
+/// The frame acquired from the context, multisampled if the context is.
+pub const FRAME: &str = "frame";
+
+/// A transient attachment.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AttachmentDesc {
+    pub format: wgpu::TextureFormat,
+    /// Size relative to the frame, e.g. 0.5 for half resolution.
+    pub scale: f32,
+    pub depth: bool,
+}
+
+#[derive(Debug)]
+pub enum GraphError {
+    /// A pass reads or writes an attachment that wasn't declared.
+    Undeclared {
+        pass: &'static str,
+        attachment: &'static str,
+    },
+    /// A pass reads an attachment no pass writes.
+    Unwritten {
+        pass: &'static str,
+        attachment: &'static str,
+    },
+    /// The passes depend on each other in a cycle.
+    Cycle(Vec<&'static str>),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::Undeclared { pass, attachment } => {
+                write!(f, "pass {} uses undeclared attachment {}", pass, attachment)
+            }
+            GraphError::Unwritten { pass, attachment } => write!(
+                f,
+                "pass {} reads {}, which no pass writes",
+                pass, attachment
+            ),
+            GraphError::Cycle(passes) => {
+                write!(f, "passes {} depend on each other", passes.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// The transient attachments of the current frame, for passes to sample.
+pub struct Attachments<'a> {
+    targets: &'a HashMap<&'static str, RenderTarget>,
+}
+
+impl Attachments<'_> {
+    /// `None` for [`FRAME`] and attachments no pass writes this frame.
+    pub fn get(&self, name: &str) -> Option<&RenderTarget> {
+        self.targets.get(name)
+    }
+}
+
+/// The attachment a pass records into.
+pub enum Output<'a> {
+    Frame(&'a wgpu::TextureView),
+    Transient(&'a RenderTarget),
+}
+
+impl Output<'_> {
+    /// Width and height in physical pixels.
+    pub fn size(&self, ctx: &Context) -> [u32; 2] {
+        match self {
+            Output::Frame(_) => [ctx.size.width, ctx.size.height],
+            Output::Transient(target) => [target.size().width, target.size().height],
+        }
+    }
+
+    /// Resolves into the frame if the context is multisampled.
+    pub fn color_attachment<'a>(
+        &'a self,
+        ctx: &'a Context,
+        load_op: wgpu::LoadOp,
+        clear_color: Color,
+    ) -> wgpu::RenderPassColorAttachmentDescriptor<'a> {
+        match self {
+            Output::Frame(view) => ctx.color_attachment(view, load_op, clear_color),
+            Output::Transient(target) => target.color_attachment(load_op, clear_color),
+        }
+    }
+
+    /// `Context::depth` for the frame; `None` for transient attachments
+    /// declared without depth.
+    pub fn depth_attachment<'a>(
+        &'a self,
+        ctx: &'a Context,
+    ) -> Option<wgpu::RenderPassDepthStencilAttachmentDescriptor<'a>> {
+        match self {
+            Output::Frame(_) => Some(ctx.depth.attachment()),
+            Output::Transient(target) => target.depth_attachment(),
+        }
+    }
+}
+
+/// Sample count pipelines drawing into `attachment` need.
+pub fn sample_count(ctx: &Context, attachment: &str) -> u32 {
+    if attachment == FRAME {
+        ctx.sample_count()
+    } else {
+        1
+    }
+}
+
+#[derive(Default)]
+pub struct RenderGraph {
+    declared: HashMap<&'static str, AttachmentDesc>,
+    targets: HashMap<&'static str, RenderTarget>,
+    clear_color: Option<Color>,
+}
+
+impl RenderGraph {
+    /// Clears [`FRAME`] to `color` before any pass draws into it. `None`,
+    /// the default, leaves it as it is, for when the first pass covers
+    /// all of it anyway.
+    pub fn set_clear_color(&mut self, color: Option<Color>) {
+        self.clear_color = color;
+    }
+
+    pub fn clear_color(&self) -> Option<Color> {
+        self.clear_color
+    }
+
+    /// Declares a transient attachment passes can write and read. It is
+    /// only allocated while some pass writes it.
+    pub fn declare(&mut self, name: &'static str, desc: AttachmentDesc) {
+        if self.declared.insert(name, desc) != Some(desc) {
+            self.targets.remove(name);
+        }
+    }
+
+    /// Drops every transient attachment, e.g. after `Context::recover`;
+    /// they are allocated again on the next frame.
+    pub fn release(&mut self) {
+        self.targets.clear();
+    }
+
+    /// The order `passes` would be recorded in, as indices into it.
+    pub fn schedule(&self, passes: &[&mut dyn Pass]) -> Result<Vec<usize>, GraphError> {
+        for pass in passes {
+            for &attachment in pass.reads().iter().chain(Some(&pass.writes())) {
+                if attachment != FRAME && !self.declared.contains_key(attachment) {
+                    return Err(GraphError::Undeclared {
+                        pass: pass.name(),
+                        attachment,
+                    });
+                }
+            }
+        }
+
+        // Each pass waits for the writers of what it reads and for earlier
+        // writers of what it writes.
+        let mut dependencies = vec![vec![]; passes.len()];
+        for (index, pass) in passes.iter().enumerate() {
+            for &attachment in pass.reads() {
+                let writers: Vec<usize> = (0..passes.len())
+                    .filter(|&other| other != index && passes[other].writes() == attachment)
+                    .collect();
+                if writers.is_empty() {
+                    return Err(GraphError::Unwritten {
+                        pass: pass.name(),
+                        attachment,
+                    });
+                }
+                dependencies[index].extend(writers);
+            }
+            dependencies[index]
+                .extend((0..index).filter(|&other| passes[other].writes() == pass.writes()));
+        }
+
+        // Repeatedly take the first pass whose dependencies are scheduled,
+        // so independent passes keep the order they were given in.
+        let mut order = Vec::with_capacity(passes.len());
+        let mut scheduled = vec![false; passes.len()];
+        while order.len() < passes.len() {
+            let next = (0..passes.len()).find(|&index| {
+                !scheduled[index] && dependencies[index].iter().all(|&other| scheduled[other])
+            });
+            match next {
+                Some(index) => {
+                    scheduled[index] = true;
+                    order.push(index);
+                }
+                None => {
+                    return Err(GraphError::Cycle(
+                        (0..passes.len())
+                            .filter(|&index| !scheduled[index])
+                            .map(|index| passes[index].name())
+                            .collect(),
+                    ))
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// Prepares and records `passes` into `frame`, in schedule order.
+    pub fn render(
+        &mut self,
+        ctx: &mut Context,
+        frame: &mut Frame,
+        passes: &mut [&mut dyn Pass],
+    ) -> Result<(), EngineError> {
+        let _scope = validation::scope("frame");
+        let _profile = profiler::scope("render");
+        let order = self.schedule(passes)?;
+        let written: Vec<&'static str> = passes.iter().map(|pass| pass.writes()).collect();
+        self.allocate(ctx, &written)?;
+
+        let attachments = Attachments {
+            targets: &self.targets,
+        };
+        for &index in &order {
+            passes[index].prepare(ctx, &mut frame.encoder, &attachments)?;
+        }
+        if let Some(color) = self.clear_color {
+            let output = Output::Frame(frame.target.view());
+            // Passes load the frame, so an empty pass clearing it is
+            // enough.
+            frame
+                .encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    color_attachments: &[output.color_attachment(ctx, wgpu::LoadOp::Clear, color)],
+                    depth_stencil_attachment: None,
+                });
+        }
+        for &index in &order {
+            let pass = &passes[index];
+            let output = match pass.writes() {
+                FRAME => Output::Frame(frame.target.view()),
+                name => Output::Transient(&self.targets[name]),
+            };
+            pass.record(ctx, &mut frame.encoder, &output);
+        }
+        Ok(())
+    }
+
+    /// Creates the attachments in `written` that are missing or no longer
+    /// match the frame size, and drops the ones nothing writes.
+    fn allocate(&mut self, ctx: &mut Context, written: &[&str]) -> Result<(), BudgetExceeded> {
+        self.targets.retain(|name, _| written.contains(name));
+        for (&name, desc) in &self.declared {
+            if !written.contains(&name) {
+                continue;
+            }
+            let width = ((ctx.sc_desc.width as f32 * desc.scale) as u32).max(1);
+            let height = ((ctx.sc_desc.height as f32 * desc.scale) as u32).max(1);
+            let current = self.targets.get(name).map(RenderTarget::size);
+            if !matches!(current, Some(size) if size.width == width && size.height == height) {
+                let label = format!("graph/{}", name);
+                let target =
+                    RenderTarget::new(ctx, &label, width, height, desc.format, desc.depth)?;
+                self.targets.insert(name, target);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use std::rc::Rc;
+
+use crate::{
+    bind_group_cache::{Binding, LayoutId},
+    color::Color,
+    gpu_mem::{self, BudgetExceeded},
+    sampler::SamplerDesc,
+    Context,
+};
+
+/// Depth with an 8-bit stencil, for masking.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// Depth-tested, depth-writing state for pipelines drawing into a
+/// [`DepthBuffer`]. The stencil is neither tested nor written.
+pub fn depth_stencil_state() -> wgpu::DepthStencilStateDescriptor {
+    wgpu::DepthStencilStateDescriptor {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_read_mask: 0,
+        stencil_write_mask: 0,
+    }
+}
+
+/// State for pipelines drawing into a [`DepthBuffer`] for its stencil
+/// only, which neither test nor write depth.
+pub fn stencil_only_state() -> wgpu::DepthStencilStateDescriptor {
+    wgpu::DepthStencilStateDescriptor {
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::Always,
+        ..depth_stencil_state()
+    }
+}
+
+/// A depth texture sized to match the color attachment it is used with.
+pub struct DepthBuffer {
+    texture: gpu_mem::Texture,
+    view: wgpu::TextureView,
+}
+
+impl DepthBuffer {
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &gpu_mem::GpuMemory,
+        label: &str,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Result<Self, BudgetExceeded> {
+        let texture = memory.create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            },
+        )?;
+        let view = texture.create_default_view();
+        Ok(Self { texture, view })
+    }
+
+    pub fn texture(&self) -> &gpu_mem::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Attachment that clears the buffer to the far plane, and the stencil
+    /// to 0.
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachmentDescriptor<'_> {
+        wgpu::RenderPassDepthStencilAttachmentDescriptor {
+            attachment: &self.view,
+            depth_load_op: wgpu::LoadOp::Clear,
+            depth_store_op: wgpu::StoreOp::Store,
+            clear_depth: 1.0,
+            stencil_load_op: wgpu::LoadOp::Clear,
+            stencil_store_op: wgpu::StoreOp::Store,
+            clear_stencil: 0,
+        }
+    }
+}
+
+/// A multisampled color buffer that is rendered into and resolved to a
+/// single-sampled view, such as the swap chain frame.
+pub struct MultisampleBuffer {
+    texture: gpu_mem::Texture,
+    view: wgpu::TextureView,
+}
+
+impl MultisampleBuffer {
+    pub fn new(
+        device: &wgpu::Device,
+        memory: &gpu_mem::GpuMemory,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Result<Self, BudgetExceeded> {
+        let texture = memory.create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            },
+        )?;
+        let view = texture.create_default_view();
+        Ok(Self { texture, view })
+    }
+
+    pub fn texture(&self) -> &gpu_mem::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
+
+/// An offscreen color texture, optionally with a depth buffer, that passes
+/// can render into instead of the swap chain frame and that can then be
+/// sampled like any other texture.
+pub struct RenderTarget {
+    label: String,
+    color: gpu_mem::Texture,
+    view: wgpu::TextureView,
+    /// Single-layer array view, so the target can stand in for the interface
+    /// atlas.
+    array_view: wgpu::TextureView,
+    depth: Option<DepthBuffer>,
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+}
+
+impl RenderTarget {
+    pub fn new(
+        ctx: &mut Context,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        with_depth: bool,
+    ) -> Result<Self, BudgetExceeded> {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+
+        let color = ctx.memory.create_texture(
+            &ctx.device,
+            &wgpu::TextureDescriptor {
+                label: Some(&format!("{}/color", label)),
+                size,
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            },
+        )?;
+        let view = color.create_default_view();
+        let array_view = color.create_view(&wgpu::TextureViewDescriptor {
+            format,
+            dimension: wgpu::TextureViewDimension::D2Array,
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            array_layer_count: 1,
+        });
+
+        let depth = if with_depth {
+            Some(DepthBuffer::new(
+                &ctx.device,
+                &ctx.memory,
+                &format!("{}/depth", label),
+                width,
+                height,
+                1,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            label: label.to_owned(),
+            color,
+            view,
+            array_view,
+            depth,
+            format,
+            size,
+        })
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn size(&self) -> wgpu::Extent3d {
+        self.size
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn color_attachment(
+        &self,
+        load_op: wgpu::LoadOp,
+        clear_color: Color,
+    ) -> wgpu::RenderPassColorAttachmentDescriptor<'_> {
+        wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: &self.view,
+            resolve_target: None,
+            load_op,
+            store_op: wgpu::StoreOp::Store,
+            clear_color: clear_color.into(),
+        }
+    }
+
+    /// Creates the target again on the context's current device, e.g. after
+    /// `Context::recover`. The contents have to be re-rendered.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded> {
+        *self = Self::new(
+            ctx,
+            &self.label,
+            self.size.width,
+            self.size.height,
+            self.format,
+            self.depth.is_some(),
+        )?;
+        Ok(())
+    }
+
+    /// Attachment for the depth buffer, cleared to the far plane. `None` if
+    /// the target was created without one.
+    pub fn depth_attachment(&self) -> Option<wgpu::RenderPassDepthStencilAttachmentDescriptor<'_>> {
+        self.depth.as_ref().map(DepthBuffer::attachment)
+    }
+
+    /// A bind group sampling the color texture through `layout`, which must
+    /// have a 2D array texture at binding 0 and a sampler at binding 1.
+    pub fn bind_group(&self, ctx: &mut Context, layout: LayoutId) -> Rc<wgpu::BindGroup> {
+        let sampler = ctx.create_sampler(SamplerDesc::LINEAR);
+        ctx.bind_groups.bind_group(
+            &ctx.device,
+            &self.label,
+            layout,
+            &[
+                Binding::TextureView {
+                    view: &self.array_view,
+                    id: self.color.id(),
+                },
+                Binding::Sampler {
+                    sampler: &sampler,
+                    id: sampler.id(),
+                },
+            ],
+        )
+    }
+}
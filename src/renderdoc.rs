@@ -0,0 +1,99 @@
+//! RenderDoc's in-application API, for triggering captures from a hotkey.
+//!
+//! Only available when the process was launched from RenderDoc or had it
+//! injected before the device was created; the library is never loaded
+//! here, since RenderDoc can't hook a device that already exists.
+
+use std::os::raw::{c_int, c_void};
+
+use libloading::Library;
+
+/// `eRENDERDOC_API_Version_1_1_0`, the oldest version with everything we
+/// call.
+const API_VERSION: c_int = 10100;
+
+type GetApi = unsafe extern "C" fn(version: c_int, api: *mut *mut c_void) -> c_int;
+
+/// The start of `RENDERDOC_API_1_1_0`, up to the last entry used.
+#[repr(C)]
+struct Api {
+    get_api_version: unsafe extern "C" fn(*mut c_int, *mut c_int, *mut c_int),
+    /// `SetCaptureOptionU32` through `GetCaptureFilePathTemplate`.
+    _unused: [*const c_void; 12],
+    get_num_captures: unsafe extern "C" fn() -> u32,
+    _get_capture: *const c_void,
+    trigger_capture: unsafe extern "C" fn(),
+}
+
+pub struct RenderDoc {
+    api: *const Api,
+    // Keeps the API table alive.
+    _library: Library,
+}
+
+impl RenderDoc {
+    /// Connects to RenderDoc if it is already loaded in this process.
+    pub fn attach() -> Option<Self> {
+        let library = loaded_library()?;
+        let mut api = std::ptr::null_mut();
+        unsafe {
+            let get_api = library.get::<GetApi>(b"RENDERDOC_GetAPI\0").ok()?;
+            if get_api(API_VERSION, &mut api) != 1 || api.is_null() {
+                return None;
+            }
+        }
+        Some(Self {
+            api: api as *const Api,
+            _library: library,
+        })
+    }
+
+    /// The version of the RenderDoc that is attached.
+    pub fn version(&self) -> (i32, i32, i32) {
+        let (mut major, mut minor, mut patch) = (0, 0, 0);
+        unsafe { ((*self.api).get_api_version)(&mut major, &mut minor, &mut patch) };
+        (major, minor, patch)
+    }
+
+    /// Captures the next presented frame.
+    pub fn trigger_capture(&self) {
+        unsafe { ((*self.api).trigger_capture)() }
+    }
+
+    /// Captures taken so far in this session.
+    pub fn capture_count(&self) -> u32 {
+        unsafe { ((*self.api).get_num_captures)() }
+    }
+}
+
+#[cfg(unix)]
+fn loaded_library() -> Option<Library> {
+    use libloading::os::unix;
+
+    // RTLD_NOLOAD: only succeed if RenderDoc is already there.
+    let library =
+        unix::Library::open(Some("librenderdoc.so"), libc::RTLD_NOW | libc::RTLD_NOLOAD).ok()?;
+    Some(library.into())
+}
+
+#[cfg(windows)]
+fn loaded_library() -> Option<Library> {
+    use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
+
+    use winapi::um::libloaderapi::GetModuleHandleW;
+
+    // Check the DLL is already injected before taking a reference to it.
+    let name = OsStr::new("renderdoc.dll")
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<_>>();
+    if unsafe { GetModuleHandleW(name.as_ptr()) }.is_null() {
+        return None;
+    }
+    Library::new("renderdoc.dll").ok()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn loaded_library() -> Option<Library> {
+    None
+}
@@ -0,0 +1,158 @@
+//! Seeded random numbers, so procedural content and replays come out the
+//! same from the same seed.
+//!
+//! [`Rngs`] hands each subsystem its own named stream, derived from the
+//! seed and the name alone. Drawing more numbers in one subsystem never
+//! shifts another's, e.g. a particle effect doesn't change the next level.
+//! The streams serialize with their position, for save files.
+//!
+//! The generator is PCG32 (XSH RR): small, fast, and its whole state is two
+//! integers. It is not suitable for anything security-related.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+/// One stream of pseudo-random numbers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rng {
+    #[serde(with = "hex")]
+    state: u64,
+    /// Odd; picks one of 2^63 independent sequences.
+    #[serde(with = "hex")]
+    increment: u64,
+}
+
+impl Rng {
+    /// The sequence `stream` of `seed`. Different streams of the same seed
+    /// don't overlap.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        // The top 24 bits, as many as an f32 mantissa holds.
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Uniform in `range`, which must not be empty.
+    pub fn range_u32(&mut self, range: Range<u32>) -> u32 {
+        assert!(range.start < range.end, "empty range {:?}", range);
+        let span = u64::from(range.end - range.start);
+        // Multiply-shift is biased by at most span / 2^32, which doesn't
+        // matter for gameplay.
+        range.start + ((u64::from(self.next_u32()) * span) >> 32) as u32
+    }
+
+    /// Uniform in `range`.
+    pub fn range_f32(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// `true` with probability `p`.
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.next_f32() < p
+    }
+
+    /// A random element, or `None` if `items` is empty.
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.range_u32(0..items.len() as u32) as usize)
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            let other = self.range_u32(0..index as u32 + 1) as usize;
+            items.swap(index, other);
+        }
+    }
+}
+
+/// The engine's random streams, one per subsystem name, all derived from
+/// one seed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rngs {
+    #[serde(with = "hex")]
+    seed: u64,
+    /// Ordered so saves come out the same.
+    streams: BTreeMap<String, Rng>,
+}
+
+impl Rngs {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: BTreeMap::new(),
+        }
+    }
+
+    /// Seeded from the clock, for runs that don't need to be reproduced.
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Self::new(nanos)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts every stream over from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+
+    /// The stream named `name`, e.g. `"particles"`, created on first use.
+    pub fn stream(&mut self, name: &str) -> &mut Rng {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_owned())
+            .or_insert_with(|| Rng::new(seed, fnv1a(name)))
+    }
+}
+
+/// Saves integers as hex strings, as TOML and RON files only hold signed
+/// 64-bit integers.
+mod hex {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#018x}", value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        u64::from_str_radix(text.trim_start_matches("0x"), 16).map_err(D::Error::custom)
+    }
+}
+
+/// A hash of `name` that, unlike std's, is the same on every build.
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,105 @@
+use std::{collections::HashMap, ops::Deref, rc::Rc};
+
+use crate::gpu_mem::{GpuMemory, Kind, Tracked};
+
+/// How a texture is filtered and addressed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub address_mode: wgpu::AddressMode,
+    /// Maximum anisotropy, 1 to disable.
+    ///
+    /// wgpu 0.5 can't configure anisotropic filtering yet, so this only
+    /// keeps samplers that ask for it apart in the cache for now.
+    pub anisotropy: u8,
+}
+
+impl SamplerDesc {
+    /// Blocky magnification, for pixel art.
+    pub const NEAREST: Self = Self {
+        filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        address_mode: wgpu::AddressMode::ClampToEdge,
+        anisotropy: 1,
+    };
+
+    /// Smooth filtering within a single mip level, for UI images.
+    pub const LINEAR: Self = Self {
+        filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        address_mode: wgpu::AddressMode::ClampToEdge,
+        anisotropy: 1,
+    };
+
+    /// Smooth filtering across mip levels, for repeating 3D content.
+    pub const TRILINEAR: Self = Self {
+        filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        address_mode: wgpu::AddressMode::Repeat,
+        anisotropy: 1,
+    };
+}
+
+/// A shared handle to a cached sampler.
+#[derive(Clone)]
+pub struct Sampler {
+    sampler: Rc<wgpu::Sampler>,
+    id: u64,
+}
+
+impl Sampler {
+    /// Identifies the sampler for as long as the cache keeps it.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Deref for Sampler {
+    type Target = wgpu::Sampler;
+
+    fn deref(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+}
+
+/// Creates each distinct sampler once and hands out shared handles to it.
+pub struct SamplerCache {
+    memory: GpuMemory,
+    samplers: HashMap<SamplerDesc, (Sampler, Tracked)>,
+}
+
+impl SamplerCache {
+    pub fn new(memory: GpuMemory) -> Self {
+        Self {
+            memory,
+            samplers: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, device: &wgpu::Device, desc: SamplerDesc) -> Sampler {
+        let memory = &self.memory;
+        let (sampler, _) = self.samplers.entry(desc).or_insert_with(|| {
+            let tracked = memory.track_object("sampler", Kind::Sampler);
+            let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: desc.address_mode,
+                address_mode_v: desc.address_mode,
+                address_mode_w: desc.address_mode,
+                mag_filter: desc.filter,
+                min_filter: desc.filter,
+                mipmap_filter: desc.mipmap_filter,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: 100.0,
+                compare: wgpu::CompareFunction::Undefined,
+            });
+            (
+                Sampler {
+                    sampler: Rc::new(sampler),
+                    id: tracked.id(),
+                },
+                tracked,
+            )
+        });
+        sampler.clone()
+    }
+}
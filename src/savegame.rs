@@ -0,0 +1,159 @@
+//! Save games, so a player's progress survives restarts.
+//!
+//! A save is a RON document holding the format version and the world as a
+//! [`Scene`]:
+//!
+//! ```text
+//! SaveGame(
+//!     version: 1,
+//!     world: Scene(
+//!         entities: [...],
+//!     ),
+//! )
+//! ```
+//!
+//! Saves outlive the build that wrote them. When the format changes, bump
+//! it by appending to [`MIGRATIONS`] a function that rewrites a document of
+//! the previous version into the new one; loading runs every migration from
+//! the file's version up before decoding it. Saves from a newer build are
+//! refused rather than guessed at.
+
+use std::{fmt, path::Path};
+
+use crate::{
+    ecs::{Entity, World},
+    scene::{
+        ron::{self, Value},
+        Scene, SceneError,
+    },
+};
+
+/// Where the game is saved unless `--save` names another file.
+pub const DEFAULT_PATH: &str = "save.ron";
+
+/// Rewrites a document of one version into the next, in place.
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` upgrades version `n + 1` to `n + 2`.
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// The version saves are written with.
+pub const VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Parse(ron::ParseError),
+    /// The file is valid RON but not a valid save.
+    Invalid(String),
+    /// Written by a newer build with this format version.
+    Unsupported(i64),
+    World(SceneError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "failed to access save game: {}", err),
+            SaveError::Parse(err) => write!(f, "failed to parse save game: {}", err),
+            SaveError::Invalid(message) => write!(f, "invalid save game: {}", message),
+            SaveError::Unsupported(version) => write!(
+                f,
+                "save game version {} is newer than the supported {}",
+                version, VERSION
+            ),
+            SaveError::World(err) => write!(f, "invalid save game world: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<ron::ParseError> for SaveError {
+    fn from(err: ron::ParseError) -> Self {
+        SaveError::Parse(err)
+    }
+}
+
+impl From<SceneError> for SaveError {
+    fn from(err: SceneError) -> Self {
+        SaveError::World(err)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SaveGame {
+    pub world: Scene,
+}
+
+impl SaveGame {
+    /// The entities of `world` scenes store.
+    pub fn from_world(world: &World) -> Self {
+        Self {
+            world: Scene::from_world(world),
+        }
+    }
+
+    /// Spawns the saved entities into `world`.
+    pub fn restore(&self, world: &mut World) -> Vec<Entity> {
+        self.world.spawn(world)
+    }
+
+    /// Reads a save from disk. Saves are the player's files, so this
+    /// doesn't go through the [`vfs`](crate::vfs).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Writes the save next to `path` first and then moves it over, so a
+    /// crash while saving leaves the previous save intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        let partial = path.with_extension("partial");
+        std::fs::write(&partial, self.to_ron())?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Decodes a save of any version up to [`VERSION`], migrating it first.
+    pub fn parse(source: &str) -> Result<Self, SaveError> {
+        let mut value = ron::parse(source)?;
+        let version = match value.field("version") {
+            Some(&Value::Integer(version)) if version > i64::from(VERSION) => {
+                return Err(SaveError::Unsupported(version));
+            }
+            Some(&Value::Integer(version)) if version >= 1 => version as u32,
+            _ => return Err(invalid("expected a version of at least 1")),
+        };
+        for (from, migrate) in (version..).zip(&MIGRATIONS[version as usize - 1..]) {
+            migrate(&mut value)
+                .map_err(|err| invalid(format!("migrating from version {}: {}", from, err)))?;
+        }
+        let world = value
+            .field("world")
+            .ok_or_else(|| invalid("missing world"))?;
+        Ok(Self {
+            world: Scene::from_value(world)?,
+        })
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::to_string(&Value::Struct(
+            Some("SaveGame"),
+            vec![
+                ("version".to_owned(), Value::Integer(i64::from(VERSION))),
+                ("world".to_owned(), self.world.to_value()),
+            ],
+        ))
+    }
+}
+
+fn invalid(message: impl Into<String>) -> SaveError {
+    SaveError::Invalid(message.into())
+}
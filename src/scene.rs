@@ -0,0 +1,384 @@
+//! Entity hierarchies saved to and loaded from RON files, so levels and
+//! screens are data rather than code.
+//!
+//! A scene is a list of entities with the components the engine knows
+//! about. Parents are indices into that list:
+//!
+//! ```text
+//! Scene(
+//!     entities: [
+//!         (
+//!             transform: Some((translation: (40.0, 60.0, 0.0))),
+//!             sprite: Some((size: (48.0, 48.0), color: (0.2, 0.4, 0.9, 1.0))),
+//!         ),
+//!         (
+//!             transform: Some((translation: (16.0, 16.0, 0.0))),
+//!             parent: Some(0),
+//!         ),
+//!     ],
+//! )
+//! ```
+//!
+//! Colors are sRGB, as tuples or hex strings like `"#3366e6"`. Missing
+//! components and transform fields take their defaults.
+
+pub mod ron;
+
+use std::{collections::HashMap, fmt, path::Path};
+
+use nalgebra as na;
+
+use self::ron::Value;
+use crate::{
+    color::{Color, ParseColorError},
+    components::{Parent, Sprite, Transform, Velocity},
+    ecs::{Entity, World},
+    vfs,
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse(ron::ParseError),
+    /// The file is valid RON but not a valid scene.
+    Invalid(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "failed to read scene: {}", err),
+            SceneError::Parse(err) => write!(f, "failed to parse scene: {}", err),
+            SceneError::Invalid(message) => write!(f, "invalid scene: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(err: std::io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+
+impl From<ron::ParseError> for SceneError {
+    fn from(err: ron::ParseError) -> Self {
+        SceneError::Parse(err)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneEntity {
+    pub transform: Option<Transform>,
+    /// Index of the parent in [`Scene::entities`].
+    pub parent: Option<usize>,
+    pub velocity: Option<Velocity>,
+    pub sprite: Option<Sprite>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+}
+
+impl Scene {
+    /// Every entity in `world` with a component scenes store. Parents that
+    /// aren't part of the scene are dropped.
+    pub fn from_world(world: &World) -> Self {
+        let entities: Vec<Entity> = world
+            .entities()
+            .filter(|&entity| {
+                world.get::<Transform>(entity).is_some()
+                    || world.get::<Parent>(entity).is_some()
+                    || world.get::<Velocity>(entity).is_some()
+                    || world.get::<Sprite>(entity).is_some()
+            })
+            .collect();
+        let indices: HashMap<Entity, usize> = entities
+            .iter()
+            .enumerate()
+            .map(|(index, &entity)| (entity, index))
+            .collect();
+
+        let entities = entities
+            .iter()
+            .map(|&entity| SceneEntity {
+                transform: world.get(entity).copied(),
+                parent: world
+                    .get::<Parent>(entity)
+                    .and_then(|parent| indices.get(&parent.0).copied()),
+                velocity: world.get(entity).copied(),
+                sprite: world.get(entity).copied(),
+            })
+            .collect();
+        Self { entities }
+    }
+
+    /// Spawns the scene's entities into `world`, in order.
+    pub fn spawn(&self, world: &mut World) -> Vec<Entity> {
+        let spawned: Vec<Entity> = self.entities.iter().map(|_| world.spawn()).collect();
+        for (&entity, scene_entity) in spawned.iter().zip(&self.entities) {
+            if let Some(transform) = scene_entity.transform {
+                world.insert(entity, transform);
+            }
+            if let Some(parent) = scene_entity.parent {
+                world.insert(entity, Parent(spawned[parent]));
+            }
+            if let Some(velocity) = scene_entity.velocity {
+                world.insert(entity, velocity);
+            }
+            if let Some(sprite) = scene_entity.sprite {
+                world.insert(entity, sprite);
+            }
+        }
+        spawned
+    }
+
+    /// Reads a scene through the [`vfs`](crate::vfs).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        Self::parse(&vfs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        std::fs::write(path, self.to_ron())?;
+        Ok(())
+    }
+
+    pub fn parse(source: &str) -> Result<Self, SceneError> {
+        Self::from_value(&ron::parse(source)?)
+    }
+
+    /// Decodes a scene from a parsed document, e.g. one embedded in a
+    /// [save game](crate::savegame).
+    pub fn from_value(value: &Value) -> Result<Self, SceneError> {
+        let items = value
+            .field("entities")
+            .and_then(Value::items)
+            .ok_or_else(|| invalid("expected a list of entities"))?;
+        let entities: Vec<SceneEntity> = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                decode_entity(item, index, items.len())
+                    .map_err(|err| invalid(format!("entity {}: {}", index, err)))
+            })
+            .collect::<Result<_, _>>()?;
+        if let Some(index) = parent_cycle(&entities) {
+            return Err(invalid(format!("entity {} is its own ancestor", index)));
+        }
+        Ok(Self { entities })
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::to_string(&self.to_value())
+    }
+
+    pub fn to_value(&self) -> Value {
+        let entities = self.entities.iter().map(encode_entity).collect();
+        Value::Struct(
+            Some("Scene"),
+            vec![("entities".to_owned(), Value::List(entities))],
+        )
+    }
+}
+
+fn invalid(message: impl Into<String>) -> SceneError {
+    SceneError::Invalid(message.into())
+}
+
+fn encode_entity(entity: &SceneEntity) -> Value {
+    let option = |value: Option<Value>| Value::Option(value.map(Box::new));
+    Value::Struct(
+        None,
+        vec![
+            (
+                "transform".to_owned(),
+                option(
+                    entity
+                        .transform
+                        .map(|transform| encode_transform(&transform)),
+                ),
+            ),
+            (
+                "parent".to_owned(),
+                option(entity.parent.map(|parent| Value::Integer(parent as i64))),
+            ),
+            (
+                "velocity".to_owned(),
+                option(entity.velocity.map(|velocity| floats(&velocity.0))),
+            ),
+            (
+                "sprite".to_owned(),
+                option(entity.sprite.map(|sprite| {
+                    Value::Struct(
+                        None,
+                        vec![
+                            ("size".to_owned(), floats(&sprite.size)),
+                            ("color".to_owned(), floats(&sprite.color.to_srgb())),
+                        ],
+                    )
+                })),
+            ),
+        ],
+    )
+}
+
+fn encode_transform(transform: &Transform) -> Value {
+    Value::Struct(
+        None,
+        vec![
+            (
+                "translation".to_owned(),
+                floats(transform.translation.as_slice()),
+            ),
+            (
+                "rotation".to_owned(),
+                floats(transform.rotation.coords.as_slice()),
+            ),
+            ("scale".to_owned(), floats(transform.scale.as_slice())),
+        ],
+    )
+}
+
+fn floats(values: &[f32]) -> Value {
+    Value::Tuple(values.iter().map(|&value| Value::Float(value)).collect())
+}
+
+/// The entity at `index` of `count`.
+fn decode_entity(value: &Value, index: usize, count: usize) -> Result<SceneEntity, String> {
+    let parent = match component(value, "parent")? {
+        Some(&Value::Integer(parent)) if parent == index as i64 => {
+            return Err("parent is the entity itself".to_owned())
+        }
+        Some(Value::Integer(parent)) if (0..count as i64).contains(parent) => {
+            Some(*parent as usize)
+        }
+        Some(_) => return Err("parent is not the index of an entity".to_owned()),
+        None => None,
+    };
+    Ok(SceneEntity {
+        transform: component(value, "transform")?
+            .map(decode_transform)
+            .transpose()?,
+        parent,
+        velocity: component(value, "velocity")?
+            .map(|value| decode_floats(value, "velocity").map(Velocity))
+            .transpose()?,
+        sprite: component(value, "sprite")?
+            .map(|value| {
+                Ok::<_, String>(Sprite {
+                    size: decode_floats(required(value, "size")?, "size")?,
+                    color: decode_color(required(value, "color")?)?,
+                })
+            })
+            .transpose()?,
+    })
+}
+
+/// The first entity found among its own ancestors, if parents go round in
+/// a circle anywhere.
+fn parent_cycle(entities: &[SceneEntity]) -> Option<usize> {
+    // The walk up from each entity stops at the first one an earlier walk
+    // went through, which already leads to a root, unless it was this walk.
+    let mut walked_from = vec![None; entities.len()];
+    for start in 0..entities.len() {
+        let mut current = Some(start);
+        while let Some(index) = current {
+            match walked_from[index] {
+                Some(walk) if walk == start => return Some(index),
+                Some(_) => break,
+                None => walked_from[index] = Some(start),
+            }
+            current = entities[index].parent;
+        }
+    }
+    None
+}
+
+fn decode_transform(value: &Value) -> Result<Transform, String> {
+    let mut transform = Transform::default();
+    if let Some(value) = value.field("translation") {
+        transform.translation = decode_floats::<3>(value, "translation")?.into();
+    }
+    if let Some(value) = value.field("rotation") {
+        let [i, j, k, w] = decode_floats(value, "rotation")?;
+        transform.rotation = na::UnitQuaternion::from_quaternion(na::Quaternion::new(w, i, j, k));
+    }
+    if let Some(value) = value.field("scale") {
+        transform.scale = decode_floats::<3>(value, "scale")?.into();
+    }
+    Ok(transform)
+}
+
+/// The inside of the optional component `name`, if it is there.
+fn component<'a>(entity: &'a Value, name: &str) -> Result<Option<&'a Value>, String> {
+    match entity.field(name) {
+        Some(Value::Option(value)) => Ok(value.as_deref()),
+        Some(_) => Err(format!("{} is not an option", name)),
+        None => Ok(None),
+    }
+}
+
+fn required<'a>(value: &'a Value, name: &str) -> Result<&'a Value, String> {
+    value.field(name).ok_or_else(|| format!("missing {}", name))
+}
+
+/// An sRGB tuple or hex string.
+fn decode_color(value: &Value) -> Result<Color, String> {
+    match value {
+        Value::String(hex) => hex.parse().map_err(|err: ParseColorError| err.to_string()),
+        value => decode_floats(value, "color").map(Color::from_srgb),
+    }
+}
+
+fn decode_floats<const N: usize>(value: &Value, name: &str) -> Result<[f32; N], String> {
+    let error = || format!("{} is not {} numbers", name, N);
+    let items = value.items().ok_or_else(error)?;
+    if items.len() != N {
+        return Err(error());
+    }
+    let mut floats = [0.0; N];
+    for (float, item) in floats.iter_mut().zip(items) {
+        *float = item.as_f32().ok_or_else(error)?;
+    }
+    Ok(floats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        match Scene::parse(source) {
+            Err(SceneError::Invalid(message)) => message,
+            Err(err) => panic!("expected an invalid scene, got {}", err),
+            Ok(_) => panic!("expected an invalid scene"),
+        }
+    }
+
+    #[test]
+    fn parents_round_trip() {
+        let scene = Scene::parse("(entities: [(), (parent: Some(0)), (parent: Some(1))])").unwrap();
+        let parents: Vec<_> = scene.entities.iter().map(|entity| entity.parent).collect();
+        assert_eq!(parents, [None, Some(0), Some(1)]);
+        let reparsed = Scene::parse(&scene.to_ron()).unwrap();
+        assert_eq!(reparsed.entities, scene.entities);
+    }
+
+    #[test]
+    fn rejects_self_parent() {
+        assert_eq!(
+            error("(entities: [(), (parent: Some(1))])"),
+            "entity 1: parent is the entity itself"
+        );
+    }
+
+    #[test]
+    fn rejects_parent_cycles() {
+        assert_eq!(
+            error("(entities: [(), (parent: Some(2)), (parent: Some(3)), (parent: Some(1))])"),
+            "entity 1 is its own ancestor"
+        );
+    }
+}
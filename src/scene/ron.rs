@@ -0,0 +1,433 @@
+//! Just enough RON (Rusty Object Notation) for scene files.
+//!
+//! Supports numbers, including `inf`, `-inf` and `NaN`, strings, booleans,
+//! `None`/`Some(..)`, tuples, lists and structs with or without a name,
+//! trailing commas and `//` comments. Enums other than `Option`, maps and
+//! escapes other than `\"` and `\\` are not supported.
+
+use std::fmt::{self, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Integer(i64),
+    Float(f32),
+    String(String),
+    Option(Option<Box<Value>>),
+    Tuple(Vec<Value>),
+    /// A struct's fields in order. The name, if any, is only written.
+    Struct(Option<&'static str>, Vec<(String, Value)>),
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Value::Float(value) => Some(value),
+            Value::Integer(value) => Some(value as f32),
+            _ => None,
+        }
+    }
+
+    /// The field `name` of a struct.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(_, fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// The items of a tuple or list.
+    pub fn items(&self) -> Option<&[Value]> {
+        match self {
+            Value::Tuple(items) | Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Where and why a document isn't valid RON.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(source: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { source, offset: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.offset < source.len() {
+        return Err(parser.error("expected the end of the document"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.offset..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        let before = &self.source[..self.offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+        ParseError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.offset += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.offset += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consumes `c` after any whitespace, if it is next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.offset += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", c)))
+        }
+    }
+
+    fn identifier(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if length == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        self.offset += length;
+        Some(&self.source[self.offset - length..self.offset])
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => self.parenthesized(),
+            Some('[') => {
+                self.offset += 1;
+                let items = self.sequence(']')?;
+                Ok(Value::List(items))
+            }
+            Some('"') => self.string().map(Value::String),
+            Some(c) if c == '-' || c == '+' || c.is_ascii_digit() => self.number(),
+            _ => {
+                let start = self.offset;
+                match self.identifier() {
+                    Some("true") => Ok(Value::Bool(true)),
+                    Some("false") => Ok(Value::Bool(false)),
+                    Some("None") => Ok(Value::Option(None)),
+                    Some("inf") => Ok(Value::Float(f32::INFINITY)),
+                    Some("NaN") => Ok(Value::Float(f32::NAN)),
+                    Some("Some") => {
+                        self.expect('(')?;
+                        let value = self.value()?;
+                        self.eat(',');
+                        self.expect(')')?;
+                        Ok(Value::Option(Some(Box::new(value))))
+                    }
+                    // A struct name, which is optional and ignored.
+                    Some(_) if self.rest().trim_start().starts_with('(') => self.parenthesized(),
+                    _ => {
+                        self.offset = start;
+                        Err(self.error("expected a value"))
+                    }
+                }
+            }
+        }
+    }
+
+    /// A tuple or a struct, starting at its `(`.
+    fn parenthesized(&mut self) -> Result<Value, ParseError> {
+        self.expect('(')?;
+        let start = self.offset;
+        let is_struct = self.identifier().is_some() && self.eat(':');
+        self.offset = start;
+        if !is_struct {
+            return self.sequence(')').map(Value::Tuple);
+        }
+
+        let mut fields = vec![];
+        while !self.eat(')') {
+            let name = match self.identifier() {
+                Some(name) => name.to_owned(),
+                None => return Err(self.error("expected a field name")),
+            };
+            self.expect(':')?;
+            fields.push((name, self.value()?));
+            if !self.eat(',') {
+                self.expect(')')?;
+                break;
+            }
+        }
+        Ok(Value::Struct(None, fields))
+    }
+
+    /// Comma-separated values up to `end`, after the opening bracket.
+    fn sequence(&mut self, end: char) -> Result<Vec<Value>, ParseError> {
+        let mut items = vec![];
+        while !self.eat(end) {
+            items.push(self.value()?);
+            if !self.eat(',') {
+                self.expect(end)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.offset += index + 1;
+                    return Ok(string);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c)) if c == '"' || c == '\\' => string.push(c),
+                    _ => {
+                        self.offset += index;
+                        return Err(self.error("unsupported escape sequence"));
+                    }
+                },
+                c => string.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let rest = self.rest();
+        // Signed infinities; `inf` and `NaN` alone are read as identifiers.
+        for &(name, value) in &[("-inf", f32::NEG_INFINITY), ("+inf", f32::INFINITY)] {
+            let after = rest.strip_prefix(name);
+            if after.is_some_and(|after| {
+                !after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+            }) {
+                self.offset += name.len();
+                return Ok(Value::Float(value));
+            }
+        }
+        let length = rest
+            .char_indices()
+            .find(|&(index, c)| {
+                !(c.is_ascii_digit()
+                    || c == '.'
+                    || c == 'e'
+                    || c == 'E'
+                    || c == '_'
+                    || ((c == '-' || c == '+')
+                        && (index == 0 || rest[..index].ends_with(['e', 'E']))))
+            })
+            .map_or(rest.len(), |(index, _)| index);
+        let text = rest[..length].replace('_', "");
+        let value = if text.contains(['.', 'e', 'E']) {
+            text.parse().ok().map(Value::Float)
+        } else {
+            text.parse().ok().map(Value::Integer)
+        };
+        match value {
+            Some(value) => {
+                self.offset += length;
+                Ok(value)
+            }
+            None => Err(self.error(format!("invalid number `{}`", text))),
+        }
+    }
+}
+
+/// Writes `value` with structs and lists spread over indented lines.
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, 0);
+    out.push('\n');
+    out
+}
+
+fn write_value(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Bool(value) => write!(out, "{}", value).unwrap(),
+        Value::Integer(value) => write!(out, "{}", value).unwrap(),
+        // Debug keeps the decimal point, so the value reads back as a float,
+        // and writes `inf`, `-inf` and `NaN` the way they are parsed.
+        Value::Float(value) => write!(out, "{:?}", value).unwrap(),
+        Value::String(value) => {
+            out.push('"');
+            for c in value.chars() {
+                if c == '"' || c == '\\' {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+        }
+        Value::Option(None) => out.push_str("None"),
+        Value::Option(Some(value)) => {
+            out.push_str("Some(");
+            write_value(out, value, indent);
+            out.push(')');
+        }
+        Value::Tuple(items) => {
+            out.push('(');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, item, indent);
+            }
+            out.push(')');
+        }
+        Value::Struct(name, fields) => {
+            out.push_str(name.unwrap_or(""));
+            out.push_str("(\n");
+            for (field, value) in fields {
+                write_indent(out, indent + 1);
+                write!(out, "{}: ", field).unwrap();
+                write_value(out, value, indent + 1);
+                out.push_str(",\n");
+            }
+            write_indent(out, indent);
+            out.push(')');
+        }
+        Value::List(items) if items.is_empty() => out.push_str("[]"),
+        Value::List(items) => {
+            out.push_str("[\n");
+            for item in items {
+                write_indent(out, indent + 1);
+                write_value(out, item, indent + 1);
+                out.push_str(",\n");
+            }
+            write_indent(out, indent);
+            out.push(']');
+        }
+    }
+}
+
+fn write_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("    ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(source: &str) -> (usize, usize) {
+        let err = parse(source).unwrap_err();
+        (err.line, err.column)
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = Value::Struct(
+            Some("Scene"),
+            vec![
+                ("flag".to_owned(), Value::Bool(true)),
+                ("count".to_owned(), Value::Integer(-3)),
+                (
+                    "floats".to_owned(),
+                    Value::Tuple(vec![
+                        Value::Float(1.0),
+                        Value::Float(-0.5),
+                        Value::Float(1e10),
+                        Value::Float(f32::INFINITY),
+                        Value::Float(f32::NEG_INFINITY),
+                    ]),
+                ),
+                (
+                    "name".to_owned(),
+                    Value::String("say \"hi\" \\ bye".to_owned()),
+                ),
+                (
+                    "items".to_owned(),
+                    Value::List(vec![
+                        Value::Option(None),
+                        Value::Option(Some(Box::new(Value::Struct(
+                            None,
+                            vec![("inner".to_owned(), Value::List(vec![]))],
+                        )))),
+                    ]),
+                ),
+            ],
+        );
+        let parsed = parse(&to_string(&value)).unwrap();
+        // Names are only written, so the parsed struct has none.
+        match (&parsed, &value) {
+            (Value::Struct(None, parsed), Value::Struct(_, fields)) => assert_eq!(parsed, fields),
+            _ => panic!("expected a struct, got {:?}", parsed),
+        }
+    }
+
+    #[test]
+    fn round_trip_nan() {
+        match parse(&to_string(&Value::Float(f32::NAN))).unwrap() {
+            Value::Float(value) => assert!(value.is_nan()),
+            value => panic!("expected a float, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn infinity_needs_a_boundary() {
+        assert_eq!(parse("-inf").unwrap(), Value::Float(f32::NEG_INFINITY));
+        assert_eq!(position("-infinity"), (1, 1));
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(position("(\n    a: 1,\n    b 2,\n)"), (3, 7));
+        assert_eq!(position("[1, 2,, 3]"), (1, 7));
+        assert_eq!(position("(a: 1.2.3)"), (1, 5));
+        assert_eq!(position("// comment\n\"open"), (2, 2));
+        assert_eq!(position("\"bad \\n\""), (1, 6));
+        assert_eq!(position("(a: 1) x"), (1, 8));
+    }
+}
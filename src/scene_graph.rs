@@ -0,0 +1,91 @@
+//! Parent/child hierarchies of entities.
+//!
+//! An entity's [`Transform`] is relative to its [`Parent`]'s, and
+//! [`propagate`] combines them into a [`GlobalTransform`] for everything
+//! that draws. A parent that was despawned or has no transform is ignored,
+//! leaving the child a root.
+
+use std::collections::HashMap;
+
+use nalgebra as na;
+
+use crate::{
+    components::{GlobalTransform, Parent, Transform},
+    ecs::{Entity, World},
+};
+
+/// Updates the [`GlobalTransform`] of every entity with a [`Transform`].
+/// Call once per update, after the systems moving things.
+pub fn propagate(world: &mut World) {
+    let locals: HashMap<Entity, (na::Matrix4<f32>, Option<Entity>)> = world
+        .query::<Transform>()
+        .map(|(entity, transform)| {
+            let parent = world.get::<Parent>(entity).map(|parent| parent.0);
+            (entity, (transform.matrix(), parent))
+        })
+        .collect();
+
+    let mut globals = HashMap::with_capacity(locals.len());
+    for &entity in locals.keys() {
+        resolve(entity, &locals, &mut globals, &mut vec![]);
+    }
+
+    for (entity, matrix) in globals {
+        match world.get_mut::<GlobalTransform>(entity) {
+            Some(global) => {
+                global.previous = global.matrix;
+                global.matrix = matrix;
+            }
+            None => world.insert(
+                entity,
+                GlobalTransform {
+                    matrix,
+                    previous: matrix,
+                },
+            ),
+        }
+    }
+}
+
+/// Computes the global matrix of `entity` and its ancestors into `globals`.
+/// `path` holds the descendants being resolved, to break cycles.
+fn resolve(
+    entity: Entity,
+    locals: &HashMap<Entity, (na::Matrix4<f32>, Option<Entity>)>,
+    globals: &mut HashMap<Entity, na::Matrix4<f32>>,
+    path: &mut Vec<Entity>,
+) -> na::Matrix4<f32> {
+    if let Some(&global) = globals.get(&entity) {
+        return global;
+    }
+    let (local, parent) = locals[&entity];
+    let parent = parent.filter(|parent| locals.contains_key(parent) && !path.contains(parent));
+    let global = match parent {
+        Some(parent) => {
+            path.push(entity);
+            let parent = resolve(parent, locals, globals, path);
+            path.pop();
+            parent * local
+        }
+        None => local,
+    };
+    globals.insert(entity, global);
+    global
+}
+
+/// The entities whose [`Parent`] is `entity`.
+pub fn children(world: &World, entity: Entity) -> Vec<Entity> {
+    world
+        .query::<Parent>()
+        .filter(|(_, parent)| parent.0 == entity)
+        .map(|(child, _)| child)
+        .collect()
+}
+
+/// Despawns `entity` and everything under it.
+pub fn despawn_recursive(world: &mut World, entity: Entity) {
+    for child in children(world, entity) {
+        despawn_recursive(world, child);
+    }
+    world.despawn(entity);
+}
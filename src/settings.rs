@@ -0,0 +1,140 @@
+//! User settings kept in a TOML file between runs.
+//!
+//! Every field is optional in the file; missing ones take their defaults,
+//! so an empty or missing file is valid. Environment variables and command
+//! line flags override the file for a single run without being saved.
+
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+use crate::display::DisplayMode;
+
+/// Where settings are read from unless `CONFIG_FILE` names another file.
+pub const DEFAULT_PATH: &str = "config.toml";
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(err) => write!(f, "failed to access settings: {}", err),
+            SettingsError::Parse(err) => write!(f, "failed to parse settings: {}", err),
+            SettingsError::Serialize(err) => write!(f, "failed to write settings: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(err: std::io::Error) -> Self {
+        SettingsError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for SettingsError {
+    fn from(err: toml::de::Error) -> Self {
+        SettingsError::Parse(err)
+    }
+}
+
+impl From<toml::ser::Error> for SettingsError {
+    fn from(err: toml::ser::Error) -> Self {
+        SettingsError::Serialize(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Window size in physical pixels. `None` leaves it to the platform.
+    pub resolution: Option<[u32; 2]>,
+    pub vsync: bool,
+    pub fullscreen: DisplayMode,
+    /// Samples per pixel; 1 disables multisampling.
+    pub msaa: u32,
+    /// Size in logical pixels the interface is laid out at, letterboxed in
+    /// the window. `None` lays it out on the window itself.
+    pub interface_size: Option<[f32; 2]>,
+    /// Anything [`AdapterChoice::parse`](crate::adapter::AdapterChoice::parse)
+    /// accepts.
+    pub adapter: String,
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            resolution: None,
+            vsync: true,
+            fullscreen: DisplayMode::Windowed,
+            msaa: 1,
+            interface_size: None,
+            adapter: "high-performance".to_owned(),
+            key_bindings: KeyBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings at `path`, or the defaults if there is no file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SettingsError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SettingsError> {
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Keys for the engine's own shortcuts, by `VirtualKeyCode` name, e.g.
+/// `"F12"` or `"V"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    /// Pressed with Alt.
+    pub toggle_fullscreen: VirtualKeyCode,
+    pub toggle_vsync: VirtualKeyCode,
+    pub screenshot: VirtualKeyCode,
+    pub renderdoc_capture: VirtualKeyCode,
+    pub record: VirtualKeyCode,
+    pub overlay: VirtualKeyCode,
+    pub profiler: VirtualKeyCode,
+    pub save_scene: VirtualKeyCode,
+    /// Pauses and resumes the simulation.
+    pub pause_time: VirtualKeyCode,
+    /// Switches between real time and slow motion.
+    pub slow_motion: VirtualKeyCode,
+    /// Outlines the triangles of the interface.
+    pub wireframe: VirtualKeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            toggle_fullscreen: VirtualKeyCode::Return,
+            toggle_vsync: VirtualKeyCode::V,
+            screenshot: VirtualKeyCode::F12,
+            renderdoc_capture: VirtualKeyCode::F8,
+            record: VirtualKeyCode::F9,
+            overlay: VirtualKeyCode::F3,
+            profiler: VirtualKeyCode::F4,
+            save_scene: VirtualKeyCode::F6,
+            pause_time: VirtualKeyCode::F5,
+            slow_motion: VirtualKeyCode::F7,
+            wireframe: VirtualKeyCode::F2,
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Compiles the WGSL shaders in `assets/shaders` to SPIR-V, so the
+//! sources are the only copy kept in the repository.
+//!
+//! wgpu 0.5 only takes SPIR-V, so until it is upgraded to a release that
+//! accepts WGSL itself, naga translates each shader. There is one file per
+//! stage, e.g. `interface.vert.wgsl`, with a `main` entry point.
+//!
+//! Shaders share code through `#include "common.wgsl"` lines, which are
+//! replaced by the named file, relative to the including one. A file is
+//! only included once per shader, so includes can include each other.
+//! Files that aren't `<name>.<stage>.wgsl` are only ever included.
+//!
+//! One source can also serve several variants of a shader: lines between
+//! `#ifdef TEXTURED` and `#else` or `#endif` are only compiled into the
+//! variants with that feature, and `#ifndef` does the opposite. The
+//! features are the fixed set in [`ShaderFeatures`], so a misspelt one is
+//! an error rather than a branch that is never taken.
+//!
+//! Shaders are compiled when they are first loaded; builds with the
+//! `embed-assets` feature compile them in the build script and embed the
+//! result. Both go through [`compile`], which `build.rs` includes by path,
+//! so this module only depends on std and naga.
+
+use std::{collections::HashSet, fmt, ops};
+
+use naga::{
+    back::spv,
+    front::wgsl,
+    valid::{Capabilities, ValidationFlags, Validator},
+};
+
+/// The entry point every shader file defines.
+pub const ENTRY_POINT: &str = "main";
+
+/// The features a shader variant is compiled with.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderFeatures(u32);
+
+/// The names of the features in [`ShaderFeatures`], by bit.
+const FEATURE_NAMES: &[&str] = &[
+    "TEXTURED",
+    "SDF_TEXT",
+    "VERTEX_COLOR",
+    "PREMULTIPLIED",
+    "INSTANCED",
+];
+
+impl ShaderFeatures {
+    pub const NONE: Self = Self(0);
+    /// Samples a texture.
+    pub const TEXTURED: Self = Self(1 << 0);
+    /// Reads the texture as a signed distance field, for sharp text at any
+    /// scale.
+    pub const SDF_TEXT: Self = Self(1 << 1);
+    /// Tints by the vertex color.
+    pub const VERTEX_COLOR: Self = Self(1 << 2);
+    /// Outputs premultiplied alpha, from premultiplied textures.
+    pub const PREMULTIPLIED: Self = Self(1 << 3);
+    /// Places each instance by per-instance attributes.
+    pub const INSTANCED: Self = Self(1 << 4);
+
+    /// The feature a directive names, e.g. `TEXTURED`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        FEATURE_NAMES
+            .iter()
+            .position(|&other| other == name)
+            .map(|bit| Self(1 << bit))
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The names of the features set, in bit order.
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        (0..).zip(FEATURE_NAMES).filter_map(move |(bit, &name)| {
+            if self.0 & (1 << bit) != 0 {
+                Some(name)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Every combination of the features set, starting with none.
+    pub fn subsets(self) -> impl Iterator<Item = Self> {
+        (0..=self.0)
+            .filter(move |bits| bits & !self.0 == 0)
+            .map(Self)
+    }
+}
+
+impl ops::BitOr for ShaderFeatures {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl ops::BitOrAssign for ShaderFeatures {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl fmt::Debug for ShaderFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("NONE");
+        }
+        let names: Vec<_> = self.names().collect();
+        f.write_str(&names.join(" | "))
+    }
+}
+
+/// The name of the variant of the shader `name` with `features`, e.g.
+/// `interface.frag+TEXTURED+VERTEX_COLOR`, or just `name` without any.
+pub fn variant_name(name: &str, features: ShaderFeatures) -> String {
+    features
+        .names()
+        .fold(name.to_owned(), |variant, feature| variant + "+" + feature)
+}
+
+/// Where and why a shader failed to compile.
+#[derive(Debug)]
+pub struct CompileError {
+    pub file: String,
+    /// 1-based, or 0 if the error isn't about a specific line.
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(
+                f,
+                "{}:{}:{}: {}",
+                self.file, self.line, self.column, self.message
+            )
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Reads the file an `#include` names, given its path, e.g.
+/// `shaders/common.wgsl`.
+pub type Include<'a> = dyn FnMut(&str) -> Result<String, String> + 'a;
+
+/// Whether `file`, e.g. `interface.vert.wgsl`, is a shader rather than a
+/// file shaders include.
+pub fn is_shader(file: &str) -> bool {
+    matches!(
+        file.strip_suffix(".wgsl")
+            .and_then(|name| name.rsplit_once('.')),
+        Some((_, "vert" | "frag" | "comp"))
+    )
+}
+
+/// A shader variant compiled to SPIR-V.
+pub struct Compiled {
+    pub spirv: Vec<u8>,
+    /// The features the source checks for, i.e. that variants of it can
+    /// differ in. Only counts the files it included for this variant.
+    pub tested: ShaderFeatures,
+}
+
+/// Compiles the variant with `features` of the WGSL `source` of `file`,
+/// e.g. `shaders/interface.vert.wgsl`, reading the files it includes with
+/// `include`.
+pub fn compile(
+    file: &str,
+    source: &str,
+    features: ShaderFeatures,
+    include: &mut Include,
+) -> Result<Compiled, CompileError> {
+    let mut preprocessor = Preprocessor {
+        include,
+        features,
+        tested: ShaderFeatures::NONE,
+        included: HashSet::new(),
+        files: vec![],
+        lines: vec![],
+        output: String::new(),
+    };
+    preprocessor.expand(file, source)?;
+    let Preprocessor {
+        tested,
+        files,
+        lines,
+        output,
+        ..
+    } = preprocessor;
+    let source = &output;
+
+    // Locations in the expanded source are mapped back to the files the
+    // lines came from.
+    let error = |span: Option<naga::Span>, message: String| {
+        let location = span.map(|span| span.location(source));
+        let origin = location.and_then(|location| lines.get(location.line_number as usize - 1));
+        CompileError {
+            file: origin.map_or(file, |&(index, _)| &files[index]).to_owned(),
+            line: origin.map_or(0, |&(_, line)| line),
+            column: location.map_or(0, |location| location.line_position),
+            message,
+        }
+    };
+    let module = wgsl::parse_str(source).map_err(|err| {
+        let span = err.labels().next().map(|(span, _)| span);
+        error(span, err.message().to_owned())
+    })?;
+    let stage = module
+        .entry_points
+        .iter()
+        .find(|entry_point| entry_point.name == ENTRY_POINT)
+        .map(|entry_point| entry_point.stage)
+        .ok_or_else(|| error(None, format!("no `{}` entry point", ENTRY_POINT)))?;
+    let info = Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|err| {
+            let span = err.spans().next().map(|(span, _)| *span);
+            error(span, chain(err.as_inner()))
+        })?;
+
+    let options = spv::Options {
+        // The engine's shaders use wgpu's clip space, which needs no
+        // flipping, and keep their names in debug builds for RenderDoc.
+        flags: if cfg!(debug_assertions) {
+            spv::WriterFlags::DEBUG
+        } else {
+            spv::WriterFlags::empty()
+        },
+        ..spv::Options::default()
+    };
+    let pipeline = spv::PipelineOptions {
+        shader_stage: stage,
+        entry_point: ENTRY_POINT.to_owned(),
+    };
+    let words = spv::write_vec(&module, &info, &options, Some(&pipeline))
+        .map_err(|err| error(None, err.to_string()))?;
+    Ok(Compiled {
+        spirv: words.iter().flat_map(|word| word.to_le_bytes()).collect(),
+        tested,
+    })
+}
+
+/// An `#ifdef` or `#ifndef` block being expanded.
+struct Conditional {
+    line: u32,
+    /// Whether the lines around the block are kept.
+    outer: bool,
+    /// Whether the lines of the current branch are kept.
+    keep: bool,
+    has_else: bool,
+}
+
+/// Expands `#include` lines and conditionals.
+struct Preprocessor<'a, 'b> {
+    include: &'a mut Include<'b>,
+    features: ShaderFeatures,
+    /// Features the directives named so far.
+    tested: ShaderFeatures,
+    /// Paths already expanded.
+    included: HashSet<String>,
+    files: Vec<String>,
+    /// The file, as an index into `files`, and line each line of `output`
+    /// came from.
+    lines: Vec<(usize, u32)>,
+    output: String,
+}
+
+impl Preprocessor<'_, '_> {
+    fn expand(&mut self, file: &str, source: &str) -> Result<(), CompileError> {
+        self.included.insert(file.to_owned());
+        let index = self.files.len();
+        self.files.push(file.to_owned());
+        // Conditionals end in the file they start in.
+        let mut conditionals: Vec<Conditional> = vec![];
+        for (line, text) in (1..).zip(source.lines()) {
+            let keep = conditionals.last().is_none_or(|block| block.keep);
+            // WGSL has no use for `#`, so any line starting with one is a
+            // directive.
+            let directive = match text.trim_start().strip_prefix('#') {
+                Some(directive) => directive,
+                None => {
+                    if keep {
+                        self.output.push_str(text);
+                        self.output.push('\n');
+                        self.lines.push((index, line));
+                    }
+                    continue;
+                }
+            };
+            let error = |message| CompileError {
+                file: file.to_owned(),
+                line,
+                column: 1,
+                message,
+            };
+            let (directive, argument) = match directive.split_once(char::is_whitespace) {
+                Some((directive, argument)) => (directive, argument.trim()),
+                None => (directive.trim_end(), ""),
+            };
+            match directive {
+                "include" => {
+                    let name = argument
+                        .strip_prefix('"')
+                        .and_then(|rest| rest.strip_suffix('"'))
+                        .ok_or_else(|| error("expected #include \"file\"".to_owned()))?;
+                    if !keep {
+                        continue;
+                    }
+                    let path = match file.rsplit_once('/') {
+                        Some((dir, _)) => format!("{}/{}", dir, name),
+                        None => name.to_owned(),
+                    };
+                    if self.included.contains(&path) {
+                        continue;
+                    }
+                    let included = (self.include)(&path)
+                        .map_err(|err| error(format!("failed to include {}: {}", path, err)))?;
+                    self.expand(&path, &included)?;
+                }
+                "ifdef" | "ifndef" => {
+                    let feature = ShaderFeatures::from_name(argument).ok_or_else(|| {
+                        error(format!(
+                            "unknown feature `{}`, expected one of {}",
+                            argument,
+                            FEATURE_NAMES.join(", ")
+                        ))
+                    })?;
+                    self.tested |= feature;
+                    let set = self.features.contains(feature);
+                    conditionals.push(Conditional {
+                        line,
+                        outer: keep,
+                        keep: keep && set == (directive == "ifdef"),
+                        has_else: false,
+                    });
+                }
+                "else" => match conditionals.last_mut() {
+                    Some(block) if !block.has_else => {
+                        block.has_else = true;
+                        block.keep = block.outer && !block.keep;
+                    }
+                    Some(_) => return Err(error("second #else for one #ifdef".to_owned())),
+                    None => return Err(error("#else without #ifdef".to_owned())),
+                },
+                "endif" => {
+                    conditionals
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef".to_owned()))?;
+                }
+                _ => return Err(error(format!("unknown directive #{}", directive))),
+            }
+        }
+        match conditionals.first() {
+            Some(block) => Err(CompileError {
+                file: file.to_owned(),
+                line: block.line,
+                column: 1,
+                message: "#ifdef without #endif".to_owned(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `err` followed by its sources, which is where naga puts the details.
+fn chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
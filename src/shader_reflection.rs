@@ -0,0 +1,367 @@
+//! What a shader binds and reads as vertex input, read back from its
+//! SPIR-V with naga, so bind group layouts can be generated from the
+//! shaders and pipelines checked against them instead of kept in sync by
+//! hand.
+//!
+//! Reflection starts from the SPIR-V rather than the WGSL so that shaders
+//! loaded precompiled, e.g. from the embedded assets, are covered too.
+
+use naga::{
+    front::spv, AddressSpace, Binding, ImageClass, ImageDimension, ScalarKind, StorageAccess,
+    TypeInner,
+};
+
+use crate::pipeline_cache::VertexLayout;
+
+/// One resource a shader binds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceBinding {
+    pub group: u32,
+    pub binding: u32,
+    /// Uniform buffers are never dynamic here, as that is up to the
+    /// pipeline rather than the shader.
+    pub ty: wgpu::BindingType,
+}
+
+/// A vertex shader input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VertexInput {
+    pub location: u32,
+    pub kind: ScalarKind,
+}
+
+/// What a shader declares a uniform or storage buffer to hold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferLayout {
+    pub group: u32,
+    pub binding: u32,
+    pub size: u32,
+    /// The fields of the struct the buffer holds, with those of nested
+    /// structs in their place, or the buffer's whole contents as one if it
+    /// doesn't hold a struct.
+    pub members: Vec<BufferMember>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferMember {
+    /// Only kept in SPIR-V compiled with debug info.
+    pub name: Option<String>,
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderInterface {
+    pub stage: wgpu::ShaderStage,
+    /// Sorted by group, then binding.
+    pub bindings: Vec<ResourceBinding>,
+    /// The buffers among `bindings`, in the same order.
+    pub buffers: Vec<BufferLayout>,
+    /// Sorted by location. Empty for stages other than vertex.
+    pub inputs: Vec<VertexInput>,
+    /// Invocations per workgroup along each axis. Only set for compute.
+    pub workgroup_size: [u32; 3],
+}
+
+impl ShaderInterface {
+    /// The interface of the `entry_point` in `spirv`.
+    pub fn reflect(spirv: &[u8], entry_point: &str) -> Result<Self, String> {
+        let module = spv::parse_u8_slice(spirv, &spv::Options::default())
+            .map_err(|err| format!("failed to reflect: {}", err))?;
+        let entry_point = module
+            .entry_points
+            .iter()
+            .find(|entry| entry.name == entry_point)
+            .ok_or_else(|| format!("no `{}` entry point", entry_point))?;
+        let stage = match entry_point.stage {
+            naga::ShaderStage::Vertex => wgpu::ShaderStage::VERTEX,
+            naga::ShaderStage::Fragment => wgpu::ShaderStage::FRAGMENT,
+            naga::ShaderStage::Compute => wgpu::ShaderStage::COMPUTE,
+            stage => return Err(format!("unsupported {:?} shader", stage)),
+        };
+
+        let mut bindings = vec![];
+        let mut buffers = vec![];
+        for (_, global) in module.global_variables.iter() {
+            let binding = match &global.binding {
+                Some(binding) => binding,
+                None => continue,
+            };
+            let name = global.name.as_deref().unwrap_or("?");
+            let ty = binding_type(global.space, &module.types[global.ty].inner)
+                .ok_or_else(|| format!("{} has a type that can't be bound", name))?;
+            bindings.push(ResourceBinding {
+                group: binding.group,
+                binding: binding.binding,
+                ty,
+            });
+            if let AddressSpace::Uniform | AddressSpace::Storage { .. } = global.space {
+                let mut members = vec![];
+                flatten(&module, global.ty, global.name.clone(), 0, &mut members);
+                buffers.push(BufferLayout {
+                    group: binding.group,
+                    binding: binding.binding,
+                    size: module.types[global.ty].inner.size(module.to_ctx()),
+                    members,
+                });
+            }
+        }
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+        buffers.sort_by_key(|buffer| (buffer.group, buffer.binding));
+
+        let mut inputs = vec![];
+        if stage == wgpu::ShaderStage::VERTEX {
+            for argument in &entry_point.function.arguments {
+                match (&argument.binding, &module.types[argument.ty].inner) {
+                    (Some(binding), inner) => inputs.extend(vertex_input(binding, inner)),
+                    (None, TypeInner::Struct { members, .. }) => {
+                        for member in members {
+                            if let Some(binding) = &member.binding {
+                                inputs
+                                    .extend(vertex_input(binding, &module.types[member.ty].inner));
+                            }
+                        }
+                    }
+                    (None, _) => {}
+                }
+            }
+            inputs.sort_by_key(|input| input.location);
+        }
+
+        Ok(Self {
+            stage,
+            bindings,
+            buffers,
+            inputs,
+            workgroup_size: entry_point.workgroup_size,
+        })
+    }
+
+    /// The layout of the buffer at `binding` of `group`, if it binds one.
+    pub fn buffer(&self, group: u32, binding: u32) -> Option<&BufferLayout> {
+        self.buffers
+            .iter()
+            .find(|buffer| buffer.group == group && buffer.binding == binding)
+    }
+
+    /// Checks that `layouts` provide every input, in a format that reads
+    /// as the type the shader expects.
+    pub fn check_vertex_layouts(&self, layouts: &[VertexLayout]) -> Result<(), String> {
+        for input in &self.inputs {
+            let format = layouts
+                .iter()
+                .flat_map(|layout| &layout.attributes)
+                .find(|attribute| attribute.shader_location == input.location)
+                .map(|attribute| attribute.format)
+                .ok_or_else(|| format!("no vertex attribute for location {}", input.location))?;
+            if format_kind(format) != input.kind {
+                return Err(format!(
+                    "vertex attribute {} is {:?}, but the shader reads {:?}",
+                    input.location, format, input.kind
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `entries`, the layout of bind group `group`, has every
+    /// binding the shader uses in that group, visible to its stage.
+    pub fn check_bind_group_layout(
+        &self,
+        group: u32,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> Result<(), String> {
+        for binding in self
+            .bindings
+            .iter()
+            .filter(|binding| binding.group == group)
+        {
+            let entry = entries
+                .iter()
+                .find(|entry| entry.binding == binding.binding)
+                .ok_or_else(|| {
+                    format!("bind group {} has no binding {}", group, binding.binding)
+                })?;
+            if !same_type(entry.ty, binding.ty) {
+                return Err(format!(
+                    "binding {} of group {} is {:?}, but the shader uses {:?}",
+                    binding.binding, group, entry.ty, binding.ty
+                ));
+            }
+            if !entry.visibility.contains(self.stage) {
+                return Err(format!(
+                    "binding {} of group {} isn't visible to the {:?} stage",
+                    binding.binding, group, self.stage
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The layouts of bind groups 0 up to the highest one `shaders` use, with
+/// each binding visible to the stages using it.
+pub fn bind_group_layouts(
+    shaders: &[&ShaderInterface],
+) -> Result<Vec<Vec<wgpu::BindGroupLayoutEntry>>, String> {
+    let mut groups: Vec<Vec<wgpu::BindGroupLayoutEntry>> = vec![];
+    for shader in shaders {
+        for binding in &shader.bindings {
+            let group = binding.group as usize;
+            if groups.len() <= group {
+                groups.resize_with(group + 1, Vec::new);
+            }
+            let entries = &mut groups[group];
+            match entries
+                .iter_mut()
+                .find(|entry| entry.binding == binding.binding)
+            {
+                Some(entry) if same_type(entry.ty, binding.ty) => {
+                    entry.visibility |= shader.stage;
+                }
+                Some(entry) => {
+                    return Err(format!(
+                        "stages disagree on binding {} of group {}: {:?} and {:?}",
+                        binding.binding, binding.group, entry.ty, binding.ty
+                    ))
+                }
+                None => entries.push(wgpu::BindGroupLayoutEntry {
+                    binding: binding.binding,
+                    visibility: shader.stage,
+                    ty: binding.ty,
+                }),
+            }
+        }
+    }
+    for entries in &mut groups {
+        entries.sort_by_key(|entry| entry.binding);
+    }
+    Ok(groups)
+}
+
+/// Whether a layout's `actual` type binds what the shader declared as
+/// `reflected`, which can't say whether a uniform buffer is dynamic.
+fn same_type(actual: wgpu::BindingType, reflected: wgpu::BindingType) -> bool {
+    use wgpu::BindingType::*;
+    match (actual, reflected) {
+        (UniformBuffer { .. }, UniformBuffer { .. }) => true,
+        (
+            StorageBuffer { readonly, .. },
+            StorageBuffer {
+                readonly: reflected,
+                ..
+            },
+        ) => readonly == reflected,
+        _ => actual == reflected,
+    }
+}
+
+fn binding_type(space: AddressSpace, inner: &TypeInner) -> Option<wgpu::BindingType> {
+    match (space, inner) {
+        (AddressSpace::Uniform, _) => Some(wgpu::BindingType::UniformBuffer { dynamic: false }),
+        (AddressSpace::Storage { access }, _) => Some(wgpu::BindingType::StorageBuffer {
+            dynamic: false,
+            readonly: !access.contains(StorageAccess::STORE),
+        }),
+        (AddressSpace::Handle, &TypeInner::Sampler { comparison }) => {
+            Some(wgpu::BindingType::Sampler { comparison })
+        }
+        (
+            AddressSpace::Handle,
+            &TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            },
+        ) => {
+            let dimension = match (dim, arrayed) {
+                (ImageDimension::D1, false) => wgpu::TextureViewDimension::D1,
+                (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                (ImageDimension::D3, false) => wgpu::TextureViewDimension::D3,
+                (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+                _ => return None,
+            };
+            let (component_type, multisampled) = match class {
+                ImageClass::Sampled { kind, multi } => (component_type(kind)?, multi),
+                ImageClass::Depth { multi } => (wgpu::TextureComponentType::Float, multi),
+                // Nothing uses storage textures yet, and naming their
+                // formats isn't worth it until something does.
+                _ => return None,
+            };
+            Some(wgpu::BindingType::SampledTexture {
+                dimension,
+                component_type,
+                multisampled,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Appends the members of a `ty` at `offset` to `members`: its fields if
+/// it is a struct, or itself. naga wraps buffer contents in a struct of
+/// their own, which this flattens away too.
+fn flatten(
+    module: &naga::Module,
+    ty: naga::Handle<naga::Type>,
+    name: Option<String>,
+    offset: u32,
+    members: &mut Vec<BufferMember>,
+) {
+    match &module.types[ty].inner {
+        TypeInner::Struct {
+            members: fields, ..
+        } => {
+            for field in fields {
+                flatten(
+                    module,
+                    field.ty,
+                    field.name.clone(),
+                    offset + field.offset,
+                    members,
+                );
+            }
+        }
+        inner => members.push(BufferMember {
+            name,
+            offset,
+            size: inner.size(module.to_ctx()),
+        }),
+    }
+}
+
+fn component_type(kind: ScalarKind) -> Option<wgpu::TextureComponentType> {
+    match kind {
+        ScalarKind::Float => Some(wgpu::TextureComponentType::Float),
+        ScalarKind::Sint => Some(wgpu::TextureComponentType::Sint),
+        ScalarKind::Uint => Some(wgpu::TextureComponentType::Uint),
+        _ => None,
+    }
+}
+
+fn vertex_input(binding: &Binding, inner: &TypeInner) -> Option<VertexInput> {
+    let location = match *binding {
+        Binding::Location { location, .. } => location,
+        Binding::BuiltIn(_) => return None,
+    };
+    let kind = match *inner {
+        TypeInner::Scalar(scalar) | TypeInner::Vector { scalar, .. } => scalar.kind,
+        _ => return None,
+    };
+    Some(VertexInput { location, kind })
+}
+
+/// The type a shader reads an attribute of `format` as.
+fn format_kind(format: wgpu::VertexFormat) -> ScalarKind {
+    use wgpu::VertexFormat::*;
+    match format {
+        Uchar2 | Uchar4 | Ushort2 | Ushort4 | Uint | Uint2 | Uint3 | Uint4 => ScalarKind::Uint,
+        Char2 | Char4 | Short2 | Short4 | Int | Int2 | Int3 | Int4 => ScalarKind::Sint,
+        Uchar2Norm | Uchar4Norm | Char2Norm | Char4Norm | Ushort2Norm | Ushort4Norm
+        | Short2Norm | Short4Norm | Half2 | Half4 | Float | Float2 | Float3 | Float4 => {
+            ScalarKind::Float
+        }
+    }
+}
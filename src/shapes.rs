@@ -0,0 +1,547 @@
+//! Vector shapes for the interface: paths of lines, curves and arcs,
+//! tessellated into triangles that
+//! [`InterfacePass::fill_path`](crate::InterfacePass::fill_path) and
+//! [`InterfacePass::stroke_path`](crate::InterfacePass::stroke_path) draw
+//! like any other quad.
+//!
+//! Curves are flattened into lines as they are added, close enough that
+//! the difference is under the path's tolerance, so a path is only ever a
+//! list of polygons. Fills are ear-clipped one contour at a time: a contour
+//! inside another paints over it rather than cutting a hole, and a contour
+//! crossing itself fills only in part. Strokes have mitred joins, bevelled
+//! where the mitre would be too long or flat where a line turns right
+//! back, and butt ends.
+
+use std::f32::consts::PI;
+
+/// How far, in interface units, flattened curves may stray from the real
+/// ones unless [`Path::with_tolerance`] says otherwise.
+pub const DEFAULT_TOLERANCE: f32 = 0.25;
+
+/// How long a mitre may be, in stroke widths, before the join is bevelled
+/// instead. SVG's default.
+const MITER_LIMIT: f32 = 4.0;
+
+/// Points closer than this are merged.
+const EPSILON: f32 = 1e-6;
+
+/// One run of connected points in a [`Path`].
+#[derive(Copy, Clone, Debug)]
+struct Contour {
+    /// Index of its first point.
+    start: usize,
+    closed: bool,
+}
+
+/// Outlines made of lines, curves and arcs, e.g. to fill or stroke with the
+/// interface pass. Each [`Path::move_to`] starts a contour; drawing without
+/// one starts it at the origin.
+#[derive(Clone, Debug)]
+pub struct Path {
+    tolerance: f32,
+    points: Vec<[f32; 2]>,
+    contours: Vec<Contour>,
+}
+
+impl Default for Path {
+    fn default() -> Self {
+        Self::with_tolerance(DEFAULT_TOLERANCE)
+    }
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A path whose curves are flattened to within `tolerance` interface
+    /// units; lower is smoother and more triangles.
+    pub fn with_tolerance(tolerance: f32) -> Self {
+        Self {
+            tolerance: tolerance.max(EPSILON),
+            points: Vec::new(),
+            contours: Vec::new(),
+        }
+    }
+
+    /// A closed polygon through `points`.
+    pub fn polygon(points: &[[f32; 2]]) -> Self {
+        let mut path = Self::new();
+        if let Some((&first, rest)) = points.split_first() {
+            path.move_to(first);
+            for &point in rest {
+                path.line_to(point);
+            }
+            path.close();
+        }
+        path
+    }
+
+    /// A circle around `center`.
+    pub fn circle(center: [f32; 2], radius: f32) -> Self {
+        let mut path = Self::new();
+        path.move_to([center[0] + radius, center[1]]);
+        path.arc(center, radius, 0.0, 2.0 * PI);
+        path.close();
+        path
+    }
+
+    /// A rectangle from `min` to `max` with its corners rounded to `radius`,
+    /// shrunk to fit if the rectangle is too small for it.
+    pub fn rounded_rect(min: [f32; 2], max: [f32; 2], radius: f32) -> Self {
+        let radius = radius
+            .min((max[0] - min[0]) / 2.0)
+            .min((max[1] - min[1]) / 2.0)
+            .max(0.0);
+        let mut path = Self::new();
+        path.move_to([min[0] + radius, min[1]]);
+        path.arc(
+            [max[0] - radius, min[1] + radius],
+            radius,
+            -PI / 2.0,
+            PI / 2.0,
+        );
+        path.arc([max[0] - radius, max[1] - radius], radius, 0.0, PI / 2.0);
+        path.arc(
+            [min[0] + radius, max[1] - radius],
+            radius,
+            PI / 2.0,
+            PI / 2.0,
+        );
+        path.arc([min[0] + radius, min[1] + radius], radius, PI, PI / 2.0);
+        path.close();
+        path
+    }
+
+    /// Starts a contour at `to`.
+    pub fn move_to(&mut self, to: [f32; 2]) {
+        if let Some(contour) = self.contours.last() {
+            // Drop a contour of just the point it was started at.
+            if self.points.len() - contour.start <= 1 {
+                self.points.truncate(contour.start);
+                self.contours.pop();
+            }
+        }
+        self.contours.push(Contour {
+            start: self.points.len(),
+            closed: false,
+        });
+        self.points.push(to);
+    }
+
+    pub fn line_to(&mut self, to: [f32; 2]) {
+        self.current();
+        self.points.push(to);
+    }
+
+    /// A quadratic Bézier curve to `to`, pulled towards `control`.
+    pub fn quadratic_to(&mut self, control: [f32; 2], to: [f32; 2]) {
+        let from = self.current();
+        // A uniform split into n lines strays at most |from - 2 control +
+        // to| / 4n² from the curve.
+        let bend = length(add(sub(from, scale(control, 2.0)), to));
+        let lines = self.lines(bend / 4.0);
+        for step in 1..=lines {
+            let t = step as f32 / lines as f32;
+            let a = lerp(from, control, t);
+            let b = lerp(control, to, t);
+            self.points.push(lerp(a, b, t));
+        }
+    }
+
+    /// A cubic Bézier curve to `to`, leaving towards `first` and arriving
+    /// from `second`.
+    pub fn cubic_to(&mut self, first: [f32; 2], second: [f32; 2], to: [f32; 2]) {
+        let from = self.current();
+        // As for quadratics, with the second derivative at most six times
+        // the larger of these.
+        let bend = length(add(sub(from, scale(first, 2.0)), second))
+            .max(length(add(sub(first, scale(second, 2.0)), to)));
+        let lines = self.lines(bend * 3.0 / 4.0);
+        for step in 1..=lines {
+            let t = step as f32 / lines as f32;
+            let a = lerp(from, first, t);
+            let b = lerp(first, second, t);
+            let c = lerp(second, to, t);
+            let ab = lerp(a, b, t);
+            let bc = lerp(b, c, t);
+            self.points.push(lerp(ab, bc, t));
+        }
+    }
+
+    /// An arc of the circle around `center`, from `start` radians through
+    /// `sweep` more, joined to the current point by a line. Angles go from
+    /// +x towards +y, which is clockwise on screen as y points down.
+    pub fn arc(&mut self, center: [f32; 2], radius: f32, start: f32, sweep: f32) {
+        let point = |angle: f32| {
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        };
+        if self.contours.is_empty() {
+            self.move_to(point(start));
+        } else {
+            self.line_to(point(start));
+        }
+        // Each line of an arc strays r (1 - cos(θ / 2)) from it.
+        let radius = radius.abs();
+        let step = if radius > self.tolerance {
+            2.0 * (1.0 - self.tolerance / radius).acos()
+        } else {
+            PI / 2.0
+        };
+        let lines = ((sweep.abs() / step).ceil() as usize).clamp(1, 1024);
+        for line in 1..=lines {
+            self.points
+                .push(point(start + sweep * line as f32 / lines as f32));
+        }
+    }
+
+    /// Closes the current contour with a line back to its start. The next
+    /// line or curve starts a new contour there.
+    pub fn close(&mut self) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.closed = true;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The point drawing continues from, starting a contour if there is
+    /// none or the last is closed.
+    fn current(&mut self) -> [f32; 2] {
+        match self.contours.last().copied() {
+            None => self.move_to([0.0, 0.0]),
+            Some(contour) if contour.closed => self.move_to(self.points[contour.start]),
+            Some(_) => (),
+        }
+        *self.points.last().unwrap()
+    }
+
+    /// How many lines keep a curve within tolerance, for one that strays
+    /// `error / n²` from n uniform lines.
+    fn lines(&self, error: f32) -> usize {
+        ((error / self.tolerance).sqrt().ceil() as usize).clamp(1, 1024)
+    }
+
+    /// The points of each contour, without repeats or the start repeated
+    /// at the end of a closed one, and whether it is closed.
+    fn contours(&self) -> impl Iterator<Item = (Vec<[f32; 2]>, bool)> + '_ {
+        self.contours
+            .iter()
+            .enumerate()
+            .map(move |(index, contour)| {
+                let end = self
+                    .contours
+                    .get(index + 1)
+                    .map_or(self.points.len(), |next| next.start);
+                let mut points: Vec<[f32; 2]> = Vec::with_capacity(end - contour.start);
+                for &point in &self.points[contour.start..end] {
+                    if points.last().is_none_or(|&last| !near(last, point)) {
+                        points.push(point);
+                    }
+                }
+                if contour.closed && points.len() > 1 && near(points[0], *points.last().unwrap()) {
+                    points.pop();
+                }
+                (points, contour.closed)
+            })
+    }
+
+    /// Triangles covering the inside of every contour, closed or not.
+    pub fn fill(&self) -> Tessellation {
+        let mut tessellation = Tessellation::default();
+        for (points, _) in self.contours() {
+            let base = tessellation.positions.len() as u32;
+            clip_ears(&points, base, &mut tessellation.indices);
+            tessellation.positions.extend_from_slice(&points);
+        }
+        tessellation
+    }
+
+    /// Triangles covering a line `width` wide along every contour.
+    pub fn stroke(&self, width: f32) -> Tessellation {
+        let mut tessellation = Tessellation::default();
+        for (points, closed) in self.contours() {
+            stroke_contour(&points, closed, width / 2.0, &mut tessellation);
+        }
+        tessellation
+    }
+}
+
+/// Triangles from [`Path::fill`] or [`Path::stroke`]: every three indices
+/// are one, into `positions`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tessellation {
+    pub positions: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+/// Triangulates the polygon `points` by cutting off ears, corners with no
+/// other point inside, until one triangle is left. Indices are offset by
+/// `base`.
+fn clip_ears(points: &[[f32; 2]], base: u32, indices: &mut Vec<u32>) {
+    let area: f32 = (0..points.len())
+        .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+        .sum();
+    if points.len() < 3 || area.abs() <= EPSILON {
+        return;
+    }
+    // Counter-clockwise with y up, so ears turn left.
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut i = 0;
+    let mut stalled = 0;
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let [prev, cur, next] = [
+            remaining[(i + len - 1) % len],
+            remaining[i % len],
+            remaining[(i + 1) % len],
+        ];
+        let [a, b, c] = [points[prev], points[cur], points[next]];
+        let ear = cross(sub(b, a), sub(c, b)) > 0.0
+            && remaining.iter().all(|&other| {
+                [prev, cur, next].contains(&other) || !in_triangle(points[other], a, b, c)
+            });
+        // Past a full lap without an ear the rest crosses itself or has
+        // no area; cut corners anyway so it still ends.
+        if ear || stalled > len {
+            indices.extend_from_slice(&[base + prev as u32, base + cur as u32, base + next as u32]);
+            remaining.remove(i % len);
+            stalled = 0;
+        } else {
+            i += 1;
+            stalled += 1;
+        }
+        i %= remaining.len();
+    }
+    indices.extend(remaining.iter().map(|&index| base + index as u32));
+}
+
+/// Adds the quads along each line of `points` and the joins between them,
+/// `half` to either side.
+fn stroke_contour(points: &[[f32; 2]], closed: bool, half: f32, out: &mut Tessellation) {
+    let len = points.len();
+    if len < 2 {
+        return;
+    }
+    let lines = if closed && len > 2 { len } else { len - 1 };
+    let direction = |line: usize| normalize(sub(points[(line + 1) % len], points[line]));
+    let normal = |line: usize| perpendicular(direction(line));
+
+    // Per point, the left and right vertices of the line arriving and of
+    // the one leaving, which differ at a bevel.
+    let mut ends = Vec::with_capacity(len);
+    for (index, &point) in points.iter().enumerate() {
+        let arriving = if index > 0 {
+            Some(index - 1)
+        } else if lines == len {
+            Some(len - 1)
+        } else {
+            None
+        };
+        let leaving = Some(index).filter(|&line| line < lines);
+        let mut vertex = |position| {
+            out.positions.push(position);
+            out.positions.len() as u32 - 1
+        };
+        let (arriving, leaving) = match (arriving, leaving) {
+            (Some(arriving), Some(leaving)) => (arriving, leaving),
+            (Some(line), None) | (None, Some(line)) => {
+                let offset = scale(normal(line), half);
+                let pair = (vertex(add(point, offset)), vertex(sub(point, offset)));
+                ends.push((pair, pair));
+                continue;
+            }
+            (None, None) => unreachable!(),
+        };
+
+        let [n0, n1] = [normal(arriving), normal(leaving)];
+        let miter = normalize(add(n0, n1));
+        if miter == [0.0, 0.0] {
+            // Turning right back, neither side is inside the turn: both
+            // lines end square, on the same two vertices.
+            let offset = scale(n0, half);
+            let (left, right) = (vertex(add(point, offset)), vertex(sub(point, offset)));
+            ends.push(((left, right), (right, left)));
+            continue;
+        }
+        let cos = dot(miter, n1);
+        if cos * MITER_LIMIT >= 1.0 {
+            let offset = scale(miter, half / cos);
+            let pair = (vertex(add(point, offset)), vertex(sub(point, offset)));
+            ends.push((pair, pair));
+            continue;
+        }
+
+        // Bevelled: two vertices on the outside of the turn and one inside,
+        // no further in than the shorter line is long.
+        let shortest = length(sub(points[arriving], point))
+            .min(length(sub(points[(leaving + 1) % len], point)));
+        let depth = (half / cos.max(EPSILON)).min(shortest.max(half));
+        let turns_left = cross(direction(arriving), direction(leaving)) > 0.0;
+        let side = if turns_left { -1.0 } else { 1.0 };
+        let inner = vertex(sub(point, scale(miter, side * depth)));
+        let outer_in = vertex(add(point, scale(n0, side * half)));
+        let outer_out = vertex(add(point, scale(n1, side * half)));
+        out.indices.extend_from_slice(&[outer_in, outer_out, inner]);
+        ends.push(if turns_left {
+            ((inner, outer_in), (inner, outer_out))
+        } else {
+            ((outer_in, inner), (outer_out, inner))
+        });
+    }
+
+    for line in 0..lines {
+        let (_, (a, b)) = ends[line];
+        let ((c, d), _) = ends[(line + 1) % len];
+        out.indices.extend_from_slice(&[a, b, c, b, d, c]);
+    }
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], by: f32) -> [f32; 2] {
+    [a[0] * by, a[1] * by]
+}
+
+fn lerp(a: [f32; 2], b: [f32; 2], t: f32) -> [f32; 2] {
+    add(a, scale(sub(b, a), t))
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length(a: [f32; 2]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 2]) -> [f32; 2] {
+    let length = length(a);
+    if length > EPSILON {
+        scale(a, 1.0 / length)
+    } else {
+        [0.0, 0.0]
+    }
+}
+
+/// `a` turned a quarter towards +y.
+fn perpendicular(a: [f32; 2]) -> [f32; 2] {
+    [-a[1], a[0]]
+}
+
+fn near(a: [f32; 2], b: [f32; 2]) -> bool {
+    let d = sub(a, b);
+    dot(d, d) <= EPSILON * EPSILON
+}
+
+/// Whether `p` is inside or on the counter-clockwise triangle `a b c`.
+fn in_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    cross(sub(b, a), sub(p, a)) >= 0.0
+        && cross(sub(c, b), sub(p, b)) >= 0.0
+        && cross(sub(a, c), sub(p, c)) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The area inside `points`, whichever way they wind.
+    fn polygon_area(points: &[[f32; 2]]) -> f32 {
+        let twice: f32 = (0..points.len())
+            .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+            .sum();
+        twice.abs() / 2.0
+    }
+
+    /// The area of every triangle added up, overlaps counted twice.
+    fn triangle_area(tessellation: &Tessellation) -> f32 {
+        tessellation
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| tessellation.positions[triangle[i] as usize]);
+                cross(sub(b, a), sub(c, a)).abs() / 2.0
+            })
+            .sum()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    /// Fills `points` wound both ways and checks the triangles cover
+    /// exactly the polygon.
+    fn assert_fills(points: &[[f32; 2]]) {
+        let mut reversed = points.to_vec();
+        reversed.reverse();
+        for points in &[points.to_vec(), reversed] {
+            let tessellation = Path::polygon(points).fill();
+            assert_eq!(tessellation.indices.len(), (points.len() - 2) * 3);
+            assert_close(triangle_area(&tessellation), polygon_area(points));
+        }
+    }
+
+    #[test]
+    fn fills_square() {
+        assert_fills(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]);
+    }
+
+    #[test]
+    fn fills_concave() {
+        // An L, concave at (4, 4).
+        assert_fills(&[
+            [0.0, 0.0],
+            [10.0, 0.0],
+            [10.0, 4.0],
+            [4.0, 4.0],
+            [4.0, 10.0],
+            [0.0, 10.0],
+        ]);
+    }
+
+    #[test]
+    fn strokes_closed_square() {
+        let square = Path::polygon(&[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0]]);
+        // Mitred corners, so the band between a 12 and an 8 wide square.
+        assert_close(triangle_area(&square.stroke(2.0)), 12.0 * 12.0 - 8.0 * 8.0);
+    }
+
+    #[test]
+    fn strokes_reversal() {
+        let mut path = Path::new();
+        path.move_to([0.0, 0.0]);
+        path.line_to([10.0, 0.0]);
+        path.line_to([0.0, 0.0]);
+        let stroke = path.stroke(2.0);
+        // Both lines at full width, on top of each other, and nothing
+        // sticking out of the end.
+        assert_close(triangle_area(&stroke), 2.0 * 10.0 * 2.0);
+        for &[x, y] in &stroke.positions {
+            assert!((0.0..=10.0).contains(&x) && (-1.0..=1.0).contains(&y));
+        }
+    }
+}
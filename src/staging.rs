@@ -0,0 +1,200 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::task::noop_waker_ref;
+
+use crate::{
+    error::{DeviceLost, EngineError},
+    gpu_mem::{self, GpuMemory},
+};
+
+const ALIGNMENT: wgpu::BufferAddress = 4;
+
+type MapFuture =
+    Pin<Box<dyn Future<Output = Result<wgpu::BufferWriteMapping, wgpu::BufferAsyncErr>>>>;
+
+fn align(size: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    (size + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+struct Chunk {
+    buffer: gpu_mem::Buffer,
+    size: wgpu::BufferAddress,
+    offset: wgpu::BufferAddress,
+}
+
+struct MappedChunk {
+    // Declared first so the mapping is released before the buffer is destroyed.
+    mapping: wgpu::BufferWriteMapping,
+    chunk: Chunk,
+}
+
+impl MappedChunk {
+    fn remaining(&self) -> wgpu::BufferAddress {
+        self.chunk.size - self.chunk.offset
+    }
+}
+
+/// Ring of mappable chunks used to stream data into device-local buffers.
+///
+/// Writes are recorded as buffer-to-buffer copies on the caller's encoder.
+/// Call [`StagingBelt::finish`] before submitting that encoder and
+/// [`StagingBelt::recall`] right after, so the chunks are remapped and
+/// reused once the GPU is done reading from them.
+pub struct StagingBelt {
+    chunk_size: wgpu::BufferAddress,
+    active: Vec<MappedChunk>,
+    closed: Vec<Chunk>,
+    recalling: Vec<(Chunk, MapFuture)>,
+    free: Vec<MappedChunk>,
+    /// Bytes written since the last `take_written`.
+    written: wgpu::BufferAddress,
+}
+
+impl StagingBelt {
+    pub fn new(chunk_size: wgpu::BufferAddress) -> Self {
+        Self {
+            chunk_size,
+            active: vec![],
+            closed: vec![],
+            recalling: vec![],
+            free: vec![],
+            written: 0,
+        }
+    }
+
+    /// Records a copy of `data` into `target` at `offset`.
+    ///
+    /// Fails when a new chunk is over the memory budget, or can't be mapped
+    /// because the device was lost.
+    pub fn write_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        memory: &GpuMemory,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) -> Result<(), EngineError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let size = data.len() as wgpu::BufferAddress;
+        let index = self.acquire(device, memory, size)?;
+        let mapped = &mut self.active[index];
+
+        let start = mapped.chunk.offset as usize;
+        mapped.mapping.as_slice()[start..start + data.len()].copy_from_slice(data);
+        encoder.copy_buffer_to_buffer(
+            &mapped.chunk.buffer,
+            mapped.chunk.offset,
+            target,
+            offset,
+            size,
+        );
+        mapped.chunk.offset += align(size);
+        self.written += size;
+
+        Ok(())
+    }
+
+    /// Bytes written since the last call.
+    pub fn take_written(&mut self) -> wgpu::BufferAddress {
+        std::mem::replace(&mut self.written, 0)
+    }
+
+    /// Unmaps every chunk written this frame. Must be called before the
+    /// encoders that reference them are submitted.
+    pub fn finish(&mut self) {
+        for mapped in self.active.drain(..) {
+            let MappedChunk { mapping, chunk } = mapped;
+            drop(mapping);
+            self.closed.push(chunk);
+        }
+    }
+
+    /// Requests the chunks closed by [`StagingBelt::finish`] to be mapped
+    /// again. They become available once the device reports the
+    /// submission that used them as complete.
+    pub fn recall(&mut self) {
+        for mut chunk in self.closed.drain(..) {
+            chunk.offset = 0;
+            let future = Box::pin(chunk.buffer.map_write(0, chunk.size));
+            self.recalling.push((chunk, future));
+        }
+    }
+
+    /// Drops every chunk that is mapped and idle.
+    pub fn trim(&mut self) {
+        self.poll_recalled();
+        self.free.clear();
+    }
+
+    fn poll_recalled(&mut self) {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        let mut pending = vec![];
+
+        for (chunk, mut future) in self.recalling.drain(..) {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(Ok(mapping)) => self.free.push(MappedChunk { mapping, chunk }),
+                Poll::Ready(Err(_)) => {}
+                Poll::Pending => pending.push((chunk, future)),
+            }
+        }
+
+        self.recalling = pending;
+    }
+
+    fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        memory: &GpuMemory,
+        size: wgpu::BufferAddress,
+    ) -> Result<usize, EngineError> {
+        if let Some(index) = self.active.iter().position(|m| m.remaining() >= size) {
+            return Ok(index);
+        }
+
+        self.poll_recalled();
+
+        let mapped = match self.free.iter().position(|m| m.remaining() >= size) {
+            Some(index) => self.free.swap_remove(index),
+            None => Self::create_chunk(device, memory, align(size).max(self.chunk_size))?,
+        };
+
+        self.active.push(mapped);
+        Ok(self.active.len() - 1)
+    }
+
+    fn create_chunk(
+        device: &wgpu::Device,
+        memory: &GpuMemory,
+        size: wgpu::BufferAddress,
+    ) -> Result<MappedChunk, EngineError> {
+        let buffer = memory.create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("staging/chunk"),
+                size,
+                usage: wgpu::BufferUsage::MAP_WRITE | wgpu::BufferUsage::COPY_SRC,
+            },
+        )?;
+
+        let future = buffer.map_write(0, size);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = futures::executor::block_on(future).map_err(|_| DeviceLost)?;
+
+        Ok(MappedChunk {
+            mapping,
+            chunk: Chunk {
+                buffer,
+                size,
+                offset: 0,
+            },
+        })
+    }
+}
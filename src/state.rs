@@ -0,0 +1,131 @@
+//! A stack of game states, e.g. the menu, gameplay and a pause screen on
+//! top of it, each with its own update, drawing and input handling.
+//!
+//! Only the top state updates and receives input. Drawing starts at the
+//! highest state that isn't an overlay, so a pause screen can dim the game
+//! it paused. States change the stack by returning a [`Transition`].
+
+use winit::event::WindowEvent;
+
+use crate::{
+    assets::{AssetLoader, Assets},
+    ecs::World,
+    events::EventBus,
+    rng::Rngs,
+    scene::Scene,
+    time::Time,
+    timers::Timers,
+    InterfacePass,
+};
+
+/// A change to the state stack, applied after the call that returned it.
+pub enum Transition {
+    None,
+    /// Pauses the current state under a new one.
+    Push(Box<dyn GameState>),
+    /// Drops the current state, resuming the one below.
+    Pop,
+    /// Swaps the current state for another.
+    Replace(Box<dyn GameState>),
+    /// Drops every state, which ends the application.
+    Quit,
+}
+
+/// What states update and react to input with: the entities, the event
+/// bus, timers, random streams, scenes and their loader, and the size of
+/// the window in logical pixels.
+pub struct StateContext<'a> {
+    pub world: &'a mut World,
+    pub events: &'a mut EventBus,
+    pub timers: &'a mut Timers,
+    pub rngs: &'a mut Rngs,
+    pub scenes: &'a mut Assets<Scene>,
+    pub loader: &'a AssetLoader,
+    pub logical_size: [f32; 2],
+}
+
+pub trait GameState {
+    /// Called when the state is pushed, e.g. to spawn its entities.
+    fn enter(&mut self, _cx: &mut StateContext) {}
+
+    /// Called when the state is popped or replaced.
+    fn exit(&mut self, _cx: &mut StateContext) {}
+
+    /// Advances the state by one fixed step.
+    fn update(&mut self, _cx: &mut StateContext, _time: &Time) -> Transition {
+        Transition::None
+    }
+
+    /// Queues the state into `pass`. `time.alpha()` is how far rendering
+    /// is between the last two fixed steps.
+    fn draw(&mut self, pass: &mut InterfacePass, world: &World, time: &Time);
+
+    fn input(&mut self, _cx: &mut StateContext, _event: &WindowEvent) -> Transition {
+        Transition::None
+    }
+
+    /// Whether the states below stay visible under this one.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Default)]
+pub struct StateStack {
+    states: Vec<Box<dyn GameState>>,
+}
+
+impl StateStack {
+    pub fn apply(&mut self, cx: &mut StateContext, transition: Transition) {
+        match transition {
+            Transition::None => {}
+            Transition::Push(mut state) => {
+                state.enter(cx);
+                self.states.push(state);
+            }
+            Transition::Pop => {
+                if let Some(mut state) = self.states.pop() {
+                    state.exit(cx);
+                }
+            }
+            Transition::Replace(state) => {
+                self.apply(cx, Transition::Pop);
+                self.apply(cx, Transition::Push(state));
+            }
+            Transition::Quit => {
+                while let Some(mut state) = self.states.pop() {
+                    state.exit(cx);
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn update(&mut self, cx: &mut StateContext, time: &Time) {
+        if let Some(state) = self.states.last_mut() {
+            let transition = state.update(cx, time);
+            self.apply(cx, transition);
+        }
+    }
+
+    pub fn input(&mut self, cx: &mut StateContext, event: &WindowEvent) {
+        if let Some(state) = self.states.last_mut() {
+            let transition = state.input(cx, event);
+            self.apply(cx, transition);
+        }
+    }
+
+    pub fn draw(&mut self, pass: &mut InterfacePass, world: &World, time: &Time) {
+        let first = self
+            .states
+            .iter()
+            .rposition(|state| !state.is_overlay())
+            .unwrap_or(0);
+        for state in &mut self.states[first..] {
+            state.draw(pass, world, time);
+        }
+    }
+}
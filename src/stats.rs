@@ -0,0 +1,66 @@
+use std::{fmt, ops::AddAssign};
+
+/// Work submitted over one frame, from `Context::begin_frame` to `end_frame`.
+///
+/// Passes add their draws to `Context::stats` as they record them; bytes
+/// streamed through the staging belt are added when the frame is
+/// presented. `Context::frame_stats` returns the last complete frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub dispatches: u32,
+    pub vertices: u32,
+    pub indices: u32,
+    pub bytes_uploaded: u64,
+    pub bind_group_switches: u32,
+}
+
+impl RenderStats {
+    /// Counts a draw of `vertices` vertices without an index buffer.
+    pub fn draw(&mut self, vertices: u32) {
+        self.draw_calls += 1;
+        self.vertices += vertices;
+    }
+
+    /// Counts an indexed draw of `indices` indices.
+    pub fn draw_indexed(&mut self, indices: u32) {
+        self.draw_calls += 1;
+        self.indices += indices;
+    }
+
+    /// Counts a compute dispatch.
+    pub fn dispatch(&mut self) {
+        self.dispatches += 1;
+    }
+
+    /// Counts a `set_bind_group` call.
+    pub fn bind_group(&mut self) {
+        self.bind_group_switches += 1;
+    }
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.dispatches += other.dispatches;
+        self.vertices += other.vertices;
+        self.indices += other.indices;
+        self.bytes_uploaded += other.bytes_uploaded;
+        self.bind_group_switches += other.bind_group_switches;
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} draws, {} dispatches, {} vertices, {} indices, {} bind groups, {:.1} KiB uploaded",
+            self.draw_calls,
+            self.dispatches,
+            self.vertices,
+            self.indices,
+            self.bind_group_switches,
+            self.bytes_uploaded as f64 / 1024.0
+        )
+    }
+}
@@ -0,0 +1,222 @@
+//! Cooperative tasks for scripted sequences, e.g. a cutscene or a timed
+//! interface flow, written as async blocks instead of state machines:
+//!
+//! ```ignore
+//! tasks::spawn(async {
+//!     tasks::wait_secs(2.0).await;
+//!     tasks::with(|cx| cx.events.send(ShowTitle)).await;
+//! });
+//! ```
+//!
+//! Tasks run on the main thread and are polled by [`Tasks::update`] once
+//! per fixed step, so waits count simulated time and stand still while the
+//! simulation does. A task can't hold on to the world across an `await`;
+//! [`with`] runs a closure with the [`StateContext`] between polls instead.
+//!
+//! [`spawn`] queues on the current thread, and whichever [`Tasks`] updates
+//! next on it picks the task up.
+
+use std::{
+    cell::{Cell, RefCell},
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::task::noop_waker_ref;
+
+use crate::{
+    state::StateContext,
+    time::{Time, FIXED_STEP},
+};
+
+/// Times per step the tasks are polled again after running the closures
+/// they passed to [`with`], so a sequence of them doesn't take a step each.
+const MAX_ROUNDS: usize = 8;
+
+/// A closure passed to [`with`].
+type Body<R> = Box<dyn FnOnce(&mut StateContext) -> R>;
+type Command = Body<()>;
+
+thread_local! {
+    static SPAWNED: RefCell<Vec<Task>> = const { RefCell::new(Vec::new()) };
+    static COMMANDS: RefCell<Vec<Command>> = const { RefCell::new(Vec::new()) };
+    /// The fixed step being run, as of the last `Tasks::update`.
+    static STEP: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    Running,
+    Finished,
+    Cancelled,
+}
+
+/// Refers to a spawned task, e.g. to cancel a cutscene when its state
+/// exits. Dropping the handle leaves the task running.
+#[derive(Clone, Debug)]
+pub struct TaskHandle {
+    status: Rc<Cell<TaskStatus>>,
+}
+
+impl TaskHandle {
+    pub fn status(&self) -> TaskStatus {
+        self.status.get()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status() == TaskStatus::Running
+    }
+
+    /// Stops the task before its next poll. Does nothing once it finished.
+    pub fn cancel(&self) {
+        if self.is_running() {
+            self.status.set(TaskStatus::Cancelled);
+        }
+    }
+}
+
+struct Task {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    status: Rc<Cell<TaskStatus>>,
+}
+
+/// Starts running `future` on the next [`Tasks::update`].
+pub fn spawn(future: impl Future<Output = ()> + 'static) -> TaskHandle {
+    let status = Rc::new(Cell::new(TaskStatus::Running));
+    SPAWNED.with(|spawned| {
+        spawned.borrow_mut().push(Task {
+            future: Box::pin(future),
+            status: status.clone(),
+        })
+    });
+    TaskHandle { status }
+}
+
+/// Polls the spawned tasks.
+#[derive(Default)]
+pub struct Tasks {
+    tasks: Vec<Task>,
+}
+
+impl Tasks {
+    /// Advances every task by one fixed step. Call from
+    /// `Application::update`, after the states updated.
+    pub fn update(&mut self, cx: &mut StateContext, time: &Time) {
+        STEP.with(|step| step.set(time.steps()));
+        let mut waker_cx = Context::from_waker(noop_waker_ref());
+        for _ in 0..MAX_ROUNDS {
+            self.tasks.extend(SPAWNED.with(|spawned| spawned.take()));
+            self.tasks.retain_mut(|task| {
+                if task.status.get() != TaskStatus::Running {
+                    return false;
+                }
+                let done = task.future.as_mut().poll(&mut waker_cx).is_ready();
+                if done {
+                    task.status.set(TaskStatus::Finished);
+                }
+                !done
+            });
+
+            let commands = COMMANDS.with(|commands| commands.take());
+            let spawned = SPAWNED.with(|spawned| !spawned.borrow().is_empty());
+            if commands.is_empty() && !spawned {
+                break;
+            }
+            for command in commands {
+                command(cx);
+            }
+        }
+    }
+
+    /// Tasks still running.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Cancels every task, e.g. when leaving the screen they belong to.
+    pub fn clear(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.status.set(TaskStatus::Cancelled);
+        }
+    }
+}
+
+/// Completes once `steps` more fixed steps have run.
+pub fn wait_steps(steps: u64) -> WaitSteps {
+    WaitSteps { steps, until: None }
+}
+
+/// Completes once `secs` seconds of simulated time have passed, rounded up
+/// to whole fixed steps.
+pub fn wait_secs(secs: f32) -> WaitSteps {
+    wait_steps((secs.max(0.0) / FIXED_STEP.as_secs_f32()).ceil() as u64)
+}
+
+/// Completes on the next fixed step.
+pub fn next_step() -> WaitSteps {
+    wait_steps(1)
+}
+
+/// Returned by [`wait_steps`] and [`wait_secs`].
+pub struct WaitSteps {
+    steps: u64,
+    /// Set on the first poll, so the wait starts when it is awaited.
+    until: Option<u64>,
+}
+
+impl Future for WaitSteps {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> {
+        let now = STEP.with(Cell::get);
+        let steps = self.steps;
+        let until = *self.until.get_or_insert(now + steps);
+        if now >= until {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Runs `f` with the [`StateContext`] and completes with what it returned.
+/// It runs between polls of the same step, so awaiting it doesn't wait for
+/// the next one.
+pub fn with<R: 'static>(f: impl FnOnce(&mut StateContext) -> R + 'static) -> With<R> {
+    With {
+        f: Some(Box::new(f)),
+        result: Rc::new(RefCell::new(None)),
+    }
+}
+
+/// Returned by [`with`].
+pub struct With<R> {
+    f: Option<Body<R>>,
+    result: Rc<RefCell<Option<R>>>,
+}
+
+impl<R: 'static> Future for With<R> {
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<R> {
+        if let Some(f) = self.f.take() {
+            let result = self.result.clone();
+            COMMANDS.with(|commands| {
+                commands
+                    .borrow_mut()
+                    .push(Box::new(move |cx| *result.borrow_mut() = Some(f(cx))))
+            });
+            return Poll::Pending;
+        }
+        match self.result.borrow_mut().take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::rc::Rc;
+
+use crate::{
+    assets::texture,
+    bind_group_cache::{Binding, LayoutId},
+    gpu_mem::{self, BudgetExceeded},
+    sampler::{Sampler, SamplerDesc},
+    Context,
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Gap left around every image so linear filtering doesn't bleed into its
+/// neighbours.
+const PADDING: u32 = 1;
+
+/// Size of the opaque white block kept at the origin of layer 0, so
+/// untextured quads can sample `uv: [0.0, 0.0]` with `index: 0` and get their
+/// vertex colour unchanged.
+const WHITE_SIZE: u32 = 4;
+
+/// Location of an image inside the atlas: normalized texture coordinates
+/// within array layer `layer`, which goes in `InterfaceVertex::index`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvRect {
+    pub layer: u32,
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+/// A horizontal strip of a layer, filled left to right.
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+#[derive(Default)]
+struct Layer {
+    shelves: Vec<Shelf>,
+    next_shelf: u32,
+}
+
+/// Packs small images into the layers of one RGBA texture array using shelf
+/// packing, so quads showing different images can share a draw call.
+///
+/// Space is never reclaimed; the atlas is meant for UI images that live as
+/// long as the interface. A CPU copy of every image is kept so the atlas can
+/// be rebuilt on a new device.
+pub struct TextureAtlas {
+    size: u32,
+    subsystem: String,
+    sampler_desc: SamplerDesc,
+    texture: gpu_mem::Texture,
+    view: wgpu::TextureView,
+    sampler: Sampler,
+    layout: LayoutId,
+    layers: Vec<Layer>,
+    /// As inserted, before premultiplying.
+    images: Vec<((u32, u32, u32), image::RgbaImage)>,
+    premultiplied: bool,
+}
+
+type Resources = (gpu_mem::Texture, wgpu::TextureView, Sampler, LayoutId);
+
+fn create_resources(
+    ctx: &mut Context,
+    subsystem: &str,
+    size: u32,
+    layers: u32,
+    sampler: SamplerDesc,
+) -> Result<Resources, BudgetExceeded> {
+    let texture = ctx.memory.create_texture(
+        &ctx.device,
+        &wgpu::TextureDescriptor {
+            label: Some(&format!("{}/atlas", subsystem)),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+            array_layer_count: layers,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        },
+    )?;
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        format: FORMAT,
+        dimension: wgpu::TextureViewDimension::D2Array,
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        array_layer_count: layers,
+    });
+
+    let sampler = ctx.create_sampler(sampler);
+
+    let layout = ctx.bind_groups.layout_id(
+        &ctx.device,
+        &format!("{}/atlas", subsystem),
+        &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    dimension: wgpu::TextureViewDimension::D2Array,
+                    component_type: wgpu::TextureComponentType::Float,
+                    multisampled: false,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+            },
+        ],
+    );
+
+    Ok((texture, view, sampler, layout))
+}
+
+impl TextureAtlas {
+    pub fn new(
+        ctx: &mut Context,
+        subsystem: &str,
+        size: u32,
+        layers: u32,
+        sampler: SamplerDesc,
+    ) -> Result<Self, BudgetExceeded> {
+        let (texture, view, sampler_handle, layout) =
+            create_resources(ctx, subsystem, size, layers, sampler)?;
+
+        let mut atlas = Self {
+            size,
+            subsystem: subsystem.to_string(),
+            sampler_desc: sampler,
+            texture,
+            view,
+            sampler: sampler_handle,
+            layout,
+            layers: (0..layers).map(|_| Layer::default()).collect(),
+            images: vec![],
+            premultiplied: false,
+        };
+
+        // The white block sits at the very corner, without padding before it,
+        // so that `uv: [0.0, 0.0]` lands inside it.
+        let white = image::RgbaImage::from_pixel(
+            WHITE_SIZE,
+            WHITE_SIZE,
+            image::Rgba([0xff, 0xff, 0xff, 0xff]),
+        );
+        let position = atlas
+            .allocate(WHITE_SIZE, WHITE_SIZE)
+            .expect("Atlas too small for its white block");
+        atlas.write(ctx, position, &white)?;
+        atlas.images.push((position, white));
+
+        Ok(atlas)
+    }
+
+    /// Rebuilds the texture on the context's current device and uploads every
+    /// image again at its old position, so existing [`UvRect`]s stay valid.
+    pub fn recreate(&mut self, ctx: &mut Context) -> Result<(), BudgetExceeded> {
+        let (texture, view, sampler, layout) = create_resources(
+            ctx,
+            &self.subsystem,
+            self.size,
+            self.layers.len() as u32,
+            self.sampler_desc,
+        )?;
+        self.texture = texture;
+        self.view = view;
+        self.sampler = sampler;
+        self.layout = layout;
+
+        for (position, image) in &self.images {
+            self.write(ctx, *position, image)?;
+        }
+        Ok(())
+    }
+
+    pub fn layout(&self) -> LayoutId {
+        self.layout
+    }
+
+    /// Whether images are stored with premultiplied alpha, for drawing with
+    /// premultiplied blending. Images already in the atlas are uploaded
+    /// again converted.
+    pub fn set_premultiplied(
+        &mut self,
+        ctx: &mut Context,
+        premultiplied: bool,
+    ) -> Result<(), BudgetExceeded> {
+        if self.premultiplied == premultiplied {
+            return Ok(());
+        }
+        self.premultiplied = premultiplied;
+        for (position, image) in &self.images {
+            self.write(ctx, *position, image)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
+    /// Width and height of each layer, in texels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Coordinates in layer 0 that sample plain white, for untextured quads.
+    pub fn white_uv(&self) -> [f32; 2] {
+        let center = WHITE_SIZE as f32 / 2.0 / self.size as f32;
+        [center, center]
+    }
+
+    /// Copies `image` into the atlas and returns where it ended up, or `None`
+    /// if there is no room left for it.
+    pub fn insert(
+        &mut self,
+        ctx: &mut Context,
+        image: &image::RgbaImage,
+    ) -> Result<Option<UvRect>, BudgetExceeded> {
+        let (width, height) = image.dimensions();
+        let (layer, x, y) = match self.allocate(width, height) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+
+        self.write(ctx, (layer, x, y), image)?;
+        self.images.push(((layer, x, y), image.clone()));
+
+        let size = self.size as f32;
+        Ok(Some(UvRect {
+            layer,
+            min: [x as f32 / size, y as f32 / size],
+            max: [(x + width) as f32 / size, (y + height) as f32 / size],
+        }))
+    }
+
+    pub fn bind_group(&self, ctx: &mut Context) -> Rc<wgpu::BindGroup> {
+        ctx.bind_groups.bind_group(
+            &ctx.device,
+            &format!("{}/atlas", self.subsystem),
+            self.layout,
+            &[
+                Binding::TextureView {
+                    view: &self.view,
+                    id: self.texture.id(),
+                },
+                Binding::Sampler {
+                    sampler: &self.sampler,
+                    id: self.sampler.id(),
+                },
+            ],
+        )
+    }
+
+    /// Finds room for a `width` by `height` image in the first layer that
+    /// has some, returning its layer and position.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32, u32)> {
+        let size = self.size;
+        self.layers
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, layer)| {
+                layer
+                    .allocate(size, width, height)
+                    .map(|(x, y)| (index as u32, x, y))
+            })
+    }
+
+    fn write(
+        &self,
+        ctx: &mut Context,
+        (layer, x, y): (u32, u32, u32),
+        image: &image::RgbaImage,
+    ) -> Result<(), BudgetExceeded> {
+        let premultiplied;
+        let image = if self.premultiplied {
+            let mut copy = image.clone();
+            texture::premultiply_alpha(&mut copy);
+            premultiplied = copy;
+            &premultiplied
+        } else {
+            image
+        };
+        let (width, height) = image.dimensions();
+        texture::write_rgba(
+            ctx,
+            &format!("{}/atlas", self.subsystem),
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                array_layer: layer,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            image,
+        )
+    }
+}
+
+impl Layer {
+    /// Finds room for a `width` by `height` image, preferring the shortest
+    /// shelf it fits on.
+    fn allocate(&mut self, size: u32, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (padded_width, padded_height) = (width + PADDING, height + PADDING);
+        if padded_width > size {
+            return None;
+        }
+
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= padded_height && shelf.x + padded_width <= size)
+            .min_by_key(|shelf| shelf.height);
+
+        let shelf = match shelf {
+            Some(shelf) => shelf,
+            None => {
+                if self.next_shelf + padded_height > size {
+                    return None;
+                }
+                self.shelves.push(Shelf {
+                    y: self.next_shelf,
+                    height: padded_height,
+                    x: 0,
+                });
+                self.next_shelf += padded_height;
+                self.shelves.last_mut().unwrap()
+            }
+        };
+
+        let position = (shelf.x, shelf.y);
+        shelf.x += padded_width;
+        Some(position)
+    }
+}
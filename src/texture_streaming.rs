@@ -0,0 +1,242 @@
+use crate::{
+    assets::texture::{self, Texture, TextureError},
+    Context,
+};
+
+/// Largest side of the version uploaded as soon as a texture is registered.
+const PREVIEW_SIZE: u32 = 64;
+
+/// Upgrades are spread over frames so streaming doesn't cause hitches.
+const UPGRADES_PER_FRAME: usize = 2;
+
+/// Fraction of the memory budget streaming may fill before it starts evicting
+/// textures.
+const BUDGET_HEADROOM: f64 = 0.9;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StreamId(usize);
+
+struct Entry {
+    label: String,
+    source: image::RgbaImage,
+    resident: Option<Texture>,
+    /// Mip levels skipped by the resident copy, i.e. it is `source` scaled
+    /// down by `1 << skipped`.
+    skipped: u32,
+    wanted: u32,
+    last_used: u64,
+}
+
+impl Entry {
+    /// Levels to skip so the largest side is still at least `size` pixels.
+    fn skip_for(&self, size: u32) -> u32 {
+        let largest = self.source.width().max(self.source.height()).max(1);
+        let mut skip = 0;
+        while largest >> (skip + 1) >= size.max(1) {
+            skip += 1;
+        }
+        skip
+    }
+
+    /// Rough size of the resident copy with `skipped` levels dropped,
+    /// including its mip chain.
+    fn size_at(&self, skipped: u32) -> wgpu::BufferAddress {
+        let width = (self.source.width() >> skipped).max(1) as wgpu::BufferAddress;
+        let height = (self.source.height() >> skipped).max(1) as wgpu::BufferAddress;
+        width * height * 4 * 4 / 3
+    }
+
+    fn upload(&mut self, ctx: &mut Context, skipped: u32) -> Result<(), TextureError> {
+        let image = if skipped == 0 {
+            self.source.clone()
+        } else {
+            image::imageops::resize(
+                &self.source,
+                (self.source.width() >> skipped).max(1),
+                (self.source.height() >> skipped).max(1),
+                image::imageops::FilterType::Triangle,
+            )
+        };
+
+        // The old copy is only replaced once the new one is uploaded, so a
+        // failed upload, e.g. over budget, leaves the texture drawable.
+        let texture =
+            Texture::from_image(ctx, &self.label, &image::DynamicImage::ImageRgba8(image))?;
+        self.resident = Some(texture);
+        self.skipped = skipped;
+        Ok(())
+    }
+}
+
+/// Keeps textures resident at the resolution they are drawn at.
+///
+/// Each texture starts as a small preview. Callers report how large a texture
+/// is drawn every frame with [`TextureStreamer::touch`]; [`TextureStreamer::update`]
+/// then uploads sharper versions of the most recently used ones and, when
+/// tracked memory gets close to its budget, evicts the least recently used.
+///
+/// Full-resolution pixels stay in system memory, so only VRAM is streamed.
+#[derive(Default)]
+pub struct TextureStreamer {
+    entries: Vec<Option<Entry>>,
+    free_ids: Vec<usize>,
+    frame: u64,
+}
+
+impl TextureStreamer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a PNG or JPEG file through the [`vfs`](crate::vfs) and uploads
+    /// its preview.
+    pub fn load(
+        &mut self,
+        ctx: &mut Context,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<StreamId, TextureError> {
+        let path = path.as_ref();
+        let image = image::load_from_memory(&texture::read_bytes(path)?)?.to_rgba();
+        self.insert(ctx, &format!("streaming/{}", path.display()), image)
+    }
+
+    /// Takes ownership of `image` and uploads its preview.
+    pub fn insert(
+        &mut self,
+        ctx: &mut Context,
+        label: &str,
+        image: image::RgbaImage,
+    ) -> Result<StreamId, TextureError> {
+        let mut entry = Entry {
+            label: label.to_owned(),
+            source: image,
+            resident: None,
+            skipped: 0,
+            wanted: 0,
+            last_used: self.frame,
+        };
+        let preview = entry.skip_for(PREVIEW_SIZE);
+        entry.wanted = preview;
+        entry.upload(ctx, preview)?;
+
+        Ok(match self.free_ids.pop() {
+            Some(id) => {
+                self.entries[id] = Some(entry);
+                StreamId(id)
+            }
+            None => {
+                self.entries.push(Some(entry));
+                StreamId(self.entries.len() - 1)
+            }
+        })
+    }
+
+    pub fn remove(&mut self, id: StreamId) {
+        if self.entries[id.0].take().is_some() {
+            self.free_ids.push(id.0);
+        }
+    }
+
+    /// Marks the texture as drawn this frame at up to `size` pixels along its
+    /// largest side.
+    pub fn touch(&mut self, id: StreamId, size: u32) {
+        let frame = self.frame;
+        if let Some(entry) = &mut self.entries[id.0] {
+            let wanted = entry.skip_for(size);
+            entry.wanted = if entry.last_used == frame {
+                entry.wanted.min(wanted)
+            } else {
+                wanted
+            };
+            entry.last_used = frame;
+        }
+    }
+
+    /// Forgets every resident copy, e.g. after `Context::recover`. Textures
+    /// are uploaded again, preview first, as they are touched.
+    pub fn recreate(&mut self) {
+        for entry in self.entries.iter_mut().flatten() {
+            entry.resident = None;
+        }
+    }
+
+    /// The resident copy, or `None` if it was evicted and hasn't been
+    /// reloaded yet.
+    pub fn get(&self, id: StreamId) -> Option<&Texture> {
+        self.entries[id.0].as_ref()?.resident.as_ref()
+    }
+
+    /// Evicts and upgrades textures. Call once per frame after drawing.
+    pub fn update(&mut self, ctx: &mut Context) -> Result<(), TextureError> {
+        let limit = ctx
+            .memory
+            .budget()
+            .map(|budget| (budget as f64 * BUDGET_HEADROOM) as wgpu::BufferAddress);
+
+        // Most recently used first, so those are upgraded first and the
+        // ones at the back are evicted first.
+        let mut order = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(id, entry)| entry.as_ref().map(|entry| (id, entry.last_used)))
+            .collect::<Vec<_>>();
+        order.sort_by_key(|&(_, last_used)| std::cmp::Reverse(last_used));
+
+        if let Some(limit) = limit {
+            self.evict_until(ctx, limit, self.frame);
+        }
+
+        let mut upgrades = 0;
+        for &(id, last_used) in &order {
+            // Only textures drawn this frame are worth uploading.
+            if upgrades == UPGRADES_PER_FRAME || last_used != self.frame {
+                break;
+            }
+
+            let entry = self.entries[id].as_ref().unwrap();
+            if entry.resident.is_some() && entry.wanted >= entry.skipped {
+                continue;
+            }
+
+            // A texture that was evicted comes back as a preview first.
+            let target = match entry.resident {
+                Some(_) => entry.skipped - 1,
+                None => entry.skip_for(PREVIEW_SIZE).max(entry.wanted),
+            };
+            // Both copies are alive while the new one uploads, so it needs
+            // room of its own.
+            let growth = entry.size_at(target);
+            if let Some(limit) = limit {
+                if !self.evict_until(ctx, limit.saturating_sub(growth), self.frame) {
+                    break;
+                }
+            }
+
+            self.entries[id].as_mut().unwrap().upload(ctx, target)?;
+            upgrades += 1;
+        }
+
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Drops resident textures last used before `frame`, oldest first, until
+    /// at most `target` bytes are allocated. Returns whether it got there.
+    fn evict_until(&mut self, ctx: &Context, target: wgpu::BufferAddress, frame: u64) -> bool {
+        while ctx.memory.allocated() > target {
+            let oldest = self
+                .entries
+                .iter_mut()
+                .filter_map(Option::as_mut)
+                .filter(|entry| entry.resident.is_some() && entry.last_used < frame)
+                .min_by_key(|entry| entry.last_used);
+
+            match oldest {
+                Some(entry) => entry.resident = None,
+                None => return false,
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+/// How much time each `Application::update` simulates, i.e. 60 Hz.
+pub const FIXED_STEP: Duration = Duration::from_nanos(16_666_667);
+/// Time the simulation may fall behind by. After a longer stall, e.g. in a
+/// debugger, it skips ahead instead of running dozens of steps at once.
+const MAX_BACKLOG: Duration = Duration::from_millis(250);
+
+/// The frame clock, ticked by the main loop once per frame.
+///
+/// Each tick adds the frame's time to a backlog that [`Time::fixed_step`]
+/// consumes in [`FIXED_STEP`]s, so the simulation runs at the same rate
+/// whatever the refresh rate. What is left over is [`Time::alpha`].
+///
+/// Time is measured between ticks, so it includes pacing and present waits
+/// and stands still while the window is minimized and not redrawing.
+///
+/// The [`scale`](Time::set_scale) only applies to the backlog: the
+/// simulation slows down or pauses, while `delta` and `elapsed`, which the
+/// interface animates with, stay in real time.
+pub struct Time {
+    start: Instant,
+    last_tick: Option<Instant>,
+    delta: Duration,
+    frame: u64,
+    backlog: Duration,
+    steps: u64,
+    scale: f32,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            last_tick: None,
+            delta: Duration::default(),
+            frame: 0,
+            backlog: Duration::default(),
+            steps: 0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Time {
+    /// Starts a new frame. The first frame has a delta of zero.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_tick.replace(now) {
+            self.delta = now - last;
+            self.frame += 1;
+            self.backlog = (self.backlog + self.delta.mul_f32(self.scale)).min(MAX_BACKLOG);
+        }
+    }
+
+    /// Takes one fixed step off the backlog; `false` once less than a step
+    /// is left. Call `Application::update` while it returns `true`.
+    pub fn fixed_step(&mut self) -> bool {
+        if self.backlog < FIXED_STEP {
+            return false;
+        }
+        self.backlog -= FIXED_STEP;
+        self.steps += 1;
+        true
+    }
+
+    /// How fast simulated time passes: 1 is real time, 0.25 slow motion and
+    /// 0 paused. Applies from the next tick.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// How far rendering is between the last two fixed steps, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        self.backlog.as_secs_f32() / FIXED_STEP.as_secs_f32()
+    }
+
+    /// Fixed steps run so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Time since the previous frame.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Time since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        self.last_tick.unwrap_or(self.start) - self.start
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.elapsed().as_secs_f64()
+    }
+
+    /// Frames ticked so far, counting from 0.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
@@ -0,0 +1,161 @@
+//! One-shot and repeating timers, e.g. flashing a widget for half a second
+//! or autosaving every five minutes.
+//!
+//! Timers count simulated time: [`Timers::update`] runs once per fixed
+//! step and fires every timer that came due, in the order they are due.
+//! A timer runs a callback with the [`StateContext`], or sends an event.
+
+use std::time::Duration;
+
+use crate::{state::StateContext, time::FIXED_STEP};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+type Once = Box<dyn FnOnce(&mut StateContext)>;
+type Repeat = Box<dyn FnMut(&mut StateContext)>;
+
+enum Action {
+    /// Taken when it fires.
+    Once(Option<Once>),
+    Repeat(Repeat),
+}
+
+struct Timer {
+    id: TimerId,
+    due: Duration,
+    /// Set for repeating timers.
+    period: Option<Duration>,
+    action: Action,
+}
+
+#[derive(Default)]
+pub struct Timers {
+    timers: Vec<Timer>,
+    next_id: u64,
+    /// Simulated time since the timers were created.
+    now: Duration,
+    /// Timers out of `timers` while their callbacks run.
+    firing: Vec<TimerId>,
+    /// Ones from `firing` cancelled by a callback.
+    cancelled: Vec<TimerId>,
+}
+
+impl Timers {
+    /// Calls `f` once, `delay` from now.
+    pub fn once(
+        &mut self,
+        delay: Duration,
+        f: impl FnOnce(&mut StateContext) + 'static,
+    ) -> TimerId {
+        self.add(delay, None, Action::Once(Some(Box::new(f))))
+    }
+
+    /// Calls `f` every `period`, starting `period` from now. Periods shorter
+    /// than a fixed step fire once per step.
+    pub fn every(
+        &mut self,
+        period: Duration,
+        f: impl FnMut(&mut StateContext) + 'static,
+    ) -> TimerId {
+        self.add(period, Some(period), Action::Repeat(Box::new(f)))
+    }
+
+    /// Sends `event` once, `delay` from now.
+    pub fn once_event<E: 'static>(&mut self, delay: Duration, event: E) -> TimerId {
+        self.once(delay, move |cx| cx.events.send(event))
+    }
+
+    /// Sends a copy of `event` every `period`.
+    pub fn every_event<E: Clone + 'static>(&mut self, period: Duration, event: E) -> TimerId {
+        self.every(period, move |cx| cx.events.send(event.clone()))
+    }
+
+    /// Stops the timer. Returns whether it was still going; one-shot
+    /// timers stop once they fired.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        if self.firing.contains(&id) {
+            if self.cancelled.contains(&id) {
+                return false;
+            }
+            self.cancelled.push(id);
+            return true;
+        }
+        let before = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        self.timers.len() != before
+    }
+
+    pub fn is_active(&self, id: TimerId) -> bool {
+        self.remaining(id).is_some()
+    }
+
+    /// Time until the timer fires next.
+    pub fn remaining(&self, id: TimerId) -> Option<Duration> {
+        self.timers
+            .iter()
+            .find(|timer| timer.id == id)
+            .map(|timer| timer.due.saturating_sub(self.now))
+    }
+
+    /// Timers still going.
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    /// Advances `cx.timers` by one fixed step and fires the timers that came
+    /// due. Callbacks can add and cancel timers, including their own.
+    pub fn update(cx: &mut StateContext) {
+        let timers = &mut *cx.timers;
+        timers.now += FIXED_STEP;
+        let now = timers.now;
+        let (mut due, pending) = std::mem::take(&mut timers.timers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|timer| timer.due <= now);
+        timers.timers = pending;
+        due.sort_by_key(|timer| (timer.due, timer.id.0));
+        timers.firing = due.iter().map(|timer| timer.id).collect();
+
+        for mut timer in due {
+            if cx.timers.cancelled.contains(&timer.id) {
+                continue;
+            }
+            match &mut timer.action {
+                Action::Once(f) => {
+                    if let Some(f) = f.take() {
+                        f(cx);
+                    }
+                }
+                Action::Repeat(f) => f(cx),
+            }
+            let timers = &mut *cx.timers;
+            if let (Some(period), false) = (timer.period, timers.cancelled.contains(&timer.id)) {
+                // Periods missed entirely are skipped rather than fired in
+                // a burst.
+                timer.due += period;
+                if timer.due <= now {
+                    timer.due = now + period;
+                }
+                timers.timers.push(timer);
+            }
+        }
+        cx.timers.firing.clear();
+        cx.timers.cancelled.clear();
+    }
+
+    fn add(&mut self, delay: Duration, period: Option<Duration>, action: Action) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.timers.push(Timer {
+            id,
+            due: self.now + delay,
+            period,
+            action,
+        });
+        id
+    }
+}
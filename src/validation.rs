@@ -0,0 +1,100 @@
+//! Reporting for errors raised inside wgpu.
+//!
+//! wgpu 0.5 has neither error scopes nor an uncaptured-error callback:
+//! validation problems are logged through the `log` crate, and fatal ones
+//! panic. The [`logging`](crate::logging) subsystem and the panic hook
+//! installed here tag both with the pass that was running, as set by
+//! [`scope`].
+//!
+//! GPU resources and command encoders are labelled `subsystem/resource`,
+//! e.g. `interface/vertices`; Vulkan and DX12 pass the labels on to GPU
+//! debuggers. wgpu 0.5 doesn't implement debug groups (its render pass
+//! hooks are empty), so the scope names double as the pass names and
+//! match the labels of the encoders they record.
+
+use std::{cell::RefCell, fmt, sync::Mutex};
+
+thread_local! {
+    static SCOPES: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A warning or error logged by wgpu or its backends.
+#[derive(Debug)]
+pub struct GpuMessage {
+    pub level: log::Level,
+    /// Innermost [`scope`] active when it was logged, if any.
+    pub scope: Option<&'static str>,
+    pub message: String,
+}
+
+impl fmt::Display for GpuMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.scope {
+            Some(scope) => write!(f, "wgpu {} in {}: {}", self.level, scope, self.message),
+            None => write!(f, "wgpu {}: {}", self.level, self.message),
+        }
+    }
+}
+
+type Handler = Box<dyn Fn(&GpuMessage) + Send>;
+
+static HANDLER: Mutex<Option<Handler>> = Mutex::new(None);
+
+/// Passes warnings and errors from wgpu and gfx to the handler, if one is
+/// set. Returns whether it took the record; otherwise the logger prints
+/// it like any other.
+pub(crate) fn report(record: &log::Record) -> bool {
+    let target = record.target();
+    if record.level() > log::Level::Warn
+        || !(target.starts_with("wgpu") || target.starts_with("gfx"))
+    {
+        return false;
+    }
+    match &*HANDLER.lock().unwrap() {
+        Some(handler) => {
+            handler(&GpuMessage {
+                level: record.level(),
+                scope: current_scope(),
+                message: record.args().to_string(),
+            });
+            true
+        }
+        None => false,
+    }
+}
+
+/// Makes panics say which scope they happened in. Call once, before
+/// creating the context.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(scope) = current_scope() {
+            log::error!("panic while recording {}", scope);
+        }
+        default_hook(info);
+    }));
+}
+
+/// Takes wgpu's messages away from the logger, which otherwise prints them
+/// tagged with their scope.
+pub fn set_handler(handler: impl Fn(&GpuMessage) + Send + 'static) {
+    *HANDLER.lock().unwrap() = Some(Box::new(handler));
+}
+
+/// Marks wgpu calls made until the guard drops as belonging to `name`.
+pub fn scope(name: &'static str) -> Scope {
+    SCOPES.with(|scopes| scopes.borrow_mut().push(name));
+    Scope(())
+}
+
+pub(crate) fn current_scope() -> Option<&'static str> {
+    SCOPES.with(|scopes| scopes.borrow().last().copied())
+}
+
+pub struct Scope(());
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        SCOPES.with(|scopes| scopes.borrow_mut().pop());
+    }
+}
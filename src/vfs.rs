@@ -0,0 +1,227 @@
+//! The virtual filesystem assets are read through, so the same relative
+//! path can come from a loose directory while developing or from a packed
+//! archive in a shipped build.
+//!
+//! Directories and [`Pak`] archives are mounted on top of each other; a
+//! read looks through the most recently mounted first, so an overlay such
+//! as a mod replaces just the files it contains. With nothing mounted,
+//! paths are read from the working directory, and absolute paths always
+//! bypass the mounts.
+//!
+//! Below every mount is the [`Source`] of the engine's own assets, e.g.
+//! `shaders/interface.vert.wgsl`: the `assets/` directory, or a copy
+//! compiled into the binary with the `embed-assets` feature.
+
+#[cfg(feature = "embed-assets")]
+mod embedded;
+pub mod pak;
+
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+    sync::{LazyLock, RwLock},
+};
+
+pub use self::pak::{Pak, PakError};
+
+/// The archive shipped builds mount, if it exists.
+pub const DEFAULT_PAK: &str = "assets.pak";
+
+/// The engine's assets next to its sources.
+pub const ASSET_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets");
+
+static VFS: LazyLock<RwLock<Vfs>> = LazyLock::new(Default::default);
+
+/// Where the engine's own assets are read from, under every mount.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    /// Files in a directory laid out like `assets/`, which can be edited
+    /// and hot reloaded.
+    Directory(PathBuf),
+    /// The copy compiled into the binary.
+    #[cfg(feature = "embed-assets")]
+    Embedded,
+}
+
+impl Default for Source {
+    /// Embedded if compiled in, [`ASSET_DIR`] otherwise.
+    fn default() -> Self {
+        #[cfg(feature = "embed-assets")]
+        return Source::Embedded;
+        #[cfg(not(feature = "embed-assets"))]
+        return Source::Directory(ASSET_DIR.into());
+    }
+}
+
+impl Source {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+        match self {
+            Source::Directory(dir) => read_file(&dir.join(path)),
+            #[cfg(feature = "embed-assets")]
+            Source::Embedded => embedded::get(&normalize(path)?).map(|file| Ok(file.to_vec())),
+        }
+    }
+
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        match self {
+            Source::Directory(dir) => Some(dir.join(path)).filter(|file| file.is_file()),
+            #[cfg(feature = "embed-assets")]
+            Source::Embedded => None,
+        }
+    }
+}
+
+/// `None` if there is no file at `path`.
+fn read_file(path: &Path) -> Option<io::Result<Vec<u8>>> {
+    match std::fs::read(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        result => Some(result),
+    }
+}
+
+pub enum Mount {
+    Directory(PathBuf),
+    Pak(Pak),
+}
+
+impl Mount {
+    fn read(&self, path: &Path) -> Option<io::Result<Vec<u8>>> {
+        match self {
+            Mount::Directory(dir) => read_file(&dir.join(path)),
+            Mount::Pak(pak) => {
+                let name = normalize(path)?;
+                if pak.contains(&name) {
+                    Some(pak.read(&name))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Vfs {
+    /// Searched last to first.
+    mounts: Vec<Mount>,
+    source: Source,
+}
+
+impl Vfs {
+    pub fn new(source: Source) -> Self {
+        Self {
+            mounts: vec![],
+            source,
+        }
+    }
+
+    pub fn set_source(&mut self, source: Source) {
+        self.source = source;
+    }
+
+    /// Mounts `path` on top of the current mounts: the directory itself, or
+    /// the archive if it is a file.
+    pub fn mount(&mut self, path: impl AsRef<Path>) -> Result<(), PakError> {
+        let path = path.as_ref();
+        let mount = if path.is_dir() {
+            Mount::Directory(path.to_owned())
+        } else {
+            Mount::Pak(Pak::open(path)?)
+        };
+        self.mounts.push(mount);
+        Ok(())
+    }
+
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    /// The contents of the file at `path` in the topmost mount that has it,
+    /// or else in the source.
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            return std::fs::read(path);
+        }
+        let found = if self.mounts.is_empty() {
+            read_file(path)
+        } else {
+            self.mounts.iter().rev().find_map(|mount| mount.read(path))
+        };
+        found.or_else(|| self.source.read(path)).unwrap_or_else(|| {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} isn't in any mount", path.display()),
+            ))
+        })
+    }
+
+    /// The file on disk `read` would read `path` from, e.g. to watch it.
+    /// `None` if it would come from an archive, the embedded source, or
+    /// doesn't exist.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            return Some(path.to_owned()).filter(|path| path.is_file());
+        }
+        if self.mounts.is_empty() && path.is_file() {
+            return Some(path.to_owned());
+        }
+        let name = normalize(path);
+        for mount in self.mounts.iter().rev() {
+            match mount {
+                Mount::Directory(dir) => {
+                    let file = dir.join(path);
+                    if file.is_file() {
+                        return Some(file);
+                    }
+                }
+                Mount::Pak(pak) => {
+                    if matches!(&name, Some(name) if pak.contains(name)) {
+                        return None;
+                    }
+                }
+            }
+        }
+        self.source.resolve(path)
+    }
+}
+
+/// `path` as an archive name: relative, `/`-separated and without `.`
+/// components. `None` for paths that can't be in an archive, e.g. ones
+/// leaving the archive root, or that aren't UTF-8.
+pub fn normalize(path: &Path) -> Option<String> {
+    let mut parts = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Mounts `path` on the process-wide filesystem; see [`Vfs::mount`].
+pub fn mount(path: impl AsRef<Path>) -> Result<(), PakError> {
+    VFS.write().expect("vfs poisoned").mount(path)
+}
+
+/// Changes where the process-wide filesystem reads the engine's assets.
+pub fn set_source(source: Source) {
+    VFS.write().expect("vfs poisoned").set_source(source);
+}
+
+/// Reads through the process-wide filesystem; see [`Vfs::read`].
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    VFS.read().expect("vfs poisoned").read(path)
+}
+
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// See [`Vfs::resolve`].
+pub fn resolve(path: impl AsRef<Path>) -> Option<PathBuf> {
+    VFS.read().expect("vfs poisoned").resolve(path)
+}
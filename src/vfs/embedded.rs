@@ -0,0 +1,32 @@
+//! The engine's own assets compiled into the binary, for builds with the
+//! `embed-assets` feature. Names are relative to the `assets/` directory.
+//!
+//! Shaders are embedded as the SPIR-V `build.rs` compiled them to, without
+//! their WGSL sources. The build lists them too, as it knows which variants
+//! of each shader there are.
+
+macro_rules! embed {
+    ($var:literal, $dir:literal; $($name:literal),* $(,)?) => {
+        &[$((
+            $name,
+            include_bytes!(concat!(env!($var), $dir, "/", $name)),
+        )),*]
+    };
+}
+
+static FILES: &[(&str, &[u8])] = embed![
+    "CARGO_MANIFEST_DIR", "/assets";
+    "icon.png",
+    "scenes/gameplay.ron",
+];
+
+static SHADERS: &[(&str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/shaders/shaders.rs"));
+
+/// The embedded file named `name`, e.g. `shaders/blit.vert.spv`.
+pub fn get(name: &str) -> Option<&'static [u8]> {
+    FILES
+        .iter()
+        .chain(SHADERS)
+        .find(|(file, _)| *file == name)
+        .map(|(_, contents)| *contents)
+}
@@ -0,0 +1,189 @@
+//! A simple archive format packing a directory of assets into one file.
+//!
+//! ```text
+//! "MPAK" | version: u32 | count: u32
+//! count x (name length: u32 | name | offset: u64 | size: u64)
+//! file contents, at the offsets above
+//! ```
+//!
+//! Integers are little-endian, names are UTF-8 paths relative to the packed
+//! directory with `/` separators, and offsets count from the start of the
+//! archive. The index is read when the archive is opened; file contents are
+//! read on demand.
+
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    fmt, fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const MAGIC: &[u8; 4] = b"MPAK";
+const VERSION: u32 = 1;
+/// Longest name accepted in an index, well past any real path, so a
+/// corrupt length can't ask for gigabytes.
+const MAX_NAME_LENGTH: u32 = 4096;
+
+#[derive(Debug)]
+pub enum PakError {
+    Io(io::Error),
+    /// The file isn't an archive of this version, or its index is cut off
+    /// or points outside the file.
+    Malformed(&'static str),
+}
+
+impl fmt::Display for PakError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PakError::Io(err) => write!(f, "failed to read archive: {}", err),
+            PakError::Malformed(reason) => write!(f, "malformed archive: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PakError {}
+
+impl From<io::Error> for PakError {
+    fn from(err: io::Error) -> Self {
+        PakError::Io(err)
+    }
+}
+
+/// An open archive.
+pub struct Pak {
+    path: PathBuf,
+    /// Offset and size of each file, by name.
+    entries: HashMap<String, (u64, u64)>,
+    file: Mutex<fs::File>,
+}
+
+impl Pak {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PakError> {
+        let path = path.as_ref();
+        let mut file = fs::File::open(path)?;
+        let file_size = file.metadata()?.len();
+
+        let mut header = [0; 12];
+        file.read_exact(&mut header)
+            .map_err(|_| PakError::Malformed("truncated header"))?;
+        if &header[..4] != MAGIC {
+            return Err(PakError::Malformed("not an archive"));
+        }
+        if u32::from_le_bytes(header[4..8].try_into().unwrap()) != VERSION {
+            return Err(PakError::Malformed("unsupported version"));
+        }
+        let count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+
+        let mut entries = HashMap::new();
+        let mut reader = io::BufReader::new(&mut file);
+        for _ in 0..count {
+            let truncated = |_| PakError::Malformed("truncated index");
+            let mut length = [0; 4];
+            reader.read_exact(&mut length).map_err(truncated)?;
+            let length = u32::from_le_bytes(length);
+            if length > MAX_NAME_LENGTH || u64::from(length) > file_size {
+                return Err(PakError::Malformed("name too long"));
+            }
+            let mut name = vec![0; length as usize];
+            reader.read_exact(&mut name).map_err(truncated)?;
+            let name = String::from_utf8(name).map_err(|_| PakError::Malformed("invalid name"))?;
+            let mut range = [0; 16];
+            reader.read_exact(&mut range).map_err(truncated)?;
+            let offset = u64::from_le_bytes(range[..8].try_into().unwrap());
+            let size = u64::from_le_bytes(range[8..].try_into().unwrap());
+            // Checked here so `read` can trust the index.
+            let in_file = offset.checked_add(size).is_some_and(|end| end <= file_size);
+            if !in_file || usize::try_from(size).is_err() {
+                return Err(PakError::Malformed("entry outside the archive"));
+            }
+            entries.insert(name, (offset, size));
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            entries,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// The packed file's names, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The contents of the file packed as `name`.
+    pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        let &(offset, size) = self
+            .entries
+            .get(name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_owned()))?;
+        let mut file = self.file.lock().expect("archive poisoned");
+        file.seek(SeekFrom::Start(offset))?;
+        let mut contents = vec![0; size as usize];
+        file.read_exact(&mut contents)?;
+        Ok(contents)
+    }
+}
+
+/// Packs every file under `dir` into an archive at `output`. Returns how
+/// many files were packed.
+pub fn write(dir: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<usize, PakError> {
+    let dir = dir.as_ref();
+    let mut files = vec![];
+    collect_files(dir, &mut files)?;
+    files.sort();
+
+    let names = files
+        .iter()
+        .map(|file| {
+            let relative = file
+                .strip_prefix(dir)
+                .expect("file outside the packed directory");
+            super::normalize(relative).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "file name isn't valid UTF-8").into()
+            })
+        })
+        .collect::<Result<Vec<String>, PakError>>()?;
+    let index_size: usize = names.iter().map(|name| 4 + name.len() + 16).sum();
+
+    let mut out = io::BufWriter::new(fs::File::create(output)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&(files.len() as u32).to_le_bytes())?;
+    let mut offset = (12 + index_size) as u64;
+    for (file, name) in files.iter().zip(&names) {
+        let size = fs::metadata(file)?.len();
+        out.write_all(&(name.len() as u32).to_le_bytes())?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        out.write_all(&size.to_le_bytes())?;
+        offset += size;
+    }
+    for file in &files {
+        io::copy(&mut fs::File::open(file)?, &mut out)?;
+    }
+    out.flush()?;
+    Ok(files.len())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
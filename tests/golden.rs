@@ -0,0 +1,278 @@
+//! Golden-image tests for the interface pass, and smoke tests checking a
+//! few pixels for the passes drawn under it.
+//!
+//! Each test renders a small scene with a headless context, reads it back
+//! and compares it against `tests/golden/<name>.png`. Mismatches write the
+//! rendered image and a diff next to each other in `target/golden/`.
+//! Run with `GOLDEN_UPDATE=1` to overwrite the references instead.
+//!
+//! They need a GPU adapter and fail rather than pass unchecked without
+//! one. On a machine that has none, set `GOLDEN_SKIP=1` to skip them.
+//!
+//! The committed references were drawn from the scene definitions, not
+//! rendered. Regenerate them with `GOLDEN_UPDATE=1` on real hardware and
+//! check the result by eye before relying on them.
+
+use std::path::PathBuf;
+
+use futures::executor::block_on;
+use image::{Rgba, RgbaImage};
+
+use minimal_error::{
+    adapter::AdapterChoice,
+    assets::texture::Texture,
+    color::Color,
+    debug_font,
+    error::EngineError,
+    passes::{Layer, ParticleEmitter, ParticlePass, Pass, Rect, SpritePass},
+    render_graph::RenderGraph,
+    Context, InterfacePass,
+};
+
+/// Largest per-channel difference that still counts as a match.
+const CHANNEL_TOLERANCE: u8 = 2;
+/// Fraction of pixels allowed to differ by more, e.g. along edges that
+/// rasterize slightly differently between drivers.
+const MISMATCH_TOLERANCE: f64 = 0.001;
+
+struct Harness {
+    ctx: Context,
+    pass: InterfacePass,
+}
+
+impl Harness {
+    /// A `width` by `height` headless context, or `None` when
+    /// `GOLDEN_SKIP` is set.
+    fn new(name: &str, width: u32, height: u32) -> Option<Self> {
+        if std::env::var_os("GOLDEN_SKIP").is_some() {
+            eprintln!("skipping golden test {}: GOLDEN_SKIP is set", name);
+            return None;
+        }
+        let mut ctx = match block_on(Context::new_headless(
+            width,
+            height,
+            AdapterChoice::default(),
+        )) {
+            Ok(ctx) => ctx,
+            Err(EngineError::NoAdapter(_)) => {
+                panic!("golden test {} needs a GPU adapter and found none", name)
+            }
+            Err(err) => panic!("Failed to create headless context: {}", err),
+        };
+        let pass = InterfacePass::new(&mut ctx).expect("Failed to create interface pass");
+        Some(Self { ctx, pass })
+    }
+
+    /// Renders what `scene` queues on a black background and reads it back.
+    fn render(&mut self, scene: impl FnOnce(&mut InterfacePass)) -> RgbaImage {
+        self.render_with(&mut [], scene)
+    }
+
+    /// Like [`Harness::render`], with `passes` drawn in order between the
+    /// background and the interface.
+    fn render_with(
+        &mut self,
+        passes: &mut [&mut dyn Pass],
+        scene: impl FnOnce(&mut InterfacePass),
+    ) -> RgbaImage {
+        let size = self.ctx.logical_size();
+        self.pass.clear();
+        self.pass.set_logical_size(size.width, size.height);
+        scene(&mut self.pass);
+
+        let mut frame = self
+            .ctx
+            .begin_frame()
+            .expect("Failed to begin golden frame");
+        let mut graph = RenderGraph::default();
+        graph.set_clear_color(Some(Color::BLACK));
+        let mut passes: Vec<&mut dyn Pass> = passes
+            .iter_mut()
+            .map(|pass| &mut **pass as &mut dyn Pass)
+            .collect();
+        passes.push(&mut self.pass);
+        graph
+            .render(&mut self.ctx, &mut frame, &mut passes)
+            .expect("Failed to render golden scene");
+        self.ctx
+            .end_frame(frame)
+            .expect("Failed to submit golden scene");
+        self.ctx
+            .read_pixels()
+            .expect("Failed to read back golden scene")
+    }
+}
+
+fn manifest_path(relative: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+/// Panics unless `actual` matches the reference image `name`.
+fn assert_golden(name: &str, actual: &RgbaImage) {
+    let reference_path = manifest_path("tests/golden").join(format!("{}.png", name));
+    if std::env::var_os("GOLDEN_UPDATE").is_some() {
+        actual
+            .save(&reference_path)
+            .expect("Failed to write reference image");
+        return;
+    }
+
+    let expected = image::open(&reference_path)
+        .unwrap_or_else(|err| panic!("Failed to open {}: {}", reference_path.display(), err))
+        .to_rgba();
+    assert_eq!(
+        actual.dimensions(),
+        expected.dimensions(),
+        "{} rendered at the wrong size",
+        name
+    );
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut mismatched = 0;
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        let reference = expected.get_pixel(x, y);
+        let error = pixel
+            .0
+            .iter()
+            .zip(reference.0.iter())
+            .map(|(&a, &b)| (a as i16 - b as i16).unsigned_abs() as u8)
+            .max()
+            .unwrap_or(0);
+        if error > CHANNEL_TOLERANCE {
+            mismatched += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 255, 255]));
+        } else {
+            let luma = (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 12;
+            diff.put_pixel(x, y, Rgba([luma as u8, luma as u8, luma as u8, 255]));
+        }
+    }
+
+    let total = (actual.width() * actual.height()) as f64;
+    if mismatched as f64 > total * MISMATCH_TOLERANCE {
+        let out = manifest_path("target/golden");
+        let _ = std::fs::create_dir_all(&out);
+        let _ = actual.save(out.join(format!("{}.actual.png", name)));
+        let _ = diff.save(out.join(format!("{}.diff.png", name)));
+        panic!(
+            "{} differs from its reference in {} of {} pixels; see {}",
+            name,
+            mismatched,
+            total,
+            out.display()
+        );
+    }
+}
+
+#[test]
+fn interface_rects() {
+    let mut harness = match Harness::new("interface_rects", 64, 48) {
+        Some(harness) => harness,
+        None => return,
+    };
+    // Overlapping quads are painted in submission order.
+    let image = harness.render(|pass| {
+        pass.draw_rect([4.0, 4.0], [28.0, 28.0], Color::srgb(1.0, 0.0, 0.0, 1.0));
+        pass.draw_rect([20.0, 12.0], [44.0, 36.0], Color::srgb(0.0, 1.0, 0.0, 1.0));
+        pass.draw_rect([36.0, 20.0], [60.0, 44.0], Color::srgb(0.0, 0.0, 1.0, 1.0));
+    });
+    assert_golden("interface_rects", &image);
+}
+
+#[test]
+fn interface_orientation() {
+    let mut harness = match Harness::new("interface_orientation", 32, 32) {
+        Some(harness) => harness,
+        None => return,
+    };
+    // Wider than tall and in the top-left corner, so a flipped or
+    // transposed camera shows up.
+    let image = harness.render(|pass| pass.draw_rect([0.0, 0.0], [16.0, 8.0], Color::WHITE));
+    assert_golden("interface_orientation", &image);
+}
+
+#[test]
+fn debug_font() {
+    let mut harness = match Harness::new("debug_font", 64, 16) {
+        Some(harness) => harness,
+        None => return,
+    };
+    let image = harness.render(|pass| {
+        debug_font::draw_text(pass, [2.0, 4.0], 1.0, Color::WHITE, "0123 FPS");
+    });
+    assert_golden("debug_font", &image);
+}
+
+#[test]
+fn particles() {
+    let mut harness = match Harness::new("particles", 32, 32) {
+        Some(harness) => harness,
+        None => return,
+    };
+    let mut particles =
+        ParticlePass::new(&mut harness.ctx, 16).expect("Failed to create particle pass");
+    // Standing still in the middle, so one frame's spawn is all on top of
+    // each other.
+    *particles.emitter_mut() = ParticleEmitter {
+        position: [16.0, 16.0],
+        spread: 0.0,
+        color: Color::srgb(1.0, 0.0, 0.0, 1.0),
+        end_color: Color::srgb(1.0, 0.0, 0.0, 1.0),
+        size: 8.0,
+        lifetime: 10.0,
+        ..Default::default()
+    };
+    particles.burst(16);
+    let image = harness.render_with(&mut [&mut particles], |_| {});
+
+    let centre = image.get_pixel(16, 16);
+    assert!(
+        centre[0] > 128 && centre[1] < 32 && centre[2] < 32,
+        "particles missing from the centre: {:?}",
+        centre
+    );
+    assert_eq!(*image.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+}
+
+#[test]
+fn sprites() {
+    let mut harness = match Harness::new("sprites", 32, 32) {
+        Some(harness) => harness,
+        None => return,
+    };
+    let mut sprites = SpritePass::new(&mut harness.ctx).expect("Failed to create sprite pass");
+    let textures: Vec<Texture> = [[255, 0, 0, 255], [0, 0, 255, 255]]
+        .iter()
+        .map(|&color| {
+            let image = image::DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba(color)));
+            Texture::from_image(&mut harness.ctx, "sprite", &image)
+                .expect("Failed to upload sprite texture")
+        })
+        .collect();
+    let red = sprites.add_texture(&mut harness.ctx, &textures[0]);
+    let blue = sprites.add_texture(&mut harness.ctx, &textures[1]);
+    let source = Rect::new([0.0, 0.0], [2.0, 2.0]);
+    // Queued first but in a higher layer, so blue covers red where they
+    // overlap.
+    sprites.set_layer(Layer::HUD);
+    sprites.draw(
+        blue,
+        source,
+        Rect::new([12.0, 12.0], [28.0, 28.0]),
+        0.0,
+        Color::WHITE,
+    );
+    sprites.set_layer(Layer::WORLD);
+    sprites.draw(
+        red,
+        source,
+        Rect::new([4.0, 4.0], [20.0, 20.0]),
+        0.0,
+        Color::WHITE,
+    );
+    let image = harness.render_with(&mut [&mut sprites], |_| {});
+
+    assert_eq!(*image.get_pixel(8, 8), Rgba([255, 0, 0, 255]));
+    assert_eq!(*image.get_pixel(16, 16), Rgba([0, 0, 255, 255]));
+    assert_eq!(*image.get_pixel(24, 24), Rgba([0, 0, 255, 255]));
+    assert_eq!(*image.get_pixel(30, 2), Rgba([0, 0, 0, 255]));
+}
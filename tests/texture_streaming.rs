@@ -0,0 +1,50 @@
+//! Streams a texture up to the size it is drawn at under a memory budget.
+//!
+//! Needs a GPU adapter, so it is ignored by default.
+//! Run it with `cargo test --test texture_streaming -- --ignored`.
+
+use futures::executor::block_on;
+use image::{Rgba, RgbaImage};
+
+use minimal_error::{
+    adapter::AdapterChoice, gpu_mem::GpuMemory, texture_streaming::TextureStreamer, Context,
+};
+
+/// Side of the source image; the preview is 64 pixels, 5 levels down.
+const SOURCE_SIZE: u32 = 2048;
+
+/// Fits the 1024 pixel copy with its upload, but not the full one.
+const BUDGET: wgpu::BufferAddress = 16 << 20;
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn upgrades_within_budget() {
+    let mut ctx = block_on(Context::new_headless(32, 32, AdapterChoice::default()))
+        .expect("Failed to create headless context");
+    // Only what is allocated from here on counts, i.e. the streamed
+    // texture, whatever the context itself holds.
+    ctx.memory = GpuMemory::new(Some(BUDGET));
+
+    let mut streamer = TextureStreamer::new();
+    let source = RgbaImage::from_pixel(SOURCE_SIZE, SOURCE_SIZE, Rgba([128, 64, 32, 255]));
+    let id = streamer
+        .insert(&mut ctx, "streamed", source)
+        .expect("Failed to upload preview");
+    let width = |streamer: &TextureStreamer| streamer.get(id).map(|texture| texture.size.width);
+    assert_eq!(width(&streamer), Some(64));
+
+    // Drawn at full size, it grows a level per update while it fits.
+    for expected in &[128, 256, 512, 1024] {
+        streamer.touch(id, SOURCE_SIZE);
+        streamer.update(&mut ctx).expect("Failed to stream texture");
+        assert_eq!(width(&streamer), Some(*expected));
+    }
+
+    // The full copy doesn't fit, so the last one stays resident.
+    for _ in 0..2 {
+        streamer.touch(id, SOURCE_SIZE);
+        streamer.update(&mut ctx).expect("Failed to stream texture");
+        assert_eq!(width(&streamer), Some(1024));
+    }
+    assert!(ctx.memory.allocated() <= BUDGET);
+}